@@ -0,0 +1,182 @@
+//! Information about the application that currently has keyboard focus
+
+/// Whether `app` matches one of `names`, ignoring case and a trailing ".exe"
+pub fn app_matches(app: &str, names: &[String]) -> bool {
+    let normalize = |name: &str| {
+        let name = name.trim().to_lowercase();
+        name.strip_suffix(".exe")
+            .map(str::to_string)
+            .unwrap_or(name)
+    };
+
+    let app = normalize(app);
+    names.iter().any(|name| normalize(name) == app)
+}
+
+// ==================== Windows Implementations ====================
+
+/// Executable name of the foreground window's process, e.g. "chrome.exe" (Windows)
+#[cfg(windows)]
+pub fn foreground_app() -> Option<String> {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        GetForegroundWindow, GetWindowThreadProcessId,
+    };
+
+    unsafe {
+        let hwnd = GetForegroundWindow();
+        if hwnd.is_null() {
+            return None;
+        }
+
+        let mut pid = 0u32;
+        GetWindowThreadProcessId(hwnd, &mut pid);
+        if pid == 0 {
+            return None;
+        }
+
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            return None;
+        }
+
+        let mut buffer = [0u16; 1024];
+        let mut len = buffer.len() as u32;
+        let ok =
+            QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, buffer.as_mut_ptr(), &mut len);
+        CloseHandle(process);
+
+        if ok == 0 {
+            return None;
+        }
+
+        let path = String::from_utf16_lossy(&buffer[..len as usize]);
+        std::path::Path::new(&path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+    }
+}
+
+/// Whether the focused control is a native password edit box (Windows).
+/// Browser and custom-drawn fields cannot be detected this way.
+#[cfg(windows)]
+pub fn is_password_field_focused() -> bool {
+    use std::mem::size_of;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        GetClassNameW, GetGUIThreadInfo, GetWindowLongW, GUITHREADINFO, GWL_STYLE,
+    };
+
+    // Edit control style that masks the input
+    const ES_PASSWORD: u32 = 0x0020;
+
+    unsafe {
+        let mut info: GUITHREADINFO = std::mem::zeroed();
+        info.cbSize = size_of::<GUITHREADINFO>() as u32;
+
+        if GetGUIThreadInfo(0, &mut info) == 0 || info.hwndFocus.is_null() {
+            return false;
+        }
+
+        // The style bit only means "password" on edit controls
+        let mut class_name = [0u16; 64];
+        let len = GetClassNameW(
+            info.hwndFocus,
+            class_name.as_mut_ptr(),
+            class_name.len() as i32,
+        );
+        let class_name = String::from_utf16_lossy(&class_name[..len.max(0) as usize]);
+        if !class_name.to_lowercase().contains("edit") {
+            return false;
+        }
+
+        GetWindowLongW(info.hwndFocus, GWL_STYLE) as u32 & ES_PASSWORD != 0
+    }
+}
+
+// ==================== macOS Implementations ====================
+
+/// Display name of the frontmost application, e.g. "Safari" (macOS)
+#[cfg(target_os = "macos")]
+pub fn foreground_app() -> Option<String> {
+    use std::process::Command;
+
+    let front = Command::new("lsappinfo").arg("front").output().ok()?;
+    let asn = String::from_utf8_lossy(&front.stdout).trim().to_string();
+    if asn.is_empty() {
+        return None;
+    }
+
+    let info = Command::new("lsappinfo")
+        .args(["info", "-only", "name", &asn])
+        .output()
+        .ok()?;
+
+    // Output looks like: "LSDisplayName"="Safari"
+    let output = String::from_utf8_lossy(&info.stdout);
+    let name = output.split_once('=')?.1.trim().trim_matches('"');
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Whether secure keyboard entry is on, which macOS enables while a password
+/// field has focus
+#[cfg(target_os = "macos")]
+pub fn is_password_field_focused() -> bool {
+    #[link(name = "Carbon", kind = "framework")]
+    extern "C" {
+        fn IsSecureEventInputEnabled() -> u8;
+    }
+
+    unsafe { IsSecureEventInputEnabled() != 0 }
+}
+
+// ==================== Linux Implementations ====================
+
+/// Window class of the active window, e.g. "firefox" (Linux, X11 only)
+#[cfg(target_os = "linux")]
+pub fn foreground_app() -> Option<String> {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _};
+    use x11rb::rust_connection::RustConnection;
+
+    let (conn, screen_num) = RustConnection::connect(None).ok()?;
+    let root = conn.setup().roots[screen_num].root;
+
+    let active_atom = conn
+        .intern_atom(false, b"_NET_ACTIVE_WINDOW")
+        .ok()?
+        .reply()
+        .ok()?
+        .atom;
+    let window = conn
+        .get_property(false, root, active_atom, AtomEnum::WINDOW, 0, 1)
+        .ok()?
+        .reply()
+        .ok()?
+        .value32()?
+        .next()?;
+
+    let class = conn
+        .get_property(false, window, AtomEnum::WM_CLASS, AtomEnum::STRING, 0, 256)
+        .ok()?
+        .reply()
+        .ok()?
+        .value;
+
+    // WM_CLASS holds "instance\0class\0", the class name is the stable one
+    let mut parts = class
+        .split(|&b| b == 0)
+        .filter(|part| !part.is_empty())
+        .map(|part| String::from_utf8_lossy(part).to_string());
+    let instance = parts.next();
+    parts.next().or(instance)
+}
+
+/// Password fields cannot be detected on Linux
+#[cfg(target_os = "linux")]
+pub fn is_password_field_focused() -> bool {
+    false
+}
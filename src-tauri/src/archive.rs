@@ -0,0 +1,240 @@
+//! Export of the history to a versioned JSON archive and import from it.
+//! Images and thumbnails are embedded as base64 so an archive is self-contained.
+
+use crate::db::{
+    get_images_dir, get_thumbnails_dir, is_valid_clip_id, ClipFilter, ClipItem, ContentType,
+    Database, ImageMetadata,
+};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Identifies a file as a Fat Clip history archive
+const ARCHIVE_FORMAT: &str = "fat-clip-history";
+
+/// Current archive version. Archives from newer versions are rejected.
+const ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct HistoryArchive {
+    format: String,
+    version: u32,
+    exported_at: DateTime<Utc>,
+    clips: Vec<ArchivedClip>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ArchivedClip {
+    #[serde(flatten)]
+    pub clip: ClipItem,
+    /// PNG data of image clips, base64 encoded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    image_data: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    thumbnail_data: Option<String>,
+}
+
+/// What to do with archived clips that are already in the history
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportConflict {
+    /// Keep the existing clip unchanged
+    Skip,
+    /// Combine tags and keep the pin and usage of either
+    #[default]
+    Merge,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub merged: usize,
+    pub skipped: usize,
+    /// Clips with an invalid id and image clips whose image could not be
+    /// restored
+    pub failed: usize,
+}
+
+/// Write the matching clips to `path`. Returns the number of exported clips.
+pub fn export_history(db: &Database, path: &Path, filter: &ClipFilter) -> Result<usize, String> {
+    let clips = db
+        .filter_clips(None, filter, i64::MAX)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(archive_clip)
+        .collect::<Vec<_>>();
+
+    let count = clips.len();
+    let archive = HistoryArchive {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+        exported_at: Utc::now(),
+        clips,
+    };
+
+    let json = serde_json::to_vec_pretty(&archive).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("Failed to write archive: {}", e))?;
+    Ok(count)
+}
+
+/// Add the clips of the archive at `path` to the history. Clips are matched by
+/// their id, which is the hash of their content.
+pub fn import_history(
+    db: &Database,
+    path: &Path,
+    conflict: ImportConflict,
+) -> Result<ImportSummary, String> {
+    let data = std::fs::read(path).map_err(|e| format!("Failed to read archive: {}", e))?;
+    let archive: HistoryArchive =
+        serde_json::from_slice(&data).map_err(|e| format!("Invalid archive: {}", e))?;
+
+    if archive.format != ARCHIVE_FORMAT {
+        return Err("Not a Fat Clip history archive".to_string());
+    }
+    if archive.version > ARCHIVE_VERSION {
+        return Err(format!(
+            "The archive was created by a newer version of Fat Clip (format version {})",
+            archive.version
+        ));
+    }
+
+    let mut summary = ImportSummary::default();
+    let mut clips = Vec::with_capacity(archive.clips.len());
+
+    for archived in archive.clips {
+        if !is_valid_clip_id(&archived.clip.id) {
+            eprintln!("Skipping clip with invalid id {:?}", archived.clip.id);
+            summary.failed += 1;
+            continue;
+        }
+
+        let exists = db
+            .get_clip_by_id(&archived.clip.id)
+            .map_err(|e| e.to_string())?
+            .is_some();
+
+        // Existing image clips already have their files
+        if exists || archived.clip.content_type != ContentType::Image {
+            clips.push(archived.clip);
+            continue;
+        }
+
+        match restore_image(archived) {
+            Ok(clip) => clips.push(clip),
+            Err(e) => {
+                eprintln!("Failed to import image clip: {}", e);
+                summary.failed += 1;
+            }
+        }
+    }
+
+    let total = clips.len();
+    let (imported, merged) = db
+        .import_clips(&clips, conflict == ImportConflict::Merge)
+        .map_err(|e| e.to_string())?;
+
+    summary.imported = imported;
+    summary.merged = merged;
+    summary.skipped = total - imported - merged;
+    Ok(summary)
+}
+
+pub(crate) fn archive_clip(clip: ClipItem) -> ArchivedClip {
+    let mut image_data = None;
+    let mut thumbnail_data = None;
+
+    if clip.content_type == ContentType::Image {
+        image_data = std::fs::read(&clip.content)
+            .map(|data| BASE64.encode(data))
+            .ok();
+        thumbnail_data = image_metadata(&clip)
+            .and_then(|m| m.thumbnail_path)
+            .and_then(|path| std::fs::read(path).ok())
+            .map(|data| BASE64.encode(data));
+    }
+
+    ArchivedClip {
+        clip,
+        image_data,
+        thumbnail_data,
+    }
+}
+
+/// Write the embedded image files of an archived clip and point the clip at them
+pub(crate) fn restore_image(archived: ArchivedClip) -> Result<ClipItem, String> {
+    let mut clip = archived.clip;
+    if !is_valid_clip_id(&clip.id) {
+        return Err(format!("Invalid clip id {:?}", clip.id));
+    }
+
+    let image_data = archived
+        .image_data
+        .ok_or_else(|| format!("Image data of clip {} is missing", clip.id))?;
+    let image_data = BASE64
+        .decode(image_data)
+        .map_err(|e| format!("Invalid image data: {}", e))?;
+
+    let image_path = get_images_dir().join(format!("{}.png", clip.id));
+    std::fs::write(&image_path, image_data).map_err(|e| format!("Failed to save image: {}", e))?;
+    clip.content = image_path.to_string_lossy().to_string();
+
+    if let Some(mut metadata) = image_metadata(&clip) {
+        let thumbnail = archived
+            .thumbnail_data
+            .and_then(|data| BASE64.decode(data).ok());
+
+        metadata.thumbnail_path = match thumbnail {
+            Some(thumbnail) => {
+                let path = get_thumbnails_dir().join(format!("{}_thumb.png", clip.id));
+                std::fs::write(&path, thumbnail)
+                    .map_err(|e| format!("Failed to save thumbnail: {}", e))?;
+                Some(path.to_string_lossy().to_string())
+            }
+            // Without a thumbnail the full image is shown
+            None => None,
+        };
+        clip.metadata = serde_json::to_value(metadata).ok();
+    }
+
+    Ok(clip)
+}
+
+fn image_metadata(clip: &ClipItem) -> Option<ImageMetadata> {
+    clip.metadata
+        .clone()
+        .and_then(|m| serde_json::from_value(m).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_with_invalid_ids_are_not_written() {
+        let clip = ClipItem {
+            id: "../../escape".to_string(),
+            content_type: ContentType::Image,
+            content: String::new(),
+            preview_text: String::new(),
+            tags: vec![],
+            source_app: String::new(),
+            created_at: Utc::now(),
+            last_used_at: Utc::now(),
+            pinned: false,
+            metadata: None,
+            use_count: 0,
+        };
+        let archived = ArchivedClip {
+            clip,
+            image_data: Some(BASE64.encode(b"png")),
+            thumbnail_data: None,
+        };
+
+        let error = restore_image(archived).unwrap_err();
+        assert!(error.contains("Invalid clip id"));
+        assert!(!get_images_dir().join("../../escape.png").exists());
+    }
+}
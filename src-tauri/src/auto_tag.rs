@@ -0,0 +1,287 @@
+//! Automatic tagging: rules from the settings add tags to new clips at capture
+//! time when all of their conditions match.
+
+use crate::clipboard::{clip_kind, clip_plain_text};
+use crate::db::ClipItem;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+fn default_rule_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoTagRule {
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_rule_enabled")]
+    pub enabled: bool,
+    /// Tags added to matching clips
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub conditions: AutoTagConditions,
+}
+
+/// Conditions of a rule. Every condition that is set has to match; a rule
+/// without conditions matches every clip.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AutoTagConditions {
+    /// Regular expression searched in the clip text
+    #[serde(default)]
+    pub content_regex: Option<String>,
+    /// Any of "plain", "rich", "image", "file"
+    #[serde(default)]
+    pub content_types: Vec<String>,
+    /// Any of the detected text kinds, e.g. "url", "email", "json", "code"
+    #[serde(default)]
+    pub kinds: Vec<String>,
+    /// Any of these applications, compared case-insensitively
+    #[serde(default)]
+    pub source_apps: Vec<String>,
+    /// Size in bytes: text length, image size or total file size
+    #[serde(default)]
+    pub min_size: Option<u64>,
+    #[serde(default)]
+    pub max_size: Option<u64>,
+}
+
+struct CompiledRule {
+    rule: AutoTagRule,
+    conditions: CompiledConditions,
+}
+
+impl CompiledRule {
+    fn new(rule: AutoTagRule) -> Result<Self, String> {
+        let conditions = CompiledConditions::new(&rule.conditions, &rule.name)?;
+        Ok(CompiledRule { rule, conditions })
+    }
+
+    fn matches(&self, clip: &ClipItem) -> bool {
+        self.conditions.matches(clip)
+    }
+}
+
+/// Conditions with the regex compiled, also used by hooks
+pub struct CompiledConditions {
+    conditions: AutoTagConditions,
+    content_regex: Option<Regex>,
+}
+
+impl CompiledConditions {
+    /// Compile the conditions of the rule or hook called `name`
+    pub fn new(conditions: &AutoTagConditions, name: &str) -> Result<Self, String> {
+        let content_regex = match &conditions.content_regex {
+            Some(pattern) if !pattern.is_empty() => Some(
+                Regex::new(pattern).map_err(|e| format!("Invalid regex in '{}': {}", name, e))?,
+            ),
+            _ => None,
+        };
+
+        Ok(CompiledConditions {
+            conditions: conditions.clone(),
+            content_regex,
+        })
+    }
+
+    pub fn matches(&self, clip: &ClipItem) -> bool {
+        let conditions = &self.conditions;
+
+        if !conditions.content_types.is_empty()
+            && !conditions
+                .content_types
+                .iter()
+                .any(|t| t.eq_ignore_ascii_case(clip.content_type.as_str()))
+        {
+            return false;
+        }
+
+        if !conditions.source_apps.is_empty()
+            && !crate::active_app::app_matches(&clip.source_app, &conditions.source_apps)
+        {
+            return false;
+        }
+
+        if !conditions.kinds.is_empty() {
+            let kind = clip_kind(clip);
+            let matched = kind.is_some_and(|kind| {
+                conditions
+                    .kinds
+                    .iter()
+                    .any(|k| k.eq_ignore_ascii_case(&kind))
+            });
+            if !matched {
+                return false;
+            }
+        }
+
+        if conditions.min_size.is_some() || conditions.max_size.is_some() {
+            let size = clip_size(clip);
+            if conditions.min_size.is_some_and(|min| size < min)
+                || conditions.max_size.is_some_and(|max| size > max)
+            {
+                return false;
+            }
+        }
+
+        if let Some(regex) = &self.content_regex {
+            match clip_plain_text(clip) {
+                Ok(text) if regex.is_match(&text) => {}
+                _ => return false,
+            }
+        }
+
+        true
+    }
+}
+
+/// The enabled rules, ready to evaluate
+#[derive(Default)]
+pub struct AutoTagger {
+    rules: Vec<CompiledRule>,
+}
+
+impl AutoTagger {
+    /// Compile the rules, failing on the first invalid regex
+    pub fn new(rules: &[AutoTagRule]) -> Result<Self, String> {
+        let rules = rules
+            .iter()
+            .filter(|rule| rule.enabled)
+            .cloned()
+            .map(CompiledRule::new)
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(AutoTagger { rules })
+    }
+
+    /// Add the tags of every matching rule to the clip
+    pub fn apply(&self, clip: &mut ClipItem) {
+        for rule in &self.rules {
+            if rule.matches(clip) {
+                for tag in &rule.rule.tags {
+                    if !clip.tags.contains(tag) {
+                        clip.tags.push(tag.clone());
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn clip_size(clip: &ClipItem) -> u64 {
+    let metadata_size = |key: &str| {
+        clip.metadata
+            .as_ref()
+            .and_then(|m| m.get(key))
+            .and_then(|v| v.as_u64())
+    };
+
+    metadata_size("size_bytes")
+        .or_else(|| metadata_size("total_size_bytes"))
+        .or_else(|| clip_plain_text(clip).ok().map(|text| text.len() as u64))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::ContentType;
+    use chrono::Utc;
+
+    fn clip(content_type: ContentType, content: &str, source_app: &str) -> ClipItem {
+        ClipItem {
+            id: "id".to_string(),
+            content_type,
+            content: content.to_string(),
+            preview_text: content.to_string(),
+            tags: vec![],
+            source_app: source_app.to_string(),
+            created_at: Utc::now(),
+            last_used_at: Utc::now(),
+            pinned: false,
+            metadata: None,
+            use_count: 0,
+        }
+    }
+
+    fn compile(conditions: AutoTagConditions) -> CompiledConditions {
+        CompiledConditions::new(&conditions, "test").unwrap()
+    }
+
+    #[test]
+    fn empty_conditions_match_everything() {
+        let conditions = compile(AutoTagConditions::default());
+        assert!(conditions.matches(&clip(ContentType::Plain, "hello", "")));
+        assert!(conditions.matches(&clip(ContentType::Image, "/tmp/a.png", "")));
+    }
+
+    #[test]
+    fn every_condition_has_to_match() {
+        let conditions = compile(AutoTagConditions {
+            content_regex: Some(r"^\d+$".to_string()),
+            content_types: vec!["plain".to_string()],
+            source_apps: vec!["Terminal".to_string()],
+            max_size: Some(4),
+            ..Default::default()
+        });
+
+        assert!(conditions.matches(&clip(ContentType::Plain, "1234", "terminal.exe")));
+        assert!(!conditions.matches(&clip(ContentType::Plain, "12345", "Terminal")));
+        assert!(!conditions.matches(&clip(ContentType::Plain, "12a", "Terminal")));
+        assert!(!conditions.matches(&clip(ContentType::Plain, "1234", "Editor")));
+        assert!(!conditions.matches(&clip(ContentType::File, "[\"1234\"]", "Terminal")));
+    }
+
+    #[test]
+    fn kinds_use_the_classification() {
+        let conditions = compile(AutoTagConditions {
+            kinds: vec!["URL".to_string()],
+            ..Default::default()
+        });
+
+        assert!(conditions.matches(&clip(ContentType::Plain, "https://example.com", "")));
+        assert!(!conditions.matches(&clip(ContentType::Plain, "just some words", "")));
+        assert!(!conditions.matches(&clip(ContentType::Image, "/tmp/a.png", "")));
+    }
+
+    #[test]
+    fn regex_does_not_match_images() {
+        let conditions = compile(AutoTagConditions {
+            content_regex: Some("png".to_string()),
+            ..Default::default()
+        });
+        assert!(!conditions.matches(&clip(ContentType::Image, "/tmp/a.png", "")));
+    }
+
+    #[test]
+    fn invalid_regex_is_rejected() {
+        let conditions = AutoTagConditions {
+            content_regex: Some("(".to_string()),
+            ..Default::default()
+        };
+        let error = CompiledConditions::new(&conditions, "broken")
+            .err()
+            .unwrap();
+        assert!(error.contains("'broken'"));
+    }
+
+    #[test]
+    fn tagger_adds_tags_of_enabled_matching_rules_once() {
+        let rule = |id: &str, enabled: bool, tags: &[&str]| AutoTagRule {
+            id: id.to_string(),
+            name: id.to_string(),
+            enabled,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            conditions: AutoTagConditions::default(),
+        };
+        let tagger = AutoTagger::new(&[
+            rule("a", true, &["one", "two"]),
+            rule("b", true, &["two"]),
+            rule("c", false, &["three"]),
+        ])
+        .unwrap();
+
+        let mut item = clip(ContentType::Plain, "hello", "");
+        tagger.apply(&mut item);
+        assert_eq!(item.tags, vec!["one", "two"]);
+    }
+}
@@ -0,0 +1,569 @@
+//! Local backups: snapshots of the database, taken with SQLite's online backup
+//! API, together with the images and thumbnails. Each backup is a folder with
+//! a manifest used to verify it before it is restored.
+
+use crate::clipboard::ClipboardManager;
+use crate::db::{get_app_dir, get_images_dir, get_thumbnails_dir, Database};
+use chrono::{DateTime, Datelike, Local, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const BACKUP_PREFIX: &str = "fat-clip-backup-";
+const DB_FILE: &str = "fat_clip.db";
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// How often the scheduler checks whether a backup is due
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BackupManifest {
+    created_at: DateTime<Utc>,
+    app_version: String,
+    clip_count: i64,
+    /// SHA-256 of the database file, hex encoded; missing in older backups
+    #[serde(default)]
+    database_sha256: Option<String>,
+    /// Image and thumbnail files with their sizes, relative to the backup
+    files: Vec<BackupFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BackupFile {
+    path: String,
+    size: u64,
+    /// SHA-256 of the content, hex encoded; missing in older backups
+    #[serde(default)]
+    sha256: Option<String>,
+}
+
+/// A backup as shown in the UI
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    /// Folder name, used to refer to the backup
+    pub id: String,
+    pub path: String,
+    pub created_at: DateTime<Utc>,
+    pub clip_count: i64,
+    pub size_bytes: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    pub enabled: bool,
+    pub dir: Option<String>,
+    pub interval_hours: u64,
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+}
+
+pub struct BackupManager {
+    clipboard_manager: Arc<ClipboardManager>,
+    config: Arc<Mutex<BackupConfig>>,
+    // Only one backup or restore runs at a time
+    busy: Arc<Mutex<()>>,
+    scheduler_started: AtomicBool,
+}
+
+impl BackupManager {
+    pub fn new(clipboard_manager: Arc<ClipboardManager>, config: BackupConfig) -> Self {
+        BackupManager {
+            clipboard_manager,
+            config: Arc::new(Mutex::new(config)),
+            busy: Arc::new(Mutex::new(())),
+            scheduler_started: AtomicBool::new(false),
+        }
+    }
+
+    pub fn set_config(&self, config: BackupConfig) {
+        if let Ok(mut current) = self.config.lock() {
+            *current = config;
+        }
+    }
+
+    /// Start the thread that takes scheduled backups. It keeps running and
+    /// follows the configuration.
+    pub fn start_scheduler(self: &Arc<Self>) {
+        if self.scheduler_started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let manager = self.clone();
+        thread::spawn(move || loop {
+            if manager.is_backup_due() {
+                if let Err(e) = manager.create_backup() {
+                    eprintln!("Scheduled backup failed: {}", e);
+                }
+            }
+            thread::sleep(SCHEDULER_INTERVAL);
+        });
+    }
+
+    fn is_backup_due(&self) -> bool {
+        let config = match self.config.lock() {
+            Ok(config) => config.clone(),
+            Err(_) => return false,
+        };
+        if !config.enabled {
+            return false;
+        }
+
+        let interval = chrono::Duration::hours(config.interval_hours.max(1) as i64);
+        match self.list_backups() {
+            Ok(backups) => backups
+                .first()
+                .is_none_or(|latest| Utc::now() - latest.created_at >= interval),
+            Err(_) => true,
+        }
+    }
+
+    fn backup_dir(&self) -> PathBuf {
+        self.config
+            .lock()
+            .ok()
+            .and_then(|config| config.dir.clone())
+            .filter(|dir| !dir.trim().is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| get_app_dir().join("backups"))
+    }
+
+    /// Backups in the backup folder, newest first
+    pub fn list_backups(&self) -> Result<Vec<BackupInfo>, String> {
+        let dir = self.backup_dir();
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut backups: Vec<BackupInfo> = std::fs::read_dir(&dir)
+            .map_err(|e| e.to_string())?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| name.starts_with(BACKUP_PREFIX))
+            })
+            .filter_map(|entry| {
+                let path = entry.path();
+                let manifest = read_manifest(&path).ok()?;
+                Some(BackupInfo {
+                    id: entry.file_name().to_string_lossy().to_string(),
+                    path: path.to_string_lossy().to_string(),
+                    created_at: manifest.created_at,
+                    clip_count: manifest.clip_count,
+                    size_bytes: dir_size(&path),
+                })
+            })
+            .collect();
+
+        backups.sort_by_key(|backup| std::cmp::Reverse(backup.created_at));
+        Ok(backups)
+    }
+
+    /// Take a backup now, verify it and rotate the old ones
+    pub fn create_backup(&self) -> Result<BackupInfo, String> {
+        let _busy = self.busy.lock().map_err(|e| e.to_string())?;
+
+        let dir = self.backup_dir();
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create backup folder: {}", e))?;
+
+        let created_at = Utc::now();
+        let stamp = created_at.with_timezone(&Local).format("%Y%m%d-%H%M%S");
+        let mut id = format!("{}{}", BACKUP_PREFIX, stamp);
+        // Backups taken within the same second, e.g. before a restore
+        let mut n = 1;
+        while dir.join(&id).exists() {
+            n += 1;
+            id = format!("{}{}-{}", BACKUP_PREFIX, stamp, n);
+        }
+        let target = dir.join(&id);
+        // Written under a temporary name so a failed backup is never listed
+        let staging = dir.join(format!(".{}.tmp", id));
+        let _ = std::fs::remove_dir_all(&staging);
+        std::fs::create_dir_all(&staging).map_err(|e| e.to_string())?;
+
+        let manifest = match self
+            .write_backup(&staging, created_at)
+            .and_then(|_| verify_backup(&staging))
+        {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&staging);
+                return Err(e);
+            }
+        };
+
+        std::fs::rename(&staging, &target).map_err(|e| e.to_string())?;
+        self.rotate_backups()?;
+
+        Ok(BackupInfo {
+            id,
+            path: target.to_string_lossy().to_string(),
+            created_at,
+            clip_count: manifest.clip_count,
+            size_bytes: dir_size(&target),
+        })
+    }
+
+    fn write_backup(&self, staging: &Path, created_at: DateTime<Utc>) -> Result<(), String> {
+        // The database first: every image it refers to already exists
+        {
+            let db = self
+                .clipboard_manager
+                .db
+                .lock()
+                .map_err(|e| e.to_string())?;
+            db.backup_to(&staging.join(DB_FILE))
+                .map_err(|e| format!("Failed to back up database: {}", e))?;
+        }
+
+        let mut files = Vec::new();
+        for (name, source) in [
+            ("images", get_images_dir()),
+            ("thumbnails", get_thumbnails_dir()),
+        ] {
+            files.extend(copy_dir(&source, &staging.join(name), name)?);
+        }
+
+        let clip_count = Database::verify_backup(&staging.join(DB_FILE))
+            .map_err(|e| format!("The backup database is damaged: {}", e))?;
+        let database_sha256 = file_sha256(&staging.join(DB_FILE))?;
+
+        let manifest = BackupManifest {
+            created_at,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            clip_count,
+            database_sha256: Some(database_sha256),
+            files,
+        };
+        let json = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+        std::fs::write(staging.join(MANIFEST_FILE), json).map_err(|e| e.to_string())
+    }
+
+    /// Remove the backups `backups_to_keep` does not keep
+    fn rotate_backups(&self) -> Result<(), String> {
+        let (keep_daily, keep_weekly) = {
+            let config = self.config.lock().map_err(|e| e.to_string())?;
+            (config.keep_daily, config.keep_weekly)
+        };
+
+        let backups = self.list_backups()?;
+        let keep = backups_to_keep(&backups, keep_daily, keep_weekly);
+
+        for backup in backups.iter().filter(|b| !keep.contains(&b.id)) {
+            if let Err(e) = std::fs::remove_dir_all(&backup.path) {
+                eprintln!("Failed to remove old backup {}: {}", backup.id, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check a backup without restoring it
+    pub fn verify(&self, id: &str) -> Result<BackupInfo, String> {
+        let path = self.backup_path(id)?;
+        verify_backup(&path)?;
+        self.list_backups()?
+            .into_iter()
+            .find(|backup| backup.id == id)
+            .ok_or_else(|| "Backup not found".to_string())
+    }
+
+    /// Replace the history with a backup. Capturing is paused while the store
+    /// is swapped, and the current state is backed up first.
+    pub fn restore_backup(&self, id: &str) -> Result<(), String> {
+        let path = self.backup_path(id)?;
+        verify_backup(&path)?;
+
+        self.create_backup()
+            .map_err(|e| format!("Failed to back up the current history: {}", e))?;
+
+        let _busy = self.busy.lock().map_err(|e| e.to_string())?;
+        self.clipboard_manager.set_capture_paused(true);
+        let result = self.swap_store(&path);
+        self.clipboard_manager.set_capture_paused(false);
+        result
+    }
+
+    fn swap_store(&self, backup: &Path) -> Result<(), String> {
+        let mut db = self
+            .clipboard_manager
+            .db
+            .lock()
+            .map_err(|e| e.to_string())?;
+
+        // Stage the files first so a failed copy leaves the live store untouched
+        let mut staged = Vec::new();
+        for (name, live) in [
+            ("images", get_images_dir()),
+            ("thumbnails", get_thumbnails_dir()),
+        ] {
+            let staging = live.with_extension("restore");
+            let _ = std::fs::remove_dir_all(&staging);
+            copy_dir(&backup.join(name), &staging, name)?;
+            staged.push((staging, live));
+        }
+
+        // The folders are swapped before the database, and swapped back if
+        // anything fails, so the database never refers to missing images
+        let mut swapped = Vec::new();
+        let result = swap_dirs(&staged, &mut swapped).and_then(|_| {
+            db.restore_from(&backup.join(DB_FILE))
+                .map_err(|e| format!("Failed to restore database: {}", e))
+        });
+
+        if let Err(e) = result {
+            for (live, old) in swapped.into_iter().rev() {
+                let _ = std::fs::remove_dir_all(&live);
+                let _ = std::fs::rename(&old, &live);
+            }
+            for (staging, _) in &staged {
+                let _ = std::fs::remove_dir_all(staging);
+            }
+            return Err(e);
+        }
+
+        for (_, old) in swapped {
+            let _ = std::fs::remove_dir_all(&old);
+        }
+        Ok(())
+    }
+
+    /// Folder of a backup; ids cannot point outside the backup folder
+    fn backup_path(&self, id: &str) -> Result<PathBuf, String> {
+        if !id.starts_with(BACKUP_PREFIX) || id.contains(['/', '\\']) || id.contains("..") {
+            return Err("Invalid backup".to_string());
+        }
+
+        let path = self.backup_dir().join(id);
+        if !path.is_dir() {
+            return Err("Backup not found".to_string());
+        }
+        Ok(path)
+    }
+}
+
+/// Move each live folder aside and the staged one in its place. `swapped`
+/// collects the live folders and where they were moved, to undo the swap.
+fn swap_dirs(
+    staged: &[(PathBuf, PathBuf)],
+    swapped: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<(), String> {
+    for (staging, live) in staged {
+        let old = live.with_extension("old");
+        let _ = std::fs::remove_dir_all(&old);
+        if live.exists() {
+            std::fs::rename(live, &old).map_err(|e| e.to_string())?;
+        }
+        swapped.push((live.clone(), old));
+        std::fs::rename(staging, live).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Ids of the backups to keep, given newest first: the newest backup of each
+/// of the last `keep_daily` days and of each of the last `keep_weekly` weeks,
+/// plus the newest backup overall
+fn backups_to_keep(
+    backups: &[BackupInfo],
+    keep_daily: usize,
+    keep_weekly: usize,
+) -> HashSet<String> {
+    let mut keep = HashSet::new();
+    let mut days = Vec::new();
+    let mut weeks = Vec::new();
+
+    for (i, backup) in backups.iter().enumerate() {
+        let local = backup.created_at.with_timezone(&Local);
+        let day = local.date_naive();
+        let week = local.iso_week();
+
+        if i == 0 {
+            keep.insert(backup.id.clone());
+        }
+        if !days.contains(&day) {
+            days.push(day);
+            if days.len() <= keep_daily {
+                keep.insert(backup.id.clone());
+            }
+        }
+        if !weeks.contains(&week) {
+            weeks.push(week);
+            if weeks.len() <= keep_weekly {
+                keep.insert(backup.id.clone());
+            }
+        }
+    }
+    keep
+}
+
+/// Check the database and every file listed in the manifest
+fn verify_backup(path: &Path) -> Result<BackupManifest, String> {
+    let manifest = read_manifest(path)?;
+
+    let clip_count = Database::verify_backup(&path.join(DB_FILE))
+        .map_err(|e| format!("The backup database is damaged: {}", e))?;
+    let database_matches = match &manifest.database_sha256 {
+        Some(expected) => file_sha256(&path.join(DB_FILE))? == *expected,
+        None => true,
+    };
+    if clip_count != manifest.clip_count || !database_matches {
+        return Err("The backup database does not match its manifest".to_string());
+    }
+
+    for file in &manifest.files {
+        let file_path = path.join(&file.path);
+        let size = std::fs::metadata(&file_path).map(|m| m.len());
+        let intact = size.ok() == Some(file.size)
+            && match &file.sha256 {
+                Some(expected) => file_sha256(&file_path).ok().as_ref() == Some(expected),
+                None => true,
+            };
+        if !intact {
+            return Err(format!(
+                "The backup file {} is missing or damaged",
+                file.path
+            ));
+        }
+    }
+
+    Ok(manifest)
+}
+
+fn read_manifest(path: &Path) -> Result<BackupManifest, String> {
+    let content = std::fs::read_to_string(path.join(MANIFEST_FILE))
+        .map_err(|_| "The backup has no manifest".to_string())?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid backup manifest: {}", e))
+}
+
+/// Copy the files of a flat directory. Returns the copied files relative to
+/// the backup, prefixed with `name`.
+fn copy_dir(source: &Path, target: &Path, name: &str) -> Result<Vec<BackupFile>, String> {
+    std::fs::create_dir_all(target).map_err(|e| e.to_string())?;
+    if !source.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(source).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+            continue;
+        }
+
+        let file_name = entry.file_name();
+        let data = std::fs::read(entry.path())
+            .map_err(|e| format!("Failed to copy {}: {}", entry.path().display(), e))?;
+        std::fs::write(target.join(&file_name), &data)
+            .map_err(|e| format!("Failed to copy {}: {}", entry.path().display(), e))?;
+        files.push(BackupFile {
+            path: format!("{}/{}", name, file_name.to_string_lossy()),
+            size: data.len() as u64,
+            sha256: Some(format!("{:x}", Sha256::digest(&data))),
+        });
+    }
+    Ok(files)
+}
+
+fn file_sha256(path: &Path) -> Result<String, String> {
+    let data =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(format!("{:x}", Sha256::digest(&data)))
+}
+
+fn dir_size(path: &Path) -> u64 {
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| match entry.file_type() {
+                    Ok(t) if t.is_dir() => dir_size(&entry.path()),
+                    _ => entry.metadata().map(|m| m.len()).unwrap_or(0),
+                })
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn backup(id: &str, created_at: DateTime<Local>) -> BackupInfo {
+        BackupInfo {
+            id: id.to_string(),
+            path: String::new(),
+            created_at: created_at.with_timezone(&Utc),
+            clip_count: 0,
+            size_bytes: 0,
+        }
+    }
+
+    fn kept(backups: &[BackupInfo], keep_daily: usize, keep_weekly: usize) -> Vec<String> {
+        let keep = backups_to_keep(backups, keep_daily, keep_weekly);
+        let mut ids: Vec<String> = keep.into_iter().collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn rotation_keeps_the_newest_backup_per_day_and_week() {
+        let at = |day: u32, hour: u32| Local.with_ymd_and_hms(2024, 5, day, hour, 0, 0).unwrap();
+        // Newest first; 2024-05-13 is a Monday
+        let backups = vec![
+            backup("a", at(15, 18)),
+            backup("b", at(15, 9)),
+            backup("c", at(14, 9)),
+            backup("d", at(13, 9)),
+            backup("e", at(12, 9)),
+            backup("f", at(5, 9)),
+            backup("g", at(1, 9)),
+        ];
+
+        assert_eq!(kept(&backups, 2, 0), vec!["a", "c"]);
+        assert_eq!(kept(&backups, 0, 2), vec!["a", "e"]);
+        assert_eq!(kept(&backups, 1, 3), vec!["a", "e", "f"]);
+        // The newest backup is always kept
+        assert_eq!(kept(&backups, 0, 0), vec!["a"]);
+        assert!(backups_to_keep(&[], 3, 3).is_empty());
+    }
+
+    #[test]
+    fn verification_checks_file_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let images = dir.path().join("source");
+        std::fs::create_dir_all(&images).unwrap();
+        std::fs::write(images.join("a.png"), b"image").unwrap();
+
+        let backup = dir.path().join("backup");
+        std::fs::create_dir_all(&backup).unwrap();
+        Database::new(backup.clone()).unwrap();
+        let files = copy_dir(&images, &backup.join("images"), "images").unwrap();
+        let manifest = BackupManifest {
+            created_at: Utc::now(),
+            app_version: String::new(),
+            clip_count: 0,
+            database_sha256: Some(file_sha256(&backup.join(DB_FILE)).unwrap()),
+            files,
+        };
+        std::fs::write(
+            backup.join(MANIFEST_FILE),
+            serde_json::to_string(&manifest).unwrap(),
+        )
+        .unwrap();
+        assert!(verify_backup(&backup).is_ok());
+
+        // Same size, different content
+        std::fs::write(backup.join("images/a.png"), b"imagf").unwrap();
+        let error = verify_backup(&backup).unwrap_err();
+        assert!(error.contains("images/a.png"));
+    }
+}
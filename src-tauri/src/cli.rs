@@ -0,0 +1,536 @@
+//! Command-line interface: `fat-clip <command>` works on the history without
+//! starting the app. It opens the same database as the app, which may be
+//! running at the same time; SQLite's locking keeps the store consistent.
+
+use crate::archive::export_history;
+use crate::clipboard::{clip_plain_text, ClipboardManager};
+use crate::clipboard_writer::ClipboardHold;
+use crate::db::{ensure_app_dir, ClipFilter, ClipItem, ContentType, Database};
+use crate::settings::Settings;
+use serde_json::json;
+use std::io::Read;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+const COMMANDS: &[&str] = &[
+    "list", "search", "get", "copy", "add", "pin", "tag", "delete", "cleanup", "export", "help",
+];
+
+/// Options that take a value
+const VALUE_OPTIONS: &[&str] = &["limit", "type", "tag", "from", "to", "older-than", "before"];
+
+const USAGE: &str = "Usage: fat-clip <command> [options]
+
+Commands:
+  list                  List recent clips
+  search <query>        Search clips
+  get <id>              Print the content of a clip
+  copy <id>             Copy a clip to the clipboard; on Linux, keeps running
+                        until something else is copied
+  add                   Add the text read from stdin as a clip
+  pin <id>              Pin a clip (--off to unpin)
+  tag <id> [tag...]     Add tags to a clip (--remove to remove them), or
+                        print its tags
+  delete <id>...        Delete clips
+  cleanup               Delete unpinned clips, by --older-than <days>,
+                        --before <date> or --from <date> --to <date>
+  export <path>         Export clips to a history archive
+
+Options:
+  --json                Print JSON instead of text
+  --limit <n>           Number of clips to list or search (default 20)
+  --type <type>         Only clips of this type: plain, rich, image, file
+  --tag <tag>           Only clips with this tag; for add, tag the new clip
+  --pinned              Only pinned clips; for add, pin the new clip
+  --from <date>         Only clips created on or after this date (YYYY-MM-DD)
+  --to <date>           Only clips created on or before this date
+";
+
+/// Run the command-line interface if the arguments name a command. Returns
+/// the exit code, or `None` to start the app.
+pub fn run() -> Option<i32> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = args.first()?.as_str();
+    let command = match command {
+        "--help" | "-h" => "help",
+        command if COMMANDS.contains(&command) => command,
+        _ => return None,
+    };
+
+    attach_console();
+
+    let args = match Args::parse(&args[1..]) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("fat-clip: {}\n\n{}", e, USAGE);
+            return Some(2);
+        }
+    };
+
+    if command == "help" || args.flag("help") {
+        print!("{}", USAGE);
+        return Some(0);
+    }
+
+    match run_command(command, &args) {
+        Ok(()) => Some(0),
+        Err(CliError::Usage(e)) => {
+            eprintln!("fat-clip: {}\n\n{}", e, USAGE);
+            Some(2)
+        }
+        Err(CliError::Failed(e)) => {
+            eprintln!("fat-clip: {}", e);
+            Some(1)
+        }
+    }
+}
+
+enum CliError {
+    Usage(String),
+    Failed(String),
+}
+
+impl From<String> for CliError {
+    fn from(e: String) -> Self {
+        CliError::Failed(e)
+    }
+}
+
+fn usage(message: &str) -> CliError {
+    CliError::Usage(message.to_string())
+}
+
+/// Positional arguments and `--name [value]` options
+struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    fn parse(raw: &[String]) -> Result<Self, String> {
+        let mut args = Args {
+            positional: Vec::new(),
+            options: Vec::new(),
+        };
+
+        let mut iter = raw.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                args.positional.extend(iter.by_ref().cloned());
+                break;
+            }
+            let Some(option) = arg.strip_prefix("--") else {
+                args.positional.push(arg.clone());
+                continue;
+            };
+
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (option, None),
+            };
+            let value = if VALUE_OPTIONS.contains(&name) {
+                match value {
+                    Some(value) => Some(value),
+                    None => Some(
+                        iter.next()
+                            .cloned()
+                            .ok_or_else(|| format!("--{} needs a value", name))?,
+                    ),
+                }
+            } else if ["json", "pinned", "off", "remove", "help"].contains(&name) {
+                None
+            } else {
+                return Err(format!("Unknown option {}", arg));
+            };
+            args.options.push((name.to_string(), value));
+        }
+
+        Ok(args)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.options.iter().any(|(n, _)| n == name)
+    }
+
+    fn value(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    fn values(&self, name: &str) -> Vec<String> {
+        self.options
+            .iter()
+            .filter(|(n, _)| n == name)
+            .filter_map(|(_, value)| value.clone())
+            .collect()
+    }
+
+    /// The only positional argument
+    fn single(&self, what: &str) -> Result<&str, CliError> {
+        match self.positional.as_slice() {
+            [value] => Ok(value),
+            [] => Err(usage(&format!("Missing {}", what))),
+            _ => Err(usage("Too many arguments")),
+        }
+    }
+
+    fn limit(&self) -> Result<i64, CliError> {
+        match self.value("limit") {
+            Some(limit) => limit
+                .parse()
+                .ok()
+                .filter(|limit| *limit > 0)
+                .ok_or_else(|| usage("--limit must be a positive number")),
+            None => Ok(20),
+        }
+    }
+
+    fn filter(&self) -> Result<ClipFilter, CliError> {
+        let from = self
+            .value("from")
+            .map(crate::parse_date_start)
+            .transpose()
+            .map_err(CliError::Usage)?;
+        let to = self
+            .value("to")
+            .map(crate::parse_date_end_exclusive)
+            .transpose()
+            .map_err(CliError::Usage)?;
+
+        Ok(ClipFilter {
+            from,
+            to,
+            tags: self.values("tag"),
+            content_types: self.values("type"),
+            pinned_only: self.flag("pinned"),
+        })
+    }
+}
+
+fn open_manager() -> Result<ClipboardManager, String> {
+    let app_dir = ensure_app_dir().map_err(|e| e.to_string())?;
+    let db =
+        Database::new(app_dir.clone()).map_err(|e| format!("Failed to open database: {}", e))?;
+    let manager = ClipboardManager::new(Arc::new(Mutex::new(db)));
+
+    // Added clips are tagged like captured ones
+    if let Ok(settings) = Settings::load(&app_dir) {
+        manager.set_auto_tag_rules(&settings.auto_tag_rules)?;
+    }
+    Ok(manager)
+}
+
+/// Clips matching the filter options and `query`, up to `--limit`
+fn filter_clips(
+    manager: &ClipboardManager,
+    query: Option<&str>,
+    args: &Args,
+) -> Result<Vec<ClipItem>, CliError> {
+    let filter = args.filter()?;
+    let limit = args.limit()?;
+    let db = manager.db.lock().map_err(|e| e.to_string())?;
+    db.filter_clips(query, &filter, limit)
+        .map_err(|e| CliError::Failed(e.to_string()))
+}
+
+fn run_command(command: &str, args: &Args) -> Result<(), CliError> {
+    let manager = open_manager()?;
+    let json = args.flag("json");
+
+    match command {
+        "list" => {
+            if !args.positional.is_empty() {
+                return Err(usage("Too many arguments"));
+            }
+            let clips = filter_clips(&manager, None, args)?;
+            print_clips(&clips, json);
+        }
+        "search" => {
+            if args.positional.is_empty() {
+                return Err(usage("Missing search query"));
+            }
+            let query = args.positional.join(" ");
+            let clips = filter_clips(&manager, Some(&query), args)?;
+            print_clips(&clips, json);
+        }
+        "get" => {
+            let clip = manager.get_clip(args.single("clip id")?)?;
+            if json {
+                print_json(&clip);
+            } else {
+                match clip.content_type {
+                    ContentType::Plain | ContentType::Rich => {
+                        print!("{}", clip_plain_text(&clip)?)
+                    }
+                    ContentType::Image => println!("{}", clip.content),
+                    ContentType::File => {
+                        let paths: Vec<String> =
+                            serde_json::from_str(&clip.content).unwrap_or_default();
+                        for path in paths {
+                            println!("{}", path);
+                        }
+                    }
+                }
+            }
+        }
+        "copy" => {
+            let id = args.single("clip id")?;
+            let hold = ClipboardHold::new()?;
+            manager.write_clip(id)?;
+            manager.record_clip_use(id)?;
+            if json {
+                print_json(&json!({ "copied": id }));
+            }
+            hold.wait()?;
+        }
+        "add" => {
+            if !args.positional.is_empty() {
+                return Err(usage("add reads the clip from stdin"));
+            }
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|e| format!("Failed to read stdin: {}", e))?;
+            // Like command substitution, drop the final newline of piped text
+            let text = text
+                .strip_suffix('\n')
+                .map(|t| t.strip_suffix('\r').unwrap_or(t))
+                .unwrap_or(&text)
+                .to_string();
+            if text.trim().is_empty() {
+                return Err(CliError::Failed("Nothing to add".to_string()));
+            }
+
+            let clip = manager.save_text_clip(text, "fat-clip CLI".to_string(), true)?;
+            add_tags(&manager, &clip.id, &args.values("tag"))?;
+            if args.flag("pinned") {
+                manager.toggle_pin(&clip.id, true)?;
+            }
+
+            let clip = manager.get_clip(&clip.id)?;
+            if json {
+                print_json(&clip);
+            } else {
+                println!("{}", clip.id);
+            }
+        }
+        "pin" => {
+            let id = args.single("clip id")?;
+            manager.get_clip(id)?;
+            manager.toggle_pin(id, !args.flag("off"))?;
+            if json {
+                print_json(&manager.get_clip(id)?);
+            }
+        }
+        "tag" => {
+            let (id, tags) = args
+                .positional
+                .split_first()
+                .ok_or_else(|| usage("Missing clip id"))?;
+            let clip = manager.get_clip(id)?;
+
+            if args.flag("remove") {
+                let remaining = clip
+                    .tags
+                    .iter()
+                    .filter(|tag| !tags.contains(tag))
+                    .cloned()
+                    .collect();
+                manager.update_tags(id, remaining)?;
+            } else {
+                add_tags(&manager, id, tags)?;
+            }
+
+            let clip = manager.get_clip(id)?;
+            if json {
+                print_json(&clip.tags);
+            } else if tags.is_empty() {
+                for tag in clip.tags {
+                    println!("{}", tag);
+                }
+            }
+        }
+        "delete" => {
+            if args.positional.is_empty() {
+                return Err(usage("Missing clip id"));
+            }
+            // Check every id first so nothing is deleted on a typo
+            for id in &args.positional {
+                manager.get_clip(id)?;
+            }
+            for id in &args.positional {
+                manager.delete_clip(id)?;
+            }
+            if json {
+                print_json(&json!({ "deleted": args.positional.len() }));
+            }
+        }
+        "cleanup" => {
+            if !args.positional.is_empty() {
+                return Err(usage("Too many arguments"));
+            }
+            let deleted = cleanup(&manager, args)?;
+            if json {
+                print_json(&json!({ "deleted": deleted }));
+            } else {
+                println!("Deleted {} clips", deleted);
+            }
+        }
+        "export" => {
+            let path = args.single("export path")?;
+            let filter = args.filter()?;
+            let exported = {
+                let db = manager.db.lock().map_err(|e| e.to_string())?;
+                export_history(&db, Path::new(path), &filter)?
+            };
+            if json {
+                print_json(&json!({ "exported": exported, "path": path }));
+            } else {
+                println!("Exported {} clips to {}", exported, path);
+            }
+        }
+        _ => return Err(usage(&format!("Unknown command {}", command))),
+    }
+
+    Ok(())
+}
+
+fn add_tags(manager: &ClipboardManager, id: &str, tags: &[String]) -> Result<(), String> {
+    if tags.is_empty() {
+        return Ok(());
+    }
+
+    let mut all = manager.get_clip(id)?.tags;
+    for tag in tags {
+        let tag = tag.trim();
+        if !tag.is_empty() && !all.iter().any(|t| t == tag) {
+            all.push(tag.to_string());
+        }
+    }
+    manager.update_tags(id, all)
+}
+
+fn cleanup(manager: &ClipboardManager, args: &Args) -> Result<usize, CliError> {
+    let db = manager.db.lock().map_err(|e| e.to_string())?;
+
+    let deleted = if let Some(days) = args.value("older-than") {
+        let days: i64 = days
+            .parse()
+            .ok()
+            .filter(|days| *days > 0)
+            .ok_or_else(|| usage("--older-than must be a positive number of days"))?;
+        db.cleanup_old_clips(days)
+    } else if let Some(before) = args.value("before") {
+        db.cleanup_before(crate::parse_date_start(before).map_err(CliError::Usage)?)
+    } else if let (Some(from), Some(to)) = (args.value("from"), args.value("to")) {
+        let start = crate::parse_date_start(from).map_err(CliError::Usage)?;
+        let end = crate::parse_date_end_exclusive(to).map_err(CliError::Usage)?;
+        if end <= start {
+            return Err(usage("--to must not be before --from"));
+        }
+        db.cleanup_between(start, end)
+    } else {
+        return Err(usage(
+            "cleanup needs --older-than, --before, or --from and --to",
+        ));
+    };
+
+    deleted.map_err(|e| CliError::Failed(e.to_string()))
+}
+
+fn print_clips(clips: &[ClipItem], json: bool) {
+    if json {
+        print_json(&clips);
+        return;
+    }
+
+    for clip in clips {
+        let preview: String = clip
+            .preview_text
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .chars()
+            .take(80)
+            .collect();
+        let tags = if clip.tags.is_empty() {
+            String::new()
+        } else {
+            format!("  [{}]", clip.tags.join(", "))
+        };
+        println!(
+            "{}  {:<5} {} {}{}",
+            clip.id,
+            clip.content_type.as_str(),
+            if clip.pinned { '*' } else { ' ' },
+            preview,
+            tags
+        );
+    }
+}
+
+fn print_json<T: serde::Serialize + ?Sized>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => eprintln!("fat-clip: {}", e),
+    }
+}
+
+/// Release builds on Windows have no console of their own; print to the one
+/// the command was started from
+fn attach_console() {
+    #[cfg(windows)]
+    unsafe {
+        use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Args::parse(&args)
+    }
+
+    #[test]
+    fn options_are_parsed_with_their_values() {
+        let args = parse(&["hello", "--tag", "a", "--tag=b", "--json", "world"]).unwrap();
+        assert_eq!(args.positional, vec!["hello", "world"]);
+        assert_eq!(args.values("tag"), vec!["a", "b"]);
+        assert_eq!(args.value("tag"), Some("b"));
+        assert!(args.flag("json"));
+        assert!(!args.flag("pinned"));
+    }
+
+    #[test]
+    fn arguments_after_a_double_dash_are_positional() {
+        let args = parse(&["--limit", "5", "--", "--json", "-x"]).unwrap();
+        assert_eq!(args.positional, vec!["--json", "-x"]);
+        assert_eq!(args.limit().ok(), Some(5));
+        assert!(!args.flag("json"));
+    }
+
+    #[test]
+    fn invalid_options_are_reported() {
+        assert_eq!(parse(&["--limit"]).err().unwrap(), "--limit needs a value");
+        assert_eq!(
+            parse(&["--verbose"]).err().unwrap(),
+            "Unknown option --verbose"
+        );
+        assert!(parse(&["--limit", "0"]).unwrap().limit().is_err());
+        assert!(parse(&["--from", "yesterday"]).unwrap().filter().is_err());
+    }
+
+    #[test]
+    fn single_needs_exactly_one_argument() {
+        assert_eq!(parse(&["abc"]).unwrap().single("clip id").ok(), Some("abc"));
+        assert!(parse(&[]).unwrap().single("clip id").is_err());
+        assert!(parse(&["a", "b"]).unwrap().single("clip id").is_err());
+    }
+}
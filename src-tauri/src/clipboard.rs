@@ -1,29 +1,178 @@
+use crate::auto_tag::{AutoTagRule, AutoTagger};
+use crate::clipboard_writer;
 use crate::db::{
-    generate_content_hash, generate_image_hash, get_images_dir, get_thumbnails_dir, ClipItem,
-    ContentType, Database, FileMetadata, ImageMetadata, RichTextMetadata,
+    generate_content_hash, generate_image_hash, get_images_dir, get_thumbnails_dir,
+    rich_content_hash, ClipItem, ClipRevision, ContentType, Database, FileMetadata, ImageMetadata,
+    RichTextContent, RichTextMetadata,
 };
+use crate::hooks::{HookRunner, HookTrigger};
+use crate::merge::{self, MergeMode, MergeSeparator, Merged};
+use crate::paste_queue::PasteQueue;
+use crate::plugins::PluginHost;
 use chrono::Utc;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a clipboard write made by the app is attributed to the app itself.
+/// Covers a few monitor polling intervals.
+const SELF_WRITE_WINDOW: Duration = Duration::from_millis(1500);
+
+/// Revisions kept per edited clip
+const MAX_CLIP_REVISIONS: usize = 50;
+
+/// Marker left by the app when it writes to the system clipboard, so the
+/// monitor does not capture the app's own writes as new clips.
+struct SelfWrite {
+    clip_id: Option<String>,
+    text_hash: Option<String>,
+    written_at: Instant,
+    use_recorded: bool,
+}
 
 pub struct ClipboardManager {
     pub db: Arc<Mutex<Database>>,
+    pub paste_queue: PasteQueue,
+    pub hooks: HookRunner,
+    pub plugins: PluginHost,
+    self_write: Mutex<Option<SelfWrite>>,
+    auto_tagger: Mutex<AutoTagger>,
+    // Set while the history is being replaced, e.g. by a restore
+    capture_paused: AtomicBool,
 }
 
 impl ClipboardManager {
     pub fn new(db: Arc<Mutex<Database>>) -> Self {
-        ClipboardManager { db }
+        ClipboardManager {
+            plugins: PluginHost::new(db.clone()),
+            db,
+            paste_queue: PasteQueue::new(),
+            hooks: HookRunner::new(),
+            self_write: Mutex::new(None),
+            auto_tagger: Mutex::new(AutoTagger::default()),
+            capture_paused: AtomicBool::new(false),
+        }
+    }
+
+    /// Stop or resume capturing clipboard changes
+    pub fn set_capture_paused(&self, paused: bool) {
+        self.capture_paused.store(paused, Ordering::SeqCst);
+    }
+
+    pub fn is_capture_paused(&self) -> bool {
+        self.capture_paused.load(Ordering::SeqCst)
+    }
+
+    /// Replace the rules applied to new captures
+    pub fn set_auto_tag_rules(&self, rules: &[AutoTagRule]) -> Result<(), String> {
+        let tagger = AutoTagger::new(rules)?;
+        let mut current = self.auto_tagger.lock().map_err(|e| e.to_string())?;
+        *current = tagger;
+        Ok(())
+    }
+
+    /// Store a new clip after applying the auto-tag rules. `fire_hooks` is
+    /// false for clips added in bulk, like imports, which would flood the hooks.
+    fn insert_new_clip(&self, mut item: ClipItem, fire_hooks: bool) -> Result<ClipItem, String> {
+        if let Ok(tagger) = self.auto_tagger.lock() {
+            tagger.apply(&mut item);
+        }
+
+        {
+            let db = self.db.lock().map_err(|e| e.to_string())?;
+            db.insert_clip(&item).map_err(|e| e.to_string())?;
+        }
+
+        if fire_hooks {
+            self.hooks.fire(HookTrigger::NewClip, &item, None);
+        }
+        self.plugins.process_new_clip(&item);
+        Ok(item)
+    }
+
+    /// Mark the upcoming clipboard change as written by the app.
+    ///
+    /// `clip_id` is the history item being re-used, if any. `text` is the plain
+    /// text placed on the clipboard; when given, only a text change with exactly
+    /// that content is treated as the app's own write, not an image or file
+    /// change.
+    pub fn mark_self_write(&self, clip_id: Option<String>, text: Option<&str>) {
+        if let Ok(mut marker) = self.self_write.lock() {
+            *marker = Some(SelfWrite {
+                clip_id,
+                text_hash: text.map(generate_content_hash),
+                written_at: Instant::now(),
+                use_recorded: false,
+            });
+        }
+    }
+
+    /// Check whether a detected clipboard change was written by the app.
+    ///
+    /// `text` is the detected plain text, or `None` for other formats. The first
+    /// match records a use of the re-selected clip instead of a new capture.
+    pub fn is_self_write(&self, text: Option<&str>) -> bool {
+        let mut marker = match self.self_write.lock() {
+            Ok(marker) => marker,
+            Err(_) => return false,
+        };
+
+        let write = match marker.as_mut() {
+            Some(write) => write,
+            None => return false,
+        };
+
+        if write.written_at.elapsed() > SELF_WRITE_WINDOW {
+            *marker = None;
+            return false;
+        }
+
+        // A text write only covers that text, and a write of another format
+        // only covers changes without text
+        let matches = match (&write.text_hash, text) {
+            (Some(expected), Some(text)) => *expected == generate_content_hash(text),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        if !matches {
+            return false;
+        }
+
+        if !write.use_recorded {
+            write.use_recorded = true;
+            if let Some(id) = &write.clip_id {
+                if let Err(e) = self.record_clip_use(id) {
+                    eprintln!("Failed to record clip use: {}", e);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Update `last_used_at` and the use counter of a re-selected clip
+    pub fn record_clip_use(&self, id: &str) -> Result<(), String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.record_clip_use(id).map_err(|e| e.to_string())
     }
 
     /// Save plain text clip
-    pub fn save_text_clip(&self, text: String, source_app: String) -> Result<ClipItem, String> {
+    pub fn save_text_clip(
+        &self,
+        text: String,
+        source_app: String,
+        fire_hooks: bool,
+    ) -> Result<ClipItem, String> {
         // Check for duplicates
         let content_hash = generate_content_hash(&text);
 
-        {
+        let id = {
             let db = self.db.lock().map_err(|e| e.to_string())?;
             if let Some(existing) = db
-                .get_clip_by_content_hash(&content_hash)
+                .find_duplicate_clip(&content_hash)
                 .map_err(|e| e.to_string())?
             {
                 // Update last_used_at for existing item
@@ -32,17 +181,15 @@ impl ClipboardManager {
                 db.insert_clip(&updated).map_err(|e| e.to_string())?;
                 return Ok(updated);
             }
-        }
-
-        // Create preview text (first 200 chars)
-        let preview_text = if text.len() > 200 {
-            format!("{}...", &text[..200])
-        } else {
-            text.clone()
+            db.new_clip_id(&content_hash).map_err(|e| e.to_string())?
         };
 
+        let preview_text = text_preview(&text);
+
+        let metadata = with_classification(None, &text);
+
         let item = ClipItem {
-            id: content_hash,
+            id,
             content_type: ContentType::Plain,
             content: text,
             preview_text,
@@ -51,15 +198,11 @@ impl ClipboardManager {
             created_at: Utc::now(),
             last_used_at: Utc::now(),
             pinned: false,
-            metadata: None,
+            use_count: 0,
+            metadata,
         };
 
-        {
-            let db = self.db.lock().map_err(|e| e.to_string())?;
-            db.insert_clip(&item).map_err(|e| e.to_string())?;
-        }
-
-        Ok(item)
+        self.insert_new_clip(item, fire_hooks)
     }
 
     /// Save image clip from clipboard
@@ -70,6 +213,7 @@ impl ClipboardManager {
         height: u32,
         format: &str,
         source_app: String,
+        fire_hooks: bool,
     ) -> Result<ClipItem, String> {
         // Generate hash from image data
         let content_hash = generate_image_hash(&image_data);
@@ -77,7 +221,7 @@ impl ClipboardManager {
         {
             let db = self.db.lock().map_err(|e| e.to_string())?;
             if let Some(existing) = db
-                .get_clip_by_content_hash(&content_hash)
+                .find_duplicate_clip(&content_hash)
                 .map_err(|e| e.to_string())?
             {
                 // Update last_used_at for existing item
@@ -130,15 +274,11 @@ impl ClipboardManager {
             created_at: Utc::now(),
             last_used_at: Utc::now(),
             pinned: false,
+            use_count: 0,
             metadata: Some(serde_json::to_value(metadata).unwrap_or_default()),
         };
 
-        {
-            let db = self.db.lock().map_err(|e| e.to_string())?;
-            db.insert_clip(&item).map_err(|e| e.to_string())?;
-        }
-
-        Ok(item)
+        self.insert_new_clip(item, fire_hooks)
     }
 
     /// Save rich text (HTML/RTF) clip
@@ -148,20 +288,16 @@ impl ClipboardManager {
         rtf_content: Option<String>,
         plain_text: String,
         source_app: String,
+        fire_hooks: bool,
     ) -> Result<ClipItem, String> {
         // Create a combined content hash
-        let content_to_hash = format!(
-            "{}{}{}",
-            html_content.as_ref().unwrap_or(&String::new()),
-            rtf_content.as_ref().unwrap_or(&String::new()),
-            plain_text
-        );
-        let content_hash = generate_content_hash(&content_to_hash);
+        let content_hash =
+            rich_content_hash(html_content.as_deref(), rtf_content.as_deref(), &plain_text);
 
         {
             let db = self.db.lock().map_err(|e| e.to_string())?;
             if let Some(existing) = db
-                .get_clip_by_content_hash(&content_hash)
+                .find_duplicate_clip(&content_hash)
                 .map_err(|e| e.to_string())?
             {
                 // Update last_used_at for existing item
@@ -181,11 +317,7 @@ impl ClipboardManager {
         .to_string();
 
         // Create preview text from plain text
-        let preview_text = if plain_text.len() > 200 {
-            format!("{}...", &plain_text[..200])
-        } else {
-            plain_text.clone()
-        };
+        let preview_text = text_preview(&plain_text);
 
         // Create metadata
         let metadata = RichTextMetadata {
@@ -210,15 +342,14 @@ impl ClipboardManager {
             created_at: Utc::now(),
             last_used_at: Utc::now(),
             pinned: false,
-            metadata: Some(serde_json::to_value(metadata).unwrap_or_default()),
+            use_count: 0,
+            metadata: with_classification(
+                Some(serde_json::to_value(metadata).unwrap_or_default()),
+                &plain_text,
+            ),
         };
 
-        {
-            let db = self.db.lock().map_err(|e| e.to_string())?;
-            db.insert_clip(&item).map_err(|e| e.to_string())?;
-        }
-
-        Ok(item)
+        self.insert_new_clip(item, fire_hooks)
     }
 
     /// Save file paths clip
@@ -226,6 +357,7 @@ impl ClipboardManager {
         &self,
         file_paths: Vec<String>,
         source_app: String,
+        fire_hooks: bool,
     ) -> Result<ClipItem, String> {
         // Create content hash from file paths
         let content = file_paths.join("\n");
@@ -234,7 +366,7 @@ impl ClipboardManager {
         {
             let db = self.db.lock().map_err(|e| e.to_string())?;
             if let Some(existing) = db
-                .get_clip_by_content_hash(&content_hash)
+                .find_duplicate_clip(&content_hash)
                 .map_err(|e| e.to_string())?
             {
                 // Update last_used_at for existing item
@@ -290,15 +422,11 @@ impl ClipboardManager {
             created_at: Utc::now(),
             last_used_at: Utc::now(),
             pinned: false,
+            use_count: 0,
             metadata: Some(serde_json::to_value(metadata).unwrap_or_default()),
         };
 
-        {
-            let db = self.db.lock().map_err(|e| e.to_string())?;
-            db.insert_clip(&item).map_err(|e| e.to_string())?;
-        }
-
-        Ok(item)
+        self.insert_new_clip(item, fire_hooks)
     }
 
     /// Convert image to PNG format
@@ -354,6 +482,235 @@ impl ClipboardManager {
         Ok(thumbnail_path)
     }
 
+    pub fn get_clip(&self, id: &str) -> Result<ClipItem, String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.get_clip_by_id(id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Clip not found".to_string())
+    }
+
+    /// Write a rich text clip back to the clipboard with all of its stored formats
+    pub fn write_rich_clip(&self, id: &str) -> Result<(), String> {
+        let clip = self.get_clip(id)?;
+        if clip.content_type != ContentType::Rich {
+            return Err("Clip is not rich text".to_string());
+        }
+
+        let rich = parse_rich_content(&clip.content)?;
+        let plain = rich_plain_text(&rich);
+
+        self.mark_self_write(Some(clip.id), Some(&plain));
+        clipboard_writer::write_rich_text(rich.html.as_deref(), rich.rtf.as_deref(), &plain)
+    }
+
+    /// Write a clip back to the clipboard as unformatted text
+    pub fn write_clip_as_plain_text(&self, id: &str) -> Result<(), String> {
+        let clip = self.get_clip(id)?;
+        let text = clip_plain_text(&clip)?;
+
+        self.mark_self_write(Some(clip.id), Some(&text));
+        clipboard_writer::write_text(&text)
+    }
+
+    /// Write any clip back to the clipboard in its original format
+    pub fn write_clip(&self, id: &str) -> Result<(), String> {
+        let clip = self.get_clip(id)?;
+        match clip.content_type {
+            ContentType::Plain => {
+                self.mark_self_write(Some(clip.id), Some(&clip.content));
+                clipboard_writer::write_text(&clip.content)
+            }
+            ContentType::Rich => self.write_rich_clip(id),
+            ContentType::File => self.write_file_clip(id, true).map(|_| ()),
+            ContentType::Image => {
+                let image_data = std::fs::read(&clip.content)
+                    .map_err(|e| format!("Failed to read image: {}", e))?;
+                self.mark_self_write(Some(clip.id), None);
+                clipboard_writer::write_image(&image_data)
+            }
+        }
+    }
+
+    /// Paths of a file clip that no longer exist on disk
+    pub fn get_missing_files(&self, id: &str) -> Result<Vec<String>, String> {
+        let clip = self.get_clip(id)?;
+        let (_, missing) = split_file_clip_paths(&clip)?;
+        Ok(missing)
+    }
+
+    /// Write a file clip back to the clipboard as real files.
+    ///
+    /// Missing files are reported as an error unless `skip_missing` is set, in
+    /// which case the remaining files are written and the skipped paths returned.
+    pub fn write_file_clip(&self, id: &str, skip_missing: bool) -> Result<Vec<String>, String> {
+        let clip = self.get_clip(id)?;
+        let (existing, missing) = split_file_clip_paths(&clip)?;
+
+        if !missing.is_empty() && !skip_missing {
+            return Err(format!("Files no longer exist: {}", missing.join(", ")));
+        }
+        if existing.is_empty() {
+            return Err("None of the files exist anymore".to_string());
+        }
+
+        self.mark_self_write(Some(clip.id), None);
+        clipboard_writer::write_files(&existing)?;
+        Ok(missing)
+    }
+
+    /// Put contents saved with `clipboard_writer::save_clipboard` back on the
+    /// clipboard without capturing them as a new clip
+    pub fn restore_clipboard(&self, saved: clipboard_writer::SavedClipboard) -> Result<(), String> {
+        self.mark_self_write(None, saved.text());
+        clipboard_writer::restore_clipboard(saved)
+    }
+
+    /// Delete `erase_count` characters before the caret with Backspace, then
+    /// paste `text` in their place
+    pub fn erase_and_paste(&self, erase_count: usize, text: &str) -> Result<(), String> {
+        clipboard_writer::tap_key(rdev::Key::Backspace, erase_count)?;
+
+        // Small delay before pasting
+        std::thread::sleep(Duration::from_millis(50));
+
+        self.mark_self_write(None, Some(text));
+        clipboard_writer::write_text(text)?;
+        clipboard_writer::simulate_paste()
+    }
+
+    /// Delete `erase_count` characters before the caret with Backspace, then
+    /// paste clip `id` in their place in its original format
+    pub fn erase_and_paste_clip(&self, erase_count: usize, id: &str) -> Result<(), String> {
+        // Written first, so nothing is erased if the clip cannot be written
+        self.write_clip(id)?;
+
+        clipboard_writer::tap_key(rdev::Key::Backspace, erase_count)?;
+
+        // Small delay before pasting
+        std::thread::sleep(Duration::from_millis(50));
+
+        clipboard_writer::simulate_paste()
+    }
+
+    /// Classify text clips captured before classification existed. Returns the
+    /// number of clips updated.
+    pub fn classify_history(&self) -> Result<usize, String> {
+        const BATCH_SIZE: i64 = 200;
+        let mut updated = 0;
+
+        loop {
+            let db = self.db.lock().map_err(|e| e.to_string())?;
+            let clips = db
+                .get_unclassified_clips(BATCH_SIZE)
+                .map_err(|e| e.to_string())?;
+            if clips.is_empty() {
+                return Ok(updated);
+            }
+
+            for clip in clips {
+                let text = clip_plain_text(&clip).unwrap_or_default();
+                let metadata = with_classification(clip.metadata, &text);
+                db.update_clip_metadata(&clip.id, metadata.as_ref())
+                    .map_err(|e| e.to_string())?;
+                updated += 1;
+            }
+        }
+    }
+
+    /// Replace the text of a plain text clip. The clip keeps its id, so tags,
+    /// pins and collections stay attached, and is found as a duplicate by its
+    /// new text; the old text becomes a revision.
+    pub fn update_clip_content(&self, id: &str, text: String) -> Result<ClipItem, String> {
+        if text.is_empty() {
+            return Err("Clip content cannot be empty".to_string());
+        }
+
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        let mut clip = db
+            .get_clip_by_id(id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Clip not found".to_string())?;
+
+        if clip.content_type != ContentType::Plain {
+            return Err("Only plain text clips can be edited".to_string());
+        }
+        if clip.content == text {
+            return Ok(clip);
+        }
+
+        clip.preview_text = text_preview(&text);
+        clip.metadata = with_classification(clip.metadata.take(), &text);
+        clip.content = text;
+
+        db.update_clip_content(
+            id,
+            &clip.content,
+            &clip.preview_text,
+            clip.metadata.as_ref(),
+            MAX_CLIP_REVISIONS,
+        )
+        .map_err(|e| e.to_string())?;
+
+        Ok(clip)
+    }
+
+    pub fn get_clip_revisions(&self, id: &str) -> Result<Vec<ClipRevision>, String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.get_clip_revisions(id).map_err(|e| e.to_string())
+    }
+
+    /// Restore the text of an earlier revision. The text being replaced is kept
+    /// as a revision too, so reverting can be undone.
+    pub fn revert_clip(&self, id: &str, revision_id: i64) -> Result<ClipItem, String> {
+        let revision = {
+            let db = self.db.lock().map_err(|e| e.to_string())?;
+            db.get_clip_revision(revision_id)
+                .map_err(|e| e.to_string())?
+                .filter(|revision| revision.clip_id == id)
+                .ok_or_else(|| "Revision not found".to_string())?
+        };
+
+        self.update_clip_content(id, revision.content)
+    }
+
+    /// Merge clips, in the given order, into a new clip. The ids of the merged
+    /// clips are recorded in its metadata as `merged_from`.
+    pub fn merge_clips(
+        &self,
+        ids: &[String],
+        separator: &MergeSeparator,
+        mode: MergeMode,
+    ) -> Result<ClipItem, String> {
+        let clips = ids
+            .iter()
+            .map(|id| self.get_clip(id))
+            .collect::<Result<Vec<_>, String>>()?;
+        let source_app = clips
+            .first()
+            .map(|clip| clip.source_app.clone())
+            .unwrap_or_default();
+
+        let mut clip = match merge::merge_clips(&clips, separator, mode)? {
+            Merged::Text(text) => self.save_text_clip(text, source_app, true)?,
+            Merged::Rich { html, plain } => {
+                self.save_rich_text_clip(Some(html), None, plain, source_app, true)?
+            }
+            Merged::Files(paths) => self.save_file_clip(paths, source_app, true)?,
+        };
+
+        let mut metadata = match clip.metadata.take() {
+            Some(serde_json::Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        };
+        metadata.insert("merged_from".to_string(), serde_json::json!(ids));
+        clip.metadata = Some(serde_json::Value::Object(metadata));
+
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.update_clip_metadata(&clip.id, clip.metadata.as_ref())
+            .map_err(|e| e.to_string())?;
+        Ok(clip)
+    }
+
     pub fn get_recent_clips(&self, limit: i64) -> Result<Vec<ClipItem>, String> {
         let db = self.db.lock().map_err(|e| e.to_string())?;
         db.get_clips(limit, 0).map_err(|e| e.to_string())
@@ -365,36 +722,52 @@ impl ClipboardManager {
     }
 
     pub fn update_tags(&self, id: &str, tags: Vec<String>) -> Result<(), String> {
-        let db = self.db.lock().map_err(|e| e.to_string())?;
-        db.update_clip_tags(id, tags).map_err(|e| e.to_string())
+        let previous = {
+            let db = self.db.lock().map_err(|e| e.to_string())?;
+            let previous = db.get_clip_by_id(id).map_err(|e| e.to_string())?;
+            db.update_clip_tags(id, tags.clone())
+                .map_err(|e| e.to_string())?;
+            previous
+        };
+
+        if let Some(mut clip) = previous {
+            let added: Vec<String> = tags
+                .iter()
+                .filter(|tag| !clip.tags.contains(tag))
+                .cloned()
+                .collect();
+            clip.tags = tags;
+            for tag in added {
+                self.hooks.fire(HookTrigger::TagAdded, &clip, Some(&tag));
+            }
+        }
+        Ok(())
     }
 
     pub fn toggle_pin(&self, id: &str, pinned: bool) -> Result<(), String> {
-        let db = self.db.lock().map_err(|e| e.to_string())?;
-        db.toggle_pin(id, pinned).map_err(|e| e.to_string())
+        let previous = {
+            let db = self.db.lock().map_err(|e| e.to_string())?;
+            let previous = db.get_clip_by_id(id).map_err(|e| e.to_string())?;
+            db.toggle_pin(id, pinned).map_err(|e| e.to_string())?;
+            previous
+        };
+
+        if let Some(mut clip) = previous.filter(|clip| pinned && !clip.pinned) {
+            clip.pinned = true;
+            self.hooks.fire(HookTrigger::Pin, &clip, None);
+        }
+        Ok(())
     }
 
     pub fn delete_clip(&self, id: &str) -> Result<(), String> {
         // Get clip info first to delete associated files if it's an image
         let clip = {
             let db = self.db.lock().map_err(|e| e.to_string())?;
-            db.get_clip_by_content_hash(id).map_err(|e| e.to_string())?
+            db.get_clip_by_id(id).map_err(|e| e.to_string())?
         };
 
         if let Some(clip) = clip {
-            if clip.content_type == ContentType::Image {
-                // Delete image file
-                let _ = std::fs::remove_file(&clip.content);
-
-                // Delete thumbnail if exists
-                if let Some(metadata) = clip.metadata {
-                    if let Ok(img_metadata) = serde_json::from_value::<ImageMetadata>(metadata) {
-                        if let Some(thumb_path) = img_metadata.thumbnail_path {
-                            let _ = std::fs::remove_file(thumb_path);
-                        }
-                    }
-                }
-            }
+            remove_clip_files(&clip);
         }
 
         let db = self.db.lock().map_err(|e| e.to_string())?;
@@ -405,7 +778,7 @@ impl ClipboardManager {
     pub fn get_image_data(&self, clip_id: &str) -> Result<Vec<u8>, String> {
         let db = self.db.lock().map_err(|e| e.to_string())?;
         let clip = db
-            .get_clip_by_content_hash(clip_id)
+            .get_clip_by_id(clip_id)
             .map_err(|e| e.to_string())?
             .ok_or_else(|| "Clip not found".to_string())?;
 
@@ -420,7 +793,7 @@ impl ClipboardManager {
     pub fn get_thumbnail_data(&self, clip_id: &str) -> Result<Vec<u8>, String> {
         let db = self.db.lock().map_err(|e| e.to_string())?;
         let clip = db
-            .get_clip_by_content_hash(clip_id)
+            .get_clip_by_id(clip_id)
             .map_err(|e| e.to_string())?
             .ok_or_else(|| "Clip not found".to_string())?;
 
@@ -441,3 +814,692 @@ impl ClipboardManager {
         self.get_image_data(clip_id)
     }
 }
+
+/// Preview of a text clip: the first 200 characters
+fn text_preview(text: &str) -> String {
+    match text.char_indices().nth(200) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+fn parse_rich_content(content: &str) -> Result<RichTextContent, String> {
+    serde_json::from_str(content).map_err(|e| format!("Invalid rich text content: {}", e))
+}
+
+/// Plain text of a rich clip, derived from the HTML if no plain text was stored
+fn rich_plain_text(rich: &RichTextContent) -> String {
+    if !rich.plain.is_empty() {
+        return rich.plain.clone();
+    }
+    rich.html
+        .as_deref()
+        .map(html_to_plain_text)
+        .unwrap_or_default()
+}
+
+/// Split the paths of a file clip into those that still exist and those that don't
+fn split_file_clip_paths(clip: &ClipItem) -> Result<(Vec<String>, Vec<String>), String> {
+    if clip.content_type != ContentType::File {
+        return Err("Clip is not a file list".to_string());
+    }
+
+    let paths: Vec<String> = serde_json::from_str(&clip.content)
+        .map_err(|e| format!("Invalid file list content: {}", e))?;
+    Ok(paths
+        .into_iter()
+        .partition(|path| std::path::Path::new(path).exists()))
+}
+
+/// Delete the image and thumbnail files of an image clip
+pub fn remove_clip_files(clip: &ClipItem) {
+    if clip.content_type != ContentType::Image {
+        return;
+    }
+
+    let _ = std::fs::remove_file(&clip.content);
+
+    if let Some(metadata) = clip.metadata.clone() {
+        if let Ok(img_metadata) = serde_json::from_value::<ImageMetadata>(metadata) {
+            if let Some(thumb_path) = img_metadata.thumbnail_path {
+                let _ = std::fs::remove_file(thumb_path);
+            }
+        }
+    }
+}
+
+/// Text representation of a clip with all formatting stripped
+pub fn clip_plain_text(clip: &ClipItem) -> Result<String, String> {
+    match clip.content_type {
+        ContentType::Plain => Ok(clip.content.clone()),
+        ContentType::Rich => Ok(rich_plain_text(&parse_rich_content(&clip.content)?)),
+        ContentType::File => {
+            let paths: Vec<String> = serde_json::from_str(&clip.content).unwrap_or_default();
+            Ok(paths.join("\n"))
+        }
+        ContentType::Image => Err("Image clips have no text".to_string()),
+    }
+}
+
+/// What a piece of text is, stored in the clip metadata as `kind` and
+/// `language`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextClassification {
+    /// One of "url", "email", "phone", "path", "color", "json", "xml", "html",
+    /// "base64", "uuid", "number", "code" or "text"
+    pub kind: String,
+    /// Guessed programming language for "code"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+impl TextClassification {
+    fn new(kind: &str) -> Self {
+        TextClassification {
+            kind: kind.to_string(),
+            language: None,
+        }
+    }
+}
+
+/// Kind of text a clip holds, e.g. "url" or "json"
+pub fn clip_kind(clip: &ClipItem) -> Option<String> {
+    if let Some(kind) = clip
+        .metadata
+        .as_ref()
+        .and_then(|m| m.get("kind"))
+        .and_then(|k| k.as_str())
+    {
+        return Some(kind.to_string());
+    }
+
+    match clip.content_type {
+        ContentType::Plain | ContentType::Rich => clip_plain_text(clip)
+            .ok()
+            .map(|text| classify_text(&text).kind),
+        ContentType::Image | ContentType::File => None,
+    }
+}
+
+/// Add the classification of `text` to a clip's metadata
+fn with_classification(
+    metadata: Option<serde_json::Value>,
+    text: &str,
+) -> Option<serde_json::Value> {
+    let classification = classify_text(text);
+    let mut metadata = match metadata {
+        Some(serde_json::Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+
+    metadata.insert("kind".to_string(), classification.kind.into());
+    match classification.language {
+        Some(language) => metadata.insert("language".to_string(), language.into()),
+        None => metadata.remove("language"),
+    };
+
+    Some(serde_json::Value::Object(metadata))
+}
+
+static UUID_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?i)[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}$").unwrap()
+});
+
+static COLOR_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?i)(#([0-9a-f]{3}|[0-9a-f]{4}|[0-9a-f]{6}|[0-9a-f]{8})|(rgb|hsl)a?\(\s*[\d.]+%?\s*[, ]\s*[\d.]+%?\s*[, ]\s*[\d.]+%?\s*([,/]\s*[\d.]+%?\s*)?\))$",
+    )
+    .unwrap()
+});
+
+static URL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?i)([a-z][a-z0-9+.-]*://\S+|www\.[^\s.]+\.\S+)$").unwrap());
+
+static EMAIL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?i)(mailto:)?[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,}$").unwrap());
+
+static PHONE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\+?\(?\d[\d\s().-]{5,}\d$").unwrap());
+
+static PATH_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^([a-zA-Z]:[\\/]|\\\\[^\\\s]+\\|~/|\.{1,2}/|/[^/\s]+(/|$))").unwrap()
+});
+
+static NUMBER_WITH_UNIT_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?i)([$€£¥]\s?[-+]?\d[\d,]*(\.\d+)?|[-+]?\d[\d,]*(\.\d+)?\s?(%|°[cf]?|px|pt|em|rem|vh|vw|[kmgt]?b|[kmgt]ib|[kmgc]?m|mm|µm|in|ft|mi|[km]?g|lbs?|oz|ms|s|min|h|hz|[kmg]hz|ml|l|[km]?w|v|ma|mah|kcal|usd|eur|gbp|cny|rmb|元|円))$",
+    )
+    .unwrap()
+});
+
+static BASE64_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(data:[\w/+.-]+;base64,)?[A-Za-z0-9+/\r\n]+={0,2}$").unwrap());
+
+/// Classify a piece of text.
+///
+/// Single-line values (URLs, colors, ...) are checked first; multi-line text is
+/// checked for structured data and source code. Anything else is "text".
+pub fn classify_text(text: &str) -> TextClassification {
+    let text = text.trim();
+    if text.is_empty() {
+        return TextClassification::new("text");
+    }
+
+    if !text.contains('\n') {
+        if let Some(kind) = classify_single_line(text) {
+            return TextClassification::new(kind);
+        }
+    }
+
+    let bracketed = (text.starts_with('{') && text.ends_with('}'))
+        || (text.starts_with('[') && text.ends_with(']'));
+    if bracketed && serde_json::from_str::<serde_json::Value>(text).is_ok() {
+        return TextClassification::new("json");
+    }
+
+    if text.starts_with('<') && text.ends_with('>') {
+        return TextClassification::new(markup_kind(text));
+    }
+
+    if is_base64(text) {
+        return TextClassification::new("base64");
+    }
+
+    if let Some(code) = detect_code(text) {
+        return code;
+    }
+
+    TextClassification::new("text")
+}
+
+fn classify_single_line(text: &str) -> Option<&'static str> {
+    if UUID_RE.is_match(text) {
+        Some("uuid")
+    } else if COLOR_RE.is_match(text) {
+        Some("color")
+    } else if URL_RE.is_match(text) {
+        Some("url")
+    } else if EMAIL_RE.is_match(text) {
+        Some("email")
+    } else if NUMBER_WITH_UNIT_RE.is_match(text) {
+        Some("number")
+    } else if PHONE_RE.is_match(text) && (7..=15).contains(&count_digits(text)) {
+        Some("phone")
+    } else if PATH_RE.is_match(text) && !text.contains("  ") {
+        Some("path")
+    } else {
+        None
+    }
+}
+
+fn count_digits(text: &str) -> usize {
+    text.chars().filter(char::is_ascii_digit).count()
+}
+
+/// "html" for HTML documents and fragments, "xml" for other markup
+fn markup_kind(text: &str) -> &'static str {
+    const HTML_MARKERS: [&str; 10] = [
+        "<!doctype html",
+        "<html",
+        "<body",
+        "<div",
+        "<span",
+        "<p>",
+        "<a ",
+        "<table",
+        "<br",
+        "<img",
+    ];
+
+    let lower = text.to_lowercase();
+    if HTML_MARKERS.iter().any(|marker| lower.contains(marker)) {
+        "html"
+    } else {
+        "xml"
+    }
+}
+
+/// Base64 needs a plausible length and padding, some non-letter characters
+/// (to rule out plain words) and has to decode
+fn is_base64(text: &str) -> bool {
+    use base64::Engine;
+
+    if !BASE64_RE.is_match(text) {
+        return false;
+    }
+
+    let data = text
+        .split_once(";base64,")
+        .map(|(_, data)| data)
+        .unwrap_or(text);
+    let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+
+    if data.len() < 16 || !data.len().is_multiple_of(4) {
+        return false;
+    }
+    if data.chars().all(|c| c.is_ascii_alphabetic()) {
+        return false;
+    }
+
+    base64::engine::general_purpose::STANDARD
+        .decode(&data)
+        .is_ok()
+}
+
+/// Share of the non-empty lines that have to look like code
+const MIN_CODE_LINE_RATIO: f32 = 0.6;
+
+/// Lines a language's patterns have to match before it is named, fewer only
+/// for shorter text
+const MIN_LANGUAGE_LINES: usize = 2;
+
+/// Per language, patterns that match single lines with a structure typical of
+/// it, such as a declaration or a statement with its punctuation. Keywords
+/// alone are not enough, they are ordinary words in prose.
+static LANGUAGE_PATTERNS: LazyLock<Vec<(&'static str, Regex)>> = LazyLock::new(|| {
+    let languages: [(&str, &[&str]); 11] = [
+        (
+            "rust",
+            &[
+                r"^\s*(pub(\([a-z]+\))?\s+)?(async\s+)?(unsafe\s+)?fn\s+\w+\s*[<(]",
+                r"^\s*(pub(\([a-z]+\))?\s+)?(struct|enum|trait|mod)\s+\w+.*[{;]\s*$",
+                r"^\s*impl(<.*>)?\s+\w.*\{\s*$",
+                r"^\s*use\s+[\w:]+(::\{.*\}|::\*)?;\s*$",
+                r"^\s*let\s+(mut\s+)?\w+(\s*:\s*[^=]+)?\s*=.*;\s*$",
+                r"\b\w+!\(.*\);?\s*$",
+            ],
+        ),
+        (
+            "python",
+            &[
+                r"^\s*(async\s+)?def\s+\w+\(.*\)(\s*->\s*.+)?:\s*$",
+                r"^\s*class\s+\w+(\(.*\))?:\s*$",
+                r"^\s*(from\s+[\w.]+\s+)?import\s+[\w.]+(\s+as\s+\w+)?(,\s*[\w.]+(\s+as\s+\w+)?)*\s*$",
+                r"^\s*(if|elif|while|for|with|try|except|else|finally)\b[^.!?]*:\s*$",
+                r"^\s*self\.\w+\s*=",
+                r"^\s*print\(.*\)\s*$",
+            ],
+        ),
+        (
+            "typescript",
+            &[
+                r"^\s*(export\s+)?(interface|type)\s+\w+(<.*>)?\s*(=|\{|extends)",
+                r"^\s*(export\s+)?(const|let|var)\s+\w+\s*:\s*[\w<>\[\]|. ]+\s*=",
+                r"\)\s*:\s*(string|number|boolean|void|any|unknown|Promise<.*>)\s*(\{|=>)",
+                r"^\s*(readonly\s+)?\w+\??\s*:\s*(string|number|boolean|[A-Z]\w*(<.*>)?)(\[\])?;\s*$",
+            ],
+        ),
+        (
+            "javascript",
+            &[
+                r"^\s*(export\s+(default\s+)?)?(async\s+)?function\*?\s*\w*\s*\(.*\)\s*\{?\s*$",
+                r"^\s*(export\s+)?(const|let|var)\s+[\w{}\[\], ]+\s*=\s*\S",
+                r"\(.*\)\s*=>\s*[{(]?",
+                r"^\s*(console\.\w+|document\.\w+|module\.exports|require)\b",
+                r#"^\s*import\s+.+\s+from\s+['"].+['"];?\s*$"#,
+            ],
+        ),
+        (
+            "java",
+            &[
+                r"^\s*(public|private|protected)\s+(static\s+)?(final\s+)?(abstract\s+)?(class|interface|enum|void|[\w<>\[\]]+)\s+\w+.*[{;]\s*$",
+                r"^\s*@(Override|Test|Autowired|Deprecated|FunctionalInterface)\b",
+                r"^\s*import\s+javax?\.[\w.*]+;\s*$",
+                r"^\s*package\s+[\w.]+;\s*$",
+                r"\bSystem\.(out|err)\.print",
+            ],
+        ),
+        (
+            "csharp",
+            &[
+                r"^\s*using\s+System(\.\w+)*;\s*$",
+                r"^\s*namespace\s+[\w.]+\s*[{;]?\s*$",
+                r"\{\s*get;\s*(private\s+)?(set;\s*)?\}",
+                r"\bConsole\.Write(Line)?\(",
+                r"^\s*(public|private|internal|protected)\s+(static\s+)?(async\s+)?(partial\s+)?(class|record|struct|void|Task|string|int|bool|var)\b.*[{;)]\s*$",
+            ],
+        ),
+        (
+            "cpp",
+            &[
+                r"^\s*#\s*(include|define|ifndef|ifdef|endif|pragma)\b",
+                r"\bstd::\w+",
+                r"^\s*(int|void|char|bool|auto|double|float|unsigned|size_t)\s+\**\w+\s*\(.*\)\s*[{;]?\s*$",
+                r"\b(cout|cerr)\s*<<",
+                r"\bnullptr\b",
+            ],
+        ),
+        (
+            "go",
+            &[
+                r"^\s*package\s+\w+\s*$",
+                r"^\s*func\s+(\(.*\)\s*)?\w+\(.*\).*\{\s*$",
+                r"^\s*\w+(,\s*\w+)*\s*:=\s*\S",
+                r"^\s*if\s+err\s*!=\s*nil\s*\{",
+                r#"^\s*import\s+(\(|"[\w./-]+")"#,
+                r"\bfmt\.\w+\(",
+            ],
+        ),
+        (
+            "css",
+            &[
+                r"^\s*([.#]?[\w-]+|\*)([\s>+~,.#:\[\]=()-]+[\w-]*)*\s*\{\s*$",
+                r"^\s*-?[a-z][a-z-]*\s*:\s*[^;{}]+;\s*$",
+                r"^\s*@(media|import|keyframes|font-face|supports)\b",
+            ],
+        ),
+        (
+            "sql",
+            &[
+                r"^\s*(SELECT|INSERT INTO|UPDATE|DELETE FROM|CREATE (TABLE|INDEX|VIEW)|ALTER TABLE|DROP TABLE|WITH)\b",
+                r"^\s*(FROM|WHERE|(LEFT |RIGHT |INNER |OUTER )?JOIN|GROUP BY|ORDER BY|HAVING|LIMIT|VALUES|SET)\b",
+                r"(?i)^\s*(select|insert|update|delete)\b.*;\s*$",
+            ],
+        ),
+        (
+            "shell",
+            &[
+                r"^#!/(usr/)?bin/(env\s+)?\w+",
+                r"^\s*(\$\s+)?(sudo\s+)?(apt(-get)?|brew|npm|yarn|pnpm|pip3?|cargo|git|docker|kubectl|systemctl)\s+[a-z][\w-]*",
+                r"^\s*(\$\s+)?(sudo\s+)?(cd|ls|mkdir|rm|cp|mv|chmod|chown|curl|wget|echo|grep|cat|tar|ssh|export|source)\b.*(\s-\w|/|\||&&|\$|=)",
+                r"\|\s*(grep|sed|awk|xargs|sort|uniq|head|tail|wc)\b",
+                r"^\s*(if|while|for)\s.*;\s*(then|do)\s*$",
+                r"^\s*(fi|done|esac)\s*$",
+            ],
+        ),
+    ];
+
+    languages
+        .iter()
+        .map(|(language, patterns)| {
+            let pattern = patterns
+                .iter()
+                .map(|pattern| format!("(?:{})", pattern))
+                .collect::<Vec<_>>()
+                .join("|");
+            (*language, Regex::new(&pattern).unwrap())
+        })
+        .collect()
+});
+
+/// Lines that look like code in any language: statement and block endings,
+/// closing brackets, comments, assignments and calls
+static CODE_LINE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"^\s*(.*[;{]\s*$|[}\])]+[;,)]*\s*$|(//|/\*|\*/)|[\w.\[\]'"]+\s*([-+*/%|&^]|\*\*|//)?=\s*[^=\s]|[\w.]+\(.*\)[;,]?\s*$|(return|break|continue|pass)\b[^.!?]*$)"#,
+    )
+    .unwrap()
+});
+
+/// Detect source code and guess its language.
+///
+/// Most non-empty lines have to look like code. The language is the one whose
+/// patterns match the most lines, if they match at least
+/// `MIN_LANGUAGE_LINES`. A single line only counts as code if a language
+/// pattern matches it.
+fn detect_code(text: &str) -> Option<TextClassification> {
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+
+    let mut language_lines = vec![0usize; LANGUAGE_PATTERNS.len()];
+    let mut code_lines = 0;
+
+    for line in &lines {
+        let mut is_code = CODE_LINE_RE.is_match(line);
+        for (count, (_, pattern)) in language_lines.iter_mut().zip(LANGUAGE_PATTERNS.iter()) {
+            if pattern.is_match(line) {
+                *count += 1;
+                is_code = true;
+            }
+        }
+        if is_code {
+            code_lines += 1;
+        }
+    }
+
+    if (code_lines as f32) < lines.len() as f32 * MIN_CODE_LINE_RATIO {
+        return None;
+    }
+
+    let min_language_lines = MIN_LANGUAGE_LINES.min(lines.len());
+    let mut language: Option<(&str, usize)> = None;
+    for ((name, _), count) in LANGUAGE_PATTERNS.iter().zip(language_lines) {
+        if count >= min_language_lines && language.is_none_or(|(_, best)| count > best) {
+            language = Some((name, count));
+        }
+    }
+
+    if language.is_none() && lines.len() < 2 {
+        return None;
+    }
+
+    Some(TextClassification {
+        kind: "code".to_string(),
+        language: language.map(|(name, _)| name.to_string()),
+    })
+}
+
+/// Strip tags from an HTML fragment, keeping line structure and decoding entities
+pub fn html_to_plain_text(html: &str) -> String {
+    const BLOCK_TAGS: [&str; 12] = [
+        "br",
+        "p",
+        "div",
+        "li",
+        "tr",
+        "h1",
+        "h2",
+        "h3",
+        "h4",
+        "h5",
+        "h6",
+        "blockquote",
+    ];
+
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&decode_html_entities(&rest[..start]));
+
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => {
+                rest = "";
+                break;
+            }
+        };
+
+        let tag = rest[start + 1..end].trim().to_lowercase();
+        let closing = tag.starts_with('/');
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect();
+
+        rest = &rest[end + 1..];
+
+        if !closing && (name == "script" || name == "style") {
+            // Drop everything up to the matching closing tag
+            let close = format!("</{}", name);
+            rest = match rest.to_ascii_lowercase().find(&close) {
+                Some(pos) => rest[pos..].find('>').map_or("", |e| &rest[pos + e + 1..]),
+                None => "",
+            };
+        } else if BLOCK_TAGS.contains(&name.as_str()) && (closing || name == "br") {
+            text.push('\n');
+        }
+    }
+
+    text.push_str(&decode_html_entities(rest));
+
+    // Collapse runs of blank lines left over from nested blocks
+    let mut result = String::with_capacity(text.len());
+    let mut newlines = 0;
+    for c in text.trim().chars() {
+        if c == '\n' {
+            newlines += 1;
+            if newlines > 2 {
+                continue;
+            }
+        } else {
+            newlines = 0;
+        }
+        result.push(c);
+    }
+    result
+}
+
+fn decode_html_entities(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest.find(';').filter(|end| *end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" | "#39" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end))
+        });
+
+        match decoded {
+            Some((c, end)) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn language(text: &str) -> Option<String> {
+        let classification = classify_text(text);
+        assert_eq!(classification.kind, "code", "not code: {}", text);
+        classification.language
+    }
+
+    fn assert_prose(text: &str) {
+        assert_eq!(classify_text(text).kind, "text", "not prose: {}", text);
+    }
+
+    #[test]
+    fn detects_languages_from_their_structure() {
+        let rust = "use std::fs;\n\npub fn read(path: &str) -> String {\n    let text = fs::read_to_string(path).unwrap();\n    text\n}";
+        assert_eq!(language(rust).as_deref(), Some("rust"));
+
+        let python = "import os\n\ndef size(path):\n    if os.path.exists(path):\n        return os.path.getsize(path)\n    return 0";
+        assert_eq!(language(python).as_deref(), Some("python"));
+
+        let typescript = "interface User {\n  name: string;\n  age: number;\n}\nconst admin: User = { name: \"root\", age: 1 };";
+        assert_eq!(language(typescript).as_deref(), Some("typescript"));
+
+        let javascript = "const items = [1, 2, 3];\nfunction total(list) {\n  return list.reduce((a, b) => a + b, 0);\n}\nconsole.log(total(items));";
+        assert_eq!(language(javascript).as_deref(), Some("javascript"));
+
+        let go = "package main\n\nimport \"fmt\"\n\nfunc main() {\n\tname := \"go\"\n\tfmt.Println(name)\n}";
+        assert_eq!(language(go).as_deref(), Some("go"));
+
+        let css = ".button {\n  color: red;\n  padding: 4px 8px;\n}";
+        assert_eq!(language(css).as_deref(), Some("css"));
+
+        let sql = "SELECT id, name\nFROM users\nWHERE age > 18\nORDER BY name;";
+        assert_eq!(language(sql).as_deref(), Some("sql"));
+
+        let shell = "#!/bin/sh\ncd /tmp && ls -la\nps aux | grep fat-clip";
+        assert_eq!(language(shell).as_deref(), Some("shell"));
+
+        assert_eq!(
+            language("SELECT * FROM users WHERE id = 1").as_deref(),
+            Some("sql")
+        );
+    }
+
+    #[test]
+    fn code_without_a_known_language_has_none() {
+        assert_eq!(language("x = compute(y);\nprint_result(x);"), None);
+    }
+
+    #[test]
+    fn prose_with_code_words_is_text() {
+        assert_prose(
+            "We need to import the new data and return the old reports by Friday.\n\
+             Let me know if that works for you.",
+        );
+        assert_prose(
+            "Use the {name} placeholder; it is replaced when the snippet is pasted.\n\
+             Everything else stays as it is.",
+        );
+        assert_prose(
+            "Select the files from the list where you saved them.\n\
+             Then update the settings and delete the old copies.",
+        );
+        assert_prose("for example:\nthe function returns a value when it is done.");
+        assert_prose(
+            "Dear team,\n\nthe release is done; thanks for the help {again}.\nBest regards",
+        );
+    }
+
+    #[test]
+    fn single_lines_need_a_language_pattern() {
+        assert_prose("Ship it;");
+        assert_prose("select what you like from the menu");
+    }
+
+    #[test]
+    fn own_writes_are_recognized_and_count_one_use() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        let manager = ClipboardManager::new(Arc::new(Mutex::new(db)));
+        let clip = manager
+            .save_text_clip("hello".to_string(), "test".to_string(), false)
+            .unwrap();
+
+        manager.mark_self_write(Some(clip.id.clone()), Some("hello"));
+        assert!(!manager.is_self_write(Some("a new copy")));
+        assert!(!manager.is_self_write(None));
+        // Polled twice before the clipboard changes again
+        assert!(manager.is_self_write(Some("hello")));
+        assert!(manager.is_self_write(Some("hello")));
+
+        let db = manager.db.lock().unwrap();
+        let clip = db.get_clip_by_id(&clip.id).unwrap().unwrap();
+        assert_eq!(clip.use_count, 1);
+        drop(db);
+
+        // A write without text covers any change
+        manager.mark_self_write(None, None);
+        assert!(manager.is_self_write(None));
+        assert!(manager.is_self_write(Some("anything")));
+    }
+}
@@ -4,7 +4,9 @@ use std::thread;
 use std::time::Duration;
 use tauri::Emitter;
 
+use crate::active_app;
 use crate::clipboard::ClipboardManager;
+use crate::db::ClipItem;
 
 /// Represents different types of clipboard content
 #[derive(Debug, Clone)]
@@ -72,6 +74,11 @@ impl ClipboardMonitor {
             };
 
             loop {
+                if clipboard_manager.is_capture_paused() {
+                    thread::sleep(Duration::from_millis(500));
+                    continue;
+                }
+
                 // Check for text content
                 if let Ok(text) = clipboard.get_text() {
                     if !text.trim().is_empty() {
@@ -86,14 +93,16 @@ impl ClipboardMonitor {
                             }
                         };
 
-                        if should_process {
+                        if should_process && clipboard_manager.is_self_write(Some(&text)) {
+                            // Written by the app itself, only the clip's use was recorded
+                            let _ = app_handle.emit("clipboard-updated", ());
+                        } else if should_process {
                             println!("New text content detected: {}", &text[..text.len().min(50)]);
-                            if let Err(e) =
-                                clipboard_manager.save_text_clip(text, "Unknown".to_string())
-                            {
-                                eprintln!("Failed to save text clip: {}", e);
-                            } else {
-                                let _ = app_handle.emit("clipboard-updated", ());
+                            match clipboard_manager.save_text_clip(text, source_app(), true) {
+                                Ok(item) => {
+                                    on_clip_captured(&clipboard_manager, &app_handle, &item)
+                                }
+                                Err(e) => eprintln!("Failed to save text clip: {}", e),
                             }
                         }
                     }
@@ -114,7 +123,9 @@ impl ClipboardMonitor {
                             }
                         };
 
-                        if should_process {
+                        if should_process && clipboard_manager.is_self_write(None) {
+                            let _ = app_handle.emit("clipboard-updated", ());
+                        } else if should_process {
                             println!(
                                 "New image content detected: {}x{}px",
                                 image_data.width, image_data.height
@@ -123,16 +134,18 @@ impl ClipboardMonitor {
                             // Convert image data to PNG
                             match convert_to_png(&image_data) {
                                 Ok(png_data) => {
-                                    if let Err(e) = clipboard_manager.save_image_clip(
+                                    match clipboard_manager.save_image_clip(
                                         png_data,
                                         image_data.width as u32,
                                         image_data.height as u32,
                                         "png",
-                                        "Unknown".to_string(),
+                                        source_app(),
+                                        true,
                                     ) {
-                                        eprintln!("Failed to save image clip: {}", e);
-                                    } else {
-                                        let _ = app_handle.emit("clipboard-updated", ());
+                                        Ok(item) => {
+                                            on_clip_captured(&clipboard_manager, &app_handle, &item)
+                                        }
+                                        Err(e) => eprintln!("Failed to save image clip: {}", e),
                                     }
                                 }
                                 Err(e) => {
@@ -211,17 +224,19 @@ fn check_windows_clipboard(
                 }
             };
 
-            if should_process {
+            if should_process && clipboard_manager.is_self_write(Some(&text)) {
+                let _ = app_handle.emit("clipboard-updated", ());
+            } else if should_process {
                 println!("New HTML content detected");
-                if let Err(e) = clipboard_manager.save_rich_text_clip(
+                match clipboard_manager.save_rich_text_clip(
                     Some(html),
                     None,
                     text,
-                    "Unknown".to_string(),
+                    source_app(),
+                    true,
                 ) {
-                    eprintln!("Failed to save HTML clip: {}", e);
-                } else {
-                    let _ = app_handle.emit("clipboard-updated", ());
+                    Ok(item) => on_clip_captured(clipboard_manager, app_handle, &item),
+                    Err(e) => eprintln!("Failed to save HTML clip: {}", e),
                 }
             }
         }
@@ -244,12 +259,13 @@ fn check_windows_clipboard(
                 }
             };
 
-            if should_process {
+            if should_process && clipboard_manager.is_self_write(None) {
+                let _ = app_handle.emit("clipboard-updated", ());
+            } else if should_process {
                 println!("New files detected: {} items", files.len());
-                if let Err(e) = clipboard_manager.save_file_clip(files, "Unknown".to_string()) {
-                    eprintln!("Failed to save file clip: {}", e);
-                } else {
-                    let _ = app_handle.emit("clipboard-updated", ());
+                match clipboard_manager.save_file_clip(files, source_app(), true) {
+                    Ok(item) => on_clip_captured(clipboard_manager, app_handle, &item),
+                    Err(e) => eprintln!("Failed to save file clip: {}", e),
                 }
             }
         }
@@ -286,17 +302,19 @@ fn check_macos_clipboard(
                 }
             };
 
-            if should_process {
+            if should_process && clipboard_manager.is_self_write(Some(&text)) {
+                let _ = app_handle.emit("clipboard-updated", ());
+            } else if should_process {
                 println!("New HTML content detected on macOS");
-                if let Err(e) = clipboard_manager.save_rich_text_clip(
+                match clipboard_manager.save_rich_text_clip(
                     Some(html),
                     None,
                     text,
-                    "Unknown".to_string(),
+                    source_app(),
+                    true,
                 ) {
-                    eprintln!("Failed to save HTML clip: {}", e);
-                } else {
-                    let _ = app_handle.emit("clipboard-updated", ());
+                    Ok(item) => on_clip_captured(clipboard_manager, app_handle, &item),
+                    Err(e) => eprintln!("Failed to save HTML clip: {}", e),
                 }
             }
         }
@@ -319,12 +337,13 @@ fn check_macos_clipboard(
                 }
             };
 
-            if should_process {
+            if should_process && clipboard_manager.is_self_write(None) {
+                let _ = app_handle.emit("clipboard-updated", ());
+            } else if should_process {
                 println!("New files detected on macOS: {} items", files.len());
-                if let Err(e) = clipboard_manager.save_file_clip(files, "Unknown".to_string()) {
-                    eprintln!("Failed to save file clip: {}", e);
-                } else {
-                    let _ = app_handle.emit("clipboard-updated", ());
+                match clipboard_manager.save_file_clip(files, source_app(), true) {
+                    Ok(item) => on_clip_captured(clipboard_manager, app_handle, &item),
+                    Err(e) => eprintln!("Failed to save file clip: {}", e),
                 }
             }
         }
@@ -356,12 +375,13 @@ fn check_linux_clipboard(
                 }
             };
 
-            if should_process {
+            if should_process && clipboard_manager.is_self_write(None) {
+                let _ = app_handle.emit("clipboard-updated", ());
+            } else if should_process {
                 println!("New files detected on Linux: {} items", files.len());
-                if let Err(e) = clipboard_manager.save_file_clip(files, "Unknown".to_string()) {
-                    eprintln!("Failed to save file clip: {}", e);
-                } else {
-                    let _ = app_handle.emit("clipboard-updated", ());
+                match clipboard_manager.save_file_clip(files, source_app(), true) {
+                    Ok(item) => on_clip_captured(clipboard_manager, app_handle, &item),
+                    Err(e) => eprintln!("Failed to save file clip: {}", e),
                 }
             }
         }
@@ -369,6 +389,24 @@ fn check_linux_clipboard(
     }
 }
 
+/// Name of the focused application, which most likely placed the content on
+/// the clipboard
+fn source_app() -> String {
+    active_app::foreground_app().unwrap_or_else(|| "Unknown".to_string())
+}
+
+/// Notify the frontend about a new capture and queue it while the paste queue is active
+fn on_clip_captured(
+    clipboard_manager: &ClipboardManager,
+    app_handle: &tauri::AppHandle,
+    item: &ClipItem,
+) {
+    if clipboard_manager.paste_queue.push(&item.id) {
+        let _ = app_handle.emit("paste-queue-updated", ());
+    }
+    let _ = app_handle.emit("clipboard-updated", ());
+}
+
 /// Generate hash for content deduplication
 fn generate_hash(content: &str) -> String {
     use std::collections::hash_map::DefaultHasher;
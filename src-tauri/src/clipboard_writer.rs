@@ -0,0 +1,232 @@
+//! Writing stored clips back to the system clipboard
+
+use arboard::{Clipboard, ImageData};
+use std::path::{Path, PathBuf};
+
+/// Clipboard contents saved to be put back later
+pub enum SavedClipboard {
+    Empty,
+    Text(String),
+    Html { html: String, plain: String },
+    Image(ImageData<'static>),
+    Files(Vec<PathBuf>),
+}
+
+impl SavedClipboard {
+    /// Plain text the clipboard holds once restored
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            SavedClipboard::Text(text) | SavedClipboard::Html { plain: text, .. } => Some(text),
+            SavedClipboard::Empty | SavedClipboard::Image(_) | SavedClipboard::Files(_) => None,
+        }
+    }
+}
+
+/// Keeps what this process writes to the clipboard available after the write.
+///
+/// On X11 the clipboard is served by the process that wrote it, and arboard
+/// stops serving once its last `Clipboard` is dropped. A process that exits
+/// right after writing, like the command-line interface, has to stay until
+/// another application takes over.
+pub struct ClipboardHold {
+    #[cfg(target_os = "linux")]
+    _clipboard: Clipboard,
+}
+
+impl ClipboardHold {
+    pub fn new() -> Result<Self, String> {
+        Ok(ClipboardHold {
+            #[cfg(target_os = "linux")]
+            _clipboard: Clipboard::new().map_err(|e| e.to_string())?,
+        })
+    }
+
+    /// Block until another application owns the clipboard. Other platforms
+    /// keep the contents after the process exits, so this returns at once.
+    pub fn wait(self) -> Result<(), String> {
+        #[cfg(target_os = "linux")]
+        return linux::wait_for_new_owner();
+
+        #[cfg(not(target_os = "linux"))]
+        Ok(())
+    }
+}
+
+/// Read the current clipboard contents in the richest format available
+pub fn save_clipboard() -> SavedClipboard {
+    let mut clipboard = match Clipboard::new() {
+        Ok(clipboard) => clipboard,
+        Err(_) => return SavedClipboard::Empty,
+    };
+
+    if let Ok(files) = clipboard.get().file_list() {
+        if !files.is_empty() {
+            return SavedClipboard::Files(files);
+        }
+    }
+    if let Ok(image) = clipboard.get_image() {
+        return SavedClipboard::Image(image);
+    }
+
+    let plain = match clipboard.get_text() {
+        Ok(plain) => plain,
+        Err(_) => return SavedClipboard::Empty,
+    };
+    match clipboard.get().html() {
+        Ok(html) => SavedClipboard::Html { html, plain },
+        Err(_) => SavedClipboard::Text(plain),
+    }
+}
+
+/// Put contents saved with `save_clipboard` back on the clipboard
+pub fn restore_clipboard(saved: SavedClipboard) -> Result<(), String> {
+    match saved {
+        SavedClipboard::Empty => {
+            let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+            clipboard.clear().map_err(|e| e.to_string())
+        }
+        SavedClipboard::Text(text) => write_text(&text),
+        SavedClipboard::Html { html, plain } => write_rich_text(Some(&html), None, &plain),
+        SavedClipboard::Image(image) => {
+            let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+            clipboard.set_image(image).map_err(|e| e.to_string())
+        }
+        SavedClipboard::Files(paths) => write_files(&paths),
+    }
+}
+
+/// Write plain text to the clipboard
+pub fn write_text(text: &str) -> Result<(), String> {
+    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+    clipboard.set_text(text).map_err(|e| e.to_string())
+}
+
+/// Write an encoded image (PNG, JPEG, ...) to the clipboard as raw RGBA
+pub fn write_image(image_data: &[u8]) -> Result<(), String> {
+    use image::ImageReader;
+    use std::io::Cursor;
+
+    // Decode to get raw RGBA data
+    let reader = ImageReader::new(Cursor::new(image_data))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?;
+
+    let img = reader.decode().map_err(|e| e.to_string())?;
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+
+    let image_data = ImageData {
+        width: width as usize,
+        height: height as usize,
+        bytes: rgba.into_raw().into(),
+    };
+
+    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+    clipboard.set_image(image_data).map_err(|e| e.to_string())
+}
+
+/// Write rich text with every format the platform supports.
+///
+/// HTML is always accompanied by the plain text fallback. RTF needs a native
+/// writer on every platform, since arboard cannot put it on the clipboard.
+pub fn write_rich_text(html: Option<&str>, rtf: Option<&str>, plain: &str) -> Result<(), String> {
+    if rtf.is_some() {
+        #[cfg(windows)]
+        return windows::write_rich_text(html, rtf, plain);
+
+        #[cfg(target_os = "macos")]
+        return macos::write_rich_text(html, rtf, plain);
+
+        #[cfg(target_os = "linux")]
+        return linux::write_rich_text(html, rtf, plain);
+    }
+
+    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+    match html {
+        Some(html) => clipboard
+            .set_html(html, Some(plain))
+            .map_err(|e| e.to_string()),
+        None => clipboard.set_text(plain).map_err(|e| e.to_string()),
+    }
+}
+
+/// Put files on the clipboard so that pasting in a file manager copies them.
+///
+/// Windows gets `CF_HDROP` and macOS file URLs through arboard. Linux offers
+/// both `text/uri-list` and `x-special/gnome-copied-files`.
+pub fn write_files(paths: &[impl AsRef<Path>]) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    {
+        linux::write_files(paths)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+        clipboard.set().file_list(paths).map_err(|e| e.to_string())
+    }
+}
+
+/// Simulate the platform paste shortcut (Cmd+V on macOS, Ctrl+V elsewhere)
+pub fn simulate_paste() -> Result<(), String> {
+    use rdev::{simulate, EventType, Key};
+
+    let modifier = if cfg!(target_os = "macos") {
+        Key::MetaLeft
+    } else {
+        Key::ControlLeft
+    };
+
+    simulate(&EventType::KeyPress(modifier))
+        .map_err(|e| format!("Failed to simulate modifier: {:?}", e))?;
+    simulate(&EventType::KeyPress(Key::KeyV))
+        .map_err(|e| format!("Failed to simulate v: {:?}", e))?;
+    simulate(&EventType::KeyRelease(Key::KeyV))
+        .map_err(|e| format!("Failed to simulate v: {:?}", e))?;
+    simulate(&EventType::KeyRelease(modifier))
+        .map_err(|e| format!("Failed to simulate modifier: {:?}", e))
+}
+
+/// Release modifier keys the user may still be holding, e.g. those of the
+/// shortcut that started a paste, so simulated keys are not combined with them
+pub fn release_modifiers(keys: &[rdev::Key]) -> Result<(), String> {
+    use rdev::{simulate, EventType};
+
+    for key in keys {
+        simulate(&EventType::KeyRelease(*key))
+            .map_err(|e| format!("Failed to simulate modifier: {:?}", e))?;
+    }
+
+    Ok(())
+}
+
+/// Press and release `key` `count` times, e.g. to move the caret after pasting
+pub fn tap_key(key: rdev::Key, count: usize) -> Result<(), String> {
+    use rdev::{simulate, EventType};
+    use std::{thread, time::Duration};
+
+    for _ in 0..count {
+        simulate(&EventType::KeyPress(key))
+            .map_err(|e| format!("Failed to simulate key: {:?}", e))?;
+        simulate(&EventType::KeyRelease(key))
+            .map_err(|e| format!("Failed to simulate key: {:?}", e))?;
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    Ok(())
+}
+
+// ==================== Windows Implementations ====================
+
+#[cfg(windows)]
+mod windows;
+
+// ==================== macOS Implementations ====================
+
+#[cfg(target_os = "macos")]
+mod macos;
+
+// ==================== Linux Implementations ====================
+
+#[cfg(target_os = "linux")]
+mod linux;
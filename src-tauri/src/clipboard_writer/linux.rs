@@ -0,0 +1,222 @@
+//! Linux clipboard writing for formats arboard cannot offer.
+//!
+//! File managers disagree on the format they paste: most read `text/uri-list`,
+//! while Nautilus and its forks expect `x-special/gnome-copied-files`. Rich
+//! text needs `text/rtf` next to HTML. A single clipboard owner has to offer
+//! all of them, which arboard cannot do, so this module owns the X11
+//! CLIPBOARD selection itself from a background thread.
+
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode, SelectionNotifyEvent,
+    SelectionRequestEvent, WindowClass, SELECTION_NOTIFY_EVENT,
+};
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::{COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE};
+
+/// Offer a list of files on the clipboard until another owner takes over
+pub fn write_files(paths: &[impl AsRef<Path>]) -> Result<(), String> {
+    let uris: Vec<String> = paths.iter().map(|path| file_uri(path.as_ref())).collect();
+
+    let uri_list = uris
+        .iter()
+        .map(|uri| format!("{}\r\n", uri))
+        .collect::<String>();
+    let gnome_copied_files = format!("copy\n{}", uris.join("\n"));
+    let plain_text = uris.join("\n");
+
+    serve(vec![
+        ("text/uri-list", uri_list.into_bytes()),
+        (
+            "x-special/gnome-copied-files",
+            gnome_copied_files.into_bytes(),
+        ),
+        ("UTF8_STRING", plain_text.into_bytes()),
+    ])
+}
+
+/// Offer rich text as HTML, RTF and plain text until another owner takes over
+pub fn write_rich_text(html: Option<&str>, rtf: Option<&str>, plain: &str) -> Result<(), String> {
+    let mut formats = Vec::new();
+    if let Some(html) = html {
+        formats.push(("text/html", html.as_bytes().to_vec()));
+    }
+    if let Some(rtf) = rtf {
+        // LibreOffice asks for the older MIME type
+        formats.push(("text/rtf", rtf.as_bytes().to_vec()));
+        formats.push(("application/rtf", rtf.as_bytes().to_vec()));
+    }
+    formats.push(("UTF8_STRING", plain.as_bytes().to_vec()));
+    formats.push(("text/plain;charset=utf-8", plain.as_bytes().to_vec()));
+
+    serve(formats)
+}
+
+/// Take ownership of the clipboard and answer requests for `formats`, given as
+/// target name and data, from a background thread
+fn serve(formats: Vec<(&str, Vec<u8>)>) -> Result<(), String> {
+    let (conn, screen_num) = RustConnection::connect(None).map_err(|e| e.to_string())?;
+    let root = conn.setup().roots[screen_num].root;
+
+    let window = conn.generate_id().map_err(|e| e.to_string())?;
+    conn.create_window(
+        COPY_DEPTH_FROM_PARENT,
+        window,
+        root,
+        0,
+        0,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        COPY_FROM_PARENT,
+        &CreateWindowAux::new(),
+    )
+    .map_err(|e| e.to_string())?;
+
+    let clipboard = intern(&conn, "CLIPBOARD")?;
+    let targets = intern(&conn, "TARGETS")?;
+    let formats = formats
+        .into_iter()
+        .map(|(name, data)| Ok((intern(&conn, name)?, data)))
+        .collect::<Result<Vec<(u32, Vec<u8>)>, String>>()?;
+
+    conn.set_selection_owner(window, clipboard, CURRENT_TIME)
+        .map_err(|e| e.to_string())?;
+    if selection_owner(&conn, clipboard)? != window {
+        return Err("Failed to take clipboard ownership".to_string());
+    }
+
+    thread::spawn(move || loop {
+        let event = match conn.wait_for_event() {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("Clipboard owner connection failed: {}", e);
+                return;
+            }
+        };
+
+        match event {
+            Event::SelectionRequest(request) => {
+                if let Err(e) = respond(&conn, targets, &formats, &request) {
+                    eprintln!("Failed to answer clipboard request: {}", e);
+                }
+            }
+            // Another application owns the clipboard now
+            Event::SelectionClear(_) => return,
+            _ => {}
+        }
+    });
+
+    Ok(())
+}
+
+/// How often `wait_for_new_owner` checks the clipboard owner
+const OWNER_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Block until the clipboard has another owner than now
+pub fn wait_for_new_owner() -> Result<(), String> {
+    let (conn, _) = RustConnection::connect(None).map_err(|e| e.to_string())?;
+    let clipboard = intern(&conn, "CLIPBOARD")?;
+    let owner = selection_owner(&conn, clipboard)?;
+    while selection_owner(&conn, clipboard)? == owner {
+        thread::sleep(OWNER_POLL_INTERVAL);
+    }
+    Ok(())
+}
+
+fn selection_owner(conn: &RustConnection, selection: u32) -> Result<u32, String> {
+    conn.get_selection_owner(selection)
+        .map_err(|e| e.to_string())?
+        .reply()
+        .map(|reply| reply.owner)
+        .map_err(|e| e.to_string())
+}
+
+/// Build a `file://` URI, percent-encoding everything but unreserved characters
+fn file_uri(path: &Path) -> String {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+fn intern(conn: &RustConnection, name: &str) -> Result<u32, String> {
+    conn.intern_atom(false, name.as_bytes())
+        .map_err(|e| e.to_string())?
+        .reply()
+        .map(|reply| reply.atom)
+        .map_err(|e| e.to_string())
+}
+
+/// Store the requested target on the requestor window and notify it
+fn respond(
+    conn: &RustConnection,
+    targets: u32,
+    formats: &[(u32, Vec<u8>)],
+    request: &SelectionRequestEvent,
+) -> Result<(), String> {
+    // Obsolete clients may leave the property unset
+    let property = if request.property == NONE {
+        request.target
+    } else {
+        request.property
+    };
+
+    let data = formats
+        .iter()
+        .find(|(target, _)| *target == request.target)
+        .map(|(_, data)| data);
+
+    let stored = if request.target == targets {
+        let available: Vec<u32> = std::iter::once(targets)
+            .chain(formats.iter().map(|(target, _)| *target))
+            .collect();
+        conn.change_property32(
+            PropMode::REPLACE,
+            request.requestor,
+            property,
+            AtomEnum::ATOM,
+            &available,
+        )
+        .map_err(|e| e.to_string())?;
+        true
+    } else if let Some(data) = data {
+        conn.change_property8(
+            PropMode::REPLACE,
+            request.requestor,
+            property,
+            request.target,
+            data,
+        )
+        .map_err(|e| e.to_string())?;
+        true
+    } else {
+        false
+    };
+
+    let notify = SelectionNotifyEvent {
+        response_type: SELECTION_NOTIFY_EVENT,
+        sequence: 0,
+        time: request.time,
+        requestor: request.requestor,
+        selection: request.selection,
+        target: request.target,
+        property: if stored { property } else { NONE },
+    };
+    conn.send_event(false, request.requestor, EventMask::NO_EVENT, notify)
+        .map_err(|e| e.to_string())?;
+    conn.flush().map_err(|e| e.to_string())
+}
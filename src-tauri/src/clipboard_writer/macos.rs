@@ -0,0 +1,68 @@
+//! macOS clipboard writing for formats arboard cannot offer (RTF).
+//!
+//! NSPasteboard is driven through JavaScript for Automation, like the monitor
+//! reads it through AppleScript. The data is handed over in temporary files
+//! to stay clear of argument size limits.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+const SCRIPT: &str = r#"ObjC.import('AppKit');
+function run(argv) {
+    const pb = $.NSPasteboard.generalPasteboard;
+    pb.clearContents;
+    for (let i = 0; i + 1 < argv.length; i += 2) {
+        const data = $.NSData.dataWithContentsOfFile(argv[i + 1]);
+        if (!pb.setDataForType(data, argv[i])) {
+            throw new Error('Failed to set ' + argv[i]);
+        }
+    }
+}"#;
+
+/// Write plain text, HTML and RTF as one pasteboard item
+pub fn write_rich_text(html: Option<&str>, rtf: Option<&str>, plain: &str) -> Result<(), String> {
+    let mut formats = vec![("public.utf8-plain-text", plain)];
+    if let Some(html) = html {
+        formats.push(("public.html", html));
+    }
+    if let Some(rtf) = rtf {
+        formats.push(("public.rtf", rtf));
+    }
+
+    let files = TempFiles(
+        formats
+            .iter()
+            .map(|_| std::env::temp_dir().join(format!("fat-clip-{}", uuid::Uuid::new_v4())))
+            .collect(),
+    );
+
+    let mut command = Command::new("osascript");
+    command.arg("-l").arg("JavaScript").arg("-e").arg(SCRIPT);
+    for ((pasteboard_type, data), path) in formats.iter().zip(&files.0) {
+        std::fs::write(path, data).map_err(|e| format!("Failed to write temp file: {}", e))?;
+        command.arg(pasteboard_type).arg(path);
+    }
+
+    let output = command
+        .output()
+        .map_err(|e| format!("Failed to run osascript: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to write rich text: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Files removed when dropped
+struct TempFiles(Vec<PathBuf>);
+
+impl Drop for TempFiles {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Windows clipboard writing for formats arboard cannot combine (RTF)
+
+use windows_sys::Win32::Foundation::{GlobalFree, HANDLE};
+use windows_sys::Win32::System::DataExchange::{
+    CloseClipboard, EmptyClipboard, OpenClipboard, RegisterClipboardFormatW, SetClipboardData,
+};
+use windows_sys::Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
+
+const CF_UNICODETEXT: u32 = 13;
+
+/// Write plain text, CF_HTML and RTF in a single clipboard session
+pub fn write_rich_text(html: Option<&str>, rtf: Option<&str>, plain: &str) -> Result<(), String> {
+    let html_format = register_format("HTML Format");
+    let rtf_format = register_format("Rich Text Format");
+
+    let text_bytes: Vec<u8> = plain
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect();
+
+    unsafe {
+        if OpenClipboard(std::ptr::null_mut()) == 0 {
+            return Err("Failed to open clipboard".to_string());
+        }
+
+        if EmptyClipboard() == 0 {
+            CloseClipboard();
+            return Err("Failed to empty clipboard".to_string());
+        }
+
+        let mut result = set_data(CF_UNICODETEXT, &text_bytes);
+
+        if let (Ok(()), Some(html)) = (&result, html) {
+            let mut cf_html = build_cf_html(html).into_bytes();
+            cf_html.push(0);
+            result = set_data(html_format, &cf_html);
+        }
+
+        if let (Ok(()), Some(rtf)) = (&result, rtf) {
+            let mut rtf_bytes = rtf.as_bytes().to_vec();
+            rtf_bytes.push(0);
+            result = set_data(rtf_format, &rtf_bytes);
+        }
+
+        CloseClipboard();
+        result
+    }
+}
+
+fn register_format(name: &str) -> u32 {
+    let wide: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe { RegisterClipboardFormatW(wide.as_ptr()) }
+}
+
+/// Copy bytes into movable global memory and hand it over to the open clipboard
+unsafe fn set_data(format: u32, bytes: &[u8]) -> Result<(), String> {
+    let handle = GlobalAlloc(GMEM_MOVEABLE, bytes.len());
+    if handle.is_null() {
+        return Err("Failed to allocate global memory".to_string());
+    }
+
+    let ptr = GlobalLock(handle);
+    if ptr.is_null() {
+        GlobalFree(handle);
+        return Err("Failed to lock global memory".to_string());
+    }
+
+    std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr as *mut u8, bytes.len());
+    GlobalUnlock(handle);
+
+    // On success the clipboard owns the memory
+    if SetClipboardData(format, handle as HANDLE).is_null() {
+        GlobalFree(handle);
+        return Err("Failed to set clipboard data".to_string());
+    }
+
+    Ok(())
+}
+
+/// Wrap an HTML fragment in the CF_HTML envelope with its byte offsets
+fn build_cf_html(fragment: &str) -> String {
+    const PREFIX: &str = "<html><body>\r\n<!--StartFragment-->";
+    const SUFFIX: &str = "<!--EndFragment-->\r\n</body></html>";
+
+    let header = |start_html: usize, end_html: usize, start_frag: usize, end_frag: usize| {
+        format!(
+            "Version:0.9\r\nStartHTML:{:010}\r\nEndHTML:{:010}\r\nStartFragment:{:010}\r\nEndFragment:{:010}\r\n",
+            start_html, end_html, start_frag, end_frag
+        )
+    };
+
+    let start_html = header(0, 0, 0, 0).len();
+    let start_frag = start_html + PREFIX.len();
+    let end_frag = start_frag + fragment.len();
+    let end_html = end_frag + SUFFIX.len();
+
+    format!(
+        "{}{}{}{}",
+        header(start_html, end_html, start_frag, end_frag),
+        PREFIX,
+        fragment,
+        SUFFIX
+    )
+}
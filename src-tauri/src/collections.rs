@@ -0,0 +1,179 @@
+//! Collections: nested, colored groups of clips with their own ordering
+
+use crate::db::{ClipItem, Collection, Database};
+use chrono::Utc;
+use std::sync::{Arc, Mutex};
+
+pub struct CollectionManager {
+    db: Arc<Mutex<Database>>,
+}
+
+impl CollectionManager {
+    pub fn new(db: Arc<Mutex<Database>>) -> Self {
+        CollectionManager { db }
+    }
+
+    pub fn get_collections(&self) -> Result<Vec<Collection>, String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.get_collections().map_err(|e| e.to_string())
+    }
+
+    /// Create a collection at the end of its parent
+    pub fn create_collection(
+        &self,
+        name: String,
+        parent_id: Option<String>,
+        color: Option<String>,
+    ) -> Result<Collection, String> {
+        let name = validate_name(&name)?;
+        let color = validate_color(color)?;
+
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        let collections = db.get_collections().map_err(|e| e.to_string())?;
+
+        if let Some(parent_id) = &parent_id {
+            if !collections.iter().any(|c| &c.id == parent_id) {
+                return Err("Parent collection not found".to_string());
+            }
+        }
+
+        let position = collections
+            .iter()
+            .filter(|c| c.parent_id == parent_id)
+            .map(|c| c.position + 1)
+            .max()
+            .unwrap_or(0);
+
+        let collection = Collection {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            parent_id,
+            color,
+            position,
+            created_at: Utc::now(),
+            clip_count: 0,
+        };
+
+        db.insert_collection(&collection)
+            .map_err(|e| e.to_string())?;
+        Ok(collection)
+    }
+
+    pub fn update_collection(
+        &self,
+        id: &str,
+        name: String,
+        color: Option<String>,
+    ) -> Result<(), String> {
+        let name = validate_name(&name)?;
+        let color = validate_color(color)?;
+
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        let updated = db
+            .update_collection(id, &name, color.as_deref())
+            .map_err(|e| e.to_string())?;
+        if updated == 0 {
+            return Err("Collection not found".to_string());
+        }
+        Ok(())
+    }
+
+    /// Move a collection to another parent and/or position. A collection cannot
+    /// be moved into itself or one of its descendants.
+    pub fn move_collection(
+        &self,
+        id: &str,
+        parent_id: Option<String>,
+        index: usize,
+    ) -> Result<(), String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        let collections = db.get_collections().map_err(|e| e.to_string())?;
+
+        if !collections.iter().any(|c| c.id == id) {
+            return Err("Collection not found".to_string());
+        }
+
+        // Walk up from the new parent to make sure we do not create a cycle
+        let mut ancestor = parent_id.clone();
+        while let Some(ancestor_id) = ancestor {
+            if ancestor_id == id {
+                return Err("A collection cannot be moved into itself".to_string());
+            }
+            ancestor = collections
+                .iter()
+                .find(|c| c.id == ancestor_id)
+                .ok_or_else(|| "Parent collection not found".to_string())?
+                .parent_id
+                .clone();
+        }
+
+        db.move_collection(id, parent_id.as_deref(), index)
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn delete_collection(&self, id: &str) -> Result<(), String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.delete_collection(id).map_err(|e| e.to_string())
+    }
+
+    pub fn add_clips(&self, collection_id: &str, clip_ids: &[String]) -> Result<(), String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.add_clips_to_collection(collection_id, clip_ids)
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn remove_clip(&self, collection_id: &str, clip_id: &str) -> Result<(), String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.remove_clip_from_collection(collection_id, clip_id)
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn get_clips(&self, collection_id: &str) -> Result<Vec<ClipItem>, String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.get_collection_clips(collection_id)
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn move_clip(
+        &self,
+        collection_id: &str,
+        clip_id: &str,
+        index: usize,
+    ) -> Result<(), String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.move_clip_in_collection(collection_id, clip_id, index)
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn get_clip_collection_ids(&self, clip_id: &str) -> Result<Vec<String>, String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.get_clip_collection_ids(clip_id)
+            .map_err(|e| e.to_string())
+    }
+}
+
+fn validate_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Collection name cannot be empty".to_string());
+    }
+    Ok(name.to_string())
+}
+
+/// Accept "#rgb", "#rrggbb" and "#rrggbbaa" colors, empty means no color
+fn validate_color(color: Option<String>) -> Result<Option<String>, String> {
+    let color = match color.map(|c| c.trim().to_lowercase()) {
+        Some(color) if !color.is_empty() => color,
+        _ => return Ok(None),
+    };
+
+    let valid = color.strip_prefix('#').is_some_and(|hex| {
+        matches!(hex.len(), 3 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+    });
+
+    if valid {
+        Ok(Some(color))
+    } else {
+        Err(format!("Invalid color '{}'", color))
+    }
+}
@@ -1,7 +1,9 @@
 use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection, Result as SqliteResult};
+use rusqlite::backup::Progress;
+use rusqlite::{params, Connection, OpenFlags, Result as SqliteResult, MAIN_DB};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum ContentType {
@@ -44,9 +46,27 @@ pub struct ClipItem {
     pub last_used_at: DateTime<Utc>,
     pub pinned: bool,
     pub metadata: Option<serde_json::Value>,
+    /// Number of times the clip was written back to the clipboard by the app
+    #[serde(default)]
+    pub use_count: i64,
 }
 
 /// Metadata for image content
+/// Which clips to list or export. Every condition that is set has to match.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ClipFilter {
+    /// Clips created at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Clips created before this time
+    pub to: Option<DateTime<Utc>>,
+    /// Clips with any of these tags
+    pub tags: Vec<String>,
+    /// Any of "plain", "rich", "image", "file"
+    pub content_types: Vec<String>,
+    pub pinned_only: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageMetadata {
     pub width: u32,
@@ -71,6 +91,128 @@ pub struct RichTextMetadata {
     pub html_preview: Option<String>,
 }
 
+/// Stored content of a rich text clip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RichTextContent {
+    pub html: Option<String>,
+    pub rtf: Option<String>,
+    #[serde(default)]
+    pub plain: String,
+}
+
+/// Reusable text kept apart from the clipboard history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snippet {
+    pub id: String,
+    pub folder_id: Option<String>,
+    pub name: String,
+    /// Template text, placeholders are expanded at paste time
+    pub content: String,
+    /// Short text that expands into the snippet, e.g. ";sig"
+    pub abbreviation: Option<String>,
+    /// Applications the abbreviation expands in, empty for all
+    #[serde(default)]
+    pub expansion_apps: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub use_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnippetFolder {
+    pub id: String,
+    pub name: String,
+    pub parent_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A named group of clips. Collections can be nested and keep their clips in
+/// a user-defined order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collection {
+    pub id: String,
+    pub name: String,
+    pub parent_id: Option<String>,
+    /// Display color such as "#f59e0b"
+    pub color: Option<String>,
+    /// Position among the collections with the same parent
+    pub position: i64,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub clip_count: i64,
+}
+
+/// An earlier content of an edited clip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipRevision {
+    pub id: i64,
+    pub clip_id: String,
+    pub content: String,
+    pub preview_text: String,
+    /// When this content was replaced
+    pub created_at: DateTime<Utc>,
+}
+
+/// State of a clip as last exchanged with other devices. Each field carries
+/// the time it was changed, which decides between conflicting changes.
+#[derive(Debug, Clone)]
+pub struct SyncState {
+    pub clip_id: String,
+    /// Whether the clip is in the history, as opposed to deleted
+    pub present: bool,
+    pub present_at: DateTime<Utc>,
+    pub tags: Vec<String>,
+    pub tags_at: DateTime<Utc>,
+    pub pinned: bool,
+    pub pinned_at: DateTime<Utc>,
+}
+
+/// An entry of the sync journal
+#[derive(Debug, Clone)]
+pub struct SyncChangeRecord {
+    pub seq: i64,
+    pub at: DateTime<Utc>,
+    /// "insert", "delete", "tags" or "pin"
+    pub op: String,
+    pub clip_id: String,
+    /// The tags or pin as JSON, for tag and pin changes
+    pub value: Option<String>,
+    /// Device the change was received from, `None` for local changes
+    pub origin: Option<String>,
+}
+
+/// A device paired for sync over the local network
+#[derive(Debug, Clone)]
+pub struct SyncPeer {
+    pub device_id: String,
+    pub name: String,
+    /// Shared key, base64 encoded
+    pub key: String,
+    /// "host:port" to connect to; unknown for devices that paired with us
+    pub address: Option<String>,
+    /// Our journal position up to which changes were sent to the peer
+    pub sent_seq: i64,
+    /// The peer's journal position up to which its changes were received
+    pub received_seq: i64,
+    pub paired_at: DateTime<Utc>,
+    pub last_sync_at: Option<DateTime<Utc>>,
+}
+
+/// Usage of a tag across the history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagStats {
+    pub name: String,
+    pub clip_count: i64,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// Marks clips whose id or pin changed for sync, see `sync_dirty`
+const SYNC_DIRTY_UPDATE_TRIGGER: &str =
+    "CREATE TRIGGER IF NOT EXISTS sync_dirty_update AFTER UPDATE OF id, pinned ON clip_items BEGIN
+         INSERT OR REPLACE INTO sync_dirty (clip_id) VALUES (OLD.id);
+         INSERT OR REPLACE INTO sync_dirty (clip_id) VALUES (NEW.id);
+     END;";
+
 pub struct Database {
     conn: Connection,
 }
@@ -79,13 +221,82 @@ impl Database {
     pub fn new(app_dir: PathBuf) -> SqliteResult<Self> {
         let db_path = app_dir.join("fat_clip.db");
         let conn = Connection::open(db_path)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON")?;
+        // The command-line interface may use the database at the same time
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
 
         let db = Database { conn };
         db.init_tables()?;
+        db.migrate()?;
 
         Ok(db)
     }
 
+    /// Copy the database to `path` with SQLite's online backup API
+    pub fn backup_to(&self, path: &Path) -> SqliteResult<()> {
+        self.conn.backup(MAIN_DB, path, None)
+    }
+
+    /// Replace the contents of the database with the backup at `path`.
+    ///
+    /// Paired devices and the end of the sync journal are kept: peers remember
+    /// how far they have read the journal, so its positions must not go back.
+    pub fn restore_from(&mut self, path: &Path) -> SqliteResult<()> {
+        let peers = self.get_sync_peers()?;
+        let journal_end = self.sync_journal_end()?;
+
+        self.conn.restore(MAIN_DB, path, None::<fn(Progress)>)?;
+
+        // The backup may predate newer tables and migrations
+        self.init_tables()?;
+        self.migrate()?;
+
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM sync_peers", [])?;
+        for peer in &peers {
+            save_sync_peer(&tx, peer)?;
+        }
+        let updated = tx.execute(
+            "UPDATE sqlite_sequence SET seq = MAX(seq, ?1) WHERE name = 'sync_changes'",
+            params![journal_end],
+        )?;
+        if updated == 0 && journal_end > 0 {
+            tx.execute(
+                "INSERT INTO sqlite_sequence (name, seq) VALUES ('sync_changes', ?1)",
+                params![journal_end],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// The highest position ever used in the sync journal
+    fn sync_journal_end(&self) -> SqliteResult<i64> {
+        match self.conn.query_row(
+            "SELECT seq FROM sqlite_sequence WHERE name = 'sync_changes'",
+            [],
+            |row| row.get(0),
+        ) {
+            Ok(seq) => Ok(seq),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Check a backup database and return the number of clips in it
+    pub fn verify_backup(path: &Path) -> SqliteResult<i64> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+        let result: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+        if result != "ok" {
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CORRUPT),
+                Some(result),
+            ));
+        }
+
+        conn.query_row("SELECT COUNT(*) FROM clip_items", [], |row| row.get(0))
+    }
+
     fn init_tables(&self) -> SqliteResult<()> {
         self.conn.execute(
             "CREATE TABLE IF NOT EXISTS clip_items (
@@ -93,16 +304,22 @@ impl Database {
                 content_type TEXT NOT NULL,
                 content TEXT NOT NULL,
                 preview_text TEXT NOT NULL,
-                tags TEXT NOT NULL DEFAULT '[]',
                 source_app TEXT NOT NULL,
                 created_at TEXT NOT NULL,
                 last_used_at TEXT NOT NULL,
                 pinned INTEGER NOT NULL DEFAULT 0,
-                metadata TEXT
+                metadata TEXT,
+                use_count INTEGER NOT NULL DEFAULT 0
             )",
             [],
         )?;
 
+        // Columns added after the first release
+        self.ensure_column("clip_items", "use_count", "INTEGER NOT NULL DEFAULT 0")?;
+        // Hash of the current content used to find duplicates; differs from the
+        // id once a clip was edited
+        self.ensure_column("clip_items", "content_hash", "TEXT")?;
+
         // Create index for faster queries
         self.conn.execute(
             "CREATE INDEX IF NOT EXISTS idx_created_at ON clip_items(created_at DESC)",
@@ -119,279 +336,762 @@ impl Database {
             [],
         )?;
 
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_content_hash ON clip_items(content_hash)",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS snippet_folders (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                parent_id TEXT,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS snippets (
+                id TEXT PRIMARY KEY,
+                folder_id TEXT,
+                name TEXT NOT NULL,
+                content TEXT NOT NULL,
+                abbreviation TEXT,
+                expansion_apps TEXT NOT NULL DEFAULT '[]',
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                use_count INTEGER NOT NULL DEFAULT 0
+            )",
+            [],
+        )?;
+
+        self.ensure_column("snippets", "expansion_apps", "TEXT NOT NULL DEFAULT '[]'")?;
+
+        self.conn.execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_snippet_abbreviation ON snippets(abbreviation)",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS collections (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                parent_id TEXT,
+                color TEXT,
+                position INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS collection_items (
+                collection_id TEXT NOT NULL REFERENCES collections(id) ON DELETE CASCADE,
+                clip_id TEXT NOT NULL REFERENCES clip_items(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL DEFAULT 0,
+                added_at TEXT NOT NULL,
+                PRIMARY KEY (collection_id, clip_id)
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_collection_items_clip ON collection_items(clip_id)",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS clip_revisions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                clip_id TEXT NOT NULL REFERENCES clip_items(id) ON DELETE CASCADE,
+                content TEXT NOT NULL,
+                preview_text TEXT NOT NULL,
+                metadata TEXT,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_clip_revisions_clip ON clip_revisions(clip_id, id DESC)",
+            [],
+        )?;
+
+        // Tags and the clips carrying them, in the order they were added
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS tags (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE
+            );
+            CREATE TABLE IF NOT EXISTS clip_tags (
+                clip_id TEXT NOT NULL REFERENCES clip_items(id) ON DELETE CASCADE,
+                tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+                position INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (clip_id, tag_id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_clip_tags_tag ON clip_tags(tag_id);",
+        )?;
+
+        // Clips with their tags as a JSON array, as read by `clip_from_row`
+        self.conn.execute(
+            "CREATE VIEW IF NOT EXISTS clips AS
+             SELECT id, content_type, content, preview_text,
+                    (SELECT json_group_array(tags.name ORDER BY clip_tags.position)
+                     FROM clip_tags JOIN tags ON tags.id = clip_tags.tag_id
+                     WHERE clip_tags.clip_id = clip_items.id) AS tags,
+                    source_app, created_at, last_used_at, pinned, metadata, use_count, content_hash
+             FROM clip_items",
+            [],
+        )?;
+
+        // Last synced state of every clip, including deleted ones
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS sync_state (
+                clip_id TEXT PRIMARY KEY,
+                present INTEGER NOT NULL,
+                present_at TEXT NOT NULL,
+                tags TEXT NOT NULL,
+                tags_at TEXT NOT NULL,
+                pinned INTEGER NOT NULL,
+                pinned_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // How far the change log of each other device has been read
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS sync_cursors (
+                device_id TEXT PRIMARY KEY,
+                offset INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        // Append-only journal of the changes made here or received from
+        // other devices, in the order they were applied
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS sync_changes (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                at TEXT NOT NULL,
+                op TEXT NOT NULL,
+                clip_id TEXT NOT NULL,
+                value TEXT,
+                origin TEXT
+            )",
+            [],
+        )?;
+
+        // Clips whose existence, tags or pin changed since the changes were
+        // last recorded for sync. A clip marked again gets a new position.
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sync_dirty (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                clip_id TEXT NOT NULL UNIQUE
+            );
+            CREATE TRIGGER IF NOT EXISTS sync_dirty_insert AFTER INSERT ON clip_items BEGIN
+                INSERT OR REPLACE INTO sync_dirty (clip_id) VALUES (NEW.id);
+            END;
+            CREATE TRIGGER IF NOT EXISTS sync_dirty_delete AFTER DELETE ON clip_items BEGIN
+                INSERT OR REPLACE INTO sync_dirty (clip_id) VALUES (OLD.id);
+            END;
+            CREATE TRIGGER IF NOT EXISTS sync_dirty_tag_insert AFTER INSERT ON clip_tags BEGIN
+                INSERT OR REPLACE INTO sync_dirty (clip_id) VALUES (NEW.clip_id);
+            END;
+            CREATE TRIGGER IF NOT EXISTS sync_dirty_tag_delete AFTER DELETE ON clip_tags BEGIN
+                INSERT OR REPLACE INTO sync_dirty (clip_id) VALUES (OLD.clip_id);
+            END;",
+        )?;
+        self.conn.execute_batch(SYNC_DIRTY_UPDATE_TRIGGER)?;
+
+        // Devices paired for sync over the local network
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS sync_peers (
+                device_id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                key TEXT NOT NULL,
+                address TEXT,
+                sent_seq INTEGER NOT NULL DEFAULT 0,
+                received_seq INTEGER NOT NULL DEFAULT 0,
+                paired_at TEXT NOT NULL,
+                last_sync_at TEXT
+            )",
+            [],
+        )?;
+
+        Ok(())
+    }
+
+    /// One-off data migrations, tracked with `PRAGMA user_version`
+    fn migrate(&self) -> SqliteResult<()> {
+        let version: i64 = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        // Tags were a JSON array in a column of clip_items before they had
+        // their own tables
+        if self.has_column("clip_items", "tags")? {
+            self.migrate_tags()?;
+        }
+
+        if version < 2 {
+            self.backfill_content_hashes()?;
+            self.conn.execute_batch("PRAGMA user_version = 2")?;
+        }
+
+        // Changes made before they were tracked are found by comparing every
+        // clip once
+        if version < 3 {
+            self.conn.execute_batch(
+                "INSERT OR IGNORE INTO sync_dirty (clip_id) SELECT id FROM clip_items;
+                 INSERT OR IGNORE INTO sync_dirty (clip_id) SELECT clip_id FROM sync_state WHERE present = 1;
+                 PRAGMA user_version = 3",
+            )?;
+        }
+
+        // Hashes used to come from std's DefaultHasher, which may change
+        // between Rust releases
+        if version < 4 {
+            self.rehash_clips()?;
+            self.conn.execute_batch("PRAGMA user_version = 4")?;
+        }
+
+        Ok(())
+    }
+
+    /// Move the tags of every clip into `tags` and `clip_tags` and drop the
+    /// old column
+    fn migrate_tags(&self) -> SqliteResult<()> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        let clips: Vec<(String, String)> = {
+            let mut stmt = tx.prepare("SELECT id, tags FROM clip_items WHERE tags != '[]'")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<SqliteResult<_>>()?
+        };
+        for (id, tags) in &clips {
+            let tags: Vec<String> = serde_json::from_str(tags).unwrap_or_default();
+            set_clip_tags(&tx, id, &tags)?;
+        }
+
+        // The column cannot be dropped while the trigger names it
+        tx.execute_batch(&format!(
+            "DROP TRIGGER IF EXISTS sync_dirty_update;
+             ALTER TABLE clip_items DROP COLUMN tags;
+             {}",
+            SYNC_DIRTY_UPDATE_TRIGGER
+        ))?;
+
+        tx.commit()
+    }
+
+    /// Set the content hash of clips stored before it had a column. Edited plain
+    /// text clips no longer match their id, so plain text is hashed again.
+    fn backfill_content_hashes(&self) -> SqliteResult<()> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        let clips: Vec<(String, String, String)> = {
+            let mut stmt = tx.prepare(
+                "SELECT id, content_type, content FROM clip_items WHERE content_hash IS NULL",
+            )?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            rows.collect::<SqliteResult<_>>()?
+        };
+
+        for (id, content_type, content) in clips {
+            let content_type = ContentType::from_str(&content_type).unwrap_or(ContentType::Plain);
+            tx.execute(
+                "UPDATE clip_items SET content_hash = ?2 WHERE id = ?1",
+                params![id, content_hash(&id, &content_type, &content)],
+            )?;
+        }
+
+        tx.commit()
+    }
+
+    /// Hash text clips again with `stable_hash`. Clips whose id is still the
+    /// hash of their content move to the new hash, so devices agree on the
+    /// ids of clips they stored on their own; sync sends the move as a
+    /// removal and an addition. Images keep their ids, their original data is
+    /// not kept to hash again.
+    fn rehash_clips(&self) -> SqliteResult<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        // Ids change together with the rows that refer to them
+        tx.execute_batch("PRAGMA defer_foreign_keys = ON")?;
+
+        let clips: Vec<(String, String, String, Option<String>)> = {
+            let mut stmt = tx.prepare(
+                "SELECT id, content_type, content, content_hash FROM clip_items
+                 WHERE content_type IN ('plain', 'rich')",
+            )?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })?;
+            rows.collect::<SqliteResult<_>>()?
+        };
+
+        for (id, content_type, content, old_hash) in clips {
+            let content_type = ContentType::from_str(&content_type).unwrap_or(ContentType::Plain);
+            let hash = content_hash(&id, &content_type, &content);
+            let taken: bool = tx.query_row(
+                "SELECT EXISTS(SELECT 1 FROM clip_items WHERE id = ?1)",
+                params![hash],
+                |row| row.get(0),
+            )?;
+
+            if old_hash.as_deref() == Some(id.as_str()) && !taken {
+                tx.execute(
+                    "UPDATE clip_items SET id = ?2, content_hash = ?2 WHERE id = ?1",
+                    params![id, hash],
+                )?;
+                tx.execute(
+                    "UPDATE collection_items SET clip_id = ?2 WHERE clip_id = ?1",
+                    params![id, hash],
+                )?;
+                tx.execute(
+                    "UPDATE clip_revisions SET clip_id = ?2 WHERE clip_id = ?1",
+                    params![id, hash],
+                )?;
+                tx.execute(
+                    "UPDATE clip_tags SET clip_id = ?2 WHERE clip_id = ?1",
+                    params![id, hash],
+                )?;
+            } else {
+                tx.execute(
+                    "UPDATE clip_items SET content_hash = ?2 WHERE id = ?1",
+                    params![id, hash],
+                )?;
+            }
+        }
+
+        tx.commit()
+    }
+
+    /// Add a column to an existing table if an older database lacks it
+    fn ensure_column(&self, table: &str, column: &str, definition: &str) -> SqliteResult<()> {
+        if !self.has_column(table, column)? {
+            self.conn.execute(
+                &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+                [],
+            )?;
+        }
+
         Ok(())
     }
 
+    fn has_column(&self, table: &str, column: &str) -> SqliteResult<bool> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!("PRAGMA table_info({})", table))?;
+        let columns = stmt
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<SqliteResult<Vec<_>>>()?;
+        Ok(columns.iter().any(|name| name == column))
+    }
+
+    /// Insert a clip, or update the last use and content of a clip with the
+    /// same id. The tags are only set on new clips.
     pub fn insert_clip(&self, item: &ClipItem) -> SqliteResult<()> {
-        let tags_json = serde_json::to_string(&item.tags).unwrap_or_default();
         let metadata_json = item.metadata.as_ref().map(|m| m.to_string());
+        let tx = self.conn.unchecked_transaction()?;
+        let is_new = !clip_exists(&tx, &item.id)?;
 
-        self.conn.execute(
-            "INSERT INTO clip_items (id, content_type, content, preview_text, tags, source_app, created_at, last_used_at, pinned, metadata)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+        tx.execute(
+            "INSERT INTO clip_items (id, content_type, content, preview_text, source_app, created_at, last_used_at, pinned, metadata, use_count, content_hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
              ON CONFLICT(id) DO UPDATE SET
              last_used_at = excluded.last_used_at,
-             content = excluded.content",
+             content = excluded.content,
+             content_hash = excluded.content_hash",
             params![
                 item.id,
                 item.content_type.as_str(),
                 item.content,
                 item.preview_text,
-                tags_json,
                 item.source_app,
                 item.created_at.to_rfc3339(),
                 item.last_used_at.to_rfc3339(),
                 item.pinned as i32,
                 metadata_json,
+                item.use_count,
+                content_hash(&item.id, &item.content_type, &item.content),
             ],
         )?;
 
-        Ok(())
+        if is_new {
+            set_clip_tags(&tx, &item.id, &item.tags)?;
+        }
+        tx.commit()
     }
 
     pub fn get_clips(&self, limit: i64, offset: i64) -> SqliteResult<Vec<ClipItem>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, content_type, content, preview_text, tags, source_app, created_at, last_used_at, pinned, metadata
-             FROM clip_items
+            "SELECT id, content_type, content, preview_text, tags, source_app, created_at, last_used_at, pinned, metadata, use_count
+             FROM clips
              ORDER BY pinned DESC, created_at DESC
              LIMIT ?1 OFFSET ?2"
         )?;
 
         let clips = stmt
-            .query_map(params![limit, offset], |row| {
-                let content_type_str: String = row.get(1)?;
-                let tags_json: String = row.get(4)?;
-                let metadata_str: Option<String> = row.get(9)?;
-
-                Ok(ClipItem {
-                    id: row.get(0)?,
-                    content_type: ContentType::from_str(&content_type_str)
-                        .unwrap_or(ContentType::Plain),
-                    content: row.get(2)?,
-                    preview_text: row.get(3)?,
-                    tags: serde_json::from_str(&tags_json).unwrap_or_default(),
-                    source_app: row.get(5)?,
-                    created_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(6)?)
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .unwrap_or_else(|_| Utc::now()),
-                    last_used_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(7)?)
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .unwrap_or_else(|_| Utc::now()),
-                    pinned: row.get::<_, i32>(8)? != 0,
-                    metadata: metadata_str.and_then(|s| serde_json::from_str(&s).ok()),
-                })
-            })?
+            .query_map(params![limit, offset], clip_from_row)?
             .collect::<SqliteResult<Vec<_>>>()?;
 
         Ok(clips)
     }
 
-    pub fn search_clips(&self, query: &str, limit: i64) -> SqliteResult<Vec<ClipItem>> {
-        let query_lower = query.to_lowercase();
+    /// Insert imported clips in one transaction. Clips with an invalid id are
+    /// skipped. Text clips already stored with the same content, and other
+    /// clips with the same id, are skipped, or merged into the existing clip
+    /// when `merge_existing` is set: tags are combined and the pin, use count
+    /// and last use are kept from whichever is higher. Returns the number of
+    /// inserted and merged clips.
+    pub fn import_clips(
+        &self,
+        clips: &[ClipItem],
+        merge_existing: bool,
+    ) -> SqliteResult<(usize, usize)> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut inserted = 0;
+        let mut merged = 0;
+
+        for clip in clips {
+            if !is_valid_clip_id(&clip.id) {
+                continue;
+            }
 
-        // Check if it's a tag search
-        if query_lower.starts_with("tag:") || query_lower.starts_with("#") {
-            let tag = if query_lower.starts_with("tag:") {
-                query[4..].trim()
-            } else {
-                query[1..].trim()
+            // Edited text keeps its id, so text is matched by what it says
+            let hash = content_hash(&clip.id, &clip.content_type, &clip.content);
+            let existing = match clip.content_type {
+                ContentType::Plain | ContentType::Rich => tx.query_row(
+                    "SELECT id FROM clip_items WHERE content_hash = ?1
+                     ORDER BY last_used_at DESC LIMIT 1",
+                    params![hash],
+                    |row| row.get::<_, String>(0),
+                ),
+                _ => tx.query_row(
+                    "SELECT id FROM clip_items WHERE id = ?1",
+                    params![clip.id],
+                    |row| row.get(0),
+                ),
+            };
+            let existing = match existing {
+                Ok(existing) => Some(existing),
+                Err(rusqlite::Error::QueryReturnedNoRows) => None,
+                Err(e) => return Err(e),
             };
-            return self.search_by_tag(tag, limit);
-        }
 
-        // Check if it's a content type filter
-        if let Some(content_type) = query_lower.strip_prefix("type:") {
-            let content_type = content_type.trim();
-            return self.search_by_content_type(content_type, limit);
-        }
+            let Some(existing_id) = existing else {
+                // The id may belong to a local clip edited to other text
+                let id = if self.get_clip_by_id(&clip.id)?.is_some() {
+                    self.new_clip_id(&hash)?
+                } else {
+                    clip.id.clone()
+                };
+                tx.execute(
+                    "INSERT INTO clip_items (id, content_type, content, preview_text, source_app, created_at, last_used_at, pinned, metadata, use_count, content_hash)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                    params![
+                        id,
+                        clip.content_type.as_str(),
+                        clip.content,
+                        clip.preview_text,
+                        clip.source_app,
+                        clip.created_at.to_rfc3339(),
+                        clip.last_used_at.to_rfc3339(),
+                        clip.pinned as i32,
+                        clip.metadata.as_ref().map(|m| m.to_string()),
+                        clip.use_count,
+                        hash,
+                    ],
+                )?;
+                set_clip_tags(&tx, &id, &clip.tags)?;
+                inserted += 1;
+                continue;
+            };
 
-        // Simple LIKE search on preview_text and content
-        let search_pattern = format!("%{}%", query_lower);
+            if !merge_existing {
+                continue;
+            }
 
-        let mut stmt = self.conn.prepare(
-            "SELECT id, content_type, content, preview_text, tags, source_app, created_at, last_used_at, pinned, metadata
-             FROM clip_items
-             WHERE LOWER(preview_text) LIKE ?1 OR LOWER(content) LIKE ?1
-             ORDER BY pinned DESC, created_at DESC
-             LIMIT ?2"
-        )?;
+            let mut tags = clip_tags(&tx, &existing_id)?;
+            for tag in &clip.tags {
+                if !tags.contains(tag) {
+                    tags.push(tag.clone());
+                }
+            }
 
-        let clips = stmt
-            .query_map(params![search_pattern, limit], |row| {
-                let content_type_str: String = row.get(1)?;
-                let tags_json: String = row.get(4)?;
-                let metadata_str: Option<String> = row.get(9)?;
+            tx.execute(
+                "UPDATE clip_items SET
+                 pinned = MAX(pinned, ?2),
+                 use_count = MAX(use_count, ?3),
+                 last_used_at = MAX(last_used_at, ?4)
+                 WHERE id = ?1",
+                params![
+                    existing_id,
+                    clip.pinned as i32,
+                    clip.use_count,
+                    clip.last_used_at.to_rfc3339(),
+                ],
+            )?;
+            set_clip_tags(&tx, &existing_id, &tags)?;
+            merged += 1;
+        }
 
-                Ok(ClipItem {
-                    id: row.get(0)?,
-                    content_type: ContentType::from_str(&content_type_str)
-                        .unwrap_or(ContentType::Plain),
-                    content: row.get(2)?,
-                    preview_text: row.get(3)?,
-                    tags: serde_json::from_str(&tags_json).unwrap_or_default(),
-                    source_app: row.get(5)?,
-                    created_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(6)?)
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .unwrap_or_else(|_| Utc::now()),
-                    last_used_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(7)?)
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .unwrap_or_else(|_| Utc::now()),
-                    pinned: row.get::<_, i32>(8)? != 0,
-                    metadata: metadata_str.and_then(|s| serde_json::from_str(&s).ok()),
-                })
-            })?
-            .collect::<SqliteResult<Vec<_>>>()?;
+        tx.commit()?;
+        Ok((inserted, merged))
+    }
 
-        Ok(clips)
+    pub fn search_clips(&self, query: &str, limit: i64) -> SqliteResult<Vec<ClipItem>> {
+        self.filter_clips(Some(query), &ClipFilter::default(), limit)
     }
 
-    pub fn search_by_tag(&self, tag: &str, limit: i64) -> SqliteResult<Vec<ClipItem>> {
-        let tag_lower = tag.to_lowercase();
-        let mut stmt = self.conn.prepare(
-            "SELECT id, content_type, content, preview_text, tags, source_app, created_at, last_used_at, pinned, metadata
-             FROM clip_items
-             WHERE LOWER(tags) LIKE ?1
+    /// The first `limit` clips matching `filter` and, if given, `query` as in
+    /// `search_clips`
+    pub fn filter_clips(
+        &self,
+        query: Option<&str>,
+        filter: &ClipFilter,
+        limit: i64,
+    ) -> SqliteResult<Vec<ClipItem>> {
+        let (condition, value) = match query {
+            Some(query) => search_condition(query),
+            None => ("1", String::new()),
+        };
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT id, content_type, content, preview_text, tags, source_app, created_at, last_used_at, pinned, metadata, use_count
+             FROM clips
+             WHERE ({})
+             AND (?2 IS NULL OR created_at >= ?2)
+             AND (?3 IS NULL OR created_at < ?3)
+             AND (json_array_length(?4) = 0 OR id IN (
+                 SELECT clip_tags.clip_id FROM clip_tags JOIN tags ON tags.id = clip_tags.tag_id
+                 WHERE tags.name IN (SELECT value FROM json_each(?4))
+             ))
+             AND (json_array_length(?5) = 0
+                  OR content_type IN (SELECT LOWER(value) FROM json_each(?5)))
+             AND (?6 = 0 OR pinned = 1)
              ORDER BY pinned DESC, created_at DESC
-             LIMIT ?2"
-        )?;
+             LIMIT ?7",
+            condition
+        ))?;
 
-        let tag_pattern = format!("%\"{}\"%", tag_lower);
         let clips = stmt
-            .query_map(params![tag_pattern, limit], |row| {
-                let content_type_str: String = row.get(1)?;
-                let tags_json: String = row.get(4)?;
-                let metadata_str: Option<String> = row.get(9)?;
-
-                Ok(ClipItem {
-                    id: row.get(0)?,
-                    content_type: ContentType::from_str(&content_type_str)
-                        .unwrap_or(ContentType::Plain),
-                    content: row.get(2)?,
-                    preview_text: row.get(3)?,
-                    tags: serde_json::from_str(&tags_json).unwrap_or_default(),
-                    source_app: row.get(5)?,
-                    created_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(6)?)
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .unwrap_or_else(|_| Utc::now()),
-                    last_used_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(7)?)
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .unwrap_or_else(|_| Utc::now()),
-                    pinned: row.get::<_, i32>(8)? != 0,
-                    metadata: metadata_str.and_then(|s| serde_json::from_str(&s).ok()),
-                })
-            })?
+            .query_map(
+                params![
+                    value,
+                    filter.from.map(|from| from.to_rfc3339()),
+                    filter.to.map(|to| to.to_rfc3339()),
+                    serde_json::to_string(&filter.tags).unwrap_or_default(),
+                    serde_json::to_string(&filter.content_types).unwrap_or_default(),
+                    filter.pinned_only as i32,
+                    limit,
+                ],
+                clip_from_row,
+            )?
             .collect::<SqliteResult<Vec<_>>>()?;
 
         Ok(clips)
     }
 
-    pub fn search_by_content_type(
-        &self,
-        content_type: &str,
-        limit: i64,
-    ) -> SqliteResult<Vec<ClipItem>> {
+    /// Text clips stored before classification existed
+    pub fn get_unclassified_clips(&self, limit: i64) -> SqliteResult<Vec<ClipItem>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, content_type, content, preview_text, tags, source_app, created_at, last_used_at, pinned, metadata
-             FROM clip_items
-             WHERE LOWER(content_type) = ?1
-             ORDER BY pinned DESC, created_at DESC
-             LIMIT ?2"
+            "SELECT id, content_type, content, preview_text, tags, source_app, created_at, last_used_at, pinned, metadata, use_count
+             FROM clips
+             WHERE content_type IN ('plain', 'rich') AND json_extract(metadata, '$.kind') IS NULL
+             LIMIT ?1"
         )?;
 
         let clips = stmt
-            .query_map(params![content_type.to_lowercase(), limit], |row| {
-                let content_type_str: String = row.get(1)?;
-                let tags_json: String = row.get(4)?;
-                let metadata_str: Option<String> = row.get(9)?;
-
-                Ok(ClipItem {
-                    id: row.get(0)?,
-                    content_type: ContentType::from_str(&content_type_str)
-                        .unwrap_or(ContentType::Plain),
-                    content: row.get(2)?,
-                    preview_text: row.get(3)?,
-                    tags: serde_json::from_str(&tags_json).unwrap_or_default(),
-                    source_app: row.get(5)?,
-                    created_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(6)?)
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .unwrap_or_else(|_| Utc::now()),
-                    last_used_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(7)?)
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .unwrap_or_else(|_| Utc::now()),
-                    pinned: row.get::<_, i32>(8)? != 0,
-                    metadata: metadata_str.and_then(|s| serde_json::from_str(&s).ok()),
-                })
-            })?
+            .query_map(params![limit], clip_from_row)?
             .collect::<SqliteResult<Vec<_>>>()?;
 
         Ok(clips)
     }
 
-    pub fn update_clip_tags(&self, id: &str, tags: Vec<String>) -> SqliteResult<()> {
-        let tags_json = serde_json::to_string(&tags).unwrap_or_default();
+    pub fn update_clip_metadata(
+        &self,
+        id: &str,
+        metadata: Option<&serde_json::Value>,
+    ) -> SqliteResult<()> {
         self.conn.execute(
-            "UPDATE clip_items SET tags = ?1 WHERE id = ?2",
-            params![tags_json, id],
+            "UPDATE clip_items SET metadata = ?1 WHERE id = ?2",
+            params![metadata.map(|m| m.to_string()), id],
         )?;
         Ok(())
     }
 
-    pub fn toggle_pin(&self, id: &str, pinned: bool) -> SqliteResult<()> {
-        self.conn.execute(
-            "UPDATE clip_items SET pinned = ?1 WHERE id = ?2",
-            params![pinned as i32, id],
+    /// Replace the content of a clip, keeping its id, tags, pin and collections.
+    /// The previous content is stored as a revision; only the newest
+    /// `max_revisions` revisions of the clip are kept.
+    pub fn update_clip_content(
+        &self,
+        id: &str,
+        content: &str,
+        preview_text: &str,
+        metadata: Option<&serde_json::Value>,
+        max_revisions: usize,
+    ) -> SqliteResult<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        tx.execute(
+            "INSERT INTO clip_revisions (clip_id, content, preview_text, metadata, created_at)
+             SELECT id, content, preview_text, metadata, ?2 FROM clip_items WHERE id = ?1",
+            params![id, Utc::now().to_rfc3339()],
         )?;
-        Ok(())
-    }
 
-    pub fn delete_clip(&self, id: &str) -> SqliteResult<()> {
-        self.conn
-            .execute("DELETE FROM clip_items WHERE id = ?1", params![id])?;
-        Ok(())
+        let updated = tx.execute(
+            "UPDATE clip_items SET content = ?2, preview_text = ?3, metadata = ?4, content_hash = ?5
+             WHERE id = ?1",
+            params![
+                id,
+                content,
+                preview_text,
+                metadata.map(|m| m.to_string()),
+                generate_content_hash(content),
+            ],
+        )?;
+
+        tx.execute(
+            "DELETE FROM clip_revisions WHERE clip_id = ?1 AND id NOT IN (
+                 SELECT id FROM clip_revisions WHERE clip_id = ?1 ORDER BY id DESC LIMIT ?2
+             )",
+            params![id, max_revisions as i64],
+        )?;
+
+        tx.commit()?;
+        Ok(updated)
     }
 
-    pub fn get_clip_by_content_hash(&self, hash: &str) -> SqliteResult<Option<ClipItem>> {
+    /// Revisions of a clip, newest first
+    pub fn get_clip_revisions(&self, clip_id: &str) -> SqliteResult<Vec<ClipRevision>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, content_type, content, preview_text, tags, source_app, created_at, last_used_at, pinned, metadata
-             FROM clip_items
-             WHERE id = ?1"
+            "SELECT id, clip_id, content, preview_text, created_at
+             FROM clip_revisions
+             WHERE clip_id = ?1
+             ORDER BY id DESC",
         )?;
 
-        let result = stmt.query_row(params![hash], |row| {
-            let content_type_str: String = row.get(1)?;
-            let tags_json: String = row.get(4)?;
-            let metadata_str: Option<String> = row.get(9)?;
-
-            Ok(ClipItem {
-                id: row.get(0)?,
-                content_type: ContentType::from_str(&content_type_str)
-                    .unwrap_or(ContentType::Plain),
-                content: row.get(2)?,
-                preview_text: row.get(3)?,
-                tags: serde_json::from_str(&tags_json).unwrap_or_default(),
-                source_app: row.get(5)?,
-                created_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(6)?)
-                    .map(|dt| dt.with_timezone(&Utc))
-                    .unwrap_or_else(|_| Utc::now()),
-                last_used_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(7)?)
-                    .map(|dt| dt.with_timezone(&Utc))
-                    .unwrap_or_else(|_| Utc::now()),
-                pinned: row.get::<_, i32>(8)? != 0,
-                metadata: metadata_str.and_then(|s| serde_json::from_str(&s).ok()),
-            })
-        });
+        let revisions = stmt
+            .query_map(params![clip_id], revision_from_row)?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        Ok(revisions)
+    }
+
+    pub fn get_clip_revision(&self, id: i64) -> SqliteResult<Option<ClipRevision>> {
+        let result = self.conn.query_row(
+            "SELECT id, clip_id, content, preview_text, created_at
+             FROM clip_revisions
+             WHERE id = ?1",
+            params![id],
+            revision_from_row,
+        );
 
         match result {
-            Ok(item) => Ok(Some(item)),
+            Ok(revision) => Ok(Some(revision)),
             Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
             Err(e) => Err(e),
         }
     }
 
-    pub fn cleanup_old_clips(&self, days: i64) -> SqliteResult<usize> {
-        let cutoff = Utc::now() - chrono::Duration::days(days);
-        let result = self.conn.execute(
-            "DELETE FROM clip_items WHERE pinned = 0 AND created_at < ?1",
+    pub fn update_clip_tags(&self, id: &str, tags: Vec<String>) -> SqliteResult<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        set_clip_tags(&tx, id, &tags)?;
+        tx.commit()
+    }
+
+    pub fn toggle_pin(&self, id: &str, pinned: bool) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE clip_items SET pinned = ?1 WHERE id = ?2",
+            params![pinned as i32, id],
+        )?;
+        Ok(())
+    }
+
+    pub fn set_clip_timestamps(
+        &self,
+        id: &str,
+        created_at: DateTime<Utc>,
+        last_used_at: DateTime<Utc>,
+    ) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE clip_items SET created_at = ?1, last_used_at = ?2 WHERE id = ?3",
+            params![created_at.to_rfc3339(), last_used_at.to_rfc3339(), id],
+        )?;
+        Ok(())
+    }
+
+    /// Record that a stored clip was written back to the clipboard
+    pub fn record_clip_use(&self, id: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE clip_items SET last_used_at = ?1, use_count = use_count + 1 WHERE id = ?2",
+            params![Utc::now().to_rfc3339(), id],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_clip(&self, id: &str) -> SqliteResult<()> {
+        self.conn
+            .execute("DELETE FROM clip_items WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Look up a clip by its id. Ids stay the same when clips are edited; use
+    /// `find_duplicate_clip` to look up clips by their current content.
+    pub fn get_clip_by_id(&self, id: &str) -> SqliteResult<Option<ClipItem>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, content_type, content, preview_text, tags, source_app, created_at, last_used_at, pinned, metadata, use_count
+             FROM clips
+             WHERE id = ?1"
+        )?;
+
+        let result = stmt.query_row(params![id], clip_from_row);
+
+        match result {
+            Ok(item) => Ok(Some(item)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The clip whose current content has this hash, the most recently used one
+    /// if edits made several clips equal
+    pub fn find_duplicate_clip(&self, content_hash: &str) -> SqliteResult<Option<ClipItem>> {
+        let result = self.conn.query_row(
+            "SELECT id, content_type, content, preview_text, tags, source_app, created_at, last_used_at, pinned, metadata, use_count
+             FROM clips
+             WHERE content_hash = ?1
+             ORDER BY last_used_at DESC
+             LIMIT 1",
+            params![content_hash],
+            clip_from_row,
+        );
+
+        match result {
+            Ok(item) => Ok(Some(item)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Id for a new clip with this content hash: the hash itself, unless an
+    /// edited clip kept it as its id, then one derived from it
+    pub fn new_clip_id(&self, content_hash: &str) -> SqliteResult<String> {
+        let mut id = content_hash.to_string();
+        let mut attempt = 0;
+        while self.get_clip_by_id(&id)?.is_some() {
+            attempt += 1;
+            id = generate_content_hash(&format!("{}:{}", content_hash, attempt));
+        }
+        Ok(id)
+    }
+
+    pub fn cleanup_old_clips(&self, days: i64) -> SqliteResult<usize> {
+        let cutoff = Utc::now() - chrono::Duration::days(days);
+        let result = self.conn.execute(
+            "DELETE FROM clip_items WHERE pinned = 0 AND created_at < ?1",
             params![cutoff.to_rfc3339()],
         )?;
         Ok(result)
@@ -418,38 +1118,108 @@ impl Database {
     }
 
     pub fn get_all_tags(&self) -> SqliteResult<Vec<String>> {
-        let mut stmt = self.conn.prepare("SELECT tags FROM clip_items")?;
-        let rows = stmt.query_map([], |row| {
-            let tags_json: String = row.get(0)?;
-            Ok(tags_json)
-        })?;
-
-        let mut all_tags = std::collections::HashSet::new();
-        for tags_json in rows {
-            if let Ok(tags) = serde_json::from_str::<Vec<String>>(&tags_json?) {
-                for tag in tags {
-                    // Keep original case for display
-                    all_tags.insert(tag);
-                }
-            }
-        }
+        // Keep original case for display
+        let mut stmt = self.conn.prepare(
+            "SELECT name FROM tags
+             WHERE EXISTS (SELECT 1 FROM clip_tags WHERE clip_tags.tag_id = tags.id)",
+        )?;
+
+        let mut tags = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<SqliteResult<Vec<_>>>()?;
 
-        let mut tags: Vec<String> = all_tags.into_iter().collect();
         tags.sort_by_key(|a| a.to_lowercase());
         Ok(tags)
     }
 
+    /// Up to 10 tags in use containing `query`, ignoring case
     pub fn search_tags(&self, query: &str) -> SqliteResult<Vec<String>> {
-        let all_tags = self.get_all_tags()?;
-        let query_lower = query.to_lowercase();
+        let mut stmt = self.conn.prepare(
+            "SELECT name FROM tags
+             WHERE INSTR(LOWER(name), LOWER(?1)) > 0
+               AND EXISTS (SELECT 1 FROM clip_tags WHERE clip_tags.tag_id = tags.id)
+             ORDER BY LOWER(name)
+             LIMIT 10",
+        )?;
 
-        let filtered: Vec<String> = all_tags
-            .into_iter()
-            .filter(|tag| tag.to_lowercase().contains(&query_lower))
-            .take(10)
-            .collect();
+        let tags = stmt
+            .query_map(params![query], |row| row.get(0))?
+            .collect::<SqliteResult<Vec<_>>>()?;
 
-        Ok(filtered)
+        Ok(tags)
+    }
+
+    /// Every tag with the number of clips carrying it and the most recent use
+    /// of any of those clips
+    pub fn get_tag_stats(&self) -> SqliteResult<Vec<TagStats>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT tags.name, COUNT(*), MAX(clip_items.last_used_at)
+             FROM tags
+             JOIN clip_tags ON clip_tags.tag_id = tags.id
+             JOIN clip_items ON clip_items.id = clip_tags.clip_id
+             GROUP BY tags.id",
+        )?;
+
+        let mut stats = stmt
+            .query_map([], |row| {
+                Ok(TagStats {
+                    name: row.get(0)?,
+                    clip_count: row.get(1)?,
+                    last_used_at: row
+                        .get::<_, Option<String>>(2)?
+                        .map(|value| parse_timestamp(&value)),
+                })
+            })?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        stats.sort_by_key(|tag| tag.name.to_lowercase());
+        Ok(stats)
+    }
+
+    /// Replace the tags in `tags` with `replacement` on every clip, or remove
+    /// them when `replacement` is `None`. Runs in a single transaction and
+    /// returns the number of clips changed.
+    pub fn replace_tags(&self, tags: &[String], replacement: Option<&str>) -> SqliteResult<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        let clip_ids: Vec<String> = {
+            let mut stmt = tx.prepare(
+                "SELECT DISTINCT clip_tags.clip_id
+                 FROM clip_tags JOIN tags ON tags.id = clip_tags.tag_id
+                 WHERE tags.name IN (SELECT value FROM json_each(?1))",
+            )?;
+            let rows = stmt.query_map(
+                params![serde_json::to_string(tags).unwrap_or_default()],
+                |row| row.get(0),
+            )?;
+            rows.collect::<SqliteResult<Vec<_>>>()?
+        };
+
+        for id in &clip_ids {
+            let mut updated: Vec<String> = Vec::new();
+            for tag in clip_tags(&tx, id)? {
+                let tag = if tags.contains(&tag) {
+                    match replacement {
+                        Some(replacement) => replacement.to_string(),
+                        None => continue,
+                    }
+                } else {
+                    tag
+                };
+                if !updated.contains(&tag) {
+                    updated.push(tag);
+                }
+            }
+
+            set_clip_tags(&tx, id, &updated)?;
+        }
+        tx.execute(
+            "DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM clip_tags)",
+            [],
+        )?;
+
+        tx.commit()?;
+        Ok(clip_ids.len())
     }
 
     #[allow(dead_code)]
@@ -460,41 +1230,732 @@ impl Database {
         limit: i64,
     ) -> SqliteResult<Vec<ClipItem>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, content_type, content, preview_text, tags, source_app, created_at, last_used_at, pinned, metadata
-             FROM clip_items
+            "SELECT id, content_type, content, preview_text, tags, source_app, created_at, last_used_at, pinned, metadata, use_count
+             FROM clips
              WHERE content_type = ?1
              ORDER BY pinned DESC, created_at DESC
              LIMIT ?2"
         )?;
 
         let clips = stmt
-            .query_map(params![content_type.as_str(), limit], |row| {
-                let content_type_str: String = row.get(1)?;
-                let tags_json: String = row.get(4)?;
-                let metadata_str: Option<String> = row.get(9)?;
+            .query_map(params![content_type.as_str(), limit], clip_from_row)?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        Ok(clips)
+    }
+
+    /// Insert a snippet or update it if the id already exists
+    pub fn save_snippet(&self, snippet: &Snippet) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO snippets (id, folder_id, name, content, abbreviation, expansion_apps, created_at, updated_at, use_count)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT(id) DO UPDATE SET
+             folder_id = excluded.folder_id,
+             name = excluded.name,
+             content = excluded.content,
+             abbreviation = excluded.abbreviation,
+             expansion_apps = excluded.expansion_apps,
+             updated_at = excluded.updated_at",
+            params![
+                snippet.id,
+                snippet.folder_id,
+                snippet.name,
+                snippet.content,
+                snippet.abbreviation,
+                serde_json::to_string(&snippet.expansion_apps).unwrap_or_default(),
+                snippet.created_at.to_rfc3339(),
+                snippet.updated_at.to_rfc3339(),
+                snippet.use_count,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_snippet(&self, id: &str) -> SqliteResult<Option<Snippet>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, folder_id, name, content, abbreviation, expansion_apps, created_at, updated_at, use_count
+             FROM snippets
+             WHERE id = ?1",
+        )?;
+
+        match stmt.query_row(params![id], snippet_from_row) {
+            Ok(snippet) => Ok(Some(snippet)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Snippets in a folder (`None` for the top level), optionally filtered by
+    /// name, abbreviation or content
+    pub fn get_snippets(
+        &self,
+        folder_id: Option<&str>,
+        query: Option<&str>,
+    ) -> SqliteResult<Vec<Snippet>> {
+        let pattern = format!("%{}%", query.unwrap_or("").to_lowercase());
+        let mut stmt = self.conn.prepare(
+            "SELECT id, folder_id, name, content, abbreviation, expansion_apps, created_at, updated_at, use_count
+             FROM snippets
+             WHERE folder_id IS ?1
+               AND (LOWER(name) LIKE ?2 OR LOWER(IFNULL(abbreviation, '')) LIKE ?2 OR LOWER(content) LIKE ?2)
+             ORDER BY LOWER(name)",
+        )?;
+
+        let snippets = stmt
+            .query_map(params![folder_id, pattern], snippet_from_row)?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        Ok(snippets)
+    }
 
-                Ok(ClipItem {
+    /// All snippets that have an abbreviation
+    pub fn get_snippets_with_abbreviation(&self) -> SqliteResult<Vec<Snippet>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, folder_id, name, content, abbreviation, expansion_apps, created_at, updated_at, use_count
+             FROM snippets
+             WHERE abbreviation IS NOT NULL",
+        )?;
+
+        let snippets = stmt
+            .query_map([], snippet_from_row)?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        Ok(snippets)
+    }
+
+    pub fn record_snippet_use(&self, id: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "UPDATE snippets SET use_count = use_count + 1 WHERE id = ?1",
+            params![id],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_snippet(&self, id: &str) -> SqliteResult<()> {
+        self.conn
+            .execute("DELETE FROM snippets WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    pub fn save_snippet_folder(&self, folder: &SnippetFolder) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO snippet_folders (id, name, parent_id, created_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(id) DO UPDATE SET
+             name = excluded.name,
+             parent_id = excluded.parent_id",
+            params![
+                folder.id,
+                folder.name,
+                folder.parent_id,
+                folder.created_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_snippet_folders(&self) -> SqliteResult<Vec<SnippetFolder>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, parent_id, created_at FROM snippet_folders ORDER BY LOWER(name)",
+        )?;
+
+        let folders = stmt
+            .query_map([], |row| {
+                Ok(SnippetFolder {
                     id: row.get(0)?,
-                    content_type: ContentType::from_str(&content_type_str)
-                        .unwrap_or(ContentType::Plain),
-                    content: row.get(2)?,
-                    preview_text: row.get(3)?,
-                    tags: serde_json::from_str(&tags_json).unwrap_or_default(),
-                    source_app: row.get(5)?,
-                    created_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(6)?)
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .unwrap_or_else(|_| Utc::now()),
-                    last_used_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(7)?)
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .unwrap_or_else(|_| Utc::now()),
-                    pinned: row.get::<_, i32>(8)? != 0,
-                    metadata: metadata_str.and_then(|s| serde_json::from_str(&s).ok()),
+                    name: row.get(1)?,
+                    parent_id: row.get(2)?,
+                    created_at: parse_timestamp(&row.get::<_, String>(3)?),
                 })
             })?
             .collect::<SqliteResult<Vec<_>>>()?;
 
+        Ok(folders)
+    }
+
+    /// Delete a folder. Its snippets and subfolders move up to the parent folder.
+    pub fn delete_snippet_folder(&self, id: &str) -> SqliteResult<()> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        let parent_id: Option<String> = match tx.query_row(
+            "SELECT parent_id FROM snippet_folders WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        ) {
+            Ok(parent_id) => parent_id,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        tx.execute(
+            "UPDATE snippets SET folder_id = ?1 WHERE folder_id = ?2",
+            params![parent_id, id],
+        )?;
+        tx.execute(
+            "UPDATE snippet_folders SET parent_id = ?1 WHERE parent_id = ?2",
+            params![parent_id, id],
+        )?;
+        tx.execute("DELETE FROM snippet_folders WHERE id = ?1", params![id])?;
+
+        tx.commit()
+    }
+
+    pub fn insert_collection(&self, collection: &Collection) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT INTO collections (id, name, parent_id, color, position, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                collection.id,
+                collection.name,
+                collection.parent_id,
+                collection.color,
+                collection.position,
+                collection.created_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// All collections with their clip counts, ordered by position
+    pub fn get_collections(&self) -> SqliteResult<Vec<Collection>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT c.id, c.name, c.parent_id, c.color, c.position, c.created_at, COUNT(ci.clip_id)
+             FROM collections c
+             LEFT JOIN collection_items ci ON ci.collection_id = c.id
+             GROUP BY c.id
+             ORDER BY c.position, LOWER(c.name)",
+        )?;
+
+        let collections = stmt
+            .query_map([], |row| {
+                Ok(Collection {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    parent_id: row.get(2)?,
+                    color: row.get(3)?,
+                    position: row.get(4)?,
+                    created_at: parse_timestamp(&row.get::<_, String>(5)?),
+                    clip_count: row.get(6)?,
+                })
+            })?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        Ok(collections)
+    }
+
+    /// Rename and recolor a collection
+    pub fn update_collection(
+        &self,
+        id: &str,
+        name: &str,
+        color: Option<&str>,
+    ) -> SqliteResult<usize> {
+        self.conn.execute(
+            "UPDATE collections SET name = ?1, color = ?2 WHERE id = ?3",
+            params![name, color, id],
+        )
+    }
+
+    /// Move a collection under `parent_id` (`None` for the top level) at `index`
+    /// among its new siblings
+    pub fn move_collection(
+        &self,
+        id: &str,
+        parent_id: Option<&str>,
+        index: usize,
+    ) -> SqliteResult<()> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        let mut siblings: Vec<String> = {
+            let mut stmt = tx.prepare(
+                "SELECT id FROM collections
+                 WHERE parent_id IS ?1 AND id != ?2
+                 ORDER BY position, LOWER(name)",
+            )?;
+            let rows = stmt.query_map(params![parent_id, id], |row| row.get(0))?;
+            rows.collect::<SqliteResult<Vec<_>>>()?
+        };
+        siblings.insert(index.min(siblings.len()), id.to_string());
+
+        tx.execute(
+            "UPDATE collections SET parent_id = ?1 WHERE id = ?2",
+            params![parent_id, id],
+        )?;
+        for (position, sibling_id) in siblings.iter().enumerate() {
+            tx.execute(
+                "UPDATE collections SET position = ?1 WHERE id = ?2",
+                params![position as i64, sibling_id],
+            )?;
+        }
+
+        tx.commit()
+    }
+
+    /// Delete a collection. Nested collections move up to its parent, the
+    /// clips themselves are kept.
+    pub fn delete_collection(&self, id: &str) -> SqliteResult<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        delete_collection(&tx, id)?;
+        tx.commit()
+    }
+
+    /// Append clips to the end of a collection, skipping clips already in it
+    pub fn add_clips_to_collection(
+        &self,
+        collection_id: &str,
+        clip_ids: &[String],
+    ) -> SqliteResult<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        let now = Utc::now().to_rfc3339();
+
+        for clip_id in clip_ids {
+            tx.execute(
+                "INSERT OR IGNORE INTO collection_items (collection_id, clip_id, position, added_at)
+                 SELECT ?1, ?2, IFNULL(MAX(position) + 1, 0), ?3
+                 FROM collection_items WHERE collection_id = ?1",
+                params![collection_id, clip_id, now],
+            )?;
+        }
+
+        tx.commit()
+    }
+
+    /// Take a clip out of a collection
+    pub fn remove_clip_from_collection(
+        &self,
+        collection_id: &str,
+        clip_id: &str,
+    ) -> SqliteResult<()> {
+        self.conn.execute(
+            "DELETE FROM collection_items WHERE collection_id = ?1 AND clip_id = ?2",
+            params![collection_id, clip_id],
+        )?;
+        Ok(())
+    }
+
+    /// Clips of a collection in their collection order
+    pub fn get_collection_clips(&self, collection_id: &str) -> SqliteResult<Vec<ClipItem>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT c.id, c.content_type, c.content, c.preview_text, c.tags, c.source_app, c.created_at, c.last_used_at, c.pinned, c.metadata, c.use_count
+             FROM collection_items ci
+             JOIN clips c ON c.id = ci.clip_id
+             WHERE ci.collection_id = ?1
+             ORDER BY ci.position",
+        )?;
+
+        let clips = stmt
+            .query_map(params![collection_id], clip_from_row)?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
         Ok(clips)
     }
+
+    /// Move a clip to `index` within a collection
+    pub fn move_clip_in_collection(
+        &self,
+        collection_id: &str,
+        clip_id: &str,
+        index: usize,
+    ) -> SqliteResult<()> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        let mut clip_ids: Vec<String> = {
+            let mut stmt = tx.prepare(
+                "SELECT clip_id FROM collection_items
+                 WHERE collection_id = ?1 AND clip_id != ?2
+                 ORDER BY position",
+            )?;
+            let rows = stmt.query_map(params![collection_id, clip_id], |row| row.get(0))?;
+            rows.collect::<SqliteResult<Vec<_>>>()?
+        };
+        clip_ids.insert(index.min(clip_ids.len()), clip_id.to_string());
+
+        for (position, id) in clip_ids.iter().enumerate() {
+            tx.execute(
+                "UPDATE collection_items SET position = ?1 WHERE collection_id = ?2 AND clip_id = ?3",
+                params![position as i64, collection_id, id],
+            )?;
+        }
+
+        tx.commit()
+    }
+
+    /// Ids of the collections a clip belongs to
+    pub fn get_clip_collection_ids(&self, clip_id: &str) -> SqliteResult<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT collection_id FROM collection_items WHERE clip_id = ?1")?;
+
+        let ids = stmt
+            .query_map(params![clip_id], |row| row.get(0))?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        Ok(ids)
+    }
+
+    /// Clips marked as changed since their changes were last recorded for
+    /// sync, with the position of the mark
+    pub fn get_sync_dirty(&self) -> SqliteResult<Vec<(i64, String)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT seq, clip_id FROM sync_dirty ORDER BY seq ASC")?;
+        let marks = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<SqliteResult<Vec<_>>>()?;
+        Ok(marks)
+    }
+
+    /// Remove a mark once its changes are recorded. A clip marked again since
+    /// has a new position and stays marked.
+    pub fn clear_sync_dirty(&self, seq: i64) -> SqliteResult<()> {
+        self.conn
+            .execute("DELETE FROM sync_dirty WHERE seq = ?1", params![seq])?;
+        Ok(())
+    }
+
+    pub fn get_sync_state(&self, clip_id: &str) -> SqliteResult<Option<SyncState>> {
+        let result = self.conn.query_row(
+            "SELECT clip_id, present, present_at, tags, tags_at, pinned, pinned_at
+             FROM sync_state WHERE clip_id = ?1",
+            params![clip_id],
+            sync_state_from_row,
+        );
+
+        match result {
+            Ok(state) => Ok(Some(state)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save_sync_state(&self, state: &SyncState) -> SqliteResult<()> {
+        let tags_json = serde_json::to_string(&state.tags).unwrap_or_default();
+        self.conn.execute(
+            "INSERT OR REPLACE INTO sync_state (clip_id, present, present_at, tags, tags_at, pinned, pinned_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                state.clip_id,
+                state.present as i32,
+                state.present_at.to_rfc3339(),
+                tags_json,
+                state.tags_at.to_rfc3339(),
+                state.pinned as i32,
+                state.pinned_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Byte offset up to which the change log of `device_id` has been applied
+    pub fn get_sync_cursor(&self, device_id: &str) -> SqliteResult<u64> {
+        let result = self.conn.query_row(
+            "SELECT offset FROM sync_cursors WHERE device_id = ?1",
+            params![device_id],
+            |row| row.get::<_, i64>(0),
+        );
+
+        match result {
+            Ok(offset) => Ok(offset as u64),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn set_sync_cursor(&self, device_id: &str, offset: u64) -> SqliteResult<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO sync_cursors (device_id, offset) VALUES (?1, ?2)",
+            params![device_id, offset as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Append a change to the sync journal and return its position
+    pub fn add_sync_change(
+        &self,
+        at: DateTime<Utc>,
+        op: &str,
+        clip_id: &str,
+        value: Option<&str>,
+        origin: Option<&str>,
+    ) -> SqliteResult<i64> {
+        self.conn.execute(
+            "INSERT INTO sync_changes (at, op, clip_id, value, origin) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![at.to_rfc3339(), op, clip_id, value, origin],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Journal entries after position `after_seq`, oldest first
+    pub fn get_sync_changes(
+        &self,
+        after_seq: i64,
+        limit: i64,
+    ) -> SqliteResult<Vec<SyncChangeRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT seq, at, op, clip_id, value, origin FROM sync_changes
+             WHERE seq > ?1 ORDER BY seq ASC LIMIT ?2",
+        )?;
+
+        let changes = stmt
+            .query_map(params![after_seq, limit], |row| {
+                Ok(SyncChangeRecord {
+                    seq: row.get(0)?,
+                    at: parse_timestamp(&row.get::<_, String>(1)?),
+                    op: row.get(2)?,
+                    clip_id: row.get(3)?,
+                    value: row.get(4)?,
+                    origin: row.get(5)?,
+                })
+            })?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        Ok(changes)
+    }
+
+    pub fn save_sync_peer(&self, peer: &SyncPeer) -> SqliteResult<()> {
+        save_sync_peer(&self.conn, peer)
+    }
+
+    pub fn get_sync_peers(&self) -> SqliteResult<Vec<SyncPeer>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT device_id, name, key, address, sent_seq, received_seq, paired_at, last_sync_at
+             FROM sync_peers ORDER BY paired_at ASC",
+        )?;
+
+        let peers = stmt
+            .query_map([], sync_peer_from_row)?
+            .collect::<SqliteResult<Vec<_>>>()?;
+
+        Ok(peers)
+    }
+
+    pub fn get_sync_peer(&self, device_id: &str) -> SqliteResult<Option<SyncPeer>> {
+        let result = self.conn.query_row(
+            "SELECT device_id, name, key, address, sent_seq, received_seq, paired_at, last_sync_at
+             FROM sync_peers WHERE device_id = ?1",
+            params![device_id],
+            sync_peer_from_row,
+        );
+
+        match result {
+            Ok(peer) => Ok(Some(peer)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn delete_sync_peer(&self, device_id: &str) -> SqliteResult<()> {
+        self.conn.execute(
+            "DELETE FROM sync_peers WHERE device_id = ?1",
+            params![device_id],
+        )?;
+        Ok(())
+    }
+}
+
+/// Delete a collection, moving its nested collections up to its parent
+fn delete_collection(conn: &Connection, id: &str) -> SqliteResult<()> {
+    let parent_id: Option<String> = match conn.query_row(
+        "SELECT parent_id FROM collections WHERE id = ?1",
+        params![id],
+        |row| row.get(0),
+    ) {
+        Ok(parent_id) => parent_id,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    conn.execute(
+        "UPDATE collections SET parent_id = ?1 WHERE parent_id = ?2",
+        params![parent_id, id],
+    )?;
+    conn.execute("DELETE FROM collections WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
+/// SQL condition on `?1` for a search query, and the value to bind: "tag:"
+/// or "#" match a whole tag ignoring case, "type:" the content type, "kind:"
+/// the detected kind or code language, anything else the text
+fn search_condition(query: &str) -> (&'static str, String) {
+    let query_lower = query.to_lowercase();
+
+    if let Some(tag) = query.strip_prefix('#') {
+        return tag_condition(tag);
+    }
+    if query_lower.starts_with("tag:") {
+        return tag_condition(&query[4..]);
+    }
+    if let Some(content_type) = query_lower.strip_prefix("type:") {
+        return ("LOWER(content_type) = ?1", content_type.trim().to_string());
+    }
+    if let Some(kind) = query_lower.strip_prefix("kind:") {
+        return (
+            "json_extract(metadata, '$.kind') = ?1 OR json_extract(metadata, '$.language') = ?1",
+            kind.trim().to_string(),
+        );
+    }
+
+    (
+        "LOWER(preview_text) LIKE ?1 OR LOWER(content) LIKE ?1",
+        format!("%{}%", query_lower),
+    )
+}
+
+fn tag_condition(tag: &str) -> (&'static str, String) {
+    (
+        "id IN (
+             SELECT clip_tags.clip_id FROM clip_tags JOIN tags ON tags.id = clip_tags.tag_id
+             WHERE LOWER(tags.name) = LOWER(?1)
+         )",
+        tag.trim().to_string(),
+    )
+}
+
+fn clip_exists(conn: &Connection, clip_id: &str) -> SqliteResult<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM clip_items WHERE id = ?1)",
+        params![clip_id],
+        |row| row.get(0),
+    )
+}
+
+/// Tags of a clip in the order they were added
+fn clip_tags(conn: &Connection, clip_id: &str) -> SqliteResult<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT tags.name FROM clip_tags JOIN tags ON tags.id = clip_tags.tag_id
+         WHERE clip_tags.clip_id = ?1
+         ORDER BY clip_tags.position",
+    )?;
+    let tags = stmt
+        .query_map(params![clip_id], |row| row.get(0))?
+        .collect::<SqliteResult<Vec<_>>>()?;
+    Ok(tags)
+}
+
+/// Replace the tags of a clip, creating tags not used before. Tags the clip
+/// keeps stay in place, so only actual changes mark the clip for sync.
+fn set_clip_tags(conn: &Connection, clip_id: &str, tags: &[String]) -> SqliteResult<()> {
+    if clip_tags(conn, clip_id)? == tags {
+        return Ok(());
+    }
+
+    conn.execute(
+        "DELETE FROM clip_tags
+         WHERE clip_id = ?1
+           AND tag_id NOT IN (SELECT id FROM tags WHERE name IN (SELECT value FROM json_each(?2)))",
+        params![clip_id, serde_json::to_string(tags).unwrap_or_default()],
+    )?;
+
+    for (position, tag) in tags.iter().enumerate() {
+        conn.execute(
+            "INSERT OR IGNORE INTO tags (name) VALUES (?1)",
+            params![tag],
+        )?;
+        conn.execute(
+            "INSERT INTO clip_tags (clip_id, tag_id, position)
+             SELECT ?1, id, ?3 FROM tags WHERE name = ?2
+             ON CONFLICT (clip_id, tag_id) DO UPDATE SET position = excluded.position",
+            params![clip_id, tag, position as i64],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn save_sync_peer(conn: &Connection, peer: &SyncPeer) -> SqliteResult<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO sync_peers (device_id, name, key, address, sent_seq, received_seq, paired_at, last_sync_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            peer.device_id,
+            peer.name,
+            peer.key,
+            peer.address,
+            peer.sent_seq,
+            peer.received_seq,
+            peer.paired_at.to_rfc3339(),
+            peer.last_sync_at.map(|at| at.to_rfc3339()),
+        ],
+    )?;
+    Ok(())
+}
+
+fn sync_state_from_row(row: &rusqlite::Row) -> SqliteResult<SyncState> {
+    Ok(SyncState {
+        clip_id: row.get(0)?,
+        present: row.get::<_, i32>(1)? != 0,
+        present_at: parse_timestamp(&row.get::<_, String>(2)?),
+        tags: serde_json::from_str(&row.get::<_, String>(3)?).unwrap_or_default(),
+        tags_at: parse_timestamp(&row.get::<_, String>(4)?),
+        pinned: row.get::<_, i32>(5)? != 0,
+        pinned_at: parse_timestamp(&row.get::<_, String>(6)?),
+    })
+}
+
+fn sync_peer_from_row(row: &rusqlite::Row) -> SqliteResult<SyncPeer> {
+    Ok(SyncPeer {
+        device_id: row.get(0)?,
+        name: row.get(1)?,
+        key: row.get(2)?,
+        address: row.get(3)?,
+        sent_seq: row.get(4)?,
+        received_seq: row.get(5)?,
+        paired_at: parse_timestamp(&row.get::<_, String>(6)?),
+        last_sync_at: row
+            .get::<_, Option<String>>(7)?
+            .map(|at| parse_timestamp(&at)),
+    })
+}
+
+fn snippet_from_row(row: &rusqlite::Row) -> SqliteResult<Snippet> {
+    Ok(Snippet {
+        id: row.get(0)?,
+        folder_id: row.get(1)?,
+        name: row.get(2)?,
+        content: row.get(3)?,
+        abbreviation: row.get(4)?,
+        expansion_apps: serde_json::from_str(&row.get::<_, String>(5)?).unwrap_or_default(),
+        created_at: parse_timestamp(&row.get::<_, String>(6)?),
+        updated_at: parse_timestamp(&row.get::<_, String>(7)?),
+        use_count: row.get(8)?,
+    })
+}
+
+fn revision_from_row(row: &rusqlite::Row) -> SqliteResult<ClipRevision> {
+    Ok(ClipRevision {
+        id: row.get(0)?,
+        clip_id: row.get(1)?,
+        content: row.get(2)?,
+        preview_text: row.get(3)?,
+        created_at: parse_timestamp(&row.get::<_, String>(4)?),
+    })
+}
+
+fn parse_timestamp(value: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now())
+}
+
+/// Map a row selected with the standard clip column list to a `ClipItem`
+fn clip_from_row(row: &rusqlite::Row) -> SqliteResult<ClipItem> {
+    let content_type_str: String = row.get(1)?;
+    let tags_json: String = row.get(4)?;
+    let metadata_str: Option<String> = row.get(9)?;
+
+    Ok(ClipItem {
+        id: row.get(0)?,
+        content_type: ContentType::from_str(&content_type_str).unwrap_or(ContentType::Plain),
+        content: row.get(2)?,
+        preview_text: row.get(3)?,
+        tags: serde_json::from_str(&tags_json).unwrap_or_default(),
+        source_app: row.get(5)?,
+        created_at: parse_timestamp(&row.get::<_, String>(6)?),
+        last_used_at: parse_timestamp(&row.get::<_, String>(7)?),
+        pinned: row.get::<_, i32>(8)? != 0,
+        metadata: metadata_str.and_then(|s| serde_json::from_str(&s).ok()),
+        use_count: row.get(10)?,
+    })
 }
 
 pub fn get_app_dir() -> PathBuf {
@@ -519,20 +1980,437 @@ pub fn ensure_app_dir() -> std::io::Result<PathBuf> {
     Ok(app_dir)
 }
 
+/// Whether `id` has the form of a clip id, a hex digest from `stable_hash`
+/// or the shorter hashes of older versions. Ids from archives and other
+/// devices name image files, so they are checked before use.
+pub fn is_valid_clip_id(id: &str) -> bool {
+    (1..=32).contains(&id.len())
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// Hash of a clip's current content. Text is hashed from what is stored,
+/// other clips keep the hash they were created with as their id.
+fn content_hash(id: &str, content_type: &ContentType, content: &str) -> String {
+    match content_type {
+        ContentType::Plain => generate_content_hash(content),
+        ContentType::Rich => serde_json::from_str::<serde_json::Value>(content)
+            .ok()
+            .map(|formats| {
+                rich_content_hash(
+                    formats["html"].as_str(),
+                    formats["rtf"].as_str(),
+                    formats["plain"].as_str().unwrap_or_default(),
+                )
+            })
+            .unwrap_or_else(|| id.to_string()),
+        _ => id.to_string(),
+    }
+}
+
+/// Hex of the first 16 bytes of a SHA-256 digest. Clip ids are derived from
+/// it and shared with other devices and archives, so it must give the same
+/// value on every build.
+fn stable_hash(data: &[u8]) -> String {
+    Sha256::digest(data)[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
 pub fn generate_content_hash(content: &str) -> String {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
+    stable_hash(content.as_bytes())
+}
 
-    let mut hasher = DefaultHasher::new();
-    content.hash(&mut hasher);
-    format!("{:x}", hasher.finish())
+/// Hash of a rich text clip over all of its formats
+pub fn rich_content_hash(html: Option<&str>, rtf: Option<&str>, plain: &str) -> String {
+    generate_content_hash(&format!(
+        "{}{}{}",
+        html.unwrap_or_default(),
+        rtf.unwrap_or_default(),
+        plain
+    ))
 }
 
 pub fn generate_image_hash(image_data: &[u8]) -> String {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
+    stable_hash(image_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_db() -> (tempfile::TempDir, Database) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        (dir, db)
+    }
+
+    fn text_clip(text: &str, tags: &[&str]) -> ClipItem {
+        ClipItem {
+            id: generate_content_hash(text),
+            content_type: ContentType::Plain,
+            content: text.to_string(),
+            preview_text: text.to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            source_app: String::new(),
+            created_at: Utc::now(),
+            last_used_at: Utc::now(),
+            pinned: false,
+            metadata: None,
+            use_count: 0,
+        }
+    }
+
+    #[test]
+    fn tags_are_kept_apart_from_collections() {
+        let (_dir, db) = open_db();
+        let clip = text_clip("hello", &["work", "home"]);
+        db.insert_clip(&clip).unwrap();
+        assert!(db.get_collections().unwrap().is_empty());
+        assert_eq!(
+            db.get_clip_by_id(&clip.id).unwrap().unwrap().tags,
+            vec!["work", "home"]
+        );
+
+        let collection = Collection {
+            id: "c".to_string(),
+            name: "Board".to_string(),
+            parent_id: None,
+            color: None,
+            position: 0,
+            created_at: Utc::now(),
+            clip_count: 0,
+        };
+        db.insert_collection(&collection).unwrap();
+        db.add_clips_to_collection("c", std::slice::from_ref(&clip.id))
+            .unwrap();
+        db.update_clip_tags(&clip.id, vec!["home".to_string()])
+            .unwrap();
+        assert_eq!(db.get_collection_clips("c").unwrap()[0].tags, vec!["home"]);
+
+        db.delete_collection("c").unwrap();
+        assert_eq!(
+            db.get_clip_by_id(&clip.id).unwrap().unwrap().tags,
+            vec!["home"]
+        );
+    }
+
+    #[test]
+    fn replace_tags_matches_like_tag_stats() {
+        let (_dir, db) = open_db();
+        db.insert_clip(&text_clip("one", &["Work"])).unwrap();
+        db.insert_clip(&text_clip("two", &["work"])).unwrap();
+
+        let stats = db.get_tag_stats().unwrap();
+        assert_eq!(stats.len(), 2);
+
+        assert_eq!(
+            db.replace_tags(&["work".to_string()], Some("job")).unwrap(),
+            1
+        );
+        let names: Vec<String> = db
+            .get_tag_stats()
+            .unwrap()
+            .into_iter()
+            .map(|tag| tag.name)
+            .collect();
+        assert_eq!(names, vec!["job", "Work"]);
+    }
+
+    #[test]
+    fn tag_stats_count_clips_and_last_use() {
+        let (_dir, db) = open_db();
+        let mut old = text_clip("one", &["work", "home"]);
+        old.last_used_at = Utc::now() - chrono::Duration::days(2);
+        let recent = text_clip("two", &["work"]);
+        db.insert_clip(&old).unwrap();
+        db.insert_clip(&recent).unwrap();
+
+        let stats = db.get_tag_stats().unwrap();
+        let counts: Vec<(&str, i64)> = stats
+            .iter()
+            .map(|tag| (tag.name.as_str(), tag.clip_count))
+            .collect();
+        assert_eq!(counts, vec![("home", 1), ("work", 2)]);
+        assert_eq!(
+            stats[0].last_used_at.unwrap().timestamp(),
+            old.last_used_at.timestamp()
+        );
+        assert_eq!(
+            stats[1].last_used_at.unwrap().timestamp(),
+            recent.last_used_at.timestamp()
+        );
+
+        db.delete_clip(&recent.id).unwrap();
+        assert_eq!(db.get_tag_stats().unwrap()[1].clip_count, 1);
+        assert_eq!(db.search_tags("OR").unwrap(), vec!["work"]);
+    }
+
+    #[test]
+    fn tag_search_matches_whole_tags() {
+        let (_dir, db) = open_db();
+        db.insert_clip(&text_clip("one", &["Work"])).unwrap();
+        db.insert_clip(&text_clip("two", &["homework"])).unwrap();
+        db.insert_clip(&text_clip("three", &["work\"", "x"]))
+            .unwrap();
+
+        let found = db.search_clips("tag:work", 10).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].content, "one");
+    }
+
+    #[test]
+    fn filters_and_search_are_combined() {
+        let (_dir, db) = open_db();
+        let mut old = text_clip("old note", &["work"]);
+        old.created_at = Utc::now() - chrono::Duration::days(3);
+        let mut pinned = text_clip("pinned note", &["home"]);
+        pinned.pinned = true;
+        db.insert_clip(&old).unwrap();
+        db.insert_clip(&pinned).unwrap();
+        db.insert_clip(&text_clip("new note", &["work", "x"]))
+            .unwrap();
+        db.insert_clip(&text_clip("other", &["work"])).unwrap();
+
+        let contents = |query: Option<&str>, filter: &ClipFilter, limit: i64| -> Vec<String> {
+            db.filter_clips(query, filter, limit)
+                .unwrap()
+                .into_iter()
+                .map(|clip| clip.content)
+                .collect()
+        };
+
+        let work = ClipFilter {
+            tags: vec!["work".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            contents(Some("note"), &work, 10),
+            vec!["new note", "old note"]
+        );
+        assert_eq!(contents(Some("note"), &work, 1), vec!["new note"]);
+
+        let recent = ClipFilter {
+            from: Some(Utc::now() - chrono::Duration::days(1)),
+            content_types: vec!["PLAIN".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            contents(Some("tag:work"), &recent, 10),
+            vec!["other", "new note"]
+        );
+
+        let pinned_only = ClipFilter {
+            pinned_only: true,
+            ..Default::default()
+        };
+        assert_eq!(contents(None, &pinned_only, 10), vec!["pinned note"]);
+        assert!(contents(Some("#work"), &pinned_only, 10).is_empty());
+    }
 
-    let mut hasher = DefaultHasher::new();
-    image_data.hash(&mut hasher);
-    format!("{:x}", hasher.finish())
+    #[test]
+    fn migration_moves_json_tags_into_tables() {
+        let dir = tempfile::tempdir().unwrap();
+        let clip = text_clip("one", &[]);
+        {
+            let db = Database::new(dir.path().to_path_buf()).unwrap();
+            db.insert_clip(&clip).unwrap();
+            db.conn
+                .execute_batch(
+                    r#"ALTER TABLE clip_items ADD COLUMN tags TEXT NOT NULL DEFAULT '[]';
+                       UPDATE clip_items SET tags = '["b","a"]'"#,
+                )
+                .unwrap();
+        }
+
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        assert!(!db.has_column("clip_items", "tags").unwrap());
+        assert_eq!(
+            db.get_clip_by_id(&clip.id).unwrap().unwrap().tags,
+            vec!["b", "a"]
+        );
+        assert!(db.get_collections().unwrap().is_empty());
+    }
+
+    #[test]
+    fn edited_clips_are_found_by_their_new_content() {
+        let (_dir, db) = open_db();
+        let clip = text_clip("before", &[]);
+        db.insert_clip(&clip).unwrap();
+
+        db.update_clip_content(&clip.id, "after", "after", None, 10)
+            .unwrap();
+
+        let found = db
+            .find_duplicate_clip(&generate_content_hash("after"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, clip.id);
+        assert!(db
+            .find_duplicate_clip(&generate_content_hash("before"))
+            .unwrap()
+            .is_none());
+
+        // The old text copied again needs an id the edited clip does not use
+        let id = db.new_clip_id(&generate_content_hash("before")).unwrap();
+        assert_ne!(id, clip.id);
+        assert_eq!(
+            db.new_clip_id(&generate_content_hash("new")).unwrap(),
+            generate_content_hash("new")
+        );
+    }
+
+    #[test]
+    fn migration_backfills_content_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let clip = text_clip("before", &[]);
+        {
+            let db = Database::new(dir.path().to_path_buf()).unwrap();
+            db.insert_clip(&clip).unwrap();
+            db.conn
+                .execute_batch(
+                    "UPDATE clip_items SET content = 'after', content_hash = NULL;
+                     PRAGMA user_version = 1",
+                )
+                .unwrap();
+        }
+
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        let found = db
+            .find_duplicate_clip(&generate_content_hash("after"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, clip.id);
+    }
+
+    #[test]
+    fn migration_moves_clips_to_stable_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let db = Database::new(dir.path().to_path_buf()).unwrap();
+            db.insert_clip(&text_clip("one", &["kept"])).unwrap();
+            db.insert_clip(&text_clip("two", &[])).unwrap();
+            // "one" as an older version stored it, "two" edited since
+            db.conn
+                .execute_batch(
+                    "BEGIN;
+                     PRAGMA defer_foreign_keys = ON;
+                     UPDATE clip_tags SET clip_id = '1a2b'
+                     WHERE clip_id = (SELECT id FROM clip_items WHERE content = 'one');
+                     UPDATE clip_items SET id = '1a2b', content_hash = '1a2b' WHERE content = 'one';
+                     UPDATE clip_items SET id = '3c4d', content_hash = '5e6f' WHERE content = 'two';
+                     COMMIT;",
+                )
+                .unwrap();
+            db.conn.execute_batch("PRAGMA user_version = 3").unwrap();
+        }
+
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        let one = db
+            .find_duplicate_clip(&generate_content_hash("one"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(one.id, generate_content_hash("one"));
+        assert_eq!(one.tags, vec!["kept"]);
+        assert!(db.get_clip_by_id("1a2b").unwrap().is_none());
+
+        let two = db
+            .find_duplicate_clip(&generate_content_hash("two"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(two.id, "3c4d");
+    }
+
+    #[test]
+    fn imported_clips_need_a_valid_id() {
+        assert!(is_valid_clip_id(&generate_content_hash("x")));
+        for id in [
+            "",
+            "../../etc/passwd",
+            "ABC",
+            "0123456789abcdef0123456789abcdef0",
+            "a/b",
+        ] {
+            assert!(!is_valid_clip_id(id), "{}", id);
+        }
+
+        let (_dir, db) = open_db();
+        let mut clip = text_clip("evil", &[]);
+        clip.id = "../evil".to_string();
+        assert_eq!(
+            db.import_clips(&[clip, text_clip("good", &[])], false)
+                .unwrap(),
+            (1, 0)
+        );
+        assert!(db.get_clip_by_id("../evil").unwrap().is_none());
+    }
+
+    #[test]
+    fn imported_text_is_matched_by_content() {
+        let (_dir, db) = open_db();
+        let local = text_clip("before", &["local"]);
+        db.insert_clip(&local).unwrap();
+        db.update_clip_content(&local.id, "after", "after", None, 10)
+            .unwrap();
+
+        // The same text as the edited clip, and the text it was edited from
+        let same_text = text_clip("after", &["archived"]);
+        let old_text = text_clip("before", &[]);
+        assert_eq!(old_text.id, local.id);
+        assert_eq!(
+            db.import_clips(&[same_text, old_text], true).unwrap(),
+            (1, 1)
+        );
+
+        let edited = db.get_clip_by_id(&local.id).unwrap().unwrap();
+        assert_eq!(edited.content, "after");
+        assert_eq!(edited.tags, vec!["local", "archived"]);
+
+        let imported = db
+            .find_duplicate_clip(&generate_content_hash("before"))
+            .unwrap()
+            .unwrap();
+        assert_ne!(imported.id, local.id);
+        assert_eq!(imported.content, "before");
+    }
+
+    #[test]
+    fn restore_keeps_peers_and_journal_positions() {
+        let (dir, mut db) = open_db();
+        db.add_sync_change(Utc::now(), "insert", "a", None, None)
+            .unwrap();
+        let backup = dir.path().join("backup.db");
+        db.backup_to(&backup).unwrap();
+
+        for id in ["b", "c"] {
+            db.add_sync_change(Utc::now(), "insert", id, None, None)
+                .unwrap();
+        }
+        let peer = SyncPeer {
+            device_id: "peer".to_string(),
+            name: "Laptop".to_string(),
+            key: "a2V5".to_string(),
+            address: None,
+            sent_seq: 3,
+            received_seq: 7,
+            paired_at: Utc::now(),
+            last_sync_at: None,
+        };
+        db.save_sync_peer(&peer).unwrap();
+
+        db.restore_from(&backup).unwrap();
+
+        let peers = db.get_sync_peers().unwrap();
+        assert_eq!(peers.len(), 1);
+        assert_eq!((peers[0].sent_seq, peers[0].received_seq), (3, 7));
+
+        // The peer has seen up to 3, so a new change has to come after it
+        db.add_sync_change(Utc::now(), "insert", "d", None, None)
+            .unwrap();
+        let changes = db.get_sync_changes(3, 10).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].clip_id, "d");
+    }
 }
@@ -0,0 +1,312 @@
+//! D-Bus service on Linux: the `org.fatclip.History` interface at
+//! `/org/fatclip/History` on the session bus, for desktop extensions and
+//! scripts, e.g.
+//!
+//! ```sh
+//! gdbus call --session --dest org.fatclip.History \
+//!     --object-path /org/fatclip/History --method org.fatclip.History.Search "" 10
+//! ```
+//!
+//! The bus is the one in `DBUS_SESSION_BUS_ADDRESS`, so a private bus can be
+//! used by pointing that variable at it.
+
+use crate::clipboard::{clip_plain_text, ClipboardManager};
+use crate::clipboard_writer;
+use crate::db::{ClipItem, ContentType};
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{Emitter, Listener};
+use zbus::blocking::Connection;
+use zbus::fdo;
+use zbus::object_server::SignalEmitter;
+
+const BUS_NAME: &str = "org.fatclip.History";
+const OBJECT_PATH: &str = "/org/fatclip/History";
+
+/// How many recent clips are checked for new ones after a change
+const NEW_CLIPS_WINDOW: i64 = 50;
+
+/// A clip as returned by `Search`: id, content type, preview, creation time
+/// (RFC 3339), pinned and tags
+type ClipSummary = (String, String, String, String, bool, Vec<String>);
+
+struct History {
+    clipboard_manager: Arc<ClipboardManager>,
+    /// Called after the history was changed over D-Bus
+    changed: Box<dyn Fn() + Send + Sync>,
+}
+
+#[zbus::interface(name = "org.fatclip.History")]
+impl History {
+    /// Clips matching `query`, the most recent ones if it is empty
+    #[zbus(out_args("clips"))]
+    fn search(&self, query: &str, limit: u32) -> fdo::Result<Vec<ClipSummary>> {
+        let limit = i64::from(limit.max(1));
+        let clips = if query.trim().is_empty() {
+            self.clipboard_manager.get_recent_clips(limit)
+        } else {
+            self.clipboard_manager.search_clips(query, limit)
+        }
+        .map_err(fdo::Error::Failed)?;
+
+        Ok(clips.iter().map(summary).collect())
+    }
+
+    /// The text of a clip: the plain text of text clips, the image path of
+    /// images and one path per line for files
+    #[zbus(out_args("text"))]
+    fn get(&self, id: &str) -> fdo::Result<String> {
+        let clip = self
+            .clipboard_manager
+            .get_clip(id)
+            .map_err(fdo::Error::Failed)?;
+
+        match clip.content_type {
+            ContentType::Plain | ContentType::Rich => {
+                clip_plain_text(&clip).map_err(fdo::Error::Failed)
+            }
+            ContentType::Image => Ok(clip.content),
+            ContentType::File => {
+                let paths: Vec<String> = serde_json::from_str(&clip.content).unwrap_or_default();
+                Ok(paths.join("\n"))
+            }
+        }
+    }
+
+    /// Add a text clip and return its id
+    #[zbus(out_args("id"))]
+    fn add(&self, text: String) -> fdo::Result<String> {
+        if text.trim().is_empty() {
+            return Err(fdo::Error::InvalidArgs("text is empty".to_string()));
+        }
+
+        let clip = self
+            .clipboard_manager
+            .save_text_clip(text, "D-Bus".to_string(), true)
+            .map_err(fdo::Error::Failed)?;
+        (self.changed)();
+        Ok(clip.id)
+    }
+
+    /// Copy a clip and paste it into the focused application
+    fn paste(&self, id: &str) -> fdo::Result<()> {
+        self.clipboard_manager
+            .write_clip(id)
+            .map_err(fdo::Error::Failed)?;
+
+        // Give the clipboard a moment before pasting
+        std::thread::sleep(Duration::from_millis(50));
+        clipboard_writer::simulate_paste().map_err(fdo::Error::Failed)?;
+
+        let _ = self.clipboard_manager.record_clip_use(id);
+        (self.changed)();
+        Ok(())
+    }
+
+    /// A clip was added to the history
+    #[zbus(signal)]
+    async fn clip_added(
+        emitter: &SignalEmitter<'_>,
+        id: &str,
+        content_type: &str,
+        preview: &str,
+    ) -> zbus::Result<()>;
+}
+
+fn summary(clip: &ClipItem) -> ClipSummary {
+    (
+        clip.id.clone(),
+        clip.content_type.as_str().to_string(),
+        clip.preview_text.clone(),
+        clip.created_at.to_rfc3339(),
+        clip.pinned,
+        clip.tags.clone(),
+    )
+}
+
+/// Publish the interface and signal clips added from now on. Failures, such
+/// as no session bus, are logged.
+pub fn start(clipboard_manager: Arc<ClipboardManager>, app: tauri::AppHandle) {
+    std::thread::spawn(move || {
+        let emitter = app.clone();
+        let changed = move || {
+            let _ = emitter.emit("clipboard-updated", ());
+        };
+        let connection = match publish(clipboard_manager.clone(), changed) {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("Failed to publish the D-Bus service: {}", e);
+                return;
+            }
+        };
+
+        let newest = Mutex::new(newest_created_at(&clipboard_manager));
+        app.listen_any("clipboard-updated", move |_| {
+            if let Err(e) = signal_new_clips(&connection, &clipboard_manager, &newest) {
+                eprintln!("Failed to signal new clips over D-Bus: {}", e);
+            }
+        });
+    });
+}
+
+/// Serve the interface on the session bus under its well-known name
+fn publish(
+    clipboard_manager: Arc<ClipboardManager>,
+    changed: impl Fn() + Send + Sync + 'static,
+) -> zbus::Result<Connection> {
+    let history = History {
+        clipboard_manager,
+        changed: Box::new(changed),
+    };
+    zbus::blocking::connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, history)?
+        .build()
+}
+
+fn newest_created_at(clipboard_manager: &ClipboardManager) -> Option<DateTime<Utc>> {
+    clipboard_manager
+        .get_recent_clips(NEW_CLIPS_WINDOW)
+        .ok()?
+        .iter()
+        .map(|clip| clip.created_at)
+        .max()
+}
+
+/// Emit `ClipAdded` for clips created since the newest one already signalled
+fn signal_new_clips(
+    connection: &Connection,
+    clipboard_manager: &ClipboardManager,
+    newest: &Mutex<Option<DateTime<Utc>>>,
+) -> Result<(), String> {
+    let mut newest = newest.lock().map_err(|e| e.to_string())?;
+
+    let mut clips: Vec<ClipItem> = clipboard_manager
+        .get_recent_clips(NEW_CLIPS_WINDOW)?
+        .into_iter()
+        .filter(|clip| newest.is_none_or(|newest| clip.created_at > newest))
+        .collect();
+    if clips.is_empty() {
+        return Ok(());
+    }
+    clips.sort_by_key(|clip| clip.created_at);
+
+    let iface = connection
+        .object_server()
+        .interface::<_, History>(OBJECT_PATH)
+        .map_err(|e| e.to_string())?;
+    for clip in &clips {
+        zbus::block_on(History::clip_added(
+            iface.signal_emitter(),
+            &clip.id,
+            clip.content_type.as_str(),
+            &clip.preview_text,
+        ))
+        .map_err(|e| e.to_string())?;
+    }
+
+    *newest = clips.last().map(|clip| clip.created_at);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+
+    /// A private session bus, stopped when dropped
+    struct Bus(Child);
+
+    impl Bus {
+        /// Start `dbus-daemon` and return it with its address, or `None` if it
+        /// is not installed
+        fn start() -> Option<(Bus, String)> {
+            let mut child = Command::new("dbus-daemon")
+                .args(["--session", "--print-address", "--nofork"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(child.stdout.take()?)
+                .read_line(&mut address)
+                .ok()?;
+            Some((Bus(child), address.trim().to_string()))
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    #[test]
+    fn history_is_served_on_the_session_bus() {
+        let Some((_bus, address)) = Bus::start() else {
+            eprintln!("dbus-daemon is not installed, skipping");
+            return;
+        };
+        std::env::set_var("DBUS_SESSION_BUS_ADDRESS", &address);
+
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        let manager = Arc::new(ClipboardManager::new(Arc::new(Mutex::new(db))));
+        manager
+            .save_text_clip("first clip".to_string(), "test".to_string(), true)
+            .unwrap();
+
+        let changes = Arc::new(AtomicUsize::new(0));
+        let counter = changes.clone();
+        let service = publish(manager.clone(), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })
+        .unwrap();
+        let newest = Mutex::new(newest_created_at(&manager));
+
+        let client = Connection::session().unwrap();
+        let proxy = zbus::blocking::Proxy::new(&client, BUS_NAME, OBJECT_PATH, BUS_NAME).unwrap();
+
+        let clips: Vec<ClipSummary> = proxy.call("Search", &("first", 10u32)).unwrap();
+        assert_eq!(clips.len(), 1);
+        assert_eq!(
+            (clips[0].1.as_str(), clips[0].2.as_str()),
+            ("plain", "first clip")
+        );
+        let text: String = proxy.call("Get", &(clips[0].0.as_str(),)).unwrap();
+        assert_eq!(text, "first clip");
+        assert!(proxy.call::<_, _, String>("Get", &("missing",)).is_err());
+
+        // Subscribe before the clip is added, then wait for the signal
+        let signals = proxy.receive_signal("ClipAdded").unwrap();
+        let (sender, received) = mpsc::channel();
+        std::thread::spawn(move || {
+            for signal in signals {
+                let args: (String, String, String) = signal.body().deserialize().unwrap();
+                let _ = sender.send(args);
+            }
+        });
+
+        let id: String = proxy.call("Add", &("second clip",)).unwrap();
+        assert_eq!(changes.load(Ordering::SeqCst), 1);
+        assert!(proxy.call::<_, _, String>("Add", &(" ",)).is_err());
+        signal_new_clips(&service, &manager, &newest).unwrap();
+
+        let (signalled_id, content_type, preview) =
+            received.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(signalled_id, id);
+        assert_eq!(
+            (content_type.as_str(), preview.as_str()),
+            ("plain", "second clip")
+        );
+
+        let clips: Vec<ClipSummary> = proxy.call("Search", &("", 10u32)).unwrap();
+        assert_eq!(clips.len(), 2);
+    }
+}
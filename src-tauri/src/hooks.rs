@@ -0,0 +1,759 @@
+//! Hooks: commands from the settings run when a clip is added, pinned or
+//! tagged. The clip text is written to the command's stdin and its details
+//! are passed in `FAT_CLIP_*` environment variables. The output of recent runs
+//! is kept for the app to show.
+
+use crate::auto_tag::{AutoTagConditions, CompiledConditions};
+use crate::clipboard::clip_plain_text;
+use crate::db::ClipItem;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+
+/// Runs kept for the app to show
+const MAX_RUNS: usize = 100;
+
+/// Runs waiting for a free slot; further events are skipped, which shows in
+/// the recent runs
+const MAX_PENDING: usize = 100;
+
+/// Output captured per stream
+const MAX_OUTPUT_SIZE: u64 = 64 * 1024;
+
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long the output of a stopped command is waited for
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+fn default_hook_enabled() -> bool {
+    true
+}
+
+fn default_hook_timeout_seconds() -> u64 {
+    10
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookTrigger {
+    NewClip,
+    Pin,
+    TagAdded,
+}
+
+impl HookTrigger {
+    fn as_str(&self) -> &'static str {
+        match self {
+            HookTrigger::NewClip => "new_clip",
+            HookTrigger::Pin => "pin",
+            HookTrigger::TagAdded => "tag_added",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hook {
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_hook_enabled")]
+    pub enabled: bool,
+    pub trigger: HookTrigger,
+    /// Program and arguments, split like a shell would but run without one.
+    /// `{id}`, `{type}`, `{source_app}`, `{tags}` and `{tag}` are replaced in
+    /// each argument. The clip text is only passed on stdin: an argument of
+    /// a command like `sh -c` would run any shell code copied.
+    pub command: String,
+    #[serde(default)]
+    pub conditions: AutoTagConditions,
+    /// Any of these tags; for "tag_added", the added tag
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default = "default_hook_timeout_seconds")]
+    pub timeout_seconds: u64,
+}
+
+/// The outcome of one hook run
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HookRun {
+    pub id: u64,
+    pub hook_id: String,
+    pub hook_name: String,
+    pub trigger: HookTrigger,
+    pub clip_id: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub stdout: String,
+    pub stderr: String,
+    /// Set when the command could not be run at all
+    pub error: Option<String>,
+}
+
+struct CompiledHook {
+    hook: Hook,
+    conditions: CompiledConditions,
+}
+
+impl CompiledHook {
+    fn new(hook: Hook) -> Result<Self, String> {
+        let program = split_command(&hook.command)
+            .map_err(|e| format!("Invalid command in hook '{}': {}", hook.name, e))?;
+        if program.is_empty() {
+            return Err(format!("Hook '{}' has no command", hook.name));
+        }
+        let conditions = CompiledConditions::new(&hook.conditions, &hook.name)?;
+        Ok(CompiledHook { hook, conditions })
+    }
+
+    fn matches(&self, trigger: HookTrigger, clip: &ClipItem, tag: Option<&str>) -> bool {
+        if self.hook.trigger != trigger {
+            return false;
+        }
+
+        if !self.hook.tags.is_empty() {
+            let matched = match tag {
+                Some(tag) => self.hook.tags.iter().any(|t| t == tag),
+                None => clip.tags.iter().any(|t| self.hook.tags.contains(t)),
+            };
+            if !matched {
+                return false;
+            }
+        }
+
+        self.conditions.matches(clip)
+    }
+}
+
+/// A running or waiting hook holds one of `max_concurrent` slots
+struct Slots {
+    max_concurrent: usize,
+    running: usize,
+    pending: usize,
+}
+
+pub struct HookRunner {
+    hooks: Mutex<Vec<Arc<CompiledHook>>>,
+    slots: Arc<(Mutex<Slots>, Condvar)>,
+    runs: Arc<Mutex<VecDeque<HookRun>>>,
+    next_run_id: Arc<AtomicU64>,
+    app_handle: Arc<Mutex<Option<tauri::AppHandle>>>,
+}
+
+impl HookRunner {
+    pub fn new() -> Self {
+        HookRunner {
+            hooks: Mutex::new(Vec::new()),
+            slots: Arc::new((
+                Mutex::new(Slots {
+                    max_concurrent: 1,
+                    running: 0,
+                    pending: 0,
+                }),
+                Condvar::new(),
+            )),
+            runs: Arc::new(Mutex::new(VecDeque::new())),
+            next_run_id: Arc::new(AtomicU64::new(1)),
+            app_handle: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Replace the hooks, failing on the first invalid one
+    pub fn set_hooks(&self, hooks: &[Hook], max_concurrent: usize) -> Result<(), String> {
+        let compiled = hooks
+            .iter()
+            .filter(|hook| hook.enabled)
+            .cloned()
+            .map(|hook| CompiledHook::new(hook).map(Arc::new))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        *self.hooks.lock().map_err(|e| e.to_string())? = compiled;
+
+        let (slots, available) = &*self.slots;
+        slots.lock().map_err(|e| e.to_string())?.max_concurrent = max_concurrent.max(1);
+        available.notify_all();
+        Ok(())
+    }
+
+    /// Hooks only run once the app is up; the app is told about finished runs
+    pub fn set_app_handle(&self, app: tauri::AppHandle) {
+        if let Ok(mut app_handle) = self.app_handle.lock() {
+            *app_handle = Some(app);
+        }
+    }
+
+    /// Recent runs, newest first
+    pub fn runs(&self) -> Vec<HookRun> {
+        self.runs
+            .lock()
+            .map(|runs| runs.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn clear_runs(&self) {
+        if let Ok(mut runs) = self.runs.lock() {
+            runs.clear();
+        }
+    }
+
+    /// Start the hooks matching an event in the background. `tag` is the added
+    /// tag for `TagAdded`.
+    pub fn fire(&self, trigger: HookTrigger, clip: &ClipItem, tag: Option<&str>) {
+        if self.app_handle.lock().map(|a| a.is_none()).unwrap_or(true) {
+            return;
+        }
+
+        let hooks: Vec<Arc<CompiledHook>> = match self.hooks.lock() {
+            Ok(hooks) => hooks
+                .iter()
+                .filter(|hook| hook.matches(trigger, clip, tag))
+                .cloned()
+                .collect(),
+            Err(_) => return,
+        };
+
+        for hook in hooks {
+            let (slots, _) = &*self.slots;
+            let queued = slots
+                .lock()
+                .map(|mut slots| {
+                    let queued = slots.pending < MAX_PENDING;
+                    if queued {
+                        slots.pending += 1;
+                    }
+                    queued
+                })
+                .unwrap_or(false);
+
+            let job = Job {
+                hook,
+                trigger,
+                clip: clip.clone(),
+                tag: tag.map(str::to_string),
+                slots: self.slots.clone(),
+                runs: self.runs.clone(),
+                run_id: self.next_run_id.fetch_add(1, Ordering::SeqCst),
+                app_handle: self.app_handle.clone(),
+            };
+            if queued {
+                thread::spawn(move || job.run());
+            } else {
+                let mut run = job.new_run();
+                run.error = Some("Skipped, too many hooks were waiting".to_string());
+                job.record(run);
+            }
+        }
+    }
+}
+
+struct Job {
+    hook: Arc<CompiledHook>,
+    trigger: HookTrigger,
+    clip: ClipItem,
+    tag: Option<String>,
+    slots: Arc<(Mutex<Slots>, Condvar)>,
+    runs: Arc<Mutex<VecDeque<HookRun>>>,
+    run_id: u64,
+    app_handle: Arc<Mutex<Option<tauri::AppHandle>>>,
+}
+
+impl Job {
+    fn run(self) {
+        let (slots, available) = &*self.slots;
+        {
+            let Ok(mut guard) = slots.lock() else {
+                return;
+            };
+            while guard.running >= guard.max_concurrent {
+                guard = match available.wait(guard) {
+                    Ok(guard) => guard,
+                    Err(_) => return,
+                };
+            }
+            guard.pending -= 1;
+            guard.running += 1;
+        }
+
+        let run = self.execute();
+
+        if let Ok(mut guard) = slots.lock() {
+            guard.running -= 1;
+        }
+        available.notify_one();
+
+        self.record(run);
+    }
+
+    /// Keep a run for the app to show
+    fn record(&self, run: HookRun) {
+        if let Some(error) = &run.error {
+            eprintln!("Hook '{}' failed: {}", run.hook_name, error);
+        }
+        if let Ok(mut runs) = self.runs.lock() {
+            runs.push_back(run);
+            while runs.len() > MAX_RUNS {
+                runs.pop_front();
+            }
+        }
+        if let Some(app) = self.app_handle.lock().ok().and_then(|a| a.clone()) {
+            let _ = app.emit("hook-runs-updated", ());
+        }
+    }
+
+    fn new_run(&self) -> HookRun {
+        let hook = &self.hook.hook;
+        HookRun {
+            id: self.run_id,
+            hook_id: hook.id.clone(),
+            hook_name: hook.name.clone(),
+            trigger: self.trigger,
+            clip_id: self.clip.id.clone(),
+            started_at: Utc::now(),
+            duration_ms: 0,
+            exit_code: None,
+            timed_out: false,
+            stdout: String::new(),
+            stderr: String::new(),
+            error: None,
+        }
+    }
+
+    fn execute(&self) -> HookRun {
+        let started = Instant::now();
+        let mut run = self.new_run();
+        if let Err(e) = self.run_command(&mut run) {
+            run.error = Some(e);
+        }
+        run.duration_ms = started.elapsed().as_millis() as u64;
+        run
+    }
+
+    fn run_command(&self, run: &mut HookRun) -> Result<(), String> {
+        let hook = &self.hook.hook;
+        let clip = &self.clip;
+        // Images have no text; their file path stands in for it
+        let text = clip_plain_text(clip).unwrap_or_else(|_| clip.content.clone());
+        let tags = clip.tags.join(",");
+        let tag = self.tag.clone().unwrap_or_default();
+
+        let values = [
+            ("id", clip.id.as_str()),
+            ("type", clip.content_type.as_str()),
+            ("source_app", clip.source_app.as_str()),
+            ("tags", tags.as_str()),
+            ("tag", tag.as_str()),
+        ];
+        let args: Vec<String> = split_command(&hook.command)?
+            .iter()
+            .map(|arg| fill_placeholders(arg, &values))
+            .collect();
+        let (program, args) = args.split_first().ok_or_else(|| "No command".to_string())?;
+
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .env("FAT_CLIP_EVENT", self.trigger.as_str())
+            .env("FAT_CLIP_ID", &clip.id)
+            .env("FAT_CLIP_TYPE", clip.content_type.as_str())
+            .env("FAT_CLIP_SOURCE_APP", &clip.source_app)
+            .env("FAT_CLIP_TAGS", &tags)
+            .env("FAT_CLIP_TAG", &tag)
+            .env("FAT_CLIP_PINNED", if clip.pinned { "1" } else { "0" })
+            .env("FAT_CLIP_CREATED_AT", clip.created_at.to_rfc3339())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let mut tree = CommandTree::spawn(&mut command)
+            .map_err(|e| format!("Failed to start {}: {}", program, e))?;
+
+        // Separate threads so a command that does not read its input or fills
+        // one output pipe cannot block the others
+        let stdin = tree.child.stdin.take().map(|mut stdin| {
+            thread::spawn(move || {
+                let _ = stdin.write_all(text.as_bytes());
+            })
+        });
+        let stdout = tree.child.stdout.take().map(Capture::start);
+        let stderr = tree.child.stderr.take().map(Capture::start);
+
+        let deadline = Instant::now() + Duration::from_secs(hook.timeout_seconds.max(1));
+        let status = loop {
+            match tree.child.try_wait() {
+                Ok(Some(status)) => break Some(status),
+                Ok(None) if Instant::now() >= deadline => {
+                    tree.kill();
+                    run.timed_out = true;
+                    break None;
+                }
+                Ok(None) => thread::sleep(WAIT_INTERVAL),
+                Err(e) => {
+                    tree.kill();
+                    return Err(e.to_string());
+                }
+            }
+        };
+
+        // Processes the command started may still hold its pipes. They get
+        // what is left of the timeout, then are stopped too; output that does
+        // not end after that is not waited for.
+        let deadline = if run.timed_out {
+            Instant::now() + KILL_GRACE_PERIOD
+        } else {
+            deadline
+        };
+        let threads: Vec<&thread::JoinHandle<()>> = stdin
+            .iter()
+            .chain(stdout.iter().chain(&stderr).map(|capture| &capture.thread))
+            .collect();
+        if !wait_until_finished(&threads, deadline) {
+            tree.kill();
+            run.timed_out = true;
+            wait_until_finished(&threads, Instant::now() + KILL_GRACE_PERIOD);
+        }
+
+        run.stdout = stdout.map(|c| c.text()).unwrap_or_default();
+        run.stderr = stderr.map(|c| c.text()).unwrap_or_default();
+        run.exit_code = status.and_then(|status| status.code());
+        Ok(())
+    }
+}
+
+/// Check that every enabled hook can be run, without applying any of them
+pub fn validate_hooks(hooks: &[Hook]) -> Result<(), String> {
+    for hook in hooks.iter().filter(|hook| hook.enabled) {
+        CompiledHook::new(hook.clone())?;
+    }
+    Ok(())
+}
+
+/// A started command in its own process group, or job object on Windows, so
+/// the processes it starts are stopped with it
+struct CommandTree {
+    child: Child,
+    #[cfg(windows)]
+    job: Option<job_object::JobObject>,
+}
+
+impl CommandTree {
+    fn spawn(command: &mut Command) -> std::io::Result<Self> {
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(command, 0);
+
+        let child = command.spawn()?;
+        Ok(CommandTree {
+            #[cfg(windows)]
+            job: job_object::JobObject::assign(&child),
+            child,
+        })
+    }
+
+    /// Kill the command and every process it started that is still running
+    fn kill(&mut self) {
+        #[cfg(unix)]
+        // SAFETY: the group is the one the command was started in; its id
+        // cannot be reused while any process of the group is left
+        unsafe {
+            libc::killpg(self.child.id() as libc::pid_t, libc::SIGKILL);
+        }
+        #[cfg(windows)]
+        if let Some(job) = &self.job {
+            job.terminate();
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(windows)]
+mod job_object {
+    use std::os::windows::io::AsRawHandle;
+    use std::process::Child;
+    use windows_sys::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, TerminateJobObject,
+    };
+
+    pub struct JobObject(HANDLE);
+
+    impl JobObject {
+        /// Put a started process in a new job, which the processes it starts
+        /// join too
+        pub fn assign(child: &Child) -> Option<Self> {
+            // SAFETY: a job without attributes or name; the handle is owned
+            // by the returned value
+            let job = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+            if job.is_null() {
+                return None;
+            }
+            let job = JobObject(job);
+            // SAFETY: both handles are open
+            let assigned =
+                unsafe { AssignProcessToJobObject(job.0, child.as_raw_handle() as HANDLE) };
+            (assigned != 0).then_some(job)
+        }
+
+        pub fn terminate(&self) {
+            // SAFETY: the handle is open
+            unsafe {
+                TerminateJobObject(self.0, 1);
+            }
+        }
+    }
+
+    impl Drop for JobObject {
+        fn drop(&mut self) {
+            // SAFETY: the handle is open and not used after this
+            unsafe {
+                CloseHandle(self.0);
+            }
+        }
+    }
+}
+
+/// Output read from a stream so far, keeping the first `MAX_OUTPUT_SIZE` bytes
+struct Capture {
+    output: Arc<Mutex<Vec<u8>>>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl Capture {
+    fn start<R: Read + Send + 'static>(mut stream: R) -> Self {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let captured = output.clone();
+        let thread = thread::spawn(move || {
+            let mut buffer = [0u8; 8192];
+            // Read to the end so the command does not block on a full pipe
+            while let Ok(length) = stream.read(&mut buffer) {
+                if length == 0 {
+                    break;
+                }
+                if let Ok(mut output) = captured.lock() {
+                    let room = MAX_OUTPUT_SIZE as usize - output.len();
+                    output.extend_from_slice(&buffer[..length.min(room)]);
+                }
+            }
+        });
+        Capture { output, thread }
+    }
+
+    fn text(&self) -> String {
+        self.output
+            .lock()
+            .map(|output| String::from_utf8_lossy(&output).to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Whether the threads finished before `deadline`
+fn wait_until_finished(threads: &[&thread::JoinHandle<()>], deadline: Instant) -> bool {
+    loop {
+        if threads.iter().all(|thread| thread.is_finished()) {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(WAIT_INTERVAL);
+    }
+}
+
+/// Replace `{name}` placeholders in one pass, so values are never expanded
+/// again
+fn fill_placeholders(template: &str, values: &[(&str, &str)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = values.iter().find_map(|(name, value)| {
+            rest.strip_prefix('{')
+                .and_then(|r| r.strip_prefix(name))
+                .filter(|r| r.starts_with('}'))
+                .map(|_| (name.len() + 2, value))
+        });
+        match value {
+            Some((length, value)) => {
+                filled.push_str(value);
+                rest = &rest[length..];
+            }
+            None => {
+                filled.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// Split a command line into words. Single quotes keep everything literally,
+/// double quotes and backslashes work as in a POSIX shell. On Windows a
+/// backslash is an ordinary character, as in paths.
+fn split_command(command: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("Unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') if !cfg!(windows) => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("Unterminated double quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("Unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' if !cfg!(windows) => {
+                in_word = true;
+                match chars.next() {
+                    Some(c) => word.push(c),
+                    None => return Err("Trailing backslash".to_string()),
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(command: &str) -> Vec<String> {
+        split_command(command).unwrap()
+    }
+
+    #[test]
+    fn commands_are_split_like_a_shell_would() {
+        assert_eq!(split("  notify-send  Clip  "), vec!["notify-send", "Clip"]);
+        assert_eq!(
+            split(r#"echo 'a "b"' "c 'd'" e""f"#),
+            vec!["echo", r#"a "b""#, "c 'd'", "ef"]
+        );
+        assert_eq!(split("echo '' x"), vec!["echo", "", "x"]);
+        assert!(split_command("echo 'open").is_err());
+        assert!(split_command("echo \"open").is_err());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn backslashes_escape_outside_windows() {
+        assert_eq!(split(r"echo a\ b"), vec!["echo", "a b"]);
+        assert_eq!(split(r#"echo "\"\n""#), vec!["echo", r#""\n"#]);
+        assert!(split_command("echo \\").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commands_past_their_timeout_are_stopped_with_what_they_started() {
+        let hook = Hook {
+            id: "slow".to_string(),
+            name: "Slow".to_string(),
+            enabled: true,
+            trigger: HookTrigger::NewClip,
+            // The shell waits for sleep, which holds the output pipes
+            command: "sh -c 'echo started; sleep 600; echo done'".to_string(),
+            conditions: AutoTagConditions::default(),
+            tags: Vec::new(),
+            timeout_seconds: 1,
+        };
+        let job = Job {
+            hook: Arc::new(CompiledHook::new(hook).unwrap()),
+            trigger: HookTrigger::NewClip,
+            clip: ClipItem {
+                id: "id".to_string(),
+                content_type: crate::db::ContentType::Plain,
+                content: "text".to_string(),
+                preview_text: "text".to_string(),
+                tags: vec![],
+                source_app: String::new(),
+                created_at: Utc::now(),
+                last_used_at: Utc::now(),
+                pinned: false,
+                metadata: None,
+                use_count: 0,
+            },
+            tag: None,
+            slots: Arc::new((
+                Mutex::new(Slots {
+                    max_concurrent: 1,
+                    running: 0,
+                    pending: 1,
+                }),
+                Condvar::new(),
+            )),
+            runs: Arc::new(Mutex::new(VecDeque::new())),
+            run_id: 1,
+            app_handle: Arc::new(Mutex::new(None)),
+        };
+        let slots = job.slots.clone();
+        let runs = job.runs.clone();
+
+        let started = Instant::now();
+        job.run();
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let run = runs.lock().unwrap().pop_back().unwrap();
+        assert!(run.timed_out);
+        assert_eq!(run.exit_code, None);
+        assert_eq!(run.stdout, "started\n");
+        // The slot is free for the next hook
+        assert_eq!(slots.0.lock().unwrap().running, 0);
+    }
+
+    #[test]
+    fn placeholders_are_filled_once() {
+        let values = [("id", "{tag}"), ("tag", "work")];
+        assert_eq!(fill_placeholders("{id}-{tag}", &values), "{tag}-work");
+        assert_eq!(
+            fill_placeholders("{unknown} {tag", &values),
+            "{unknown} {tag"
+        );
+        assert_eq!(fill_placeholders("{{tag}}", &values), "{work}");
+    }
+}
@@ -0,0 +1,295 @@
+//! Importers for the histories of other clipboard managers. Each importer
+//! reads the tool's on-disk format into `ExternalClip`s, which are then saved
+//! like captured clips.
+
+use crate::archive::ImportSummary;
+use crate::clipboard::ClipboardManager;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+mod clipy;
+mod copyq;
+mod ditto;
+mod gpaste;
+mod klipper;
+mod maccy;
+mod qdatastream;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportSource {
+    CopyQ,
+    Ditto,
+    Maccy,
+    Clipy,
+    GPaste,
+    Klipper,
+}
+
+/// A clip read from another clipboard manager
+#[derive(Debug)]
+pub struct ExternalClip {
+    pub content: ExternalContent,
+    pub created_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub pinned: bool,
+    /// Tags, or the groups/tabs the clip was in
+    pub tags: Vec<String>,
+    pub source_app: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ExternalContent {
+    Text(String),
+    Rich {
+        html: String,
+        plain: String,
+    },
+    Files(Vec<String>),
+    /// Encoded image data, e.g. PNG
+    Image(Vec<u8>),
+}
+
+impl ExternalClip {
+    fn new(content: ExternalContent) -> Self {
+        ExternalClip {
+            content,
+            created_at: None,
+            last_used_at: None,
+            pinned: false,
+            tags: Vec::new(),
+            source_app: None,
+        }
+    }
+}
+
+/// Where the tool keeps its history by default, if it is known for this platform
+pub fn default_path(source: ImportSource) -> Option<PathBuf> {
+    let home = dirs::home_dir()?;
+    let path = match source {
+        ImportSource::CopyQ => dirs::config_dir()?.join("copyq"),
+        ImportSource::Ditto => dirs::data_dir()?.join("Ditto").join("Ditto.db"),
+        ImportSource::Maccy => home.join(
+            "Library/Containers/org.p0deje.Maccy/Data/Library/Application Support/Maccy/Storage.sqlite",
+        ),
+        ImportSource::Clipy => home.join("Library/Application Support/com.clipy-app.Clipy"),
+        ImportSource::GPaste => dirs::data_dir()?.join("gpaste").join("history.xml"),
+        ImportSource::Klipper => dirs::data_dir()?.join("klipper").join("history2.lst"),
+    };
+    Some(path)
+}
+
+/// Read the history of `source` from a file or the directory containing it
+pub fn read_history(source: ImportSource, path: &Path) -> Result<Vec<ExternalClip>, String> {
+    if !path.exists() {
+        return Err(format!("{} does not exist", path.display()));
+    }
+
+    match source {
+        ImportSource::CopyQ => copyq::read(path),
+        ImportSource::Ditto => ditto::read(&file_in(path, "Ditto.db")),
+        ImportSource::Maccy => maccy::read(&file_in(path, "Storage.sqlite")),
+        ImportSource::Clipy => clipy::read(path),
+        ImportSource::GPaste => gpaste::read(&file_in(path, "history.xml")),
+        ImportSource::Klipper => klipper::read(&file_in(path, "history2.lst")),
+    }
+}
+
+/// Save external clips into the history. Clips that are already in the history
+/// keep their timestamps but gain the imported tags and pin.
+pub fn import_clips(
+    clipboard_manager: &ClipboardManager,
+    clips: Vec<ExternalClip>,
+    source_name: &str,
+) -> ImportSummary {
+    let mut summary = ImportSummary::default();
+
+    // Oldest first, so the newest clips end up on top when timestamps are missing
+    for clip in clips.into_iter().rev() {
+        match import_clip(clipboard_manager, clip, source_name) {
+            Ok(true) => summary.imported += 1,
+            Ok(false) => summary.merged += 1,
+            Err(e) => {
+                eprintln!("Failed to import clip: {}", e);
+                summary.failed += 1;
+            }
+        }
+    }
+
+    summary
+}
+
+/// Save one clip. Returns whether it was new.
+fn import_clip(
+    clipboard_manager: &ClipboardManager,
+    clip: ExternalClip,
+    source_name: &str,
+) -> Result<bool, String> {
+    let started = Utc::now();
+    let source_app = clip
+        .source_app
+        .clone()
+        .unwrap_or_else(|| source_name.to_string());
+
+    let saved = match clip.content {
+        ExternalContent::Text(text) => {
+            if text.trim().is_empty() {
+                return Err("Empty text".to_string());
+            }
+            clipboard_manager.save_text_clip(text, source_app, false)?
+        }
+        ExternalContent::Rich { html, plain } => {
+            clipboard_manager.save_rich_text_clip(Some(html), None, plain, source_app, false)?
+        }
+        ExternalContent::Files(paths) => {
+            if paths.is_empty() {
+                return Err("Empty file list".to_string());
+            }
+            clipboard_manager.save_file_clip(paths, source_app, false)?
+        }
+        ExternalContent::Image(data) => {
+            let (png, width, height) = to_png(&data)?;
+            clipboard_manager.save_image_clip(png, width, height, "png", source_app, false)?
+        }
+    };
+
+    let is_new = saved.created_at >= started;
+
+    let mut tags = saved.tags.clone();
+    for tag in clip.tags {
+        let tag = tag.trim().to_string();
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    let db = clipboard_manager.db.lock().map_err(|e| e.to_string())?;
+    if tags != saved.tags {
+        db.update_clip_tags(&saved.id, tags)
+            .map_err(|e| e.to_string())?;
+    }
+    if clip.pinned && !saved.pinned {
+        db.toggle_pin(&saved.id, true).map_err(|e| e.to_string())?;
+    }
+    if let Some(created_at) = clip.created_at.filter(|_| is_new) {
+        let last_used_at = clip.last_used_at.unwrap_or(created_at).max(created_at);
+        db.set_clip_timestamps(&saved.id, created_at, last_used_at)
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(is_new)
+}
+
+/// Re-encode an image as PNG and return its size
+fn to_png(data: &[u8]) -> Result<(Vec<u8>, u32, u32), String> {
+    let image = image::load_from_memory(data).map_err(|e| format!("Unsupported image: {}", e))?;
+    let mut png = Vec::new();
+    image
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok((png, image.width(), image.height()))
+}
+
+/// `path` itself if it is a file, otherwise `name` inside it
+fn file_in(path: &Path, name: &str) -> PathBuf {
+    if path.is_dir() {
+        path.join(name)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Turn "file://" URIs into local paths; other entries are kept as they are
+fn uri_to_path(uri: &str) -> String {
+    let Some(rest) = uri.strip_prefix("file://") else {
+        return uri.to_string();
+    };
+
+    // Drop the host part ("file://localhost/...")
+    let path = match rest.find('/') {
+        Some(start) => &rest[start..],
+        None => rest,
+    };
+    let path = percent_decode(path);
+
+    // "/C:/Users/..." on Windows
+    let bytes = path.as_bytes();
+    if bytes.len() > 2 && bytes[0] == b'/' && bytes[2] == b':' {
+        return path[1..].to_string();
+    }
+    path
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(byte) = bytes
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Text or rich content from the formats a clip was stored with
+fn text_content(text: Option<String>, html: Option<String>) -> Option<ExternalContent> {
+    let text = text.filter(|t| !t.trim().is_empty());
+    match html.filter(|h| !h.trim().is_empty()) {
+        Some(html) => {
+            let plain = text.unwrap_or_else(|| crate::clipboard::html_to_plain_text(&html));
+            Some(ExternalContent::Rich { html, plain })
+        }
+        None => text.map(ExternalContent::Text),
+    }
+}
+
+/// A file of the importer fixtures
+#[cfg(test)]
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/importers")
+        .join(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_uris_become_paths() {
+        assert_eq!(uri_to_path("file:///home/me/a%20b.txt"), "/home/me/a b.txt");
+        assert_eq!(uri_to_path("file://localhost/tmp/x"), "/tmp/x");
+        assert_eq!(
+            uri_to_path("file:///C:/Users/me/a.txt"),
+            "C:/Users/me/a.txt"
+        );
+        assert_eq!(uri_to_path("/already/a/path"), "/already/a/path");
+    }
+
+    #[test]
+    fn html_without_text_gets_plain_text() {
+        assert_eq!(
+            text_content(None, Some("<b>bold</b>".to_string())),
+            Some(ExternalContent::Rich {
+                html: "<b>bold</b>".to_string(),
+                plain: "bold".to_string(),
+            })
+        );
+        assert_eq!(
+            text_content(Some(" ".to_string()), Some(String::new())),
+            None
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! Clipy archives the content of each clip in its own `<uuid>.data` file, an
+//! `NSKeyedArchiver` property list, next to a Realm database with the list
+//! order. Realm files cannot be read here, so the file times stand in for the
+//! copy times.
+
+use super::{uri_to_path, ExternalClip, ExternalContent};
+use chrono::{DateTime, Utc};
+use plist::{Dictionary, Value};
+use std::path::Path;
+
+pub fn read(path: &Path) -> Result<Vec<ExternalClip>, String> {
+    let files = if path.is_dir() {
+        std::fs::read_dir(path)
+            .map_err(|e| e.to_string())?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "data"))
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut clips = Vec::new();
+    for file in files {
+        let archive = match Value::from_file(&file) {
+            Ok(archive) => archive,
+            Err(e) => {
+                eprintln!("Skipping Clipy file {}: {}", file.display(), e);
+                continue;
+            }
+        };
+
+        let Some(content) = clip_content(&archive) else {
+            continue;
+        };
+
+        let mut clip = ExternalClip::new(content);
+        clip.created_at = std::fs::metadata(&file)
+            .and_then(|m| m.modified())
+            .ok()
+            .map(DateTime::<Utc>::from);
+        clips.push(clip);
+    }
+
+    // Newest first, like the other importers
+    clips.sort_by_key(|clip| std::cmp::Reverse(clip.created_at));
+
+    if clips.is_empty() {
+        return Err("No Clipy clips found".to_string());
+    }
+    Ok(clips)
+}
+
+/// Content of an archived `CPYClipData`
+fn clip_content(archive: &Value) -> Option<ExternalContent> {
+    let archive = KeyedArchive::new(archive)?;
+    let root = archive.root()?;
+
+    let file_names = archive.string_array(root, &["fileNames", "filenames"]);
+    if !file_names.is_empty() {
+        return Some(ExternalContent::Files(file_names));
+    }
+
+    let urls = archive.string_array(root, &["URLs", "urls"]);
+    if urls.iter().any(|url| url.starts_with("file://")) {
+        return Some(ExternalContent::Files(
+            urls.iter().map(|url| uri_to_path(url)).collect(),
+        ));
+    }
+
+    archive
+        .string(root, "stringValue")
+        .filter(|text| !text.trim().is_empty())
+        .map(ExternalContent::Text)
+}
+
+/// Objects of an `NSKeyedArchiver` archive, which refer to each other by index
+struct KeyedArchive<'a> {
+    objects: &'a [Value],
+    top: &'a Dictionary,
+}
+
+impl<'a> KeyedArchive<'a> {
+    fn new(archive: &'a Value) -> Option<Self> {
+        let archive = archive.as_dictionary()?;
+        Some(KeyedArchive {
+            objects: archive.get("$objects")?.as_array()?,
+            top: archive.get("$top")?.as_dictionary()?,
+        })
+    }
+
+    fn root(&self) -> Option<&'a Dictionary> {
+        self.resolve(self.top.get("root")?)?.as_dictionary()
+    }
+
+    /// Follow a reference to the object it points at
+    fn resolve(&self, value: &'a Value) -> Option<&'a Value> {
+        match value.as_uid() {
+            Some(uid) => self.objects.get(uid.get() as usize),
+            None => Some(value),
+        }
+    }
+
+    fn string(&self, object: &'a Dictionary, key: &str) -> Option<String> {
+        let value = self.resolve(object.get(key)?)?;
+        value.as_string().map(str::to_string).or_else(|| {
+            // NSMutableString is archived as a dictionary
+            self.resolve(value.as_dictionary()?.get("NS.string")?)?
+                .as_string()
+                .map(str::to_string)
+        })
+    }
+
+    fn string_array(&self, object: &'a Dictionary, keys: &[&str]) -> Vec<String> {
+        let Some(array) = keys
+            .iter()
+            .find_map(|key| object.get(key))
+            .and_then(|value| self.resolve(value))
+            .and_then(|value| value.as_dictionary())
+            .and_then(|array| array.get("NS.objects"))
+            .and_then(|objects| objects.as_array())
+        else {
+            return Vec::new();
+        };
+
+        array
+            .iter()
+            .filter_map(|item| {
+                let item = self.resolve(item)?;
+                item.as_string().map(str::to_string).or_else(|| {
+                    // NSURL keeps its string under NS.relative
+                    self.resolve(item.as_dictionary()?.get("NS.relative")?)?
+                        .as_string()
+                        .map(str::to_string)
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixture;
+    use super::*;
+
+    #[test]
+    fn reads_archived_text_and_file_names() {
+        let clips = read(&fixture("clipy")).unwrap();
+        assert_eq!(clips.len(), 2);
+
+        let mut contents: Vec<_> = clips.iter().map(|clip| &clip.content).collect();
+        contents.sort_by_key(|content| matches!(content, ExternalContent::Files(_)));
+        assert_eq!(
+            contents,
+            vec![
+                &ExternalContent::Text("clipy text".to_string()),
+                &ExternalContent::Files(vec!["/Users/me/notes.md".to_string()]),
+            ]
+        );
+
+        // Clipy's own timestamps are in its Realm database
+        assert!(clips.iter().all(|clip| clip.created_at.is_some()));
+        assert!(clips
+            .iter()
+            .all(|clip| !clip.pinned && clip.tags.is_empty()));
+    }
+}
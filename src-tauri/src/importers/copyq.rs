@@ -0,0 +1,208 @@
+//! CopyQ keeps each tab in `copyq_tab_<base64 tab name>.dat`: a `QDataStream`
+//! with the item count followed by a map of MIME type to data per item.
+//! There are no timestamps; the tab name becomes a tag.
+
+use super::qdatastream::{q_uncompress, QDataStream};
+use super::{text_content, uri_to_path, ExternalClip, ExternalContent};
+use base64::Engine;
+use std::collections::HashMap;
+use std::path::Path;
+
+const TAGS_MIME: &str = "application/x-copyq-tags";
+const PINNED_MIME: &str = "application/x-copyq-item-pinned";
+
+/// MIME prefixes abbreviated by a digit in the compact item format
+const MIME_PREFIXES: [&str; 4] = ["application/x-copyq-", "text/", "application/", "image/"];
+
+/// Read a tab file, or every tab file in CopyQ's configuration directory
+pub fn read(path: &Path) -> Result<Vec<ExternalClip>, String> {
+    let files = if path.is_dir() {
+        let mut files: Vec<_> = std::fs::read_dir(path)
+            .map_err(|e| e.to_string())?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("copyq_tab_") && n.ends_with(".dat"))
+            })
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    if files.is_empty() {
+        return Err("No CopyQ tab files found".to_string());
+    }
+
+    let mut clips = Vec::new();
+    for file in files {
+        let data = std::fs::read(&file).map_err(|e| e.to_string())?;
+        let tab = tab_name(&file);
+        clips.extend(read_tab(&data, tab.as_deref())?);
+    }
+    Ok(clips)
+}
+
+/// Tab name from the file name, `None` for the default clipboard tab
+fn tab_name(file: &Path) -> Option<String> {
+    let stem = file.file_stem()?.to_str()?.strip_prefix("copyq_tab_")?;
+    let name = base64::engine::general_purpose::STANDARD
+        .decode(stem)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .unwrap_or_else(|| stem.to_string());
+
+    // "&" marks the keyboard accelerator in tab names
+    let name = name.replace('&', "");
+    (!name.is_empty() && !name.eq_ignore_ascii_case("clipboard")).then_some(name)
+}
+
+fn read_tab(data: &[u8], tab: Option<&str>) -> Result<Vec<ExternalClip>, String> {
+    let mut stream = QDataStream::new(data);
+    let count = stream.read_i32()?;
+    if count < 0 {
+        return Err("Not a CopyQ tab file".to_string());
+    }
+
+    let mut clips = Vec::new();
+    for _ in 0..count {
+        let item = read_item(&mut stream)?;
+        if let Some(mut clip) = item_to_clip(&item) {
+            clip.tags.extend(tab.map(str::to_string));
+            clips.push(clip);
+        }
+    }
+    Ok(clips)
+}
+
+/// The data of one item by MIME type
+fn read_item(stream: &mut QDataStream) -> Result<HashMap<String, Vec<u8>>, String> {
+    let version = stream.read_i32()?;
+    let mut item = HashMap::new();
+
+    match version {
+        // Compact format: abbreviated MIME types and optional compression
+        -2 | -1 => {
+            let size = stream.read_i32()?;
+            for _ in 0..size {
+                let mime = stream.read_string()?;
+                let mime = if version == -2 {
+                    expand_mime(&mime)
+                } else {
+                    mime
+                };
+                let compressed = stream.read_bool()?;
+                let mut data = stream.read_byte_array()?;
+                if compressed {
+                    data = q_uncompress(&data)?;
+                }
+                item.insert(mime, data);
+            }
+        }
+        // Oldest format: the version is the number of formats
+        size if size >= 0 => {
+            for _ in 0..size {
+                let mime = stream.read_string()?;
+                let data = stream.read_byte_array()?;
+                item.insert(mime, data);
+            }
+        }
+        _ => return Err(format!("Unsupported CopyQ item version {}", version)),
+    }
+
+    Ok(item)
+}
+
+fn expand_mime(mime: &str) -> String {
+    let mut chars = mime.chars();
+    match chars.next().and_then(|c| c.to_digit(10)) {
+        Some(0) => chars.as_str().to_string(),
+        Some(i) if (i as usize) <= MIME_PREFIXES.len() => {
+            format!("{}{}", MIME_PREFIXES[i as usize - 1], chars.as_str())
+        }
+        _ => mime.to_string(),
+    }
+}
+
+fn item_to_clip(item: &HashMap<String, Vec<u8>>) -> Option<ExternalClip> {
+    let text = |mime: &str| {
+        item.get(mime)
+            .map(|data| String::from_utf8_lossy(data).to_string())
+    };
+
+    let content = if let Some(uris) = text("text/uri-list").filter(|u| u.contains("file://")) {
+        ExternalContent::Files(
+            uris.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(uri_to_path)
+                .collect(),
+        )
+    } else if let Some(content) = text_content(text("text/plain"), text("text/html")) {
+        content
+    } else {
+        let image = ["image/png", "image/jpeg", "image/bmp"]
+            .iter()
+            .find_map(|mime| item.get(*mime))?;
+        ExternalContent::Image(image.clone())
+    };
+
+    let mut clip = ExternalClip::new(content);
+    clip.pinned = item.contains_key(PINNED_MIME);
+    if let Some(tags) = text(TAGS_MIME) {
+        clip.tags = tags
+            .split([',', '\n'])
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+    }
+    Some(clip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixture;
+    use super::*;
+
+    #[test]
+    fn reads_tabs_with_tags_pins_and_all_item_formats() {
+        let clips = read(&fixture("")).unwrap();
+        assert_eq!(clips.len(), 3);
+
+        let note = &clips[0];
+        assert_eq!(
+            note.content,
+            ExternalContent::Text("pinned note".to_string())
+        );
+        assert!(note.pinned);
+        assert_eq!(note.tags, vec!["todo", "later", "Work"]);
+        // CopyQ keeps no timestamps
+        assert_eq!(note.created_at, None);
+
+        assert_eq!(
+            clips[1].content,
+            ExternalContent::Rich {
+                html: "<b>bold</b>".to_string(),
+                plain: "bold".to_string(),
+            }
+        );
+        assert!(!clips[1].pinned);
+        assert_eq!(clips[1].tags, vec!["Work"]);
+
+        assert_eq!(
+            clips[2].content,
+            ExternalContent::Files(vec!["/home/user/report final.pdf".to_string()])
+        );
+    }
+
+    #[test]
+    fn clipboard_tab_is_not_a_tag() {
+        assert_eq!(tab_name(Path::new("copyq_tab_JmNsaXBib2FyZA==.dat")), None);
+        assert_eq!(
+            tab_name(Path::new("copyq_tab_Jldvcms=.dat")).as_deref(),
+            Some("Work")
+        );
+    }
+}
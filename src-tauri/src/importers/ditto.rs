@@ -0,0 +1,244 @@
+//! Ditto keeps its history in the SQLite database `Ditto.db`. `Main` holds one
+//! row per clip or group, `Data` the clipboard formats of each clip.
+
+use super::{text_content, ExternalClip, ExternalContent};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OpenFlags};
+use std::collections::HashMap;
+use std::path::Path;
+
+struct MainRow {
+    id: i64,
+    date: i64,
+    text: String,
+    parent_id: i64,
+    is_group: bool,
+    dont_auto_delete: bool,
+    last_paste_date: Option<i64>,
+}
+
+pub fn read(path: &Path) -> Result<Vec<ExternalClip>, String> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| e.to_string())?;
+
+    // Older databases have no paste date
+    let last_paste_date = if has_column(&conn, "Main", "lastPasteDate")? {
+        "lastPasteDate"
+    } else {
+        "NULL"
+    };
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT lID, lDate, mText, lParentID, bIsGroup, lDontAutoDelete, {}
+             FROM Main
+             ORDER BY lDate DESC",
+            last_paste_date
+        ))
+        .map_err(|e| format!("Not a Ditto database: {}", e))?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(MainRow {
+                id: row.get(0)?,
+                date: row.get::<_, Option<i64>>(1)?.unwrap_or(0),
+                text: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                parent_id: row.get::<_, Option<i64>>(3)?.unwrap_or(0),
+                is_group: row.get::<_, Option<i64>>(4)?.unwrap_or(0) != 0,
+                dont_auto_delete: row.get::<_, Option<i64>>(5)?.unwrap_or(0) != 0,
+                last_paste_date: row.get(6)?,
+            })
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| e.to_string())?;
+
+    let groups: HashMap<i64, String> = rows
+        .iter()
+        .filter(|row| row.is_group)
+        .map(|row| (row.id, row.text.clone()))
+        .collect();
+
+    let mut data_stmt = conn
+        .prepare("SELECT strClipBoardFormat, ooData FROM Data WHERE lParentID = ?1")
+        .map_err(|e| e.to_string())?;
+
+    let mut clips = Vec::new();
+    for row in rows.iter().filter(|row| !row.is_group) {
+        let formats: HashMap<String, Vec<u8>> = data_stmt
+            .query_map(params![row.id], |r| {
+                Ok((r.get::<_, String>(0)?, r.get::<_, Vec<u8>>(1)?))
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<_>>())
+            .map_err(|e| e.to_string())?;
+
+        let content = match clip_content(&formats) {
+            Some(content) => content,
+            // Fall back to the description Ditto shows in its list
+            None if !row.text.trim().is_empty() => ExternalContent::Text(row.text.clone()),
+            None => continue,
+        };
+
+        let mut clip = ExternalClip::new(content);
+        clip.created_at = timestamp(row.date);
+        clip.last_used_at = row.last_paste_date.and_then(timestamp);
+        clip.pinned = row.dont_auto_delete;
+        clip.tags.extend(groups.get(&row.parent_id).cloned());
+        clips.push(clip);
+    }
+
+    Ok(clips)
+}
+
+fn clip_content(formats: &HashMap<String, Vec<u8>>) -> Option<ExternalContent> {
+    if let Some(files) = formats.get("CF_HDROP").and_then(|data| parse_hdrop(data)) {
+        return Some(ExternalContent::Files(files));
+    }
+
+    let text = formats
+        .get("CF_UNICODETEXT")
+        .map(|data| utf16le_string(data))
+        .or_else(|| {
+            formats
+                .get("CF_TEXT")
+                .map(|data| String::from_utf8_lossy(trim_nul(data)).to_string())
+        });
+    let html = formats.get("HTML Format").map(|data| cf_html(data));
+
+    if let Some(content) = text_content(text, html) {
+        return Some(content);
+    }
+
+    formats.get("PNG").cloned().map(ExternalContent::Image)
+}
+
+/// Paths of a `DROPFILES` structure: a header followed by a double
+/// NUL-terminated list of ANSI or UTF-16 paths
+fn parse_hdrop(data: &[u8]) -> Option<Vec<String>> {
+    let read_u32 = |at: usize| {
+        data.get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+
+    let offset = read_u32(0)? as usize;
+    let wide = read_u32(16)? != 0;
+    let list = data.get(offset..)?;
+
+    let text = if wide {
+        utf16le_string_raw(list)
+    } else {
+        String::from_utf8_lossy(list).to_string()
+    };
+
+    let paths: Vec<String> = text
+        .split('\0')
+        .take_while(|path| !path.is_empty())
+        .map(str::to_string)
+        .collect();
+    (!paths.is_empty()).then_some(paths)
+}
+
+/// The HTML of a CF_HTML block, without its description header. The offsets
+/// in the header count bytes, and the header is not always valid UTF-8, so the
+/// data is cut before it is decoded.
+fn cf_html(data: &[u8]) -> String {
+    let data = trim_nul(data);
+    let start = String::from_utf8_lossy(data)
+        .lines()
+        .find_map(|line| line.strip_prefix("StartHTML:"))
+        .and_then(|offset| offset.trim().parse::<usize>().ok())
+        .filter(|&offset| offset < data.len());
+
+    let html = match start {
+        Some(start) => &data[start..],
+        None => data,
+    };
+    String::from_utf8_lossy(html).to_string()
+}
+
+fn utf16le_string_raw(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn utf16le_string(data: &[u8]) -> String {
+    utf16le_string_raw(data).trim_end_matches('\0').to_string()
+}
+
+fn trim_nul(data: &[u8]) -> &[u8] {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    &data[..end]
+}
+
+fn timestamp(secs: i64) -> Option<DateTime<Utc>> {
+    (secs > 0)
+        .then(|| DateTime::<Utc>::from_timestamp(secs, 0))
+        .flatten()
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool, String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|e| e.to_string())?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| e.to_string())?;
+    Ok(columns.iter().any(|c| c.eq_ignore_ascii_case(column)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixture;
+    use super::*;
+
+    #[test]
+    fn reads_clips_with_groups_pins_and_timestamps() {
+        let clips = read(&fixture("Ditto.db")).unwrap();
+        assert_eq!(clips.len(), 4);
+
+        let grouped = &clips[0];
+        assert_eq!(
+            grouped.content,
+            ExternalContent::Text("grouped note".to_string())
+        );
+        assert_eq!(grouped.tags, vec!["Snippets"]);
+        assert!(grouped.pinned);
+        assert_eq!(grouped.created_at, timestamp(1_700_000_000));
+        assert_eq!(grouped.last_used_at, timestamp(1_700_003_600));
+
+        assert_eq!(
+            clips[1].content,
+            ExternalContent::Rich {
+                html: "<html><body><!--StartFragment--><b>bold</b><!--EndFragment--></body></html>"
+                    .to_string(),
+                plain: "bold".to_string(),
+            }
+        );
+        assert!(clips[1].tags.is_empty());
+        assert!(!clips[1].pinned);
+        assert_eq!(clips[1].last_used_at, None);
+
+        assert_eq!(
+            clips[2].content,
+            ExternalContent::Files(vec![
+                "C:\\Users\\me\\a.txt".to_string(),
+                "C:\\Users\\me\\b.txt".to_string(),
+            ])
+        );
+        assert_eq!(
+            clips[3].content,
+            ExternalContent::Text("description only".to_string())
+        );
+    }
+
+    #[test]
+    fn cf_html_offsets_count_bytes() {
+        let mut data = b"Version:0.9\r\nStartHTML:0000000051\r\nSourceURL:caf\xe9\r\n".to_vec();
+        assert_eq!(data.len(), 51);
+        data.extend_from_slice("<p>é</p>\0".as_bytes());
+        assert_eq!(cf_html(&data), "<p>é</p>");
+    }
+}
@@ -0,0 +1,116 @@
+//! GPaste keeps its history in `history.xml`, newest first. Version 2 files
+//! wrap the content in `<value>`, version 1 files put it in the item itself.
+
+use super::{uri_to_path, ExternalClip, ExternalContent};
+use chrono::{DateTime, Utc};
+use std::path::Path;
+
+pub fn read(path: &Path) -> Result<Vec<ExternalClip>, String> {
+    let xml = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let document =
+        roxmltree::Document::parse(&xml).map_err(|e| format!("Invalid GPaste history: {}", e))?;
+
+    let root = document.root_element();
+    if root.tag_name().name() != "history" {
+        return Err("Not a GPaste history file".to_string());
+    }
+
+    let mut clips = Vec::new();
+    for item in root.children().filter(|n| n.has_tag_name("item")) {
+        let value = item
+            .children()
+            .find(|n| n.has_tag_name("value"))
+            .map(node_text)
+            .unwrap_or_else(|| node_text(item));
+
+        let content = match item.attribute("kind").unwrap_or("Text") {
+            "Text" => ExternalContent::Text(value),
+            "Uris" => ExternalContent::Files(
+                value
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(uri_to_path)
+                    .collect(),
+            ),
+            // The value is the path of the saved image
+            "Image" => match std::fs::read(value.trim()) {
+                Ok(data) => ExternalContent::Image(data),
+                Err(e) => {
+                    eprintln!("Skipping GPaste image {}: {}", value.trim(), e);
+                    continue;
+                }
+            },
+            // Passwords are not imported
+            _ => continue,
+        };
+
+        let mut clip = ExternalClip::new(content);
+        clip.created_at = item
+            .attribute("date")
+            .and_then(|date| date.parse::<i64>().ok())
+            .and_then(|secs| DateTime::<Utc>::from_timestamp(secs, 0));
+        clips.push(clip);
+    }
+
+    Ok(clips)
+}
+
+/// Text and CDATA content of a node
+fn node_text(node: roxmltree::Node) -> String {
+    node.children()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixture;
+    use super::*;
+
+    #[test]
+    fn reads_items_with_dates_and_skips_passwords() {
+        let clips = read(&fixture("history.xml")).unwrap();
+        assert_eq!(clips.len(), 2);
+
+        assert_eq!(
+            clips[0].content,
+            ExternalContent::Text("newest text".to_string())
+        );
+        assert_eq!(
+            clips[0].created_at,
+            DateTime::from_timestamp(1_700_000_000, 0)
+        );
+
+        assert_eq!(
+            clips[1].content,
+            ExternalContent::Files(vec![
+                "/home/user/a.txt".to_string(),
+                "/home/user/b c.txt".to_string(),
+            ])
+        );
+        assert_eq!(
+            clips[1].created_at,
+            DateTime::from_timestamp(1_699_900_000, 0)
+        );
+    }
+
+    #[test]
+    fn version_one_items_hold_the_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.xml");
+        std::fs::write(
+            &path,
+            r#"<history version="1.0"><item kind="Text"><![CDATA[old format]]></item></history>"#,
+        )
+        .unwrap();
+
+        let clips = read(&path).unwrap();
+        assert_eq!(
+            clips[0].content,
+            ExternalContent::Text("old format".to_string())
+        );
+        assert_eq!(clips[0].created_at, None);
+    }
+}
@@ -0,0 +1,120 @@
+//! KDE Klipper keeps its history in `history2.lst`: a CRC32 followed by a
+//! `QDataStream` of the Klipper version and the items, newest first. Items
+//! have no timestamps.
+
+use super::qdatastream::QDataStream;
+use super::{uri_to_path, ExternalClip, ExternalContent};
+use std::path::Path;
+
+pub fn read(path: &Path) -> Result<Vec<ExternalClip>, String> {
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+
+    let mut file = QDataStream::new(&data);
+    let crc = file.read_u32()?;
+    let history = file.read_byte_array()?;
+
+    let mut hasher = flate2::Crc::new();
+    hasher.update(&history);
+    if hasher.sum() != crc {
+        return Err("The Klipper history file is corrupted".to_string());
+    }
+
+    let mut stream = QDataStream::new(&history);
+    // Klipper version, written as a C string
+    stream.read_byte_array()?;
+
+    let mut clips = Vec::new();
+    while !stream.at_end() {
+        let kind = stream.read_string()?;
+        let content = match kind.as_str() {
+            "string" => ExternalContent::Text(stream.read_string()?),
+            "url" => {
+                let urls = stream.read_string_list()?;
+                // Metadata map and the "cut" flag
+                let metadata_len = stream.read_u32()?;
+                for _ in 0..metadata_len {
+                    stream.read_string()?;
+                    stream.read_string()?;
+                }
+                stream.read_i32()?;
+                ExternalContent::Files(urls.iter().map(|url| uri_to_path(url)).collect())
+            }
+            "image" => match read_image(&mut stream)? {
+                Some(png) => ExternalContent::Image(png),
+                None => continue,
+            },
+            // Items after an unknown type cannot be located
+            _ => break,
+        };
+        clips.push(ExternalClip::new(content));
+    }
+
+    Ok(clips)
+}
+
+/// A `QPixmap`: a non-null marker followed by PNG data without a length, so
+/// the PNG chunks have to be walked to find its end
+fn read_image(stream: &mut QDataStream) -> Result<Option<Vec<u8>>, String> {
+    const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    if stream.read_i32()? == 0 {
+        return Ok(None);
+    }
+
+    let png = stream.remaining();
+    if !png.starts_with(PNG_SIGNATURE) {
+        return Err("Unsupported image in Klipper history".to_string());
+    }
+
+    let mut end = PNG_SIGNATURE.len();
+    loop {
+        let header = png
+            .get(end..end + 8)
+            .ok_or_else(|| "Truncated image in Klipper history".to_string())?;
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let chunk_type = &header[4..8];
+        // Length, type, data and CRC
+        end += 12 + len;
+        if chunk_type == b"IEND" {
+            break;
+        }
+    }
+
+    if end > png.len() {
+        return Err("Truncated image in Klipper history".to_string());
+    }
+
+    let image = png[..end].to_vec();
+    stream.skip(end)?;
+    Ok(Some(image))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixture;
+    use super::*;
+
+    #[test]
+    fn reads_items_newest_first() {
+        let clips = read(&fixture("history2.lst")).unwrap();
+        let contents: Vec<_> = clips.into_iter().map(|clip| clip.content).collect();
+        assert_eq!(
+            contents,
+            vec![
+                ExternalContent::Text("newest text".to_string()),
+                ExternalContent::Files(vec!["/tmp/a b.txt".to_string()]),
+                ExternalContent::Text("older text".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_a_wrong_checksum() {
+        let mut data = std::fs::read(fixture("history2.lst")).unwrap();
+        data[0] ^= 0xff;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history2.lst");
+        std::fs::write(&path, data).unwrap();
+        assert!(read(&path).unwrap_err().contains("corrupted"));
+    }
+}
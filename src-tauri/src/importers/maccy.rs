@@ -0,0 +1,150 @@
+//! Maccy keeps its history in the Core Data store `Storage.sqlite`:
+//! `ZHISTORYITEM` holds one row per clip and `ZHISTORYITEMCONTENT` its
+//! pasteboard types. Pinned items have a pin key.
+
+use super::{text_content, uri_to_path, ExternalClip, ExternalContent};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OpenFlags};
+use std::path::Path;
+
+/// Core Data timestamps count seconds from 2001-01-01
+const CORE_DATA_EPOCH: i64 = 978_307_200;
+
+pub fn read(path: &Path) -> Result<Vec<ExternalClip>, String> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| e.to_string())?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT Z_PK, ZFIRSTCOPIEDAT, ZLASTCOPIEDAT, ZPIN, ZAPPLICATION, ZTITLE
+             FROM ZHISTORYITEM
+             ORDER BY ZLASTCOPIEDAT DESC",
+        )
+        .map_err(|e| format!("Not a Maccy database: {}", e))?;
+
+    let items = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<f64>>(1)?,
+                row.get::<_, Option<f64>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+                row.get::<_, Option<String>>(5)?,
+            ))
+        })
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+        .map_err(|e| e.to_string())?;
+
+    let mut content_stmt = conn
+        .prepare("SELECT ZTYPE, ZVALUE FROM ZHISTORYITEMCONTENT WHERE ZITEM = ?1")
+        .map_err(|e| e.to_string())?;
+
+    let mut clips = Vec::new();
+    for (id, first_copied, last_copied, pin, application, title) in items {
+        let contents = content_stmt
+            .query_map(params![id], |row| {
+                Ok((
+                    row.get::<_, Option<String>>(0)?.unwrap_or_default(),
+                    row.get::<_, Option<Vec<u8>>>(1)?.unwrap_or_default(),
+                ))
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(|e| e.to_string())?;
+
+        let content = match clip_content(&contents) {
+            Some(content) => content,
+            None => match title.filter(|t| !t.trim().is_empty()) {
+                Some(title) => ExternalContent::Text(title),
+                None => continue,
+            },
+        };
+
+        let mut clip = ExternalClip::new(content);
+        clip.created_at = first_copied.and_then(timestamp);
+        clip.last_used_at = last_copied.and_then(timestamp);
+        clip.pinned = pin.is_some_and(|pin| !pin.is_empty());
+        clip.source_app = application.filter(|app| !app.is_empty());
+        clips.push(clip);
+    }
+
+    Ok(clips)
+}
+
+fn clip_content(contents: &[(String, Vec<u8>)]) -> Option<ExternalContent> {
+    let values = |types: &[&str]| {
+        contents
+            .iter()
+            .filter(|(t, _)| types.contains(&t.as_str()))
+            .map(|(_, value)| String::from_utf8_lossy(value).to_string())
+            .collect::<Vec<_>>()
+    };
+
+    let files = values(&["public.file-url", "NSFilenamesPboardType"]);
+    if !files.is_empty() {
+        return Some(ExternalContent::Files(
+            files.iter().map(|url| uri_to_path(url.trim())).collect(),
+        ));
+    }
+
+    let text = values(&["public.utf8-plain-text", "NSStringPboardType"])
+        .into_iter()
+        .next();
+    let html = values(&["public.html", "Apple HTML pasteboard type"])
+        .into_iter()
+        .next();
+    if let Some(content) = text_content(text, html) {
+        return Some(content);
+    }
+
+    contents
+        .iter()
+        .find(|(t, _)| t == "public.png" || t == "public.jpeg")
+        .map(|(_, data)| ExternalContent::Image(data.clone()))
+}
+
+fn timestamp(secs: f64) -> Option<DateTime<Utc>> {
+    DateTime::<Utc>::from_timestamp(CORE_DATA_EPOCH + secs as i64, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixture;
+    use super::*;
+
+    #[test]
+    fn reads_clips_with_pins_apps_and_timestamps() {
+        let clips = read(&fixture("Storage.sqlite")).unwrap();
+        assert_eq!(clips.len(), 3);
+
+        let link = &clips[0];
+        assert_eq!(
+            link.content,
+            ExternalContent::Rich {
+                html: "<a href=\"https://example.com\">link</a>".to_string(),
+                plain: "link".to_string(),
+            }
+        );
+        assert!(link.pinned);
+        assert_eq!(link.source_app.as_deref(), Some("com.apple.Safari"));
+        assert_eq!(link.created_at, DateTime::from_timestamp(1_700_000_000, 0));
+        assert_eq!(
+            link.last_used_at,
+            DateTime::from_timestamp(1_700_003_600, 0)
+        );
+
+        assert_eq!(
+            clips[1].content,
+            ExternalContent::Files(vec!["/Users/me/notes old.md".to_string()])
+        );
+        assert!(!clips[1].pinned);
+
+        // An empty pin key and no content
+        assert_eq!(
+            clips[2].content,
+            ExternalContent::Text("title only".to_string())
+        );
+        assert!(!clips[2].pinned);
+        assert_eq!(clips[2].source_app, None);
+    }
+}
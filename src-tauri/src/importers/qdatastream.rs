@@ -0,0 +1,98 @@
+//! Reader for the subset of Qt's `QDataStream` serialization used by CopyQ
+//! and Klipper. Values are big-endian; strings are UTF-16.
+
+pub struct QDataStream<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+/// Length marking a null `QString` or `QByteArray`
+const NULL_LENGTH: u32 = 0xFFFF_FFFF;
+
+impl<'a> QDataStream<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        QDataStream { data, pos: 0 }
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    /// The data that has not been read yet
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.data[self.pos.min(self.data.len())..]
+    }
+
+    pub fn skip(&mut self, len: usize) -> Result<(), String> {
+        self.read_raw(len).map(|_| ())
+    }
+
+    fn read_raw(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(|| "Unexpected end of data".to_string())?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, String> {
+        Ok(self.read_raw(1)?[0] != 0)
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, String> {
+        let bytes = self.read_raw(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn read_i32(&mut self) -> Result<i32, String> {
+        self.read_u32().map(|value| value as i32)
+    }
+
+    /// A `QByteArray`; null arrays are returned as empty
+    pub fn read_byte_array(&mut self) -> Result<Vec<u8>, String> {
+        let len = self.read_u32()?;
+        if len == NULL_LENGTH {
+            return Ok(Vec::new());
+        }
+        Ok(self.read_raw(len as usize)?.to_vec())
+    }
+
+    /// A `QString`; null strings are returned as empty
+    pub fn read_string(&mut self) -> Result<String, String> {
+        let len = self.read_u32()?;
+        if len == NULL_LENGTH {
+            return Ok(String::new());
+        }
+
+        let units: Vec<u16> = self
+            .read_raw(len as usize)?
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        Ok(String::from_utf16_lossy(&units))
+    }
+
+    /// A `QList<QString>` or `QStringList`
+    pub fn read_string_list(&mut self) -> Result<Vec<String>, String> {
+        let count = self.read_u32()?;
+        (0..count).map(|_| self.read_string()).collect()
+    }
+}
+
+/// Undo Qt's `qCompress`: a big-endian length followed by a zlib stream
+pub fn q_uncompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    use std::io::Read;
+
+    if data.len() < 4 {
+        return Err("Invalid compressed data".to_string());
+    }
+
+    let mut decoded = Vec::new();
+    flate2::read::ZlibDecoder::new(&data[4..])
+        .read_to_end(&mut decoded)
+        .map_err(|e| format!("Invalid compressed data: {}", e))?;
+    Ok(decoded)
+}
@@ -1,19 +1,438 @@
-pub struct InputPanelManager;
+//! Input panel: typing the trigger (e.g. "/v") in any application opens a
+//! small popup near the caret. Text typed afterwards filters the history and
+//! the selection modifier plus 1-9 pastes a clip in place of trigger and query.
+
+use crate::clipboard_writer;
+use crate::key_listener::KeyListener;
+use rdev::{Event, EventType, Key};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager, PhysicalPosition};
+
+const PANEL_LABEL: &str = "input-panel";
+
+/// Gap between the caret and the top of the panel
+const CARET_OFFSET: f64 = 8.0;
+
+const RELEASE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Default)]
+struct PanelState {
+    enabled: bool,
+    trigger: String,
+    selection_modifier: String,
+    // Recently typed characters, used to spot the trigger
+    typed: String,
+    // Panel is open and typing goes into the query
+    active: bool,
+    query: String,
+    ctrl: bool,
+    alt: bool,
+    meta: bool,
+}
+
+impl PanelState {
+    fn reset(&mut self) {
+        self.typed.clear();
+        self.active = false;
+        self.query.clear();
+    }
+
+    fn selection_modifier_down(&self) -> bool {
+        if self.selection_modifier == "alt" {
+            self.alt
+        } else {
+            self.ctrl
+        }
+    }
+}
+
+/// What the listener has to do after a key event updated the state
+enum PanelAction {
+    Show { trigger_len: usize },
+    Search(String),
+    Select { index: usize, search_len: usize },
+    Hide,
+}
+
+pub struct InputPanelManager {
+    state: Arc<Mutex<PanelState>>,
+    app_handle: Arc<Mutex<Option<tauri::AppHandle>>>,
+    key_listener: Arc<KeyListener>,
+    listener_started: AtomicBool,
+}
 
 impl InputPanelManager {
-    pub fn new() -> Self {
-        InputPanelManager
+    pub fn new(key_listener: Arc<KeyListener>) -> Self {
+        InputPanelManager {
+            state: Arc::new(Mutex::new(PanelState {
+                trigger: "/v".to_string(),
+                selection_modifier: "ctrl".to_string(),
+                ..PanelState::default()
+            })),
+            app_handle: Arc::new(Mutex::new(None)),
+            key_listener,
+            listener_started: AtomicBool::new(false),
+        }
     }
 
     #[cfg(target_os = "linux")]
     #[allow(dead_code)]
     pub fn set_display_server(&self, _server: String) {}
 
-    pub fn set_app_handle(&self, _handle: tauri::AppHandle) {}
+    pub fn set_app_handle(&self, handle: tauri::AppHandle) {
+        if let Ok(mut app_handle) = self.app_handle.lock() {
+            *app_handle = Some(handle);
+        }
+    }
+
+    /// Enable or disable trigger detection. The keystroke listener is started
+    /// on first use and keeps running; while disabled it ignores all events.
+    pub fn set_enabled(&self, enabled: bool) {
+        if let Ok(mut state) = self.state.lock() {
+            state.enabled = enabled;
+            state.reset();
+        }
+
+        if !enabled {
+            self.hide_panel();
+            return;
+        }
+
+        if !self.listener_started.swap(true, Ordering::SeqCst) {
+            self.start_listener();
+        }
+    }
+
+    pub fn set_trigger(&self, trigger: String) {
+        if let Ok(mut state) = self.state.lock() {
+            state.trigger = trigger;
+            state.reset();
+        }
+    }
+
+    pub fn set_selection_modifier(&self, modifier: String) {
+        if let Ok(mut state) = self.state.lock() {
+            state.selection_modifier = modifier.to_lowercase();
+        }
+    }
+
+    /// Close the panel before the selected clip is pasted, so the simulated
+    /// backspaces and paste keys are not taken as query input.
+    pub fn finish_selection(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.reset();
+        }
+        self.hide_panel();
+    }
+
+    /// Wait until the selection modifier is up, so the backspaces and paste
+    /// keys simulated for a selection are not combined with it. If it is still
+    /// held after `timeout`, it is released synthetically.
+    pub fn wait_for_selection_modifier_release(&self, timeout: Duration) -> Result<(), String> {
+        let started = Instant::now();
+
+        loop {
+            let (down, modifier) = {
+                let state = self.state.lock().map_err(|e| e.to_string())?;
+                (
+                    state.selection_modifier_down(),
+                    state.selection_modifier.clone(),
+                )
+            };
+            if !down {
+                return Ok(());
+            }
+
+            if started.elapsed() >= timeout {
+                let keys = if modifier == "alt" {
+                    [Key::Alt, Key::AltGr]
+                } else {
+                    [Key::ControlLeft, Key::ControlRight]
+                };
+                return clipboard_writer::release_modifiers(&keys);
+            }
+            thread::sleep(RELEASE_POLL_INTERVAL);
+        }
+    }
+
+    fn hide_panel(&self) {
+        let app_handle = self.app_handle.lock().ok().and_then(|h| h.clone());
+        if let Some(app) = app_handle {
+            hide_panel_window(&app);
+        }
+    }
+
+    fn start_listener(&self) {
+        let state = self.state.clone();
+        let app_handle = self.app_handle.clone();
+
+        self.key_listener.add_handler(move |event| {
+            let action = match state.lock() {
+                Ok(mut state) => handle_event(&mut state, event),
+                Err(_) => None,
+            };
+
+            let app = match (action.is_some(), app_handle.lock()) {
+                (true, Ok(app)) => app.clone(),
+                _ => None,
+            };
+
+            if let (Some(action), Some(app)) = (action, app) {
+                let selection_modifier = state
+                    .lock()
+                    .map(|s| s.selection_modifier.clone())
+                    .unwrap_or_default();
+                apply_action(&app, action, &selection_modifier);
+            }
+        });
+    }
+}
+
+/// Update the panel state for a global input event
+fn handle_event(state: &mut PanelState, event: &Event) -> Option<PanelAction> {
+    if !state.enabled {
+        return None;
+    }
+
+    let key = match event.event_type {
+        EventType::KeyPress(key) => key,
+        EventType::KeyRelease(key) => {
+            set_modifier(state, key, false);
+            return None;
+        }
+        // Clicking moves the caret, so the typed text no longer matches
+        EventType::ButtonPress(_) => {
+            let was_active = state.active;
+            state.reset();
+            return was_active.then_some(PanelAction::Hide);
+        }
+        _ => return None,
+    };
+
+    if set_modifier(state, key, true) {
+        return None;
+    }
+
+    if state.active {
+        return handle_active_key(state, key, event);
+    }
+
+    match key {
+        Key::Backspace => {
+            state.typed.pop();
+            None
+        }
+        _ => match typed_text(state, event) {
+            Some(text) => {
+                state.typed.push_str(&text);
+
+                // Keep only as much as the trigger needs
+                let keep = state.trigger.chars().count();
+                let skip = state.typed.chars().count().saturating_sub(keep);
+                state.typed = state.typed.chars().skip(skip).collect();
+
+                if !state.trigger.is_empty() && state.typed == state.trigger {
+                    state.typed.clear();
+                    state.active = true;
+                    state.query.clear();
+                    Some(PanelAction::Show {
+                        trigger_len: state.trigger.chars().count(),
+                    })
+                } else {
+                    None
+                }
+            }
+            None => {
+                state.typed.clear();
+                None
+            }
+        },
+    }
+}
+
+fn handle_active_key(state: &mut PanelState, key: Key, event: &Event) -> Option<PanelAction> {
+    if state.selection_modifier_down() {
+        if let Some(number) = digit(key) {
+            return Some(PanelAction::Select {
+                index: number - 1,
+                search_len: state.query.chars().count(),
+            });
+        }
+    }
+
+    match key {
+        Key::Escape
+        | Key::Return
+        | Key::Tab
+        | Key::UpArrow
+        | Key::DownArrow
+        | Key::LeftArrow
+        | Key::RightArrow => {
+            state.reset();
+            Some(PanelAction::Hide)
+        }
+        Key::Backspace => {
+            // Deleting into the trigger closes the panel
+            if state.query.pop().is_none() {
+                state.reset();
+                return Some(PanelAction::Hide);
+            }
+            Some(PanelAction::Search(state.query.clone()))
+        }
+        _ => typed_text(state, event).map(|text| {
+            state.query.push_str(&text);
+            PanelAction::Search(state.query.clone())
+        }),
+    }
+}
+
+/// Track modifier keys. Returns true if `key` is a modifier.
+fn set_modifier(state: &mut PanelState, key: Key, down: bool) -> bool {
+    match key {
+        Key::ControlLeft | Key::ControlRight => state.ctrl = down,
+        Key::Alt | Key::AltGr => state.alt = down,
+        Key::MetaLeft | Key::MetaRight => state.meta = down,
+        Key::ShiftLeft | Key::ShiftRight | Key::CapsLock => {}
+        _ => return false,
+    }
+    true
+}
 
-    pub fn set_enabled(&self, _enabled: bool) {}
+/// Printable text produced by a key press, ignoring shortcuts
+fn typed_text(state: &PanelState, event: &Event) -> Option<String> {
+    if state.ctrl || state.meta {
+        return None;
+    }
 
-    pub fn set_trigger(&self, _trigger: String) {}
+    event
+        .name
+        .as_ref()
+        .filter(|name| !name.is_empty() && !name.chars().any(char::is_control))
+        .cloned()
+}
+
+fn digit(key: Key) -> Option<usize> {
+    match key {
+        Key::Num1 | Key::Kp1 => Some(1),
+        Key::Num2 | Key::Kp2 => Some(2),
+        Key::Num3 | Key::Kp3 => Some(3),
+        Key::Num4 | Key::Kp4 => Some(4),
+        Key::Num5 | Key::Kp5 => Some(5),
+        Key::Num6 | Key::Kp6 => Some(6),
+        Key::Num7 | Key::Kp7 => Some(7),
+        Key::Num8 | Key::Kp8 => Some(8),
+        Key::Num9 | Key::Kp9 => Some(9),
+        _ => None,
+    }
+}
+
+fn apply_action(app: &tauri::AppHandle, action: PanelAction, selection_modifier: &str) {
+    match action {
+        PanelAction::Show { trigger_len } => {
+            let _ = app.emit(
+                "input-panel-show",
+                serde_json::json!({
+                    "query": "",
+                    "triggerLen": trigger_len,
+                    "selectionModifier": selection_modifier,
+                }),
+            );
+            show_panel_window(app);
+        }
+        PanelAction::Search(query) => {
+            let _ = app.emit("input-panel-search", query);
+        }
+        PanelAction::Select { index, search_len } => {
+            let _ = app.emit(
+                "input-panel-select-index",
+                serde_json::json!({
+                    "index": index,
+                    "searchLen": search_len,
+                }),
+            );
+        }
+        PanelAction::Hide => {
+            let _ = app.emit("input-panel-hide", ());
+            hide_panel_window(app);
+        }
+    }
+}
+
+/// Show the panel below the caret, or below the mouse cursor where the caret
+/// position is unavailable, keeping it on the current monitor
+fn show_panel_window(app: &tauri::AppHandle) {
+    let window = match app.get_webview_window(PANEL_LABEL) {
+        Some(window) => window,
+        None => return,
+    };
+
+    let anchor = caret_position().or_else(|| app.cursor_position().ok().map(|p| (p.x, p.y)));
+
+    if let Some((x, y)) = anchor {
+        let mut position = PhysicalPosition::new(x, y + CARET_OFFSET);
+
+        if let (Ok(Some(monitor)), Ok(size)) = (app.monitor_from_point(x, y), window.outer_size()) {
+            let origin = monitor.position();
+            let bounds = monitor.size();
+            let max_x = (origin.x + bounds.width as i32 - size.width as i32) as f64;
+            let max_y = (origin.y + bounds.height as i32 - size.height as i32) as f64;
+
+            position.x = position.x.min(max_x).max(origin.x as f64);
+            if position.y > max_y {
+                // Not enough room below, open above the caret instead
+                position.y = y - size.height as f64 - CARET_OFFSET;
+            }
+        }
+
+        let _ = window.set_position(position);
+    }
+
+    let _ = window.set_always_on_top(true);
+    let _ = window.show();
+}
+
+fn hide_panel_window(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_webview_window(PANEL_LABEL) {
+        let _ = window.hide();
+    }
+}
+
+// ==================== Windows Implementations ====================
+
+/// Screen position of the text caret in the foreground window (Windows)
+#[cfg(windows)]
+fn caret_position() -> Option<(f64, f64)> {
+    use std::mem::size_of;
+    use windows_sys::Win32::Foundation::POINT;
+    use windows_sys::Win32::Graphics::Gdi::ClientToScreen;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{GetGUIThreadInfo, GUITHREADINFO};
+
+    unsafe {
+        let mut info: GUITHREADINFO = std::mem::zeroed();
+        info.cbSize = size_of::<GUITHREADINFO>() as u32;
+
+        if GetGUIThreadInfo(0, &mut info) == 0 || info.hwndCaret.is_null() {
+            return None;
+        }
+
+        let mut point = POINT {
+            x: info.rcCaret.left,
+            y: info.rcCaret.bottom,
+        };
+        if ClientToScreen(info.hwndCaret, &mut point) == 0 {
+            return None;
+        }
+
+        Some((point.x as f64, point.y as f64))
+    }
+}
 
-    pub fn set_selection_modifier(&self, _modifier: String) {}
+/// The caret position is not exposed without accessibility APIs, the panel
+/// opens at the mouse cursor instead
+#[cfg(not(windows))]
+fn caret_position() -> Option<(f64, f64)> {
+    None
 }
@@ -0,0 +1,682 @@
+//! Local JSON-RPC endpoint for other applications (launchers, editor
+//! extensions): a Unix domain socket in the app dir, or a named pipe on
+//! Windows. Requests and responses are JSON-RPC 2.0 objects, one per line.
+//!
+//! A connection must first call `authenticate` with the token stored in the
+//! app dir. It then receives a `clipboard-updated` notification whenever the
+//! history changes.
+
+use crate::clipboard::ClipboardManager;
+use crate::clipboard_writer;
+use crate::db::get_app_dir;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{Emitter, Listener};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+
+/// Largest request accepted, to bound memory use per connection
+const MAX_REQUEST_SIZE: u64 = 64 * 1024 * 1024;
+
+const DEFAULT_SEARCH_LIMIT: i64 = 20;
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const APP_ERROR: i64 = -32000;
+const NOT_AUTHENTICATED: i64 = -32001;
+
+/// Where clients connect and find the token
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IpcInfo {
+    pub enabled: bool,
+    pub endpoint: String,
+    pub token_path: String,
+}
+
+struct Listening {
+    task: JoinHandle<()>,
+    running: Arc<AtomicBool>,
+}
+
+pub struct IpcServer {
+    clipboard_manager: Arc<ClipboardManager>,
+    enabled: AtomicBool,
+    started: AtomicBool,
+    app_handle: Mutex<Option<tauri::AppHandle>>,
+    listening: Mutex<Option<Listening>>,
+    /// Outgoing queues of authenticated connections
+    subscribers: Mutex<Vec<mpsc::UnboundedSender<String>>>,
+}
+
+impl IpcServer {
+    pub fn new(clipboard_manager: Arc<ClipboardManager>, enabled: bool) -> Self {
+        Self {
+            clipboard_manager,
+            enabled: AtomicBool::new(enabled),
+            started: AtomicBool::new(false),
+            app_handle: Mutex::new(None),
+            listening: Mutex::new(None),
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Forward history changes to clients and start listening if enabled
+    pub fn start(self: &Arc<Self>, app: tauri::AppHandle) {
+        if self.started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let server = self.clone();
+        app.listen_any("clipboard-updated", move |_| {
+            server.notify("clipboard-updated")
+        });
+        if let Ok(mut app_handle) = self.app_handle.lock() {
+            *app_handle = Some(app);
+        }
+
+        if self.enabled.load(Ordering::SeqCst) {
+            self.start_listener();
+        }
+    }
+
+    pub fn set_enabled(self: &Arc<Self>, enabled: bool) {
+        let previous = self.enabled.swap(enabled, Ordering::SeqCst);
+
+        // Before `start`, the listener is started there
+        if previous != enabled && self.started.load(Ordering::SeqCst) {
+            if enabled {
+                self.start_listener();
+            } else {
+                self.stop_listener();
+            }
+        }
+    }
+
+    pub fn info(&self) -> IpcInfo {
+        IpcInfo {
+            enabled: self.enabled.load(Ordering::SeqCst),
+            endpoint: endpoint(),
+            token_path: token_path().to_string_lossy().to_string(),
+        }
+    }
+
+    fn start_listener(self: &Arc<Self>) {
+        let token = match load_token() {
+            Ok(token) => token,
+            Err(e) => {
+                eprintln!("Failed to start the local API: {}", e);
+                return;
+            }
+        };
+
+        let running = Arc::new(AtomicBool::new(true));
+        let server = self.clone();
+        let task = tauri::async_runtime::spawn(accept_loop(server, token, running.clone()));
+        if let Ok(mut listening) = self.listening.lock() {
+            if let Some(previous) = listening.replace(Listening { task, running }) {
+                previous.task.abort();
+            }
+        }
+    }
+
+    /// Stop accepting connections; open ones are closed at their next request
+    fn stop_listener(&self) {
+        if let Some(listening) = self.listening.lock().ok().and_then(|mut l| l.take()) {
+            listening.running.store(false, Ordering::SeqCst);
+            listening.task.abort();
+        }
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.clear();
+        }
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(socket_path());
+    }
+
+    fn notify(&self, method: &str) {
+        let notification = json!({ "jsonrpc": "2.0", "method": method }).to_string();
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|subscriber| subscriber.send(notification.clone()).is_ok());
+        }
+    }
+
+    fn emit(&self, event: &str) {
+        if let Some(app) = self.app_handle.lock().ok().and_then(|a| a.clone()) {
+            let _ = app.emit(event, ());
+        }
+    }
+
+    async fn serve<S>(self: Arc<Self>, stream: S, token: Arc<String>, running: Arc<AtomicBool>)
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut reader = BufReader::new(reader);
+
+        // Responses and notifications share one queue so lines never interleave
+        let (sender, mut outgoing) = mpsc::unbounded_channel::<String>();
+        tauri::async_runtime::spawn(async move {
+            while let Some(mut line) = outgoing.recv().await {
+                line.push('\n');
+                if writer.write_all(line.as_bytes()).await.is_err() {
+                    break;
+                }
+                let _ = writer.flush().await;
+            }
+        });
+
+        let mut authenticated = false;
+        loop {
+            let mut line = Vec::new();
+            match (&mut reader)
+                .take(MAX_REQUEST_SIZE)
+                .read_until(b'\n', &mut line)
+                .await
+            {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            if line.last() != Some(&b'\n') && line.len() as u64 >= MAX_REQUEST_SIZE {
+                let _ = sender.send(error_response(
+                    Value::Null,
+                    INVALID_REQUEST,
+                    "Request is too large",
+                ));
+                break;
+            }
+            if !running.load(Ordering::SeqCst) {
+                break;
+            }
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+
+            let request = match serde_json::from_slice::<Value>(&line) {
+                Ok(value) => value,
+                Err(e) => {
+                    let _ = sender.send(error_response(Value::Null, PARSE_ERROR, &e.to_string()));
+                    continue;
+                }
+            };
+            let request = match serde_json::from_value::<Request>(request) {
+                Ok(request) if request.jsonrpc == "2.0" => request,
+                _ => {
+                    let _ = sender.send(error_response(
+                        Value::Null,
+                        INVALID_REQUEST,
+                        "Expected a JSON-RPC 2.0 request object",
+                    ));
+                    continue;
+                }
+            };
+
+            let result = if request.method == "authenticate" {
+                let result = authenticate(&token, request.params);
+                if result.is_ok() && !authenticated {
+                    authenticated = true;
+                    if let Ok(mut subscribers) = self.subscribers.lock() {
+                        subscribers.push(sender.clone());
+                    }
+                }
+                result
+            } else if !authenticated {
+                Err((NOT_AUTHENTICATED, "Call authenticate first".to_string()))
+            } else {
+                let server = self.clone();
+                tauri::async_runtime::spawn_blocking(move || {
+                    server.call(&request.method, request.params)
+                })
+                .await
+                .unwrap_or_else(|e| Err((APP_ERROR, e.to_string())))
+            };
+
+            // Requests without an id are notifications and get no response
+            let Some(id) = request.id else {
+                continue;
+            };
+            let response = match result {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string(),
+                Err((code, message)) => error_response(id, code, &message),
+            };
+            if sender.send(response).is_err() {
+                break;
+            }
+        }
+
+        // Dropping the last sender ends the writer
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|subscriber| !subscriber.same_channel(&sender));
+        }
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value, (i64, String)> {
+        let manager = &self.clipboard_manager;
+
+        let result = match method {
+            "search" => {
+                let params: SearchParams = parse_params(params)?;
+                let limit = params.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).max(1);
+                let clips = if params.query.trim().is_empty() {
+                    manager.get_recent_clips(limit)
+                } else {
+                    manager.search_clips(&params.query, limit)
+                };
+                to_value(clips.map_err(app_error)?)
+            }
+            "get" => {
+                let params: IdParams = parse_params(params)?;
+                to_value(manager.get_clip(&params.id).map_err(app_error)?)
+            }
+            "add" => {
+                let params: AddParams = parse_params(params)?;
+                if params.text.trim().is_empty() {
+                    return Err((INVALID_PARAMS, "text is empty".to_string()));
+                }
+                let source_app = params.source_app.unwrap_or_else(|| "Local API".to_string());
+                let clip = manager
+                    .save_text_clip(params.text, source_app, true)
+                    .map_err(app_error)?;
+                if !params.tags.is_empty() {
+                    let mut tags = clip.tags.clone();
+                    for tag in params.tags {
+                        if !tags.contains(&tag) {
+                            tags.push(tag);
+                        }
+                    }
+                    manager.update_tags(&clip.id, tags).map_err(app_error)?;
+                }
+                if params.pinned {
+                    manager.toggle_pin(&clip.id, true).map_err(app_error)?;
+                }
+                self.emit("clipboard-updated");
+                to_value(manager.get_clip(&clip.id).map_err(app_error)?)
+            }
+            "pin" => {
+                let params: PinParams = parse_params(params)?;
+                manager.get_clip(&params.id).map_err(app_error)?;
+                manager
+                    .toggle_pin(&params.id, params.pinned)
+                    .map_err(app_error)?;
+                self.emit("clipboard-updated");
+                to_value(manager.get_clip(&params.id).map_err(app_error)?)
+            }
+            "tag" => {
+                let params: TagParams = parse_params(params)?;
+                manager.get_clip(&params.id).map_err(app_error)?;
+                manager
+                    .update_tags(&params.id, params.tags)
+                    .map_err(app_error)?;
+                self.emit("clipboard-updated");
+                to_value(manager.get_clip(&params.id).map_err(app_error)?)
+            }
+            "paste" => {
+                let params: IdParams = parse_params(params)?;
+                manager.write_clip(&params.id).map_err(app_error)?;
+                // Give the clipboard a moment before pasting
+                std::thread::sleep(Duration::from_millis(50));
+                clipboard_writer::simulate_paste().map_err(app_error)?;
+                manager.record_clip_use(&params.id).map_err(app_error)?;
+                self.emit("clipboard-updated");
+                Value::Null
+            }
+            _ => return Err((METHOD_NOT_FOUND, format!("Unknown method {}", method))),
+        };
+
+        Ok(result)
+    }
+}
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct AuthenticateParams {
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    #[serde(default)]
+    query: String,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct IdParams {
+    id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AddParams {
+    text: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    pinned: bool,
+    source_app: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct PinParams {
+    id: String,
+    #[serde(default = "default_true")]
+    pinned: bool,
+}
+
+#[derive(Deserialize)]
+struct TagParams {
+    id: String,
+    tags: Vec<String>,
+}
+
+fn parse_params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, (i64, String)> {
+    serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))
+}
+
+fn to_value<T: Serialize>(value: T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+fn app_error(message: String) -> (i64, String) {
+    (APP_ERROR, message)
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+    .to_string()
+}
+
+fn authenticate(token: &str, params: Value) -> Result<Value, (i64, String)> {
+    let params: AuthenticateParams = parse_params(params)?;
+    let matches = params.token.len() == token.len()
+        && params
+            .token
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0;
+    if !matches {
+        return Err((NOT_AUTHENTICATED, "Invalid token".to_string()));
+    }
+    Ok(json!({ "authenticated": true }))
+}
+
+fn token_path() -> PathBuf {
+    get_app_dir().join("ipc_token")
+}
+
+/// Read the token, creating it on first use. Only the current user can read it.
+fn load_token() -> Result<Arc<String>, String> {
+    let path = token_path();
+    if let Ok(token) = std::fs::read_to_string(&path) {
+        let token = token.trim();
+        if !token.is_empty() {
+            return Ok(Arc::new(token.to_string()));
+        }
+    }
+
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|e| e.to_string())?;
+    let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    std::io::Write::write_all(
+        &mut options
+            .open(&path)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?,
+        token.as_bytes(),
+    )
+    .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    Ok(Arc::new(token))
+}
+
+#[cfg(unix)]
+fn socket_path() -> PathBuf {
+    get_app_dir().join("fat-clip.sock")
+}
+
+#[cfg(unix)]
+fn endpoint() -> String {
+    socket_path().to_string_lossy().to_string()
+}
+
+#[cfg(windows)]
+fn endpoint() -> String {
+    let user = std::env::var("USERNAME").unwrap_or_default();
+    format!(r"\\.\pipe\fat-clip-{}", user)
+}
+
+#[cfg(unix)]
+async fn accept_loop(server: Arc<IpcServer>, token: Arc<String>, running: Arc<AtomicBool>) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = socket_path();
+    // A socket left behind by a previous run would make bind fail
+    let _ = std::fs::remove_file(&path);
+    let listener = match tokio::net::UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to listen on {}: {}", path.display(), e);
+            return;
+        }
+    };
+    let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600));
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let server = server.clone();
+                tauri::async_runtime::spawn(server.serve(stream, token.clone(), running.clone()));
+            }
+            Err(e) => {
+                eprintln!("Failed to accept local API connection: {}", e);
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+async fn accept_loop(server: Arc<IpcServer>, token: Arc<String>, running: Arc<AtomicBool>) {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let name = endpoint();
+    let mut pipe = match ServerOptions::new()
+        .first_pipe_instance(true)
+        .reject_remote_clients(true)
+        .create(&name)
+    {
+        Ok(pipe) => pipe,
+        Err(e) => {
+            eprintln!("Failed to create {}: {}", name, e);
+            return;
+        }
+    };
+
+    loop {
+        if let Err(e) = pipe.connect().await {
+            eprintln!("Failed to accept local API connection: {}", e);
+            continue;
+        }
+
+        // Create the next instance before serving so clients never find no pipe
+        let next = match ServerOptions::new()
+            .reject_remote_clients(true)
+            .create(&name)
+        {
+            Ok(next) => next,
+            Err(e) => {
+                eprintln!("Failed to create {}: {}", name, e);
+                return;
+            }
+        };
+        let connected = std::mem::replace(&mut pipe, next);
+        let server = server.clone();
+        tauri::async_runtime::spawn(server.serve(connected, token.clone(), running.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use tokio::io::{AsyncBufRead, DuplexStream};
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    /// A connection to a server over an in-memory stream
+    fn connect() -> (tempfile::TempDir, BufReader<DuplexStream>) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        let manager = Arc::new(ClipboardManager::new(Arc::new(Mutex::new(db))));
+        let server = Arc::new(IpcServer::new(manager, true));
+
+        let (client, stream) = tokio::io::duplex(64 * 1024);
+        tauri::async_runtime::spawn(server.serve(
+            stream,
+            Arc::new(TOKEN.to_string()),
+            Arc::new(AtomicBool::new(true)),
+        ));
+        (dir, BufReader::new(client))
+    }
+
+    async fn send(client: &mut BufReader<DuplexStream>, request: Value) {
+        let line = format!("{}\n", request);
+        client.get_mut().write_all(line.as_bytes()).await.unwrap();
+    }
+
+    async fn receive(client: &mut (impl AsyncBufRead + Unpin)) -> Option<Value> {
+        let mut line = String::new();
+        match client.read_line(&mut line).await.unwrap() {
+            0 => None,
+            _ => Some(serde_json::from_str(&line).unwrap()),
+        }
+    }
+
+    fn error_code(response: &Value) -> i64 {
+        response["error"]["code"].as_i64().unwrap()
+    }
+
+    #[test]
+    fn tokens_must_match_exactly() {
+        assert!(authenticate(TOKEN, json!({ "token": TOKEN })).is_ok());
+        for wrong in [
+            "0123456789abcdeF",
+            "0123456789abcde",
+            "",
+            "0123456789abcdef0",
+        ] {
+            let error = authenticate(TOKEN, json!({ "token": wrong })).unwrap_err();
+            assert_eq!(error.0, NOT_AUTHENTICATED, "{}", wrong);
+        }
+        assert_eq!(
+            authenticate(TOKEN, json!({})).unwrap_err().0,
+            INVALID_PARAMS
+        );
+    }
+
+    #[test]
+    fn calls_need_authentication_and_notifications_get_no_response() {
+        tauri::async_runtime::block_on(async {
+            let (_dir, mut client) = connect();
+
+            send(&mut client, json!({ "jsonrpc": "2.0", "method": "search" })).await;
+            send(
+                &mut client,
+                json!({ "jsonrpc": "2.0", "id": 1, "method": "search", "params": {} }),
+            )
+            .await;
+            let response = receive(&mut client).await.unwrap();
+            assert_eq!(response["id"], 1);
+            assert_eq!(error_code(&response), NOT_AUTHENTICATED);
+
+            send(
+                &mut client,
+                json!({ "jsonrpc": "2.0", "id": 2, "method": "authenticate", "params": { "token": "wrong" } }),
+            )
+            .await;
+            assert_eq!(
+                error_code(&receive(&mut client).await.unwrap()),
+                NOT_AUTHENTICATED
+            );
+
+            // An authenticate notification still authenticates, silently
+            send(
+                &mut client,
+                json!({ "jsonrpc": "2.0", "method": "authenticate", "params": { "token": TOKEN } }),
+            )
+            .await;
+            send(
+                &mut client,
+                json!({ "jsonrpc": "2.0", "id": 3, "method": "search", "params": {} }),
+            )
+            .await;
+            let response = receive(&mut client).await.unwrap();
+            assert_eq!(response["id"], 3);
+            assert_eq!(response["result"], json!([]));
+
+            send(
+                &mut client,
+                json!({ "jsonrpc": "2.0", "id": 4, "method": "missing" }),
+            )
+            .await;
+            assert_eq!(
+                error_code(&receive(&mut client).await.unwrap()),
+                METHOD_NOT_FOUND
+            );
+        });
+    }
+
+    #[test]
+    fn oversize_requests_close_the_connection() {
+        tauri::async_runtime::block_on(async {
+            let (_dir, client) = connect();
+            let (mut reader, mut writer) = tokio::io::split(client.into_inner());
+
+            // Write from another task, the server stops reading part way
+            let chunk = vec![b'a'; 1024 * 1024];
+            let chunks = MAX_REQUEST_SIZE as usize / chunk.len() + 1;
+            tauri::async_runtime::spawn(async move {
+                for _ in 0..chunks {
+                    if writer.write_all(&chunk).await.is_err() {
+                        break;
+                    }
+                }
+            });
+
+            let mut reader = BufReader::new(&mut reader);
+            let response = receive(&mut reader).await.unwrap();
+            assert_eq!(error_code(&response), INVALID_REQUEST);
+            assert_eq!(response["error"]["message"], "Request is too large");
+            assert!(receive(&mut reader).await.is_none());
+        });
+    }
+}
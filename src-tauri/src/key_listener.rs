@@ -0,0 +1,54 @@
+//! The global keystroke listener. rdev supports only one listener per process,
+//! so the input panel and text expansion share this one and each get every
+//! event.
+
+use rdev::{listen, Event};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Handler = Box<dyn Fn(&Event) + Send>;
+
+pub struct KeyListener {
+    handlers: Arc<Mutex<Vec<Handler>>>,
+    started: AtomicBool,
+}
+
+impl KeyListener {
+    pub fn new() -> Self {
+        KeyListener {
+            handlers: Arc::new(Mutex::new(Vec::new())),
+            started: AtomicBool::new(false),
+        }
+    }
+
+    /// Pass every global input event to `handler`, starting the listener on
+    /// first use. Handlers run on the listener thread and must return quickly.
+    pub fn add_handler(&self, handler: impl Fn(&Event) + Send + 'static) {
+        if let Ok(mut handlers) = self.handlers.lock() {
+            handlers.push(Box::new(handler));
+        }
+
+        if !self.started.swap(true, Ordering::SeqCst) {
+            self.start();
+        }
+    }
+
+    fn start(&self) {
+        let handlers = self.handlers.clone();
+
+        thread::spawn(move || {
+            let result = listen(move |event| {
+                if let Ok(handlers) = handlers.lock() {
+                    for handler in handlers.iter() {
+                        handler(&event);
+                    }
+                }
+            });
+
+            if let Err(e) = result {
+                eprintln!("Failed to listen for keystrokes: {:?}", e);
+            }
+        });
+    }
+}
@@ -1,22 +1,54 @@
 use chrono::{NaiveDate, TimeZone, Utc};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 use tauri::{Emitter, Manager};
 use tauri_plugin_autostart::ManagerExt;
 use tauri_plugin_notification::NotificationExt;
 
+mod active_app;
+mod archive;
+mod auto_tag;
+mod backup;
+mod cli;
 mod clipboard;
 mod clipboard_monitor;
+mod clipboard_writer;
+mod collections;
 mod db;
+#[cfg(target_os = "linux")]
+mod dbus;
+mod hooks;
+mod importers;
 mod input_panel;
+mod ipc;
+mod key_listener;
+mod merge;
+mod paste_queue;
+mod peer_sync;
+mod plugins;
 mod settings;
+mod snippets;
+mod sync;
+mod text_expansion;
+mod transforms;
 mod tray;
+mod type_out;
 
+use backup::BackupManager;
 use clipboard::ClipboardManager;
 use clipboard_monitor::ClipboardMonitor;
+use collections::CollectionManager;
 use db::{ensure_app_dir, Database};
 use input_panel::InputPanelManager;
+use ipc::IpcServer;
+use key_listener::KeyListener;
+use peer_sync::PeerSync;
 use settings::Settings;
+use snippets::SnippetManager;
+use sync::SyncManager;
+use text_expansion::TextExpansionManager;
+use type_out::TypeOutManager;
 
 // State to share between commands
 pub struct AppState {
@@ -24,6 +56,14 @@ pub struct AppState {
     settings: Arc<Mutex<Settings>>,
     input_panel: Arc<InputPanelManager>,
     tray_manager: Arc<tray::TrayManager>,
+    type_out: Arc<TypeOutManager>,
+    snippets: Arc<SnippetManager>,
+    text_expansion: Arc<TextExpansionManager>,
+    collections: Arc<CollectionManager>,
+    backups: Arc<BackupManager>,
+    sync: Arc<SyncManager>,
+    peer_sync: Arc<PeerSync>,
+    ipc: Arc<IpcServer>,
 }
 
 // Commands
@@ -33,7 +73,9 @@ async fn save_text_clip(
     text: String,
     source_app: String,
 ) -> Result<db::ClipItem, String> {
-    state.clipboard_manager.save_text_clip(text, source_app)
+    state
+        .clipboard_manager
+        .save_text_clip(text, source_app, true)
 }
 
 #[tauri::command]
@@ -47,7 +89,7 @@ async fn save_image_clip(
 ) -> Result<db::ClipItem, String> {
     state
         .clipboard_manager
-        .save_image_clip(image_data, width, height, &format, source_app)
+        .save_image_clip(image_data, width, height, &format, source_app, true)
 }
 
 #[tauri::command]
@@ -58,9 +100,13 @@ async fn save_rich_text_clip(
     plain_text: String,
     source_app: String,
 ) -> Result<db::ClipItem, String> {
-    state
-        .clipboard_manager
-        .save_rich_text_clip(html_content, rtf_content, plain_text, source_app)
+    state.clipboard_manager.save_rich_text_clip(
+        html_content,
+        rtf_content,
+        plain_text,
+        source_app,
+        true,
+    )
 }
 
 #[tauri::command]
@@ -71,7 +117,7 @@ async fn save_file_clip(
 ) -> Result<db::ClipItem, String> {
     state
         .clipboard_manager
-        .save_file_clip(file_paths, source_app)
+        .save_file_clip(file_paths, source_app, true)
 }
 
 #[tauri::command]
@@ -114,6 +160,91 @@ async fn search_tags(
     db.search_tags(&query).map_err(|e| e.to_string())
 }
 
+/// Tags with the number of clips using them and when they were last used
+#[tauri::command]
+async fn get_tag_stats(state: tauri::State<'_, AppState>) -> Result<Vec<db::TagStats>, String> {
+    let db = state
+        .clipboard_manager
+        .db
+        .lock()
+        .map_err(|e| e.to_string())?;
+    db.get_tag_stats().map_err(|e| e.to_string())
+}
+
+/// Rename a tag on every clip. Renaming to an existing tag merges the two.
+#[tauri::command]
+async fn rename_tag(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    old_name: String,
+    new_name: String,
+) -> Result<usize, String> {
+    let new_name = new_name.trim();
+    if new_name.is_empty() {
+        return Err("Tag name cannot be empty".to_string());
+    }
+
+    let changed = {
+        let db = state
+            .clipboard_manager
+            .db
+            .lock()
+            .map_err(|e| e.to_string())?;
+        db.replace_tags(&[old_name], Some(new_name))
+            .map_err(|e| e.to_string())?
+    };
+
+    let _ = app.emit("clipboard-updated", ());
+    Ok(changed)
+}
+
+/// Replace all `sources` tags with `target` on every clip
+#[tauri::command]
+async fn merge_tags(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    sources: Vec<String>,
+    target: String,
+) -> Result<usize, String> {
+    let target = target.trim();
+    if target.is_empty() {
+        return Err("Tag name cannot be empty".to_string());
+    }
+
+    let changed = {
+        let db = state
+            .clipboard_manager
+            .db
+            .lock()
+            .map_err(|e| e.to_string())?;
+        db.replace_tags(&sources, Some(target))
+            .map_err(|e| e.to_string())?
+    };
+
+    let _ = app.emit("clipboard-updated", ());
+    Ok(changed)
+}
+
+/// Remove a tag from every clip
+#[tauri::command]
+async fn delete_tag(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    name: String,
+) -> Result<usize, String> {
+    let changed = {
+        let db = state
+            .clipboard_manager
+            .db
+            .lock()
+            .map_err(|e| e.to_string())?;
+        db.replace_tags(&[name], None).map_err(|e| e.to_string())?
+    };
+
+    let _ = app.emit("clipboard-updated", ());
+    Ok(changed)
+}
+
 #[tauri::command]
 async fn update_clip_tags(
     state: tauri::State<'_, AppState>,
@@ -138,39 +269,70 @@ async fn delete_clip(state: tauri::State<'_, AppState>, id: String) -> Result<()
 }
 
 #[tauri::command]
-async fn write_to_clipboard(_app: tauri::AppHandle, content: String) -> Result<(), String> {
+async fn write_to_clipboard(
+    _app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    content: String,
+    clip_id: Option<String>,
+) -> Result<(), String> {
     use arboard::Clipboard;
 
     let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
+    state
+        .clipboard_manager
+        .mark_self_write(clip_id, Some(&content));
     clipboard.set_text(content).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 async fn write_image_to_clipboard(
     _app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
     image_data: Vec<u8>,
+    clip_id: Option<String>,
 ) -> Result<(), String> {
-    use arboard::{Clipboard, ImageData};
-    use image::ImageReader;
-    use std::io::Cursor;
+    state.clipboard_manager.mark_self_write(clip_id, None);
+    clipboard_writer::write_image(&image_data)
+}
 
-    // Decode PNG to get raw RGBA data
-    let reader = ImageReader::new(Cursor::new(&image_data))
-        .with_guessed_format()
-        .map_err(|e| e.to_string())?;
+/// Write a rich text clip back with HTML, RTF (where supported) and plain text
+#[tauri::command]
+async fn write_rich_clip_to_clipboard(
+    state: tauri::State<'_, AppState>,
+    clip_id: String,
+) -> Result<(), String> {
+    state.clipboard_manager.write_rich_clip(&clip_id)
+}
 
-    let img = reader.decode().map_err(|e| e.to_string())?;
-    let rgba = img.to_rgba8();
-    let (width, height) = rgba.dimensions();
+/// Write any text-like clip back to the clipboard with formatting stripped
+#[tauri::command]
+async fn write_plain_text_to_clipboard(
+    state: tauri::State<'_, AppState>,
+    clip_id: String,
+) -> Result<(), String> {
+    state.clipboard_manager.write_clip_as_plain_text(&clip_id)
+}
 
-    let image_data = ImageData {
-        width: width as usize,
-        height: height as usize,
-        bytes: rgba.into_raw().into(),
-    };
+/// List the files of a file clip that no longer exist
+#[tauri::command]
+async fn get_missing_clip_files(
+    state: tauri::State<'_, AppState>,
+    clip_id: String,
+) -> Result<Vec<String>, String> {
+    state.clipboard_manager.get_missing_files(&clip_id)
+}
 
-    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
-    clipboard.set_image(image_data).map_err(|e| e.to_string())
+/// Write a file clip back as files so that file managers can paste them.
+/// Returns the paths that were skipped because they no longer exist.
+#[tauri::command]
+async fn write_file_clip_to_clipboard(
+    state: tauri::State<'_, AppState>,
+    clip_id: String,
+    skip_missing: Option<bool>,
+) -> Result<Vec<String>, String> {
+    state
+        .clipboard_manager
+        .write_file_clip(&clip_id, skip_missing.unwrap_or(false))
 }
 
 #[tauri::command]
@@ -276,8 +438,9 @@ async fn save_settings(
     state: tauri::State<'_, AppState>,
     settings: Settings,
 ) -> Result<(), String> {
-    let mut settings = settings;
-    settings.enable_input_panel = false;
+    // Reject invalid rules and hooks before anything is saved or applied
+    auto_tag::AutoTagger::new(&settings.auto_tag_rules)?;
+    hooks::validate_hooks(&settings.hooks)?;
 
     let mut current = state.settings.lock().map_err(|e| e.to_string())?;
 
@@ -295,16 +458,35 @@ async fn save_settings(
     let old_language = current.language.clone();
     let new_language = settings.language.clone();
 
-    let input_panel_enabled = false;
+    let input_panel_enabled = settings.enable_input_panel;
     let input_panel_trigger = settings.input_panel_trigger.clone();
     let input_panel_selection_modifier = settings.input_panel_selection_modifier.clone();
 
+    let text_expansion_enabled = settings.enable_text_expansion;
+    let text_expansion_delimiters = settings.text_expansion_delimiters.clone();
+    let text_expansion_excluded_apps = settings.text_expansion_excluded_apps.clone();
+
+    let backup_config = settings.backup_config();
+    let sync_config = settings.sync_config();
+    let peer_sync_config = settings.peer_sync_config();
+    let ipc_enabled = settings.ipc_enabled;
+    let enabled_plugins = settings.enabled_plugins.clone();
+
     // Global shortcut changes are handled by the plugin rebuild on restart
 
+    // Nothing changes unless the settings file was written
+    settings.save()?;
     *current = settings.clone();
-    current.save().map_err(|e| e.to_string())?;
     drop(current); // Release the lock before async operations
 
+    state
+        .clipboard_manager
+        .set_auto_tag_rules(&settings.auto_tag_rules)?;
+    state
+        .clipboard_manager
+        .hooks
+        .set_hooks(&settings.hooks, settings.hooks_max_concurrent)?;
+
     let autostart_result = if settings.autostart_enabled {
         app.autolaunch().enable()
     } else {
@@ -349,6 +531,23 @@ async fn save_settings(
         .input_panel
         .set_selection_modifier(input_panel_selection_modifier);
 
+    state
+        .text_expansion
+        .set_delimiters(text_expansion_delimiters);
+    state
+        .text_expansion
+        .set_excluded_apps(text_expansion_excluded_apps);
+    state.text_expansion.set_enabled(text_expansion_enabled);
+
+    state.backups.set_config(backup_config);
+    state.sync.set_config(sync_config);
+    state.peer_sync.set_config(peer_sync_config);
+    state.ipc.set_enabled(ipc_enabled);
+    state
+        .clipboard_manager
+        .plugins
+        .set_enabled(&enabled_plugins);
+
     // Notify frontend that shortcuts changed
     let _ = app.emit("shortcuts-changed", ());
     let _ = app.emit("settings-changed", &settings);
@@ -506,45 +705,841 @@ async fn restart_as_admin(_app: tauri::AppHandle) -> Result<(), String> {
 #[tauri::command]
 async fn paste_and_cleanup(
     _app: tauri::AppHandle,
-    content: String,
+    state: tauri::State<'_, AppState>,
+    clip_id: String,
     trigger_len: i32,
     search_len: i32,
 ) -> Result<(), String> {
-    use rdev::{simulate, EventType, Key};
-    use std::{thread, time::Duration};
-
-    let total_to_delete = trigger_len + search_len;
-
-    // Send backspace to delete trigger and search content
-    for _ in 0..total_to_delete {
-        simulate(&EventType::KeyPress(Key::Backspace))
-            .map_err(|e| format!("Failed to simulate backspace: {:?}", e))?;
-        simulate(&EventType::KeyRelease(Key::Backspace))
-            .map_err(|e| format!("Failed to simulate backspace: {:?}", e))?;
-        thread::sleep(Duration::from_millis(10));
+    // Close the panel first so the keys sent below are not read as a query
+    state.input_panel.finish_selection();
+    state
+        .input_panel
+        .wait_for_selection_modifier_release(std::time::Duration::from_secs(1))?;
+
+    // Delete trigger and search content, then paste
+    let total_to_delete = (trigger_len + search_len).max(0) as usize;
+    state
+        .clipboard_manager
+        .erase_and_paste_clip(total_to_delete, &clip_id)
+}
+
+/// Type a clip's text with simulated key events, for fields that block pasting.
+/// The cancel shortcut is registered globally until typing finishes.
+#[tauri::command]
+async fn type_out_clip(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    clip_id: String,
+    delay_ms: Option<u64>,
+) -> Result<type_out::TypeOutResult, String> {
+    use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+
+    let (default_delay_ms, max_length, cancel_shortcut) = {
+        let settings = state.settings.lock().map_err(|e| e.to_string())?;
+        (
+            settings.type_out_delay_ms,
+            settings.type_out_max_length,
+            settings.shortcuts.cancel_type_out.to_config_string(),
+        )
+    };
+
+    let clip = state.clipboard_manager.get_clip(&clip_id)?;
+    let text = clipboard::clip_plain_text(&clip)?;
+
+    let length = text.chars().count();
+    if length > max_length {
+        return Err(format!(
+            "Text is too long to type out ({} characters, limit is {})",
+            length, max_length
+        ));
     }
 
-    // Small delay before pasting
-    thread::sleep(Duration::from_millis(50));
+    // Claimed before the cancel shortcut is registered, so a second call cannot
+    // replace or unregister the shortcut of a running type-out
+    state.type_out.start()?;
 
-    // Write content to clipboard using arboard for better compatibility
-    use arboard::Clipboard;
-    let mut clipboard = Clipboard::new().map_err(|e| e.to_string())?;
-    clipboard.set_text(content).map_err(|e| e.to_string())?;
-
-    // Simulate Ctrl+V to paste
-    simulate(&EventType::KeyPress(Key::ControlLeft))
-        .map_err(|e| format!("Failed to simulate ctrl: {:?}", e))?;
-    simulate(&EventType::KeyPress(Key::KeyV))
-        .map_err(|e| format!("Failed to simulate v: {:?}", e))?;
-    simulate(&EventType::KeyRelease(Key::KeyV))
-        .map_err(|e| format!("Failed to simulate v: {:?}", e))?;
-    simulate(&EventType::KeyRelease(Key::ControlLeft))
-        .map_err(|e| format!("Failed to simulate ctrl: {:?}", e))?;
+    let shortcut = cancel_shortcut.parse::<Shortcut>().ok();
+    if let Some(shortcut) = shortcut {
+        if let Err(e) = app.global_shortcut().register(shortcut) {
+            eprintln!("Failed to register type-out cancel shortcut: {}", e);
+        }
+    }
+    state.type_out.set_cancel_shortcut(shortcut);
+
+    let type_out = state.type_out.clone();
+    let delay = std::time::Duration::from_millis(delay_ms.unwrap_or(default_delay_ms));
+    let result = tauri::async_runtime::spawn_blocking(move || type_out.type_text(&text, delay))
+        .await
+        .map_err(|e| e.to_string());
+
+    state.type_out.set_cancel_shortcut(None);
+    if let Some(shortcut) = shortcut {
+        let _ = app.global_shortcut().unregister(shortcut);
+    }
+    state.type_out.finish();
+
+    let result = result??;
+    if result.typed_chars > 0 {
+        let _ = state.clipboard_manager.record_clip_use(&clip_id);
+        let _ = app.emit("clipboard-updated", ());
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+async fn cancel_type_out(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state.type_out.cancel();
+    Ok(())
+}
+
+#[derive(serde::Serialize)]
+struct PasteQueueStatus {
+    active: bool,
+    order: String,
+    items: Vec<db::ClipItem>,
+}
+
+/// Paste the next queued clip into the focused application
+fn paste_next_queued(
+    app: &tauri::AppHandle,
+    modifiers: tauri_plugin_global_shortcut::Modifiers,
+) -> Result<bool, String> {
+    let state = app.state::<AppState>();
+    let lifo = {
+        let settings = state.settings.lock().map_err(|e| e.to_string())?;
+        settings.paste_queue_order == "lifo"
+    };
+
+    let clip_id = match state.clipboard_manager.paste_queue.pop_next(lifo) {
+        Some(clip_id) => clip_id,
+        None => return Ok(false),
+    };
+    let _ = app.emit("paste-queue-updated", ());
+
+    state.clipboard_manager.write_clip(&clip_id)?;
+
+    // Modifiers of the queue shortcut that are still held would turn the
+    // simulated paste into the queue shortcut again
+    clipboard_writer::release_modifiers(&modifier_keys(modifiers))?;
+
+    // Give the clipboard a moment before pasting
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    clipboard_writer::simulate_paste()?;
+
+    Ok(true)
+}
+
+/// The keys of a shortcut's modifiers, both sides
+fn modifier_keys(modifiers: tauri_plugin_global_shortcut::Modifiers) -> Vec<rdev::Key> {
+    use rdev::Key;
+    use tauri_plugin_global_shortcut::Modifiers;
+
+    [
+        (Modifiers::CONTROL, [Key::ControlLeft, Key::ControlRight]),
+        (Modifiers::ALT, [Key::Alt, Key::AltGr]),
+        (Modifiers::SHIFT, [Key::ShiftLeft, Key::ShiftRight]),
+        (Modifiers::SUPER, [Key::MetaLeft, Key::MetaRight]),
+    ]
+    .into_iter()
+    .filter(|(modifier, _)| modifiers.contains(*modifier))
+    .flat_map(|(_, keys)| keys)
+    .collect()
+}
+
+#[tauri::command]
+async fn get_paste_queue(state: tauri::State<'_, AppState>) -> Result<PasteQueueStatus, String> {
+    let order = {
+        let settings = state.settings.lock().map_err(|e| e.to_string())?;
+        settings.paste_queue_order.clone()
+    };
+
+    let queue = &state.clipboard_manager.paste_queue;
+    let items = queue
+        .clip_ids()
+        .iter()
+        .filter_map(|id| state.clipboard_manager.get_clip(id).ok())
+        .collect();
+
+    Ok(PasteQueueStatus {
+        active: queue.is_active(),
+        order,
+        items,
+    })
+}
+
+/// Start or stop queueing captures. The paste shortcut is only registered
+/// while the queue is active.
+#[tauri::command]
+async fn set_paste_queue_active(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    active: bool,
+) -> Result<(), String> {
+    use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+
+    let shortcut = {
+        let settings = state.settings.lock().map_err(|e| e.to_string())?;
+        settings
+            .shortcuts
+            .paste_from_queue
+            .to_config_string()
+            .parse::<Shortcut>()
+            .map_err(|e| e.to_string())?
+    };
+
+    let queue = &state.clipboard_manager.paste_queue;
+    if active {
+        if !queue.is_active() {
+            app.global_shortcut()
+                .register(shortcut)
+                .map_err(|e| e.to_string())?;
+        }
+        queue.set_active(true, Some(shortcut));
+    } else {
+        queue.set_active(false, None);
+        let _ = app.global_shortcut().unregister(shortcut);
+    }
+
+    let _ = app.emit("paste-queue-updated", ());
+    Ok(())
+}
+
+#[tauri::command]
+async fn move_paste_queue_item(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    from_index: usize,
+    to_index: usize,
+) -> Result<(), String> {
+    state
+        .clipboard_manager
+        .paste_queue
+        .move_item(from_index, to_index)?;
+    let _ = app.emit("paste-queue-updated", ());
+    Ok(())
+}
+
+#[tauri::command]
+async fn remove_paste_queue_item(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    index: usize,
+) -> Result<(), String> {
+    state.clipboard_manager.paste_queue.remove(index)?;
+    let _ = app.emit("paste-queue-updated", ());
+    Ok(())
+}
+
+#[tauri::command]
+async fn clear_paste_queue(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.clipboard_manager.paste_queue.clear();
+    let _ = app.emit("paste-queue-updated", ());
+    Ok(())
+}
+
+/// Paste the next queued clip. Returns false if the queue is empty.
+#[tauri::command]
+async fn paste_next_from_queue(app: tauri::AppHandle) -> Result<bool, String> {
+    paste_next_queued(&app, tauri_plugin_global_shortcut::Modifiers::empty())
+}
+
+#[tauri::command]
+async fn get_snippets(
+    state: tauri::State<'_, AppState>,
+    folder_id: Option<String>,
+    query: Option<String>,
+) -> Result<Vec<db::Snippet>, String> {
+    state
+        .snippets
+        .get_snippets(folder_id.as_deref(), query.as_deref())
+}
+
+#[tauri::command]
+async fn save_snippet(
+    state: tauri::State<'_, AppState>,
+    id: Option<String>,
+    name: String,
+    content: String,
+    folder_id: Option<String>,
+    abbreviation: Option<String>,
+    expansion_apps: Option<Vec<String>>,
+) -> Result<db::Snippet, String> {
+    let snippet = state.snippets.save_snippet(
+        id,
+        name,
+        content,
+        folder_id,
+        abbreviation,
+        expansion_apps.unwrap_or_default(),
+    )?;
+    state.text_expansion.reload_abbreviations()?;
+    Ok(snippet)
+}
+
+#[tauri::command]
+async fn delete_snippet(state: tauri::State<'_, AppState>, id: String) -> Result<(), String> {
+    state.snippets.delete_snippet(&id)?;
+    state.text_expansion.reload_abbreviations()
+}
+
+/// Turn a history item into a snippet. The name defaults to its first line.
+#[tauri::command]
+async fn promote_clip_to_snippet(
+    state: tauri::State<'_, AppState>,
+    clip_id: String,
+    name: Option<String>,
+    folder_id: Option<String>,
+    abbreviation: Option<String>,
+) -> Result<db::Snippet, String> {
+    let clip = state.clipboard_manager.get_clip(&clip_id)?;
+    let snippet = state
+        .snippets
+        .promote_clip(&clip, name, folder_id, abbreviation)?;
+    state.text_expansion.reload_abbreviations()?;
+    Ok(snippet)
+}
+
+#[tauri::command]
+async fn get_snippet_folders(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<db::SnippetFolder>, String> {
+    state.snippets.get_folders()
+}
+
+#[tauri::command]
+async fn create_snippet_folder(
+    state: tauri::State<'_, AppState>,
+    name: String,
+    parent_id: Option<String>,
+) -> Result<db::SnippetFolder, String> {
+    state.snippets.create_folder(name, parent_id)
+}
+
+#[tauri::command]
+async fn rename_snippet_folder(
+    state: tauri::State<'_, AppState>,
+    id: String,
+    name: String,
+) -> Result<(), String> {
+    state.snippets.rename_folder(&id, name)
+}
+
+#[tauri::command]
+async fn delete_snippet_folder(
+    state: tauri::State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    state.snippets.delete_folder(&id)
+}
+
+/// Names of the values the user has to enter before the snippet can be pasted
+#[tauri::command]
+async fn get_snippet_inputs(
+    state: tauri::State<'_, AppState>,
+    id: String,
+) -> Result<Vec<String>, String> {
+    state.snippets.get_inputs(&id)
+}
+
+#[tauri::command]
+async fn expand_snippet(
+    state: tauri::State<'_, AppState>,
+    id: String,
+    inputs: Option<HashMap<String, String>>,
+) -> Result<snippets::ExpandedSnippet, String> {
+    state.snippets.expand(&id, &inputs.unwrap_or_default())
+}
+
+#[tauri::command]
+async fn write_snippet_to_clipboard(
+    state: tauri::State<'_, AppState>,
+    id: String,
+    inputs: Option<HashMap<String, String>>,
+) -> Result<snippets::ExpandedSnippet, String> {
+    let expanded = state.snippets.expand(&id, &inputs.unwrap_or_default())?;
+    state
+        .clipboard_manager
+        .mark_self_write(None, Some(&expanded.text));
+    clipboard_writer::write_text(&expanded.text)?;
+    Ok(expanded)
+}
+
+/// Paste an expanded snippet into the focused application and move the caret
+/// to the `{cursor}` placeholder
+#[tauri::command]
+async fn paste_snippet(
+    state: tauri::State<'_, AppState>,
+    id: String,
+    inputs: Option<HashMap<String, String>>,
+) -> Result<(), String> {
+    let expanded = state.snippets.expand(&id, &inputs.unwrap_or_default())?;
+    state
+        .clipboard_manager
+        .mark_self_write(None, Some(&expanded.text));
+    clipboard_writer::write_text(&expanded.text)?;
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    clipboard_writer::simulate_paste()?;
+
+    if let Some(count) = expanded.cursor_from_end {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        clipboard_writer::tap_key(rdev::Key::LeftArrow, count)?;
+    }
+
+    Ok(())
+}
+
+/// Pause or resume abbreviation expansion without changing the setting
+#[tauri::command]
+async fn set_text_expansion_suspended(
+    state: tauri::State<'_, AppState>,
+    suspended: bool,
+) -> Result<(), String> {
+    state.text_expansion.set_suspended(suspended);
+    Ok(())
+}
+
+#[tauri::command]
+async fn is_text_expansion_suspended(state: tauri::State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.text_expansion.is_suspended())
+}
+
+#[tauri::command]
+async fn get_collections(state: tauri::State<'_, AppState>) -> Result<Vec<db::Collection>, String> {
+    state.collections.get_collections()
+}
+
+#[tauri::command]
+async fn create_collection(
+    state: tauri::State<'_, AppState>,
+    name: String,
+    parent_id: Option<String>,
+    color: Option<String>,
+) -> Result<db::Collection, String> {
+    state.collections.create_collection(name, parent_id, color)
+}
+
+#[tauri::command]
+async fn update_collection(
+    state: tauri::State<'_, AppState>,
+    id: String,
+    name: String,
+    color: Option<String>,
+) -> Result<(), String> {
+    state.collections.update_collection(&id, name, color)
+}
+
+/// Move a collection under another parent (`None` for the top level) at `index`
+#[tauri::command]
+async fn move_collection(
+    state: tauri::State<'_, AppState>,
+    id: String,
+    parent_id: Option<String>,
+    index: usize,
+) -> Result<(), String> {
+    state.collections.move_collection(&id, parent_id, index)
+}
+
+#[tauri::command]
+async fn delete_collection(state: tauri::State<'_, AppState>, id: String) -> Result<(), String> {
+    state.collections.delete_collection(&id)
+}
+
+#[tauri::command]
+async fn add_clips_to_collection(
+    state: tauri::State<'_, AppState>,
+    collection_id: String,
+    clip_ids: Vec<String>,
+) -> Result<(), String> {
+    state.collections.add_clips(&collection_id, &clip_ids)
+}
+
+#[tauri::command]
+async fn remove_clip_from_collection(
+    state: tauri::State<'_, AppState>,
+    collection_id: String,
+    clip_id: String,
+) -> Result<(), String> {
+    state.collections.remove_clip(&collection_id, &clip_id)
+}
+
+#[tauri::command]
+async fn get_collection_clips(
+    state: tauri::State<'_, AppState>,
+    collection_id: String,
+) -> Result<Vec<db::ClipItem>, String> {
+    state.collections.get_clips(&collection_id)
+}
+
+#[tauri::command]
+async fn move_clip_in_collection(
+    state: tauri::State<'_, AppState>,
+    collection_id: String,
+    clip_id: String,
+    index: usize,
+) -> Result<(), String> {
+    state.collections.move_clip(&collection_id, &clip_id, index)
+}
+
+#[tauri::command]
+async fn get_clip_collections(
+    state: tauri::State<'_, AppState>,
+    clip_id: String,
+) -> Result<Vec<String>, String> {
+    state.collections.get_clip_collection_ids(&clip_id)
+}
+
+/// Preview an auto-tag rule: the most recent clips it would match
+#[tauri::command]
+async fn preview_auto_tag_rule(
+    state: tauri::State<'_, AppState>,
+    rule: auto_tag::AutoTagRule,
+    limit: Option<i64>,
+) -> Result<Vec<db::ClipItem>, String> {
+    let clips = state
+        .clipboard_manager
+        .get_recent_clips(limit.unwrap_or(1000))?;
+
+    let conditions = auto_tag::CompiledConditions::new(&rule.conditions, &rule.name)?;
+    Ok(clips
+        .into_iter()
+        .filter(|clip| conditions.matches(clip))
+        .collect())
+}
+
+/// Edit the text of a plain text clip, keeping the previous text as a revision
+#[tauri::command]
+async fn update_clip_content(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    id: String,
+    content: String,
+) -> Result<db::ClipItem, String> {
+    let clip = state.clipboard_manager.update_clip_content(&id, content)?;
+    let _ = app.emit("clipboard-updated", ());
+    Ok(clip)
+}
+
+#[tauri::command]
+async fn get_clip_revisions(
+    state: tauri::State<'_, AppState>,
+    id: String,
+) -> Result<Vec<db::ClipRevision>, String> {
+    state.clipboard_manager.get_clip_revisions(&id)
+}
+
+#[tauri::command]
+async fn revert_clip_revision(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    id: String,
+    revision_id: i64,
+) -> Result<db::ClipItem, String> {
+    let clip = state.clipboard_manager.revert_clip(&id, revision_id)?;
+    let _ = app.emit("clipboard-updated", ());
+    Ok(clip)
+}
+
+/// Merge clips, in the given order, into a new clip
+#[tauri::command]
+async fn merge_clips(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    ids: Vec<String>,
+    separator: merge::MergeSeparator,
+    mode: Option<merge::MergeMode>,
+) -> Result<db::ClipItem, String> {
+    let clip = state
+        .clipboard_manager
+        .merge_clips(&ids, &separator, mode.unwrap_or_default())?;
+    let _ = app.emit("clipboard-updated", ());
+    Ok(clip)
+}
+
+/// Export the clips matching `filter` to a JSON archive at `path`
+#[tauri::command]
+async fn export_history(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    filter: Option<db::ClipFilter>,
+) -> Result<usize, String> {
+    let db = state
+        .clipboard_manager
+        .db
+        .lock()
+        .map_err(|e| e.to_string())?;
+    archive::export_history(
+        &db,
+        std::path::Path::new(&path),
+        &filter.unwrap_or_default(),
+    )
+}
+
+/// Import a JSON archive created by `export_history`
+#[tauri::command]
+async fn import_history(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    path: String,
+    conflict: Option<archive::ImportConflict>,
+) -> Result<archive::ImportSummary, String> {
+    let summary = {
+        let db = state
+            .clipboard_manager
+            .db
+            .lock()
+            .map_err(|e| e.to_string())?;
+        archive::import_history(
+            &db,
+            std::path::Path::new(&path),
+            conflict.unwrap_or_default(),
+        )?
+    };
+    let _ = app.emit("clipboard-updated", ());
+    Ok(summary)
+}
+
+/// Default history location of another clipboard manager, if known
+#[tauri::command]
+async fn get_import_source_path(source: importers::ImportSource) -> Result<Option<String>, String> {
+    Ok(importers::default_path(source)
+        .filter(|path| path.exists())
+        .map(|path| path.to_string_lossy().to_string()))
+}
+
+/// Import the history of another clipboard manager from `path`
+#[tauri::command]
+async fn import_from_clipboard_manager(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    source: importers::ImportSource,
+    path: String,
+) -> Result<archive::ImportSummary, String> {
+    let clips = importers::read_history(source, std::path::Path::new(&path))?;
+    let summary =
+        importers::import_clips(&state.clipboard_manager, clips, &format!("{:?}", source));
+    let _ = app.emit("clipboard-updated", ());
+    Ok(summary)
+}
+
+#[tauri::command]
+async fn list_backups(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<backup::BackupInfo>, String> {
+    state.backups.list_backups()
+}
+
+#[tauri::command]
+async fn create_backup(state: tauri::State<'_, AppState>) -> Result<backup::BackupInfo, String> {
+    state.backups.create_backup()
+}
+
+#[tauri::command]
+async fn verify_backup(
+    state: tauri::State<'_, AppState>,
+    id: String,
+) -> Result<backup::BackupInfo, String> {
+    state.backups.verify(&id)
+}
 
+/// Replace the history with a backup. The current history is backed up first.
+#[tauri::command]
+async fn restore_backup(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    id: String,
+) -> Result<(), String> {
+    state.backups.restore_backup(&id)?;
+    let _ = app.emit("clipboard-updated", ());
     Ok(())
 }
 
+/// Exchange changes with the other devices in the sync folder
+#[tauri::command]
+async fn sync_now(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<sync::SyncSummary, String> {
+    let summary = state.sync.sync_now()?;
+    if summary.received > 0 {
+        let _ = app.emit("clipboard-updated", ());
+    }
+    Ok(summary)
+}
+
+#[tauri::command]
+async fn get_sync_peers(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<peer_sync::PeerInfo>, String> {
+    state.peer_sync.peers()
+}
+
+/// Let another device on the network pair with this one using the returned code
+#[tauri::command]
+async fn start_peer_pairing(
+    state: tauri::State<'_, AppState>,
+) -> Result<peer_sync::PairingCode, String> {
+    state.peer_sync.start_pairing()
+}
+
+#[tauri::command]
+async fn pair_sync_peer(
+    state: tauri::State<'_, AppState>,
+    address: String,
+    code: String,
+) -> Result<peer_sync::PeerInfo, String> {
+    state.peer_sync.pair(&address, &code)
+}
+
+#[tauri::command]
+async fn remove_sync_peer(
+    state: tauri::State<'_, AppState>,
+    device_id: String,
+) -> Result<(), String> {
+    state.peer_sync.remove_peer(&device_id)
+}
+
+#[tauri::command]
+async fn sync_with_peer(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    device_id: String,
+) -> Result<sync::SyncSummary, String> {
+    let summary = state.peer_sync.sync_peer(&device_id)?;
+    if summary.received > 0 {
+        let _ = app.emit("clipboard-updated", ());
+    }
+    Ok(summary)
+}
+
+/// Endpoint and token location for the local API
+#[tauri::command]
+async fn get_ipc_info(state: tauri::State<'_, AppState>) -> Result<ipc::IpcInfo, String> {
+    Ok(state.ipc.info())
+}
+
+/// Recent hook runs with their output, newest first
+#[tauri::command]
+async fn get_hook_runs(state: tauri::State<'_, AppState>) -> Result<Vec<hooks::HookRun>, String> {
+    Ok(state.clipboard_manager.hooks.runs())
+}
+
+#[tauri::command]
+async fn clear_hook_runs(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    state.clipboard_manager.hooks.clear_runs();
+    let _ = app.emit("hook-runs-updated", ());
+    Ok(())
+}
+
+/// Installed plugins with their manifest and status
+#[tauri::command]
+async fn get_plugins(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<plugins::PluginInfo>, String> {
+    Ok(state.clipboard_manager.plugins.plugins())
+}
+
+/// Find newly installed plugins; running plugins are restarted on next use
+#[tauri::command]
+async fn reload_plugins(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<plugins::PluginInfo>, String> {
+    state.clipboard_manager.plugins.load();
+    Ok(state.clipboard_manager.plugins.plugins())
+}
+
+#[tauri::command]
+async fn get_plugin_actions(
+    state: tauri::State<'_, AppState>,
+    clip_id: String,
+) -> Result<Vec<plugins::PluginActionInfo>, String> {
+    let clip = state.clipboard_manager.get_clip(&clip_id)?;
+    Ok(state.clipboard_manager.plugins.actions(&clip))
+}
+
+#[tauri::command]
+async fn run_plugin_action(
+    state: tauri::State<'_, AppState>,
+    plugin_id: String,
+    action_id: String,
+    clip_id: String,
+) -> Result<plugins::PluginActionResult, String> {
+    let clip = state.clipboard_manager.get_clip(&clip_id)?;
+    state
+        .clipboard_manager
+        .plugins
+        .run_action(&plugin_id, &action_id, &clip)
+}
+
+#[tauri::command]
+async fn get_transforms(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<transforms::TransformInfo>, String> {
+    let mut transforms = transforms::available_transforms();
+    transforms.extend(state.clipboard_manager.plugins.transforms());
+    Ok(transforms)
+}
+
+#[derive(serde::Serialize)]
+struct TransformResult {
+    text: String,
+    /// The new clip when the result was saved
+    clip: Option<db::ClipItem>,
+}
+
+/// Run a chain of transformations on a clip. The result is returned and, depending
+/// on `output`, also saved as a new clip or written to the clipboard.
+#[tauri::command]
+async fn transform_clip(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, AppState>,
+    clip_id: String,
+    operations: Vec<String>,
+    output: Option<transforms::TransformOutput>,
+) -> Result<TransformResult, String> {
+    let clip = state.clipboard_manager.get_clip(&clip_id)?;
+    let input = transforms::transform_input(&clip, &operations)?;
+    let text = state
+        .clipboard_manager
+        .plugins
+        .apply_transforms(&input, &operations)?;
+
+    if text.is_empty() {
+        return Err("The transformation produced no text".to_string());
+    }
+
+    let mut saved = None;
+    match output.unwrap_or_default() {
+        transforms::TransformOutput::Preview => {}
+        transforms::TransformOutput::Clip => {
+            saved = Some(state.clipboard_manager.save_text_clip(
+                text.clone(),
+                clip.source_app.clone(),
+                true,
+            )?);
+            let _ = app.emit("clipboard-updated", ());
+        }
+        transforms::TransformOutput::Clipboard => {
+            state.clipboard_manager.mark_self_write(None, Some(&text));
+            clipboard_writer::write_text(&text)?;
+        }
+    }
+
+    Ok(TransformResult { text, clip: saved })
+}
+
+/// Run the command-line interface if the arguments name a command. Returns
+/// the exit code, or `None` when the app should start.
+pub fn run_cli() -> Option<i32> {
+    cli::run()
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     // Ensure app directory exists
@@ -556,21 +1551,69 @@ pub fn run() {
     ));
 
     // Initialize clipboard manager
-    let clipboard_manager = Arc::new(ClipboardManager::new(db));
+    let clipboard_manager = Arc::new(ClipboardManager::new(db.clone()));
 
     // Initialize settings
     let settings = Arc::new(Mutex::new(Settings::load(&app_dir).unwrap_or_default()));
 
+    // The input panel and text expansion share one keystroke listener
+    let key_listener = Arc::new(KeyListener::new());
+
     // Initialize input panel manager
-    let input_panel = Arc::new(InputPanelManager::new());
+    let input_panel = Arc::new(InputPanelManager::new(key_listener.clone()));
 
     let tray_manager = Arc::new(tray::TrayManager::new());
 
+    let type_out = Arc::new(TypeOutManager::new());
+
+    let snippets = Arc::new(SnippetManager::new(clipboard_manager.db.clone()));
+
+    let collections = Arc::new(CollectionManager::new(clipboard_manager.db.clone()));
+
+    let text_expansion = Arc::new(TextExpansionManager::new(
+        snippets.clone(),
+        clipboard_manager.clone(),
+        key_listener,
+    ));
+
+    let backups = Arc::new(BackupManager::new(
+        clipboard_manager.clone(),
+        settings.lock().unwrap().backup_config(),
+    ));
+
+    let sync = Arc::new(SyncManager::new(
+        clipboard_manager.clone(),
+        settings.lock().unwrap().sync_config(),
+    ));
+
+    let device_id = sync::device_id().unwrap_or_else(|e| {
+        eprintln!("Failed to store device id: {}", e);
+        uuid::Uuid::new_v4().to_string()
+    });
+    let peer_sync = Arc::new(PeerSync::new(
+        db.clone(),
+        device_id,
+        settings.lock().unwrap().peer_sync_config(),
+    ));
+
+    let ipc = Arc::new(IpcServer::new(
+        clipboard_manager.clone(),
+        settings.lock().unwrap().ipc_enabled,
+    ));
+
     let state = AppState {
         clipboard_manager: clipboard_manager.clone(),
         settings: settings.clone(),
         input_panel: input_panel.clone(),
         tray_manager: tray_manager.clone(),
+        type_out: type_out.clone(),
+        snippets,
+        text_expansion: text_expansion.clone(),
+        collections,
+        backups: backups.clone(),
+        sync: sync.clone(),
+        peer_sync: peer_sync.clone(),
+        ipc: ipc.clone(),
     };
 
     tauri::Builder::default()
@@ -592,11 +1635,47 @@ pub fn run() {
             search_clips,
             get_all_tags,
             search_tags,
+            get_tag_stats,
+            rename_tag,
+            merge_tags,
+            delete_tag,
+            preview_auto_tag_rule,
+            update_clip_content,
+            get_clip_revisions,
+            revert_clip_revision,
+            merge_clips,
+            export_history,
+            import_history,
+            get_import_source_path,
+            import_from_clipboard_manager,
+            list_backups,
+            create_backup,
+            verify_backup,
+            restore_backup,
+            sync_now,
+            get_sync_peers,
+            start_peer_pairing,
+            pair_sync_peer,
+            remove_sync_peer,
+            sync_with_peer,
+            get_ipc_info,
+            get_hook_runs,
+            clear_hook_runs,
+            get_plugins,
+            reload_plugins,
+            get_plugin_actions,
+            run_plugin_action,
+            get_transforms,
+            transform_clip,
             update_clip_tags,
             toggle_clip_pin,
             delete_clip,
             write_to_clipboard,
             write_image_to_clipboard,
+            write_rich_clip_to_clipboard,
+            write_plain_text_to_clipboard,
+            get_missing_clip_files,
+            write_file_clip_to_clipboard,
             get_clip_image_data,
             get_clip_thumbnail_data,
             get_settings,
@@ -611,6 +1690,38 @@ pub fn run() {
             is_running_as_admin,
             restart_as_admin,
             paste_and_cleanup,
+            type_out_clip,
+            cancel_type_out,
+            get_paste_queue,
+            set_paste_queue_active,
+            move_paste_queue_item,
+            remove_paste_queue_item,
+            clear_paste_queue,
+            paste_next_from_queue,
+            get_snippets,
+            save_snippet,
+            delete_snippet,
+            promote_clip_to_snippet,
+            get_snippet_folders,
+            create_snippet_folder,
+            rename_snippet_folder,
+            delete_snippet_folder,
+            get_snippet_inputs,
+            expand_snippet,
+            write_snippet_to_clipboard,
+            paste_snippet,
+            set_text_expansion_suspended,
+            is_text_expansion_suspended,
+            get_collections,
+            create_collection,
+            update_collection,
+            move_collection,
+            delete_collection,
+            add_clips_to_collection,
+            remove_clip_from_collection,
+            get_collection_clips,
+            move_clip_in_collection,
+            get_clip_collections,
         ])
         .setup(move |app| {
             let app_handle = app.handle().clone();
@@ -659,13 +1770,31 @@ pub fn run() {
                 use tauri_plugin_global_shortcut::ShortcutState;
 
                 let shortcut_str = toggle_shortcut;
+                let queue_manager = clipboard_manager.clone();
 
                 app.handle().plugin(
                     tauri_plugin_global_shortcut::Builder::new()
                         .with_shortcuts([shortcut_str.as_str()])
                         .unwrap()
-                        .with_handler(move |app: &tauri::AppHandle, _shortcut, event| {
-                            if event.state == ShortcutState::Pressed {
+                        .with_handler(move |app: &tauri::AppHandle, shortcut, event| {
+                            if event.state == ShortcutState::Pressed
+                                && type_out.is_cancel_shortcut(shortcut)
+                            {
+                                type_out.cancel();
+                            } else if queue_manager.paste_queue.is_paste_shortcut(shortcut) {
+                                // Paste once the shortcut's key is up
+                                if event.state != ShortcutState::Released {
+                                    return;
+                                }
+                                // Simulated key presses must not block the event loop
+                                let app = app.clone();
+                                let modifiers = shortcut.mods;
+                                std::thread::spawn(move || {
+                                    if let Err(e) = paste_next_queued(&app, modifiers) {
+                                        eprintln!("Failed to paste from queue: {}", e);
+                                    }
+                                });
+                            } else if event.state == ShortcutState::Pressed {
                                 if let Some(window) = app.get_webview_window("main") {
                                     // Toggle window visibility
                                     let is_visible = window.is_visible().unwrap_or(false);
@@ -684,10 +1813,80 @@ pub fn run() {
 
             // Setup input panel
             {
-                input_panel.set_enabled(false);
+                let (enabled, trigger, selection_modifier) = {
+                    let settings = settings.lock().unwrap();
+                    (
+                        settings.enable_input_panel,
+                        settings.input_panel_trigger.clone(),
+                        settings.input_panel_selection_modifier.clone(),
+                    )
+                };
                 input_panel.set_app_handle(app_handle.clone());
+                input_panel.set_trigger(trigger);
+                input_panel.set_selection_modifier(selection_modifier);
+                input_panel.set_enabled(enabled);
+            }
+
+            // Classify text clips from before content classification
+            {
+                let clipboard_manager = clipboard_manager.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = clipboard_manager.classify_history() {
+                        eprintln!("Failed to classify clip history: {}", e);
+                    }
+                });
             }
 
+            // Setup auto-tag rules
+            {
+                let settings = settings.lock().unwrap();
+                if let Err(e) = clipboard_manager.set_auto_tag_rules(&settings.auto_tag_rules) {
+                    eprintln!("Failed to load auto-tag rules: {}", e);
+                }
+            }
+
+            // Setup hooks
+            {
+                let settings = settings.lock().unwrap();
+                if let Err(e) = clipboard_manager
+                    .hooks
+                    .set_hooks(&settings.hooks, settings.hooks_max_concurrent)
+                {
+                    eprintln!("Failed to load hooks: {}", e);
+                }
+                clipboard_manager.hooks.set_app_handle(app_handle.clone());
+            }
+
+            // Setup plugins
+            {
+                let enabled_plugins = settings.lock().unwrap().enabled_plugins.clone();
+                clipboard_manager.plugins.set_enabled(&enabled_plugins);
+                clipboard_manager.plugins.load();
+                clipboard_manager.plugins.set_app_handle(app_handle.clone());
+            }
+
+            // Setup text expansion
+            {
+                let (enabled, delimiters, excluded_apps) = {
+                    let settings = settings.lock().unwrap();
+                    (
+                        settings.enable_text_expansion,
+                        settings.text_expansion_delimiters.clone(),
+                        settings.text_expansion_excluded_apps.clone(),
+                    )
+                };
+                text_expansion.set_delimiters(delimiters);
+                text_expansion.set_excluded_apps(excluded_apps);
+                text_expansion.set_enabled(enabled);
+            }
+
+            backups.start_scheduler();
+            sync.start_scheduler(app_handle.clone());
+            peer_sync.start(app_handle.clone());
+            ipc.start(app_handle.clone());
+            #[cfg(target_os = "linux")]
+            dbus::start(clipboard_manager.clone(), app_handle.clone());
+
             // Setup clipboard monitor using arboard (supports text, images, files)
             {
                 let monitor = ClipboardMonitor::new(clipboard_manager.clone());
@@ -2,5 +2,8 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    if let Some(code) = fat_clip_lib::run_cli() {
+        std::process::exit(code);
+    }
     fat_clip_lib::run()
 }
@@ -0,0 +1,270 @@
+//! Merging several clips into one: text is joined with a separator, rich
+//! clips become one HTML document and file clips one list of paths.
+
+use crate::clipboard::clip_plain_text;
+use crate::db::{ClipItem, ContentType, RichTextContent};
+use serde::Deserialize;
+
+/// What goes between the merged clips. Serialized as "newline", "comma" or
+/// `{"custom": " | "}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeSeparator {
+    Newline,
+    Comma,
+    Custom(String),
+}
+
+impl MergeSeparator {
+    fn as_str(&self) -> &str {
+        match self {
+            MergeSeparator::Newline => "\n",
+            MergeSeparator::Comma => ", ",
+            MergeSeparator::Custom(separator) => separator,
+        }
+    }
+}
+
+/// Format of the merged clip
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeMode {
+    /// Rich text if any clip is rich, a file list if all clips are files,
+    /// plain text otherwise
+    #[default]
+    Auto,
+    /// Always plain text
+    Plain,
+}
+
+pub enum Merged {
+    Text(String),
+    Rich { html: String, plain: String },
+    Files(Vec<String>),
+}
+
+/// Merge the clips in the given order
+pub fn merge_clips(
+    clips: &[ClipItem],
+    separator: &MergeSeparator,
+    mode: MergeMode,
+) -> Result<Merged, String> {
+    if clips.len() < 2 {
+        return Err("Select at least two clips to merge".to_string());
+    }
+    if clips.iter().any(|c| c.content_type == ContentType::Image) {
+        return Err("Image clips cannot be merged".to_string());
+    }
+
+    let separator = separator.as_str();
+
+    if mode == MergeMode::Auto {
+        if clips.iter().all(|c| c.content_type == ContentType::File) {
+            return merge_files(clips).map(Merged::Files);
+        }
+        if clips.iter().any(|c| c.content_type == ContentType::Rich) {
+            return merge_rich(clips, separator);
+        }
+    }
+
+    Ok(Merged::Text(merge_text(clips, separator)?))
+}
+
+fn merge_text(clips: &[ClipItem], separator: &str) -> Result<String, String> {
+    Ok(clips
+        .iter()
+        .map(clip_plain_text)
+        .collect::<Result<Vec<_>, String>>()?
+        .join(separator))
+}
+
+/// One list of paths, without duplicates
+fn merge_files(clips: &[ClipItem]) -> Result<Vec<String>, String> {
+    let mut paths: Vec<String> = Vec::new();
+    for clip in clips {
+        let clip_paths: Vec<String> = serde_json::from_str(&clip.content)
+            .map_err(|e| format!("Invalid file list content: {}", e))?;
+        for path in clip_paths {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// One HTML document from the HTML of rich clips and the escaped text of the
+/// other clips
+fn merge_rich(clips: &[ClipItem], separator: &str) -> Result<Merged, String> {
+    let mut fragments = Vec::with_capacity(clips.len());
+    for clip in clips {
+        let html = match clip.content_type {
+            ContentType::Rich => serde_json::from_str::<RichTextContent>(&clip.content)
+                .map_err(|e| format!("Invalid rich text content: {}", e))?
+                .html
+                .filter(|html| !html.is_empty())
+                .map(|html| html_fragment(&html).to_string()),
+            _ => None,
+        };
+
+        fragments.push(match html {
+            Some(html) => html,
+            None => text_to_html(&clip_plain_text(clip)?),
+        });
+    }
+
+    Ok(Merged::Rich {
+        html: format!(
+            "<html><body>{}</body></html>",
+            fragments.join(&text_to_html(separator))
+        ),
+        plain: merge_text(clips, separator)?,
+    })
+}
+
+/// The part of a clipboard HTML document that holds the copied content
+fn html_fragment(html: &str) -> &str {
+    if let (Some(start), Some(end)) = (
+        html.find("<!--StartFragment-->"),
+        html.find("<!--EndFragment-->"),
+    ) {
+        let start = start + "<!--StartFragment-->".len();
+        if start <= end {
+            return &html[start..end];
+        }
+    }
+
+    let lower = html.to_ascii_lowercase();
+    if let Some(body) = lower.find("<body") {
+        if let Some(open_end) = lower[body..].find('>') {
+            let start = body + open_end + 1;
+            let end = lower.rfind("</body>").filter(|&end| end >= start);
+            return &html[start..end.unwrap_or(html.len())];
+        }
+    }
+
+    html
+}
+
+fn text_to_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\n', "<br>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn clip(content_type: ContentType, content: &str) -> ClipItem {
+        ClipItem {
+            id: String::new(),
+            content_type,
+            content: content.to_string(),
+            preview_text: String::new(),
+            tags: vec![],
+            source_app: String::new(),
+            created_at: Utc::now(),
+            last_used_at: Utc::now(),
+            pinned: false,
+            metadata: None,
+            use_count: 0,
+        }
+    }
+
+    fn rich(html: &str, plain: &str) -> ClipItem {
+        let content = serde_json::json!({ "html": html, "rtf": null, "plain": plain });
+        clip(ContentType::Rich, &content.to_string())
+    }
+
+    #[test]
+    fn auto_mode_follows_the_clip_types() {
+        let files = [
+            clip(ContentType::File, r#"["/a", "/b"]"#),
+            clip(ContentType::File, r#"["/b", "/c"]"#),
+        ];
+        let Ok(Merged::Files(paths)) =
+            merge_clips(&files, &MergeSeparator::Newline, MergeMode::Auto)
+        else {
+            panic!("file clips should merge into a file list");
+        };
+        assert_eq!(paths, vec!["/a", "/b", "/c"]);
+
+        // Plain mode lists the paths as text, duplicates included
+        let Ok(Merged::Text(text)) = merge_clips(&files, &MergeSeparator::Comma, MergeMode::Plain)
+        else {
+            panic!("plain mode should give text");
+        };
+        assert_eq!(text, "/a\n/b, /b\n/c");
+
+        let mixed = [clip(ContentType::Plain, "one"), rich("<b>two</b>", "two")];
+        assert!(matches!(
+            merge_clips(&mixed, &MergeSeparator::Newline, MergeMode::Auto),
+            Ok(Merged::Rich { .. })
+        ));
+        let Ok(Merged::Text(text)) = merge_clips(
+            &mixed,
+            &MergeSeparator::Custom(" | ".to_string()),
+            MergeMode::Plain,
+        ) else {
+            panic!("plain mode should give text");
+        };
+        assert_eq!(text, "one | two");
+    }
+
+    #[test]
+    fn images_and_single_clips_are_refused() {
+        let image = [
+            clip(ContentType::Plain, "a"),
+            clip(ContentType::Image, "a.png"),
+        ];
+        assert!(merge_clips(&image, &MergeSeparator::Newline, MergeMode::Plain).is_err());
+        let single = [clip(ContentType::Plain, "a")];
+        assert!(merge_clips(&single, &MergeSeparator::Newline, MergeMode::Auto).is_err());
+    }
+
+    #[test]
+    fn rich_merge_escapes_plain_clips() {
+        let clips = [
+            rich(
+                "<html><body><!--StartFragment--><i>x</i><!--EndFragment--></body></html>",
+                "x",
+            ),
+            clip(ContentType::Plain, "a < b & \"c\"\nd"),
+        ];
+        let Ok(Merged::Rich { html, plain }) = merge_clips(
+            &clips,
+            &MergeSeparator::Custom("<hr>".to_string()),
+            MergeMode::Auto,
+        ) else {
+            panic!("a rich clip should give a rich merge");
+        };
+        assert_eq!(
+            html,
+            "<html><body><i>x</i>&lt;hr&gt;a &lt; b &amp; &quot;c&quot;<br>d</body></html>"
+        );
+        assert_eq!(plain, "x<hr>a < b & \"c\"\nd");
+    }
+
+    #[test]
+    fn fragments_are_cut_from_clipboard_html() {
+        assert_eq!(
+            html_fragment("<p>a<!--StartFragment-->b<!--EndFragment-->c</p>"),
+            "b"
+        );
+        assert_eq!(
+            html_fragment("<HTML><BODY class=\"x\">text</BODY></HTML>"),
+            "text"
+        );
+        assert_eq!(html_fragment("<body>unclosed"), "unclosed");
+        assert_eq!(html_fragment("<b>bare</b>"), "<b>bare</b>");
+        // Markers in the wrong order are ignored
+        assert_eq!(
+            html_fragment("<!--EndFragment--><body>x</body><!--StartFragment-->"),
+            "x"
+        );
+    }
+}
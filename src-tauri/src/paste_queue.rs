@@ -0,0 +1,102 @@
+//! Paste queue: while active, every capture is queued and a global shortcut
+//! pastes the queued clips one after another.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tauri_plugin_global_shortcut::Shortcut;
+
+#[derive(Default)]
+struct QueueState {
+    active: bool,
+    clip_ids: VecDeque<String>,
+    // Global shortcut registered while the queue is active
+    paste_shortcut: Option<Shortcut>,
+}
+
+pub struct PasteQueue {
+    state: Mutex<QueueState>,
+}
+
+impl PasteQueue {
+    pub fn new() -> Self {
+        PasteQueue {
+            state: Mutex::new(QueueState::default()),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state.lock().map(|s| s.active).unwrap_or(false)
+    }
+
+    /// Turn queueing on or off. The queued clips are kept either way.
+    pub fn set_active(&self, active: bool, paste_shortcut: Option<Shortcut>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.active = active;
+            state.paste_shortcut = paste_shortcut;
+        }
+    }
+
+    /// Whether `shortcut` is the paste shortcut of the active queue
+    pub fn is_paste_shortcut(&self, shortcut: &Shortcut) -> bool {
+        self.state
+            .lock()
+            .map(|s| s.paste_shortcut.as_ref() == Some(shortcut))
+            .unwrap_or(false)
+    }
+
+    /// Append a captured clip. Returns false if the queue is not active.
+    pub fn push(&self, clip_id: &str) -> bool {
+        match self.state.lock() {
+            Ok(mut state) if state.active => {
+                state.clip_ids.push_back(clip_id.to_string());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Take the next clip to paste, the oldest for FIFO or the newest for LIFO
+    pub fn pop_next(&self, lifo: bool) -> Option<String> {
+        let mut state = self.state.lock().ok()?;
+        if lifo {
+            state.clip_ids.pop_back()
+        } else {
+            state.clip_ids.pop_front()
+        }
+    }
+
+    pub fn clip_ids(&self) -> Vec<String> {
+        self.state
+            .lock()
+            .map(|s| s.clip_ids.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Move the entry at `from` to position `to`
+    pub fn move_item(&self, from: usize, to: usize) -> Result<(), String> {
+        let mut state = self.state.lock().map_err(|e| e.to_string())?;
+        if from >= state.clip_ids.len() || to >= state.clip_ids.len() {
+            return Err("Queue index out of range".to_string());
+        }
+
+        if let Some(clip_id) = state.clip_ids.remove(from) {
+            state.clip_ids.insert(to, clip_id);
+        }
+        Ok(())
+    }
+
+    pub fn remove(&self, index: usize) -> Result<(), String> {
+        let mut state = self.state.lock().map_err(|e| e.to_string())?;
+        state
+            .clip_ids
+            .remove(index)
+            .map(|_| ())
+            .ok_or_else(|| "Queue index out of range".to_string())
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.clip_ids.clear();
+        }
+    }
+}
@@ -0,0 +1,1242 @@
+//! Opt-in sync with paired devices on the local network, exchanging the sync
+//! journal (see `sync`) incrementally.
+//!
+//! The protocol is one JSON message per line over TCP. Devices pair once with
+//! a one-time code shown by the device that is paired with: both run SPAKE2
+//! (the `spake2` crate) with the code, so an exchange that is listened to or
+//! tampered with reveals nothing to guess the code from offline, and derive a
+//! key for the pair. Later connections agree on a secret for the session with
+//! ephemeral X25519, from which HKDF derives keys bound to the key for the
+//! pair. Both sides prove they have them with HMAC-SHA256, and every message
+//! after that is encrypted with ChaCha20-Poly1305.
+
+use crate::db::{ClipItem, Database, SyncPeer};
+use crate::sync::{
+    apply_change, journal_entry, record_local_changes, SyncChange, SyncEntry, SyncSummary,
+};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use chrono::{DateTime, Utc};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use spake2::{Ed25519Group, Identity, Password, Spake2};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tauri::Emitter;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+const PROTOCOL_VERSION: u32 = 3;
+
+/// Pairing codes avoid characters that are easily confused, like 0 and O
+const PAIRING_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const PAIRING_CODE_LENGTH: usize = 8;
+const PAIRING_CODE_LIFETIME_MINUTES: i64 = 5;
+/// SPAKE2 identities of the two sides of pairing. The device ids are bound to
+/// the keys derived from it instead, as the device that pairs only learns the
+/// other's id from its answer.
+const PAIRING_CLIENT: &[u8] = b"fat-clip pairing client";
+const PAIRING_SERVER: &[u8] = b"fat-clip pairing server";
+
+/// Journal entries sent per message
+const BATCH_SIZE: usize = 100;
+/// Largest message accepted before the other device is authenticated
+const MAX_HANDSHAKE_SIZE: u64 = 4 * 1024;
+/// Largest message accepted in a session, enough for a batch with large images
+const MAX_MESSAGE_SIZE: u64 = 256 * 1024 * 1024;
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the listener checks whether it was stopped
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(200);
+/// How often the scheduler checks whether a sync is due
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+enum Message {
+    /// `message` is the SPAKE2 message of the device pairing
+    PairRequest {
+        version: u32,
+        device_id: String,
+        name: String,
+        message: String,
+    },
+    PairChallenge {
+        device_id: String,
+        name: String,
+        message: String,
+        proof: String,
+    },
+    PairProof {
+        proof: String,
+    },
+    PairAccepted,
+    /// `key` is the ephemeral X25519 public key of the device syncing
+    Hello {
+        version: u32,
+        device_id: String,
+        key: String,
+    },
+    Challenge {
+        device_id: String,
+        key: String,
+        proof: String,
+    },
+    Auth {
+        proof: String,
+    },
+    /// Ask for the journal entries after position `since`
+    Pull {
+        since: i64,
+    },
+    Changes {
+        entries: Vec<SyncEntry>,
+        /// Journal position of the last entry considered
+        last_seq: i64,
+        more: bool,
+    },
+    Push {
+        entries: Vec<SyncEntry>,
+    },
+    Ack,
+    Error {
+        message: String,
+    },
+}
+
+/// Which clips are shared with paired devices. Every filter that is set has to
+/// match.
+#[derive(Debug, Clone, Default)]
+pub struct ShareFilter {
+    /// Any of "plain", "rich", "image", "file"
+    pub content_types: Vec<String>,
+    /// Clips with any of these tags
+    pub tags: Vec<String>,
+}
+
+impl ShareFilter {
+    fn matches(&self, clip: &ClipItem) -> bool {
+        (self.content_types.is_empty()
+            || self
+                .content_types
+                .iter()
+                .any(|t| t.eq_ignore_ascii_case(clip.content_type.as_str())))
+            && (self.tags.is_empty() || clip.tags.iter().any(|t| self.tags.contains(t)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PeerSyncConfig {
+    pub enabled: bool,
+    /// Address of the interface to listen on. Listening on all interfaces lets
+    /// any device on the network connect, though only to pair with a code
+    /// shown on this device or to sync as a paired device.
+    pub listen_address: IpAddr,
+    pub port: u16,
+    /// Name shown on the other devices
+    pub device_name: String,
+    pub interval_minutes: u64,
+    pub filter: ShareFilter,
+}
+
+/// A paired device as shown in the UI
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerInfo {
+    pub device_id: String,
+    pub name: String,
+    pub address: Option<String>,
+    pub paired_at: DateTime<Utc>,
+    pub last_sync_at: Option<DateTime<Utc>>,
+}
+
+impl From<SyncPeer> for PeerInfo {
+    fn from(peer: SyncPeer) -> Self {
+        PeerInfo {
+            device_id: peer.device_id,
+            name: peer.name,
+            address: peer.address,
+            paired_at: peer.paired_at,
+            last_sync_at: peer.last_sync_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairingCode {
+    pub code: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+pub struct PeerSync {
+    db: Arc<Mutex<Database>>,
+    device_id: String,
+    config: Mutex<PeerSyncConfig>,
+    /// The code another device can pair with, while pairing is open
+    pairing: Mutex<Option<PairingCode>>,
+    /// Set to false to stop the running listener
+    listener: Mutex<Option<Arc<AtomicBool>>>,
+    last_sync: Mutex<Option<DateTime<Utc>>>,
+    scheduler_started: AtomicBool,
+    app_handle: Mutex<Option<tauri::AppHandle>>,
+}
+
+impl PeerSync {
+    pub fn new(db: Arc<Mutex<Database>>, device_id: String, config: PeerSyncConfig) -> Self {
+        PeerSync {
+            db,
+            device_id,
+            config: Mutex::new(config),
+            pairing: Mutex::new(None),
+            listener: Mutex::new(None),
+            last_sync: Mutex::new(None),
+            scheduler_started: AtomicBool::new(false),
+            app_handle: Mutex::new(None),
+        }
+    }
+
+    /// Start listening if enabled, and the thread that syncs with the paired
+    /// devices on schedule
+    pub fn start(self: &Arc<Self>, app: tauri::AppHandle) {
+        if let Ok(mut app_handle) = self.app_handle.lock() {
+            *app_handle = Some(app);
+        }
+        if self.scheduler_started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        if self.config().is_ok_and(|config| config.enabled) {
+            if let Err(e) = self.start_listener() {
+                eprintln!("{}", e);
+            }
+        }
+
+        let manager = self.clone();
+        thread::spawn(move || loop {
+            if manager.is_sync_due() {
+                for (peer, result) in manager.sync_all() {
+                    if let Err(e) = result {
+                        eprintln!("Sync with {} failed: {}", peer.name, e);
+                    }
+                }
+                if let Ok(mut last) = manager.last_sync.lock() {
+                    *last = Some(Utc::now());
+                }
+            }
+            thread::sleep(SCHEDULER_INTERVAL);
+        });
+    }
+
+    pub fn set_config(self: &Arc<Self>, config: PeerSyncConfig) {
+        let restart = match self.config.lock() {
+            Ok(mut current) => {
+                let restart = current.enabled != config.enabled
+                    || current.listen_address != config.listen_address
+                    || current.port != config.port;
+                *current = config.clone();
+                restart
+            }
+            Err(_) => return,
+        };
+
+        // Before `start`, the listener is started there
+        if restart && self.scheduler_started.load(Ordering::SeqCst) {
+            self.stop_listener();
+            if config.enabled {
+                if let Err(e) = self.start_listener() {
+                    eprintln!("{}", e);
+                }
+            }
+        }
+    }
+
+    fn config(&self) -> Result<PeerSyncConfig, String> {
+        Ok(self.config.lock().map_err(|e| e.to_string())?.clone())
+    }
+
+    fn is_sync_due(&self) -> bool {
+        let Ok(config) = self.config() else {
+            return false;
+        };
+        if !config.enabled {
+            return false;
+        }
+
+        let interval = chrono::Duration::minutes(config.interval_minutes.max(1) as i64);
+        self.last_sync
+            .lock()
+            .map(|last| last.is_none_or(|last| Utc::now() - last >= interval))
+            .unwrap_or(false)
+    }
+
+    /// Returns the address listened on
+    fn start_listener(self: &Arc<Self>) -> Result<SocketAddr, String> {
+        let config = self.config()?;
+        let listener = TcpListener::bind((config.listen_address, config.port))
+            .map_err(|e| format!("Failed to listen for sync on port {}: {}", config.port, e))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        let address = listener.local_addr().map_err(|e| e.to_string())?;
+
+        let running = Arc::new(AtomicBool::new(true));
+        if let Ok(mut current) = self.listener.lock() {
+            *current = Some(running.clone());
+        }
+
+        let manager = self.clone();
+        thread::spawn(move || {
+            while running.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let manager = manager.clone();
+                        thread::spawn(move || manager.handle_connection(stream));
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        thread::sleep(ACCEPT_INTERVAL);
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to accept sync connection: {}", e);
+                        thread::sleep(ACCEPT_INTERVAL);
+                    }
+                }
+            }
+        });
+
+        Ok(address)
+    }
+
+    fn stop_listener(&self) {
+        if let Some(running) = self.listener.lock().ok().and_then(|mut l| l.take()) {
+            running.store(false, Ordering::SeqCst);
+        }
+    }
+
+    fn is_listening(&self) -> bool {
+        self.listener.lock().map(|l| l.is_some()).unwrap_or(false)
+    }
+
+    fn emit(&self, event: &str) {
+        if let Some(app) = self.app_handle.lock().ok().and_then(|a| a.clone()) {
+            let _ = app.emit(event, ());
+        }
+    }
+
+    /// Open pairing: another device can pair with the returned code once,
+    /// within a few minutes
+    pub fn start_pairing(&self) -> Result<PairingCode, String> {
+        if !self.is_listening() {
+            return Err("Turn on sync over the local network first".to_string());
+        }
+
+        let random = random_bytes::<PAIRING_CODE_LENGTH>()?;
+        let code = random
+            .iter()
+            .map(|b| PAIRING_CODE_ALPHABET[*b as usize % PAIRING_CODE_ALPHABET.len()] as char)
+            .collect();
+        let pairing = PairingCode {
+            code,
+            expires_at: Utc::now() + chrono::Duration::minutes(PAIRING_CODE_LIFETIME_MINUTES),
+        };
+
+        *self.pairing.lock().map_err(|e| e.to_string())? = Some(pairing.clone());
+        Ok(pairing)
+    }
+
+    /// Pair with the device listening at `address` ("host:port") using the
+    /// code it shows
+    pub fn pair(&self, address: &str, code: &str) -> Result<PeerInfo, String> {
+        let code = normalize_code(code);
+        let config = self.config()?;
+        let mut conn = Connection::connect(address)?;
+
+        let (spake, message) = Spake2::<Ed25519Group>::start_a(
+            &Password::new(code.as_bytes()),
+            &Identity::new(PAIRING_CLIENT),
+            &Identity::new(PAIRING_SERVER),
+        );
+        conn.send(&Message::PairRequest {
+            version: PROTOCOL_VERSION,
+            device_id: self.device_id.clone(),
+            name: config.device_name,
+            message: BASE64.encode(message),
+        })?;
+
+        let Message::PairChallenge {
+            device_id: peer_id,
+            name,
+            message,
+            proof,
+        } = conn.recv()?
+        else {
+            return Err("Unexpected response from the other device".to_string());
+        };
+        let keys = pairing_keys(spake, &message, &self.device_id, &peer_id)?;
+        if !keys.verify("pair-server", &proof) {
+            return Err("Wrong pairing code".to_string());
+        }
+
+        conn.send(&Message::PairProof {
+            proof: keys.proof("pair-client")?,
+        })?;
+        let Message::PairAccepted = conn.recv()? else {
+            return Err("Unexpected response from the other device".to_string());
+        };
+
+        let peer = SyncPeer {
+            device_id: peer_id.clone(),
+            name,
+            key: BASE64.encode(keys.key("pair-key")?),
+            address: Some(address.trim().to_string()),
+            sent_seq: 0,
+            received_seq: 0,
+            paired_at: Utc::now(),
+            last_sync_at: None,
+        };
+        self.db
+            .lock()
+            .map_err(|e| e.to_string())?
+            .save_sync_peer(&peer)
+            .map_err(|e| e.to_string())?;
+
+        Ok(peer.into())
+    }
+
+    pub fn peers(&self) -> Result<Vec<PeerInfo>, String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        let peers = db.get_sync_peers().map_err(|e| e.to_string())?;
+        Ok(peers.into_iter().map(PeerInfo::from).collect())
+    }
+
+    pub fn remove_peer(&self, device_id: &str) -> Result<(), String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.delete_sync_peer(device_id).map_err(|e| e.to_string())
+    }
+
+    /// Sync with every paired device whose address is known
+    pub fn sync_all(&self) -> Vec<(PeerInfo, Result<SyncSummary, String>)> {
+        let peers = self
+            .db
+            .lock()
+            .ok()
+            .and_then(|db| db.get_sync_peers().ok())
+            .unwrap_or_default();
+
+        peers
+            .into_iter()
+            .filter(|peer| peer.address.is_some())
+            .map(|peer| {
+                let result = self.sync_peer(&peer.device_id);
+                (peer.into(), result)
+            })
+            .collect()
+    }
+
+    /// Fetch the changes of a paired device, then send ours
+    pub fn sync_peer(&self, device_id: &str) -> Result<SyncSummary, String> {
+        let mut peer = self
+            .db
+            .lock()
+            .map_err(|e| e.to_string())?
+            .get_sync_peer(device_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "This device is not paired".to_string())?;
+        let address = peer.address.clone().ok_or_else(|| {
+            "The address of this device is unknown; sync from that device instead".to_string()
+        })?;
+        let key = decode(&peer.key)?;
+
+        let mut conn = Connection::connect(&address)?;
+        let ephemeral = StaticSecret::from(random_bytes::<32>()?);
+        let public = PublicKey::from(&ephemeral);
+        conn.send(&Message::Hello {
+            version: PROTOCOL_VERSION,
+            device_id: self.device_id.clone(),
+            key: BASE64.encode(public.as_bytes()),
+        })?;
+
+        let Message::Challenge {
+            device_id: peer_id,
+            key: peer_public,
+            proof,
+        } = conn.recv()?
+        else {
+            return Err("Unexpected response from the other device".to_string());
+        };
+        if peer_id != peer.device_id {
+            return Err("Another device is listening at this address".to_string());
+        }
+
+        let peer_public = decode_public_key(&peer_public)?;
+        let keys = session_keys(
+            &key,
+            &ephemeral,
+            &peer_public,
+            [&public, &peer_public],
+            [&self.device_id, &peer_id],
+        )?;
+        if !keys.verify("server", &proof) {
+            return Err("The other device could not be verified".to_string());
+        }
+        conn.send(&Message::Auth {
+            proof: keys.proof("client")?,
+        })?;
+        conn.start_session(&keys, true)?;
+
+        let filter = self.config()?.filter;
+        let mut summary = SyncSummary::default();
+        {
+            let db = self.db.lock().map_err(|e| e.to_string())?;
+            record_local_changes(&db)?;
+        }
+
+        // Their changes
+        loop {
+            conn.send(&Message::Pull {
+                since: peer.received_seq,
+            })?;
+            let Message::Changes {
+                entries,
+                last_seq,
+                more,
+            } = conn.recv()?
+            else {
+                return Err("Unexpected response from the other device".to_string());
+            };
+
+            let db = self.db.lock().map_err(|e| e.to_string())?;
+            summary.received += apply_changes(&db, entries, &peer.device_id);
+            peer.received_seq = last_seq;
+            db.save_sync_peer(&peer).map_err(|e| e.to_string())?;
+            if !more {
+                break;
+            }
+        }
+
+        // Ours
+        loop {
+            let (entries, last_seq, more) = {
+                let db = self.db.lock().map_err(|e| e.to_string())?;
+                changes_for_peer(&db, peer.sent_seq, &peer.device_id, &filter)?
+            };
+
+            if !entries.is_empty() {
+                summary.sent += entries.len();
+                conn.send(&Message::Push { entries })?;
+                if !matches!(conn.recv()?, Message::Ack) {
+                    return Err("Unexpected response from the other device".to_string());
+                }
+            }
+
+            peer.sent_seq = last_seq;
+            self.db
+                .lock()
+                .map_err(|e| e.to_string())?
+                .save_sync_peer(&peer)
+                .map_err(|e| e.to_string())?;
+            if !more {
+                break;
+            }
+        }
+
+        peer.last_sync_at = Some(Utc::now());
+        self.db
+            .lock()
+            .map_err(|e| e.to_string())?
+            .save_sync_peer(&peer)
+            .map_err(|e| e.to_string())?;
+
+        Ok(summary)
+    }
+
+    fn handle_connection(&self, stream: TcpStream) {
+        let mut conn = match Connection::new(stream) {
+            Ok(conn) => conn,
+            Err(e) => {
+                eprintln!("Sync connection failed: {}", e);
+                return;
+            }
+        };
+
+        let result = match conn.recv() {
+            Ok(Message::PairRequest {
+                version,
+                device_id,
+                name,
+                message,
+            }) => check_version(version)
+                .and_then(|_| self.accept_pairing(&mut conn, device_id, name, &message)),
+            Ok(Message::Hello {
+                version,
+                device_id,
+                key,
+            }) => check_version(version).and_then(|_| self.serve(&mut conn, device_id, &key)),
+            Ok(_) => Err("Unexpected message".to_string()),
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            eprintln!("Sync connection failed: {}", e);
+            let _ = conn.send(&Message::Error { message: e });
+        }
+    }
+
+    fn accept_pairing(
+        &self,
+        conn: &mut Connection,
+        peer_id: String,
+        name: String,
+        peer_message: &str,
+    ) -> Result<(), String> {
+        // The code is used up by any attempt, so it can be guessed only once
+        let code = self
+            .pairing
+            .lock()
+            .map_err(|e| e.to_string())?
+            .take()
+            .filter(|pairing| pairing.expires_at > Utc::now())
+            .ok_or_else(|| "Pairing is not open on the other device".to_string())?
+            .code;
+
+        let (spake, message) = Spake2::<Ed25519Group>::start_b(
+            &Password::new(code.as_bytes()),
+            &Identity::new(PAIRING_CLIENT),
+            &Identity::new(PAIRING_SERVER),
+        );
+        let keys = pairing_keys(spake, peer_message, &peer_id, &self.device_id)?;
+        conn.send(&Message::PairChallenge {
+            device_id: self.device_id.clone(),
+            name: self.config()?.device_name,
+            message: BASE64.encode(message),
+            proof: keys.proof("pair-server")?,
+        })?;
+
+        let Message::PairProof { proof } = conn.recv()? else {
+            return Err("Unexpected message".to_string());
+        };
+        if !keys.verify("pair-client", &proof) {
+            return Err("Wrong pairing code".to_string());
+        }
+        conn.send(&Message::PairAccepted)?;
+
+        let peer = SyncPeer {
+            device_id: peer_id.clone(),
+            name,
+            key: BASE64.encode(keys.key("pair-key")?),
+            address: None,
+            sent_seq: 0,
+            received_seq: 0,
+            paired_at: Utc::now(),
+            last_sync_at: None,
+        };
+        self.db
+            .lock()
+            .map_err(|e| e.to_string())?
+            .save_sync_peer(&peer)
+            .map_err(|e| e.to_string())?;
+
+        self.emit("sync-peers-changed");
+        Ok(())
+    }
+
+    /// Answer the requests of a paired device until it disconnects
+    fn serve(
+        &self,
+        conn: &mut Connection,
+        peer_id: String,
+        peer_public: &str,
+    ) -> Result<(), String> {
+        let mut peer = self
+            .db
+            .lock()
+            .map_err(|e| e.to_string())?
+            .get_sync_peer(&peer_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "This device is not paired with the other device".to_string())?;
+        let key = decode(&peer.key)?;
+        let peer_public = decode_public_key(peer_public)?;
+
+        let ephemeral = StaticSecret::from(random_bytes::<32>()?);
+        let public = PublicKey::from(&ephemeral);
+        let keys = session_keys(
+            &key,
+            &ephemeral,
+            &peer_public,
+            [&peer_public, &public],
+            [&peer_id, &self.device_id],
+        )?;
+        conn.send(&Message::Challenge {
+            device_id: self.device_id.clone(),
+            key: BASE64.encode(public.as_bytes()),
+            proof: keys.proof("server")?,
+        })?;
+
+        let Message::Auth { proof } = conn.recv()? else {
+            return Err("Unexpected message".to_string());
+        };
+        if !keys.verify("client", &proof) {
+            return Err("Authentication failed".to_string());
+        }
+        conn.start_session(&keys, false)?;
+
+        let filter = self.config()?.filter;
+        {
+            let db = self.db.lock().map_err(|e| e.to_string())?;
+            record_local_changes(&db)?;
+        }
+
+        let mut received = 0;
+        // The other device closes the connection when it is done
+        while let Ok(message) = conn.recv() {
+            match message {
+                Message::Pull { since } => {
+                    let (entries, last_seq, more) = {
+                        let db = self.db.lock().map_err(|e| e.to_string())?;
+                        changes_for_peer(&db, since, &peer_id, &filter)?
+                    };
+                    conn.send(&Message::Changes {
+                        entries,
+                        last_seq,
+                        more,
+                    })?;
+                }
+                Message::Push { entries } => {
+                    let db = self.db.lock().map_err(|e| e.to_string())?;
+                    received += apply_changes(&db, entries, &peer_id);
+                    drop(db);
+                    conn.send(&Message::Ack)?;
+                }
+                _ => return Err("Unexpected message".to_string()),
+            }
+        }
+
+        peer.last_sync_at = Some(Utc::now());
+        self.db
+            .lock()
+            .map_err(|e| e.to_string())?
+            .save_sync_peer(&peer)
+            .map_err(|e| e.to_string())?;
+
+        if received > 0 {
+            self.emit("clipboard-updated");
+        }
+        Ok(())
+    }
+}
+
+fn apply_changes(db: &Database, entries: Vec<SyncEntry>, origin: &str) -> usize {
+    let mut applied = 0;
+    for entry in entries {
+        match apply_change(db, entry, origin) {
+            Ok(true) => applied += 1,
+            Ok(false) => {}
+            Err(e) => eprintln!("Failed to apply change from {}: {}", origin, e),
+        }
+    }
+    applied
+}
+
+/// The journal entries after position `since` that are shared with `peer_id`:
+/// changes received from the peer itself and clips outside the filter are left
+/// out. Returns the entries, the position of the last entry considered and
+/// whether there are more.
+fn changes_for_peer(
+    db: &Database,
+    since: i64,
+    peer_id: &str,
+    filter: &ShareFilter,
+) -> Result<(Vec<SyncEntry>, i64, bool), String> {
+    let records = db
+        .get_sync_changes(since, BATCH_SIZE as i64)
+        .map_err(|e| e.to_string())?;
+    let more = records.len() == BATCH_SIZE;
+    let last_seq = records.last().map_or(since, |record| record.seq);
+
+    let mut entries = Vec::new();
+    for record in records {
+        if record.origin.as_deref() == Some(peer_id) {
+            continue;
+        }
+
+        // Changes to clips that are gone are passed on as they are
+        let clip = db
+            .get_clip_by_id(&record.clip_id)
+            .map_err(|e| e.to_string())?;
+        if clip.as_ref().is_some_and(|clip| !filter.matches(clip)) {
+            continue;
+        }
+
+        let Some(entry) = journal_entry(db, &record)? else {
+            continue;
+        };
+
+        // A clip that only now has a shared tag was not sent before
+        if let (Some(clip), SyncChange::Tags { .. }) = (clip, &entry.change) {
+            if !filter.tags.is_empty() {
+                let added_at = db
+                    .get_sync_state(&clip.id)
+                    .map_err(|e| e.to_string())?
+                    .map_or(entry.at, |state| state.present_at);
+                entries.push(SyncEntry {
+                    at: added_at,
+                    change: SyncChange::Insert {
+                        clip: Box::new(crate::archive::archive_clip(clip)),
+                    },
+                });
+            }
+        }
+
+        entries.push(entry);
+    }
+
+    Ok((entries, last_seq, more))
+}
+
+/// A connection speaking the sync protocol. Once a session is started, every
+/// message is encrypted with a key for its direction and numbered, so messages
+/// cannot be read, changed, replayed or reordered.
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    session: Option<Session>,
+}
+
+struct Session {
+    sending: ChaCha20Poly1305,
+    receiving: ChaCha20Poly1305,
+    sent: u64,
+    received: u64,
+}
+
+impl Connection {
+    fn connect(address: &str) -> Result<Self, String> {
+        let addr = address
+            .trim()
+            .to_socket_addrs()
+            .map_err(|e| format!("Invalid address {}: {}", address, e))?
+            .next()
+            .ok_or_else(|| format!("Invalid address {}", address))?;
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+            .map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+        Connection::new(stream)
+    }
+
+    fn new(stream: TcpStream) -> Result<Self, String> {
+        stream.set_nonblocking(false).map_err(|e| e.to_string())?;
+        stream
+            .set_read_timeout(Some(IO_TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(IO_TIMEOUT)))
+            .map_err(|e| e.to_string())?;
+        let writer = stream.try_clone().map_err(|e| e.to_string())?;
+        Ok(Connection {
+            reader: BufReader::new(stream),
+            writer,
+            session: None,
+        })
+    }
+
+    /// Encrypt the messages from here on with keys for each direction
+    fn start_session(&mut self, keys: &Keys, is_client: bool) -> Result<(), String> {
+        let client_key = keys.key("client-messages")?;
+        let server_key = keys.key("server-messages")?;
+        let (sending, receiving) = if is_client {
+            (client_key, server_key)
+        } else {
+            (server_key, client_key)
+        };
+        self.session = Some(Session {
+            sending: ChaCha20Poly1305::new(&sending.into()),
+            receiving: ChaCha20Poly1305::new(&receiving.into()),
+            sent: 0,
+            received: 0,
+        });
+        Ok(())
+    }
+
+    fn send(&mut self, message: &Message) -> Result<(), String> {
+        let body = serde_json::to_vec(message).map_err(|e| e.to_string())?;
+        let mut line = match self.session.as_mut() {
+            Some(session) => {
+                let sealed = session
+                    .sending
+                    .encrypt(&message_nonce(session.sent), body.as_slice())
+                    .map_err(|_| "Failed to encrypt a message".to_string())?;
+                session.sent += 1;
+                BASE64.encode(sealed).into_bytes()
+            }
+            None => body,
+        };
+
+        line.push(b'\n');
+        self.writer
+            .write_all(&line)
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| format!("Failed to send: {}", e))
+    }
+
+    fn recv(&mut self) -> Result<Message, String> {
+        let limit = if self.session.is_some() {
+            MAX_MESSAGE_SIZE
+        } else {
+            MAX_HANDSHAKE_SIZE
+        };
+
+        let mut line = Vec::new();
+        (&mut self.reader)
+            .take(limit)
+            .read_until(b'\n', &mut line)
+            .map_err(|e| format!("Failed to receive: {}", e))?;
+        if line.pop() != Some(b'\n') {
+            if line.len() as u64 + 1 >= limit {
+                return Err("A message was too large".to_string());
+            }
+            return Err("Connection closed".to_string());
+        }
+
+        let body = match self.session.as_mut() {
+            Some(session) => {
+                let sealed = BASE64
+                    .decode(&line)
+                    .map_err(|_| "Invalid message".to_string())?;
+                let body = session
+                    .receiving
+                    .decrypt(&message_nonce(session.received), sealed.as_slice())
+                    .map_err(|_| "A message failed authentication".to_string())?;
+                session.received += 1;
+                body
+            }
+            None => line,
+        };
+
+        match serde_json::from_slice(&body).map_err(|e| format!("Invalid message: {}", e))? {
+            Message::Error { message } => Err(message),
+            message => Ok(message),
+        }
+    }
+}
+
+/// Messages are numbered in each direction, which have their own keys
+fn message_nonce(number: u64) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&number.to_be_bytes());
+    nonce.into()
+}
+
+/// Keys derived with HKDF-SHA256 from a secret shared by two devices, bound
+/// to the context of the exchange
+struct Keys {
+    hkdf: Hkdf<Sha256>,
+    context: Vec<u8>,
+}
+
+impl Keys {
+    /// `context` is length-prefixed, so its parts cannot run into each other
+    fn new(salt: Option<&[u8]>, secret: &[u8], context: &[&[u8]]) -> Self {
+        let mut encoded = Vec::new();
+        for part in context {
+            encoded.extend_from_slice(&(part.len() as u64).to_be_bytes());
+            encoded.extend_from_slice(part);
+        }
+        Keys {
+            hkdf: Hkdf::new(salt, secret),
+            context: encoded,
+        }
+    }
+
+    fn key(&self, label: &str) -> Result<[u8; 32], String> {
+        let mut key = [0u8; 32];
+        self.hkdf
+            .expand_multi_info(
+                &[b"fat-clip ", label.as_bytes(), b"\0", &self.context],
+                &mut key,
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(key)
+    }
+
+    fn confirmation(&self, label: &str) -> Result<Hmac<Sha256>, String> {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.key("confirm")?)
+            .map_err(|e| e.to_string())?;
+        mac.update(label.as_bytes());
+        Ok(mac)
+    }
+
+    /// Proof for the other device that this one has the keys
+    fn proof(&self, label: &str) -> Result<String, String> {
+        Ok(BASE64.encode(self.confirmation(label)?.finalize().into_bytes()))
+    }
+
+    /// Check a base64 encoded proof in constant time
+    fn verify(&self, label: &str, proof: &str) -> bool {
+        match (self.confirmation(label), BASE64.decode(proof)) {
+            (Ok(mac), Ok(proof)) => mac.verify_slice(&proof).is_ok(),
+            _ => false,
+        }
+    }
+}
+
+/// Keys for pairing, from SPAKE2 with the other device's message. They are
+/// the same on both devices only if both used the same code.
+fn pairing_keys(
+    spake: Spake2<Ed25519Group>,
+    peer_message: &str,
+    client_id: &str,
+    server_id: &str,
+) -> Result<Keys, String> {
+    let secret = spake
+        .finish(&decode(peer_message)?)
+        .map_err(|_| "Invalid message".to_string())?;
+    Ok(Keys::new(
+        None,
+        &secret,
+        &[b"pairing", client_id.as_bytes(), server_id.as_bytes()],
+    ))
+}
+
+/// Keys for a session between paired devices: X25519 of the ephemeral keys of
+/// both, with the key for the pair as salt, bound to the keys and the device
+/// ids (client first)
+fn session_keys(
+    pair_key: &[u8],
+    ephemeral: &StaticSecret,
+    peer_public: &PublicKey,
+    public_keys: [&PublicKey; 2],
+    device_ids: [&str; 2],
+) -> Result<Keys, String> {
+    let shared = ephemeral.diffie_hellman(peer_public);
+    if !shared.was_contributory() {
+        return Err("Invalid message".to_string());
+    }
+    Ok(Keys::new(
+        Some(pair_key),
+        shared.as_bytes(),
+        &[
+            b"session",
+            public_keys[0].as_bytes(),
+            public_keys[1].as_bytes(),
+            device_ids[0].as_bytes(),
+            device_ids[1].as_bytes(),
+        ],
+    ))
+}
+
+fn decode_public_key(value: &str) -> Result<PublicKey, String> {
+    let bytes: [u8; 32] = decode(value)?
+        .try_into()
+        .map_err(|_| "Invalid message".to_string())?;
+    Ok(PublicKey::from(bytes))
+}
+
+fn check_version(version: u32) -> Result<(), String> {
+    if version != PROTOCOL_VERSION {
+        return Err("The other device runs an incompatible version of Fat Clip".to_string());
+    }
+    Ok(())
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], String> {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, String> {
+    BASE64
+        .decode(value)
+        .map_err(|_| "Invalid message".to_string())
+}
+
+/// Pairing codes are entered without regard to case, spaces or dashes
+fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Name of this computer, shown on paired devices unless another is set
+pub fn default_device_name() -> String {
+    ["COMPUTERNAME", "HOSTNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|name| !name.is_empty()))
+        .or_else(|| {
+            std::fs::read_to_string("/etc/hostname")
+                .ok()
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+        })
+        .unwrap_or_else(|| "Fat Clip".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{generate_content_hash, ContentType};
+    use std::net::Ipv4Addr;
+
+    struct Device {
+        _dir: tempfile::TempDir,
+        sync: Arc<PeerSync>,
+    }
+
+    impl Device {
+        fn new(device_id: &str, filter: ShareFilter) -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let db = Database::new(dir.path().to_path_buf()).unwrap();
+            let config = PeerSyncConfig {
+                enabled: true,
+                listen_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+                port: 0,
+                device_name: device_id.to_string(),
+                interval_minutes: 5,
+                filter,
+            };
+            Device {
+                _dir: dir,
+                sync: Arc::new(PeerSync::new(
+                    Arc::new(Mutex::new(db)),
+                    device_id.to_string(),
+                    config,
+                )),
+            }
+        }
+
+        fn insert(&self, text: &str, tags: &[&str]) -> String {
+            let clip = ClipItem {
+                id: generate_content_hash(text),
+                content_type: ContentType::Plain,
+                content: text.to_string(),
+                preview_text: text.to_string(),
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                source_app: String::new(),
+                created_at: Utc::now(),
+                last_used_at: Utc::now(),
+                pinned: false,
+                metadata: None,
+                use_count: 0,
+            };
+            self.sync.db.lock().unwrap().insert_clip(&clip).unwrap();
+            clip.id
+        }
+
+        fn clip(&self, id: &str) -> Option<ClipItem> {
+            self.sync.db.lock().unwrap().get_clip_by_id(id).unwrap()
+        }
+    }
+
+    /// `a` pairs with `b`, which listens on the loopback interface
+    fn pair(a: &Device, b: &Device) {
+        let address = b.sync.start_listener().unwrap();
+        let code = b.sync.start_pairing().unwrap().code;
+        a.sync.pair(&address.to_string(), &code).unwrap();
+    }
+
+    #[test]
+    fn paired_devices_exchange_new_changes() {
+        let a = Device::new("a", ShareFilter::default());
+        let b = Device::new("b", ShareFilter::default());
+        pair(&a, &b);
+        assert_eq!(b.sync.peers().unwrap()[0].device_id, "a");
+
+        let first = a.insert("from a", &[]);
+        let summary = a.sync.sync_peer("b").unwrap();
+        assert_eq!((summary.received, summary.sent), (0, 1));
+        assert_eq!(b.clip(&first).unwrap().content, "from a");
+
+        let second = b.insert("from b", &[]);
+        let summary = a.sync.sync_peer("b").unwrap();
+        assert_eq!((summary.received, summary.sent), (1, 0));
+        assert_eq!(a.clip(&second).unwrap().content, "from b");
+
+        let summary = a.sync.sync_peer("b").unwrap();
+        assert_eq!((summary.received, summary.sent), (0, 0));
+    }
+
+    #[test]
+    fn only_clips_matching_the_filter_are_shared() {
+        let filter = ShareFilter {
+            content_types: vec!["plain".to_string()],
+            tags: vec!["shared".to_string()],
+        };
+        let a = Device::new("a", filter);
+        let b = Device::new("b", ShareFilter::default());
+        pair(&a, &b);
+
+        let shared = a.insert("shared", &["shared"]);
+        let private = a.insert("private", &[]);
+        assert_eq!(a.sync.sync_peer("b").unwrap().sent, 1);
+        assert!(b.clip(&shared).is_some());
+        assert!(b.clip(&private).is_none());
+
+        // Tagging it later sends the whole clip
+        a.sync
+            .db
+            .lock()
+            .unwrap()
+            .update_clip_tags(&private, vec!["shared".to_string()])
+            .unwrap();
+        a.sync.sync_peer("b").unwrap();
+        assert_eq!(b.clip(&private).unwrap().tags, vec!["shared"]);
+    }
+
+    #[test]
+    fn pairing_codes_work_once() {
+        let a = Device::new("a", ShareFilter::default());
+        let b = Device::new("b", ShareFilter::default());
+        let address = b.sync.start_listener().unwrap().to_string();
+        let code = b.sync.start_pairing().unwrap().code;
+
+        let wrong = if code.starts_with('A') { "B" } else { "A" };
+        let guess = format!("{}{}", wrong, &code[1..]);
+        assert!(a.sync.pair(&address, &guess).is_err());
+        assert!(a.sync.pair(&address, &code).is_err());
+        assert!(a.sync.peers().unwrap().is_empty());
+        assert!(b.sync.peers().unwrap().is_empty());
+    }
+
+    #[test]
+    fn codes_ignore_case_and_separators() {
+        assert_eq!(normalize_code("abcd-2345"), "ABCD2345");
+        assert_eq!(normalize_code(" AB CD 23 45 "), "ABCD2345");
+    }
+
+    #[test]
+    fn proofs_are_bound_to_the_keys_and_label() {
+        let keys = Keys::new(None, b"secret", &[b"a", b"b"]);
+        let proof = keys.proof("server").unwrap();
+        assert!(keys.verify("server", &proof));
+        assert!(!keys.verify("client", &proof));
+
+        let truncated = BASE64.decode(&proof).unwrap();
+        assert!(!keys.verify("server", &BASE64.encode(&truncated[..31])));
+        assert!(!keys.verify("server", "not base64!"));
+
+        // The parts of the context do not run into each other
+        let other = Keys::new(None, b"secret", &[b"ab", b""]);
+        assert!(!other.verify("server", &proof));
+        assert_ne!(keys.key("pair-key"), other.key("pair-key"));
+    }
+
+    #[test]
+    fn handshake_messages_are_limited_in_size() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut conn = Connection::new(listener.accept().unwrap().0).unwrap();
+
+        client
+            .write_all(&vec![b'x'; MAX_HANDSHAKE_SIZE as usize + 1])
+            .unwrap();
+        assert_eq!(conn.recv().err().unwrap(), "A message was too large");
+    }
+}
@@ -0,0 +1,1102 @@
+//! Plugins: clip processors installed in `<app dir>/plugins/<name>/`, each
+//! with a `plugin.json` manifest and a WebAssembly module. Modules run inside
+//! the app in a sandbox (wasmi): they cannot reach files, the network or other
+//! programs, only the functions the app provides, and only those their
+//! manifest declares permissions for. Every call has a fuel budget and memory
+//! is capped, so a plugin that traps or never returns fails that call without
+//! affecting the app.
+//!
+//! A module exports its `memory`, `alloc(len) -> ptr` for the app to pass a
+//! request in, and `call(ptr, len) -> i64`. A request is the JSON object
+//! `{"method": "...", "params": {...}}`, answered with `ptr << 32 | len` of
+//! `{"result": ...}` or `{"error": "..."}`:
+//!
+//! - `describe`: the transforms and actions the plugin provides
+//! - `process`: a new clip, to classify and attach metadata to
+//! - `transform`: `{transform, text}`, returns `{text}`
+//! - `action`: `{action, clip}`, may return `{text, message}`
+//!
+//! The app provides these functions in the `fat_clip` module. A module that
+//! imports one its manifest does not allow fails to load.
+//!
+//! - `log(ptr, len)`: write a message to the app's log
+//! - `clip_text(ptr, len) -> i32` (`read_content`): copy the text of the clip
+//!   being processed or acted on to `ptr` if it fits in `len` bytes. Returns
+//!   its length, or -1 without a clip.
+//! - `set_kind(ptr, len) -> i32` (`classify`): set the kind of the clip being
+//!   processed. Returns 0, or -1 on invalid text.
+//! - `set_metadata(ptr, len) -> i32` (`metadata`): attach a JSON object to the
+//!   clip being processed. Returns 0, or -1 on invalid JSON.
+
+use crate::clipboard::{clip_kind, clip_plain_text};
+use crate::db::{get_app_dir, ClipItem, Database};
+use crate::transforms::{self, TransformInfo};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tauri::Emitter;
+use wasmi::{
+    Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+    TypedFunc,
+};
+
+const MANIFEST_FILE: &str = "plugin.json";
+
+/// Module the host functions are imported from
+const HOST_MODULE: &str = "fat_clip";
+
+/// Prefix of plugin transform ids: `plugin:<plugin id>:<transform id>`
+const TRANSFORM_PREFIX: &str = "plugin:";
+
+// Fuel for a call, about the number of instructions it may run
+const START_FUEL: u64 = 100_000_000;
+/// New clips are processed in the background, but a slow plugin delays the
+/// others
+const PROCESS_FUEL: u64 = 200_000_000;
+const CALL_FUEL: u64 = 1_000_000_000;
+
+/// Largest memory a plugin may grow to
+const MAX_MEMORY_SIZE: usize = 64 * 1024 * 1024;
+
+/// Largest response accepted from a plugin
+const MAX_RESPONSE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Failed starts or calls in a row after which a plugin is not restarted
+/// until it is enabled again or the plugins are reloaded
+const MAX_FAILURES: u32 = 3;
+
+fn default_module() -> String {
+    "plugin.wasm".to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PluginPermission {
+    /// Read the text of clips
+    ReadContent,
+    /// Set the kind of new clips
+    Classify,
+    /// Attach metadata to new clips
+    Metadata,
+    /// Provide text transforms
+    Transforms,
+    /// Provide actions on clips
+    Actions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginManifest {
+    /// Letters, digits, '.', '-' and '_'
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    /// The WebAssembly module, relative to the plugin directory
+    #[serde(default = "default_module")]
+    pub module: String,
+    #[serde(default)]
+    pub permissions: Vec<PluginPermission>,
+}
+
+impl PluginManifest {
+    fn load(dir: &Path) -> Result<Self, String> {
+        let path = dir.join(MANIFEST_FILE);
+        let data = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let manifest: PluginManifest = serde_json::from_str(&data)
+            .map_err(|e| format!("Invalid manifest {}: {}", path.display(), e))?;
+
+        let valid_id = !manifest.id.is_empty()
+            && manifest
+                .id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+        if !valid_id {
+            return Err(format!("Invalid plugin id '{}'", manifest.id));
+        }
+        // The module has to ship with the plugin
+        let inside_dir = !manifest.module.is_empty()
+            && Path::new(&manifest.module)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)));
+        if !inside_dir {
+            return Err(format!(
+                "Plugin '{}' has an invalid module path",
+                manifest.id
+            ));
+        }
+        Ok(manifest)
+    }
+
+    fn allows(&self, permission: PluginPermission) -> bool {
+        self.permissions.contains(&permission)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginTransform {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginAction {
+    pub id: String,
+    pub name: String,
+    /// Clip types the action applies to, all if empty
+    #[serde(default)]
+    pub content_types: Vec<String>,
+}
+
+/// Answer to `describe`
+#[derive(Debug, Clone, Default, Deserialize)]
+struct PluginFeatures {
+    #[serde(default)]
+    transforms: Vec<PluginTransform>,
+    #[serde(default)]
+    actions: Vec<PluginAction>,
+}
+
+/// What `process` set through the host functions
+#[derive(Debug, Default)]
+struct ProcessResult {
+    kind: Option<String>,
+    metadata: Option<serde_json::Map<String, Value>>,
+}
+
+/// Answer to `action`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PluginActionResult {
+    /// Text produced by the action, e.g. to copy or save as a clip
+    pub text: Option<String>,
+    /// Message to show
+    pub message: Option<String>,
+}
+
+/// An action offered for a clip
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginActionInfo {
+    pub plugin_id: String,
+    pub plugin_name: String,
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginInfo {
+    pub manifest: PluginManifest,
+    pub dir: String,
+    pub enabled: bool,
+    pub loaded: bool,
+    /// Why the plugin is not loaded, if it failed
+    pub error: Option<String>,
+    pub transforms: Vec<PluginTransform>,
+    pub actions: Vec<PluginAction>,
+}
+
+/// What the host functions of a plugin work on during a call
+#[derive(Default)]
+struct HostState {
+    plugin_name: String,
+    /// Text of the clip the call is about, with `read_content`
+    clip_text: Option<String>,
+    result: ProcessResult,
+    limits: StoreLimits,
+}
+
+/// A loaded plugin module
+struct PluginInstance {
+    store: Store<HostState>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    call: TypedFunc<(i32, i32), i64>,
+}
+
+impl PluginInstance {
+    fn load(manifest: &PluginManifest, dir: &Path) -> Result<Self, String> {
+        let path = dir.join(&manifest.module);
+        let wasm = std::fs::read(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, &wasm).map_err(|e| format!("Invalid module: {}", e))?;
+
+        let mut store = Store::new(
+            &engine,
+            HostState {
+                plugin_name: manifest.name.clone(),
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(MAX_MEMORY_SIZE)
+                    .build(),
+                ..HostState::default()
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(START_FUEL).map_err(|e| e.to_string())?;
+
+        let instance = host_functions(&engine, manifest)?
+            .instantiate(&mut store, &module)
+            .and_then(|instance| instance.start(&mut store))
+            .map_err(|e| format!("Failed to load the module: {}", e))?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or_else(|| "The module exports no memory".to_string())?;
+        let alloc = instance
+            .get_typed_func(&store, "alloc")
+            .map_err(|e| format!("The module exports no alloc function: {}", e))?;
+        let call = instance
+            .get_typed_func(&store, "call")
+            .map_err(|e| format!("The module exports no call function: {}", e))?;
+
+        Ok(PluginInstance {
+            store,
+            memory,
+            alloc,
+            call,
+        })
+    }
+
+    /// Run a request with `clip_text` available to the plugin. The outer error
+    /// means the plugin misbehaved, the inner one is an error reported by the
+    /// plugin.
+    fn call(
+        &mut self,
+        method: &str,
+        params: Value,
+        clip_text: Option<String>,
+        fuel: u64,
+    ) -> Result<Result<Value, String>, String> {
+        let request = json!({ "method": method, "params": params }).to_string();
+        let length = i32::try_from(request.len()).map_err(|_| "The request is too large")?;
+
+        self.store.set_fuel(fuel).map_err(|e| e.to_string())?;
+        let state = self.store.data_mut();
+        state.clip_text = clip_text;
+        state.result = ProcessResult::default();
+
+        let pointer = self
+            .alloc
+            .call(&mut self.store, length)
+            .map_err(|e| format!("'{}' failed: {}", method, e))?;
+        self.memory
+            .write(&mut self.store, pointer as u32 as usize, request.as_bytes())
+            .map_err(|_| "alloc returned memory out of bounds".to_string())?;
+        let answer = self
+            .call
+            .call(&mut self.store, (pointer, length))
+            .map_err(|e| format!("'{}' failed: {}", method, e))? as u64;
+
+        let (pointer, length) = ((answer >> 32) as usize, (answer & 0xffff_ffff) as usize);
+        if length > MAX_RESPONSE_SIZE {
+            return Err("The response is too large".to_string());
+        }
+        let mut response = vec![0; length];
+        self.memory
+            .read(&self.store, pointer, &mut response)
+            .map_err(|_| "The response is out of bounds".to_string())?;
+
+        let response: Value = serde_json::from_slice(&response)
+            .map_err(|e| format!("Invalid response from the plugin: {}", e))?;
+        if let Some(error) = response.get("error") {
+            let message = error.as_str().map(str::to_string);
+            return Ok(Err(message.unwrap_or_else(|| error.to_string())));
+        }
+        Ok(Ok(response.get("result").cloned().unwrap_or(Value::Null)))
+    }
+
+    /// What the last call set through the host functions
+    fn take_result(&mut self) -> ProcessResult {
+        std::mem::take(&mut self.store.data_mut().result)
+    }
+}
+
+/// The host functions a plugin may import, given its permissions
+fn host_functions(engine: &Engine, manifest: &PluginManifest) -> Result<Linker<HostState>, String> {
+    let mut linker = Linker::new(engine);
+    linker
+        .func_wrap(
+            HOST_MODULE,
+            "log",
+            |caller: Caller<'_, HostState>, pointer: i32, length: i32| {
+                if let Some(message) = read_text(&caller, pointer, length) {
+                    eprintln!("Plugin '{}': {}", caller.data().plugin_name, message);
+                }
+            },
+        )
+        .map_err(|e| e.to_string())?;
+
+    if manifest.allows(PluginPermission::ReadContent) {
+        linker
+            .func_wrap(
+                HOST_MODULE,
+                "clip_text",
+                |mut caller: Caller<'_, HostState>, pointer: i32, capacity: i32| -> i32 {
+                    let Some(text) = caller.data().clip_text.clone() else {
+                        return -1;
+                    };
+                    if text.len() <= capacity.max(0) as usize
+                        && write_bytes(&mut caller, pointer, text.as_bytes()).is_none()
+                    {
+                        return -1;
+                    }
+                    i32::try_from(text.len()).unwrap_or(i32::MAX)
+                },
+            )
+            .map_err(|e| e.to_string())?;
+    }
+
+    if manifest.allows(PluginPermission::Classify) {
+        linker
+            .func_wrap(
+                HOST_MODULE,
+                "set_kind",
+                |mut caller: Caller<'_, HostState>, pointer: i32, length: i32| -> i32 {
+                    match read_text(&caller, pointer, length) {
+                        Some(kind) => {
+                            caller.data_mut().result.kind = Some(kind);
+                            0
+                        }
+                        None => -1,
+                    }
+                },
+            )
+            .map_err(|e| e.to_string())?;
+    }
+
+    if manifest.allows(PluginPermission::Metadata) {
+        linker
+            .func_wrap(
+                HOST_MODULE,
+                "set_metadata",
+                |mut caller: Caller<'_, HostState>, pointer: i32, length: i32| -> i32 {
+                    let metadata = read_text(&caller, pointer, length)
+                        .and_then(|json| serde_json::from_str(&json).ok());
+                    match metadata {
+                        Some(metadata) => {
+                            caller.data_mut().result.metadata = Some(metadata);
+                            0
+                        }
+                        None => -1,
+                    }
+                },
+            )
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(linker)
+}
+
+fn caller_memory(caller: &Caller<'_, HostState>) -> Option<Memory> {
+    caller.get_export("memory").and_then(Extern::into_memory)
+}
+
+/// UTF-8 text from the memory of the calling plugin
+fn read_text(caller: &Caller<'_, HostState>, pointer: i32, length: i32) -> Option<String> {
+    let length = usize::try_from(length)
+        .ok()
+        .filter(|l| *l <= MAX_RESPONSE_SIZE)?;
+    let mut bytes = vec![0; length];
+    caller_memory(caller)?
+        .read(caller, pointer as u32 as usize, &mut bytes)
+        .ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+fn write_bytes(caller: &mut Caller<'_, HostState>, pointer: i32, bytes: &[u8]) -> Option<()> {
+    caller_memory(caller)?
+        .write(caller, pointer as u32 as usize, bytes)
+        .ok()
+}
+
+#[derive(Default)]
+struct PluginState {
+    enabled: bool,
+    instance: Option<PluginInstance>,
+    features: PluginFeatures,
+    failures: u32,
+    error: Option<String>,
+}
+
+struct Plugin {
+    manifest: PluginManifest,
+    dir: PathBuf,
+    state: Mutex<PluginState>,
+}
+
+impl Plugin {
+    /// Run a call on the plugin about `clip`, loading it if needed
+    fn call(
+        &self,
+        method: &str,
+        params: Value,
+        clip: Option<&ClipItem>,
+        fuel: u64,
+    ) -> Result<Value, String> {
+        let clip_text = clip.and_then(|clip| self.clip_text(clip));
+        self.with_instance(|instance, _| instance.call(method, params, clip_text, fuel))
+    }
+
+    /// Let the plugin classify a clip and add metadata to it
+    fn process(&self, clip: &ClipItem) -> Result<ProcessResult, String> {
+        let params = self.clip_params(clip);
+        let clip_text = self.clip_text(clip);
+        self.with_instance(|instance, _| {
+            Ok(instance
+                .call("process", params, clip_text, PROCESS_FUEL)?
+                .map(|_| instance.take_result()))
+        })
+    }
+
+    /// Transforms and actions, loading the plugin to ask for them
+    fn features(&self) -> PluginFeatures {
+        self.with_instance(|_, features| Ok(Ok(features.clone())))
+            .unwrap_or_default()
+    }
+
+    /// Run `f` on the loaded plugin. A plugin that fails is dropped and
+    /// loaded again on the next use, up to `MAX_FAILURES` times in a row.
+    fn with_instance<T>(
+        &self,
+        f: impl FnOnce(&mut PluginInstance, &PluginFeatures) -> Result<Result<T, String>, String>,
+    ) -> Result<T, String> {
+        let mut state = self.state.lock().map_err(|e| e.to_string())?;
+        if !state.enabled {
+            return Err(format!("Plugin '{}' is disabled", self.manifest.name));
+        }
+        if state.failures >= MAX_FAILURES {
+            return Err(state
+                .error
+                .clone()
+                .unwrap_or_else(|| format!("Plugin '{}' keeps failing", self.manifest.name)));
+        }
+
+        let result = self.ensure_loaded(&mut state).and_then(|_| {
+            let state = &mut *state;
+            let instance = state.instance.as_mut().ok_or("Not loaded")?;
+            f(instance, &state.features)
+        });
+
+        match result {
+            Ok(result) => {
+                state.failures = 0;
+                state.error = None;
+                result.map_err(|e| format!("{}: {}", self.manifest.name, e))
+            }
+            Err(e) => {
+                let e = format!("{}: {}", self.manifest.name, e);
+                eprintln!("Plugin failed: {}", e);
+                // A trap can leave the module's memory in any state
+                state.instance = None;
+                state.failures += 1;
+                state.error = Some(e.clone());
+                Err(e)
+            }
+        }
+    }
+
+    fn ensure_loaded(&self, state: &mut PluginState) -> Result<(), String> {
+        if state.instance.is_some() {
+            return Ok(());
+        }
+
+        let mut instance = PluginInstance::load(&self.manifest, &self.dir)?;
+        let features = instance
+            .call("describe", json!({}), None, START_FUEL)?
+            .unwrap_or_else(|e| {
+                eprintln!(
+                    "Plugin '{}' failed to describe itself: {}",
+                    self.manifest.name, e
+                );
+                Value::Null
+            });
+        let mut features: PluginFeatures = serde_json::from_value(features).unwrap_or_default();
+        if !self.manifest.allows(PluginPermission::Transforms) {
+            features.transforms.clear();
+        }
+        if !self.manifest.allows(PluginPermission::Actions) {
+            features.actions.clear();
+        }
+
+        state.features = features;
+        state.instance = Some(instance);
+        Ok(())
+    }
+
+    fn set_enabled(&self, enabled: bool) {
+        if let Ok(mut state) = self.state.lock() {
+            if state.enabled != enabled {
+                // Enabling again gives a failing plugin a new chance
+                *state = PluginState {
+                    enabled,
+                    ..PluginState::default()
+                };
+            }
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.state.lock().map(|s| s.enabled).unwrap_or(false)
+    }
+
+    /// The clip as sent to the plugin, without its content
+    fn clip_params(&self, clip: &ClipItem) -> Value {
+        json!({
+            "id": clip.id,
+            "content_type": clip.content_type.as_str(),
+            "kind": clip_kind(clip),
+            "tags": clip.tags,
+            "source_app": clip.source_app,
+            "created_at": clip.created_at.to_rfc3339(),
+            "pinned": clip.pinned,
+        })
+    }
+
+    /// The text `clip_text` gives the plugin, only with `read_content`
+    fn clip_text(&self, clip: &ClipItem) -> Option<String> {
+        if !self.manifest.allows(PluginPermission::ReadContent) {
+            return None;
+        }
+        // Images have no text; their file path stands in for it
+        Some(clip_plain_text(clip).unwrap_or_else(|_| clip.content.clone()))
+    }
+}
+
+pub struct PluginHost {
+    db: Arc<Mutex<Database>>,
+    plugins: Arc<Mutex<Vec<Arc<Plugin>>>>,
+    enabled_ids: Mutex<Vec<String>>,
+    queue: Mutex<Option<mpsc::Sender<ClipItem>>>,
+    app_handle: Arc<Mutex<Option<tauri::AppHandle>>>,
+}
+
+impl PluginHost {
+    pub fn new(db: Arc<Mutex<Database>>) -> Self {
+        PluginHost {
+            db,
+            plugins: Arc::new(Mutex::new(Vec::new())),
+            enabled_ids: Mutex::new(Vec::new()),
+            queue: Mutex::new(None),
+            app_handle: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn set_app_handle(&self, app: tauri::AppHandle) {
+        if let Ok(mut app_handle) = self.app_handle.lock() {
+            *app_handle = Some(app);
+        }
+    }
+
+    /// Find the installed plugins, dropping the loaded ones
+    pub fn load(&self) {
+        let dir = plugins_dir();
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            eprintln!("Failed to create {}: {}", dir.display(), e);
+        }
+
+        let mut plugins: Vec<Arc<Plugin>> = Vec::new();
+        let entries = std::fs::read_dir(&dir).into_iter().flatten().flatten();
+        for entry in entries {
+            let path = entry.path();
+            if !path.is_dir() {
+                continue;
+            }
+            match PluginManifest::load(&path) {
+                Ok(manifest) if plugins.iter().any(|p| p.manifest.id == manifest.id) => {
+                    eprintln!(
+                        "Skipped {}: plugin '{}' is installed twice",
+                        path.display(),
+                        manifest.id
+                    );
+                }
+                Ok(manifest) => plugins.push(Arc::new(Plugin {
+                    manifest,
+                    dir: path,
+                    state: Mutex::new(PluginState::default()),
+                })),
+                Err(e) => eprintln!("Skipped plugin: {}", e),
+            }
+        }
+        plugins.sort_by(|a, b| a.manifest.name.cmp(&b.manifest.name));
+
+        let enabled_ids = self
+            .enabled_ids
+            .lock()
+            .map(|ids| ids.clone())
+            .unwrap_or_default();
+        for plugin in &plugins {
+            plugin.set_enabled(enabled_ids.contains(&plugin.manifest.id));
+        }
+        if let Ok(mut current) = self.plugins.lock() {
+            *current = plugins;
+        }
+    }
+
+    /// Enable the plugins with these ids and disable the others
+    pub fn set_enabled(&self, ids: &[String]) {
+        if let Ok(mut enabled_ids) = self.enabled_ids.lock() {
+            *enabled_ids = ids.to_vec();
+        }
+        for plugin in self.all() {
+            plugin.set_enabled(ids.contains(&plugin.manifest.id));
+        }
+    }
+
+    fn all(&self) -> Vec<Arc<Plugin>> {
+        self.plugins
+            .lock()
+            .map(|plugins| plugins.clone())
+            .unwrap_or_default()
+    }
+
+    fn enabled(&self) -> Vec<Arc<Plugin>> {
+        self.all()
+            .into_iter()
+            .filter(|plugin| plugin.is_enabled())
+            .collect()
+    }
+
+    fn find(&self, id: &str) -> Result<Arc<Plugin>, String> {
+        self.all()
+            .into_iter()
+            .find(|plugin| plugin.manifest.id == id)
+            .ok_or_else(|| format!("Plugin '{}' is not installed", id))
+    }
+
+    pub fn plugins(&self) -> Vec<PluginInfo> {
+        self.all()
+            .iter()
+            .map(|plugin| {
+                let state = plugin.state.lock();
+                let (enabled, loaded, error, features) = match &state {
+                    Ok(state) => (
+                        state.enabled,
+                        state.instance.is_some(),
+                        state.error.clone(),
+                        state.features.clone(),
+                    ),
+                    Err(_) => (false, false, None, PluginFeatures::default()),
+                };
+                PluginInfo {
+                    manifest: plugin.manifest.clone(),
+                    dir: plugin.dir.to_string_lossy().to_string(),
+                    enabled,
+                    loaded,
+                    error,
+                    transforms: features.transforms,
+                    actions: features.actions,
+                }
+            })
+            .collect()
+    }
+
+    /// The transforms of the enabled plugins, for the transform picker
+    pub fn transforms(&self) -> Vec<TransformInfo> {
+        self.enabled()
+            .iter()
+            .flat_map(|plugin| {
+                plugin
+                    .features()
+                    .transforms
+                    .into_iter()
+                    .map(|transform| TransformInfo {
+                        id: format!(
+                            "{}{}:{}",
+                            TRANSFORM_PREFIX, plugin.manifest.id, transform.id
+                        ),
+                        name: format!("{} ({})", transform.name, plugin.manifest.name),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Apply a chain of built-in and plugin transforms in order
+    pub fn apply_transforms(&self, text: &str, operations: &[String]) -> Result<String, String> {
+        if operations.is_empty() {
+            return Err("No transformation selected".to_string());
+        }
+
+        let mut text = text.to_string();
+        for operation in operations {
+            text = match operation.strip_prefix(TRANSFORM_PREFIX) {
+                Some(operation) => {
+                    let (plugin_id, transform_id) = operation
+                        .split_once(':')
+                        .ok_or_else(|| format!("Unknown transformation '{}'", operation))?;
+                    let plugin = self.find(plugin_id)?;
+                    if !plugin.manifest.allows(PluginPermission::Transforms) {
+                        return Err(format!(
+                            "Plugin '{}' may not transform text",
+                            plugin.manifest.name
+                        ));
+                    }
+                    let result = plugin.call(
+                        "transform",
+                        json!({ "transform": transform_id, "text": text }),
+                        None,
+                        CALL_FUEL,
+                    )?;
+                    result
+                        .get("text")
+                        .and_then(Value::as_str)
+                        .map(str::to_string)
+                        .ok_or_else(|| {
+                            format!("{}: the transform returned no text", plugin.manifest.name)
+                        })?
+                }
+                None => transforms::apply_transforms(&text, std::slice::from_ref(operation))?,
+            };
+        }
+        Ok(text)
+    }
+
+    /// Actions of the enabled plugins that apply to a clip
+    pub fn actions(&self, clip: &ClipItem) -> Vec<PluginActionInfo> {
+        self.enabled()
+            .iter()
+            .flat_map(|plugin| {
+                plugin
+                    .features()
+                    .actions
+                    .into_iter()
+                    .filter(|action| {
+                        action.content_types.is_empty()
+                            || action
+                                .content_types
+                                .iter()
+                                .any(|t| t.eq_ignore_ascii_case(clip.content_type.as_str()))
+                    })
+                    .map(|action| PluginActionInfo {
+                        plugin_id: plugin.manifest.id.clone(),
+                        plugin_name: plugin.manifest.name.clone(),
+                        id: action.id,
+                        name: action.name,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    pub fn run_action(
+        &self,
+        plugin_id: &str,
+        action_id: &str,
+        clip: &ClipItem,
+    ) -> Result<PluginActionResult, String> {
+        let plugin = self.find(plugin_id)?;
+        if !plugin.manifest.allows(PluginPermission::Actions) {
+            return Err(format!(
+                "Plugin '{}' may not provide actions",
+                plugin.manifest.name
+            ));
+        }
+
+        let result = plugin.call(
+            "action",
+            json!({ "action": action_id, "clip": plugin.clip_params(clip) }),
+            Some(clip),
+            CALL_FUEL,
+        )?;
+        serde_json::from_value(result)
+            .map_err(|e| format!("{}: invalid action result: {}", plugin.manifest.name, e))
+    }
+
+    /// Let the plugins classify a new clip and add metadata, in the background
+    pub fn process_new_clip(&self, clip: &ClipItem) {
+        let processors = self.enabled().iter().any(|plugin| {
+            plugin.manifest.allows(PluginPermission::Classify)
+                || plugin.manifest.allows(PluginPermission::Metadata)
+        });
+        if !processors {
+            return;
+        }
+
+        let Ok(mut queue) = self.queue.lock() else {
+            return;
+        };
+        let sender = queue.get_or_insert_with(|| {
+            let (sender, clips) = mpsc::channel::<ClipItem>();
+            let db = self.db.clone();
+            let plugins = self.plugins.clone();
+            let app_handle = self.app_handle.clone();
+            thread::spawn(move || {
+                for clip in clips {
+                    let plugins = plugins.lock().map(|p| p.clone()).unwrap_or_default();
+                    if process_clip(&db, &plugins, &clip) {
+                        if let Some(app) = app_handle.lock().ok().and_then(|a| a.clone()) {
+                            let _ = app.emit("clipboard-updated", ());
+                        }
+                    }
+                }
+            });
+            sender
+        });
+        let _ = sender.send(clip.clone());
+    }
+}
+
+/// Run the enabled processors on a clip and store their results. Returns
+/// whether the clip changed.
+fn process_clip(db: &Mutex<Database>, plugins: &[Arc<Plugin>], clip: &ClipItem) -> bool {
+    let mut kind = None;
+    let mut plugin_metadata = serde_json::Map::new();
+
+    for plugin in plugins.iter().filter(|plugin| plugin.is_enabled()) {
+        let classify = plugin.manifest.allows(PluginPermission::Classify);
+        let add_metadata = plugin.manifest.allows(PluginPermission::Metadata);
+        if !classify && !add_metadata {
+            continue;
+        }
+
+        let result = match plugin.process(clip) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!(
+                    "Plugin '{}' failed to process a clip: {}",
+                    plugin.manifest.name, e
+                );
+                continue;
+            }
+        };
+
+        if classify {
+            if let Some(result_kind) = result.kind.filter(|k| !k.trim().is_empty()) {
+                kind = Some(result_kind);
+            }
+        }
+        if add_metadata {
+            if let Some(metadata) = result.metadata {
+                plugin_metadata.insert(plugin.manifest.id.clone(), Value::Object(metadata));
+            }
+        }
+    }
+
+    if kind.is_none() && plugin_metadata.is_empty() {
+        return false;
+    }
+
+    let Ok(db) = db.lock() else {
+        return false;
+    };
+    // The clip may have changed while the plugins ran
+    let Ok(Some(current)) = db.get_clip_by_id(&clip.id) else {
+        return false;
+    };
+    let mut metadata = match current.metadata {
+        Some(Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    if let Some(kind) = kind {
+        metadata.insert("kind".to_string(), kind.into());
+        metadata.remove("language");
+    }
+    if !plugin_metadata.is_empty() {
+        let plugins = metadata
+            .entry("plugins")
+            .or_insert_with(|| Value::Object(serde_json::Map::new()));
+        if let Value::Object(plugins) = plugins {
+            plugins.extend(plugin_metadata);
+        }
+    }
+
+    match db.update_clip_metadata(&clip.id, Some(&Value::Object(metadata))) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Failed to store plugin results: {}", e);
+            false
+        }
+    }
+}
+
+fn plugins_dir() -> PathBuf {
+    get_app_dir().join("plugins")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clipboard::ClipboardManager;
+
+    const DESCRIBE: &str = r#"{"result":{"transforms":[{"id":"upper","name":"Upper"}],"actions":[{"id":"count","name":"Count"}]}}"#;
+    const TRANSFORMED: &str = r#"{"result":{"text":"TRANSFORMED"}}"#;
+    const PROCESSED: &str = r#"{"result":null}"#;
+    const METADATA: &str = r#"{"source":"test"}"#;
+
+    /// A plugin that describes itself, returns a fixed text from `transform`
+    /// and otherwise classifies the clip by its text and adds `METADATA`
+    fn test_module() -> String {
+        // Answers are returned as `ptr << 32 | len`
+        let answer = |pointer: u64, text: &str| (pointer << 32) | text.len() as u64;
+        let data = |text: &str| text.replace('"', "\\\"");
+        format!(
+            r#"(module
+                (import "fat_clip" "clip_text" (func $clip_text (param i32 i32) (result i32)))
+                (import "fat_clip" "set_kind" (func $set_kind (param i32 i32) (result i32)))
+                (import "fat_clip" "set_metadata" (func $set_metadata (param i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 256) "{describe}")
+                (data (i32.const 512) "{transformed}")
+                (data (i32.const 768) "{processed}")
+                (data (i32.const 1024) "{metadata}")
+                (func (export "alloc") (param i32) (result i32) (i32.const 8192))
+                (func (export "call") (param $ptr i32) (param $len i32) (result i64)
+                    (local $method i32)
+                    (local $text i32)
+                    ;; The first letter of the method, after {{"method":"
+                    (local.set $method (i32.load8_u offset=11 (local.get $ptr)))
+                    (if (i32.eq (local.get $method) (i32.const 100))
+                        (then (return (i64.const {describe_answer}))))
+                    (if (i32.eq (local.get $method) (i32.const 116))
+                        (then (return (i64.const {transformed_answer}))))
+                    (local.set $text (call $clip_text (i32.const 4096) (i32.const 4096)))
+                    (if (i32.ge_s (local.get $text) (i32.const 0))
+                        (then (drop (call $set_kind (i32.const 4096) (local.get $text)))))
+                    (drop (call $set_metadata (i32.const 1024) (i32.const {metadata_len})))
+                    (i64.const {processed_answer})))"#,
+            describe = data(DESCRIBE),
+            transformed = data(TRANSFORMED),
+            processed = data(PROCESSED),
+            metadata = data(METADATA),
+            describe_answer = answer(256, DESCRIBE),
+            transformed_answer = answer(512, TRANSFORMED),
+            processed_answer = answer(768, PROCESSED),
+            metadata_len = METADATA.len(),
+        )
+    }
+
+    /// A plugin that never returns
+    const LOOPING_MODULE: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "alloc") (param i32) (result i32) (i32.const 0))
+        (func (export "call") (param i32 i32) (result i64)
+            (loop $forever (br $forever))
+            (i64.const 0)))"#;
+
+    fn install(dir: &Path, wat: &str, permissions: Vec<PluginPermission>) -> Arc<Plugin> {
+        let buffer = wast::parser::ParseBuffer::new(wat).unwrap();
+        let mut module = wast::parser::parse::<wast::Wat>(&buffer).unwrap();
+        std::fs::write(dir.join("plugin.wasm"), module.encode().unwrap()).unwrap();
+
+        let plugin = Plugin {
+            manifest: PluginManifest {
+                id: "test".to_string(),
+                name: "Test".to_string(),
+                version: "1.0".to_string(),
+                description: String::new(),
+                module: default_module(),
+                permissions,
+            },
+            dir: dir.to_path_buf(),
+            state: Mutex::new(PluginState::default()),
+        };
+        plugin.set_enabled(true);
+        Arc::new(plugin)
+    }
+
+    #[test]
+    fn plugins_classify_and_transform_clips() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        let manager = ClipboardManager::new(Arc::new(Mutex::new(db)));
+        let clip = manager
+            .save_text_clip("invoice".to_string(), "test".to_string(), false)
+            .unwrap();
+
+        let plugin = install(
+            dir.path(),
+            &test_module(),
+            vec![
+                PluginPermission::ReadContent,
+                PluginPermission::Classify,
+                PluginPermission::Metadata,
+                PluginPermission::Transforms,
+            ],
+        );
+
+        assert!(process_clip(
+            &manager.db,
+            std::slice::from_ref(&plugin),
+            &clip
+        ));
+        let db = manager.db.lock().unwrap();
+        let metadata = db
+            .get_clip_by_id(&clip.id)
+            .unwrap()
+            .unwrap()
+            .metadata
+            .unwrap();
+        assert_eq!(metadata["kind"], "invoice");
+        assert_eq!(metadata["plugins"]["test"]["source"], "test");
+
+        // Actions were not allowed
+        let features = plugin.features();
+        assert_eq!(features.transforms.len(), 1);
+        assert!(features.actions.is_empty());
+        let text = plugin
+            .call(
+                "transform",
+                json!({ "transform": "upper" }),
+                None,
+                CALL_FUEL,
+            )
+            .unwrap();
+        assert_eq!(text["text"], "TRANSFORMED");
+    }
+
+    #[test]
+    fn host_functions_need_their_permission() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin = install(
+            dir.path(),
+            &test_module(),
+            vec![PluginPermission::Classify, PluginPermission::Metadata],
+        );
+
+        let error = plugin
+            .call("describe", json!({}), None, CALL_FUEL)
+            .unwrap_err();
+        assert!(error.contains("clip_text"), "{}", error);
+        assert!(plugin.state.lock().unwrap().instance.is_none());
+    }
+
+    #[test]
+    fn plugins_that_never_return_are_stopped() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin = install(dir.path(), LOOPING_MODULE, Vec::new());
+
+        for _ in 0..MAX_FAILURES {
+            let error = plugin
+                .call("transform", json!({}), None, CALL_FUEL)
+                .unwrap_err();
+            assert!(error.contains("fuel"), "{}", error);
+        }
+        // Not loaded again until it is enabled again
+        assert_eq!(plugin.state.lock().unwrap().failures, MAX_FAILURES);
+        plugin.set_enabled(false);
+        plugin.set_enabled(true);
+        assert_eq!(plugin.state.lock().unwrap().failures, 0);
+    }
+
+    #[test]
+    fn modules_must_be_inside_the_plugin() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = |module: &str| {
+            json!({ "id": "test", "name": "Test", "version": "1.0", "module": module }).to_string()
+        };
+
+        for module in ["../plugin.wasm", "/tmp/plugin.wasm", ""] {
+            std::fs::write(dir.path().join(MANIFEST_FILE), manifest(module)).unwrap();
+            assert!(PluginManifest::load(dir.path()).is_err(), "{}", module);
+        }
+        std::fs::write(dir.path().join(MANIFEST_FILE), manifest("lib/plugin.wasm")).unwrap();
+        assert!(PluginManifest::load(dir.path()).is_ok());
+    }
+}
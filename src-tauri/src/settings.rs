@@ -1,4 +1,10 @@
+use crate::auto_tag::AutoTagRule;
+use crate::backup::BackupConfig;
+use crate::hooks::Hook;
+use crate::peer_sync::{default_device_name, PeerSyncConfig, ShareFilter};
+use crate::sync::SyncConfig;
 use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr};
 use std::path::PathBuf;
 
 fn default_input_panel_selection_modifier() -> String {
@@ -9,6 +15,46 @@ fn default_timeline_mode() -> String {
     "standard".to_string()
 }
 
+fn default_paste_queue_order() -> String {
+    "fifo".to_string()
+}
+
+fn default_type_out_delay_ms() -> u64 {
+    15
+}
+
+fn default_type_out_max_length() -> usize {
+    5000
+}
+
+fn default_text_expansion_delimiters() -> String {
+    ".,!?".to_string()
+}
+
+fn default_backup_interval_hours() -> u64 {
+    24
+}
+
+fn default_backup_keep_daily() -> usize {
+    7
+}
+
+fn default_backup_keep_weekly() -> usize {
+    4
+}
+
+fn default_sync_interval_minutes() -> u64 {
+    5
+}
+
+fn default_peer_sync_port() -> u16 {
+    47833
+}
+
+fn default_hooks_max_concurrent() -> usize {
+    2
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShortcutConfig {
     pub key: String,
@@ -52,12 +98,38 @@ impl ShortcutConfig {
             meta: false,
         }
     }
+
+    pub fn default_paste_from_queue() -> Self {
+        ShortcutConfig {
+            key: "V".to_string(),
+            ctrl: true,
+            alt: true,
+            shift: false,
+            meta: false,
+        }
+    }
+
+    pub fn default_cancel_type_out() -> Self {
+        ShortcutConfig {
+            key: "Escape".to_string(),
+            ctrl: false,
+            alt: false,
+            shift: false,
+            meta: false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Shortcuts {
     // Global shortcuts
     pub toggle_window: ShortcutConfig,
+    // Only registered while the paste queue is active
+    #[serde(default = "ShortcutConfig::default_paste_from_queue")]
+    pub paste_from_queue: ShortcutConfig,
+    // Only registered while a type-out is running
+    #[serde(default = "ShortcutConfig::default_cancel_type_out")]
+    pub cancel_type_out: ShortcutConfig,
 
     // Navigation shortcuts (local, when window is focused)
     pub focus_search: ShortcutConfig,
@@ -77,6 +149,8 @@ impl Default for Shortcuts {
     fn default() -> Self {
         Shortcuts {
             toggle_window: ShortcutConfig::default_global(),
+            paste_from_queue: ShortcutConfig::default_paste_from_queue(),
+            cancel_type_out: ShortcutConfig::default_cancel_type_out(),
             focus_search: ShortcutConfig {
                 key: "/".to_string(),
                 ctrl: false,
@@ -163,10 +237,90 @@ pub struct Settings {
     pub max_history_items: i64,
     pub auto_cleanup_days: Option<i64>,
 
+    // Backups: snapshots of the database and images, rotated so the newest
+    // backup of each of the last days and weeks is kept
+    #[serde(default)]
+    pub backup_enabled: bool,
+    /// Folder the backups are written to, the "backups" folder in the app
+    /// directory if not set
+    #[serde(default)]
+    pub backup_dir: Option<String>,
+    #[serde(default = "default_backup_interval_hours")]
+    pub backup_interval_hours: u64,
+    #[serde(default = "default_backup_keep_daily")]
+    pub backup_keep_daily: usize,
+    #[serde(default = "default_backup_keep_weekly")]
+    pub backup_keep_weekly: usize,
+
+    // Sync with other devices through a shared folder
+    #[serde(default)]
+    pub sync_enabled: bool,
+    #[serde(default)]
+    pub sync_dir: Option<String>,
+    #[serde(default = "default_sync_interval_minutes")]
+    pub sync_interval_minutes: u64,
+
+    // Sync with paired devices on the local network
+    #[serde(default)]
+    pub peer_sync_enabled: bool,
+    /// Address of the interface to listen on, all interfaces if not set
+    #[serde(default)]
+    pub peer_sync_listen_address: Option<String>,
+    #[serde(default = "default_peer_sync_port")]
+    pub peer_sync_port: u16,
+    /// Name shown on paired devices, the computer name if not set
+    #[serde(default)]
+    pub peer_sync_device_name: Option<String>,
+    #[serde(default = "default_sync_interval_minutes")]
+    pub peer_sync_interval_minutes: u64,
+    /// Content types shared with paired devices, all if empty
+    #[serde(default)]
+    pub peer_sync_content_types: Vec<String>,
+    /// Only clips with any of these tags are shared, if set
+    #[serde(default)]
+    pub peer_sync_tags: Vec<String>,
+
+    // Local API for other applications
+    #[serde(default)]
+    pub ipc_enabled: bool,
+
     // Behavior
     pub paste_on_select: bool,
     pub show_notifications: bool,
 
+    // Paste queue: "fifo" or "lifo"
+    #[serde(default = "default_paste_queue_order")]
+    pub paste_queue_order: String,
+
+    // Type-out
+    #[serde(default = "default_type_out_delay_ms")]
+    pub type_out_delay_ms: u64,
+    #[serde(default = "default_type_out_max_length")]
+    pub type_out_max_length: usize,
+
+    // Text expansion: snippet abbreviations expand when followed by a space
+    // or one of the delimiter characters
+    #[serde(default)]
+    pub enable_text_expansion: bool,
+    #[serde(default = "default_text_expansion_delimiters")]
+    pub text_expansion_delimiters: String,
+    #[serde(default)]
+    pub text_expansion_excluded_apps: Vec<String>,
+
+    // Tags added to new clips by matching rules
+    #[serde(default)]
+    pub auto_tag_rules: Vec<AutoTagRule>,
+
+    // Commands run on clip events
+    #[serde(default)]
+    pub hooks: Vec<Hook>,
+    #[serde(default = "default_hooks_max_concurrent")]
+    pub hooks_max_concurrent: usize,
+
+    // Ids of the enabled plugins
+    #[serde(default)]
+    pub enabled_plugins: Vec<String>,
+
     // Window
     pub always_on_top: bool,
 
@@ -202,8 +356,34 @@ impl Default for Settings {
             language: "system".to_string(),
             max_history_items: 1000,
             auto_cleanup_days: Some(30),
+            backup_enabled: false,
+            backup_dir: None,
+            backup_interval_hours: default_backup_interval_hours(),
+            backup_keep_daily: default_backup_keep_daily(),
+            backup_keep_weekly: default_backup_keep_weekly(),
+            sync_enabled: false,
+            sync_dir: None,
+            sync_interval_minutes: default_sync_interval_minutes(),
+            peer_sync_enabled: false,
+            peer_sync_listen_address: None,
+            peer_sync_port: default_peer_sync_port(),
+            peer_sync_device_name: None,
+            peer_sync_interval_minutes: default_sync_interval_minutes(),
+            peer_sync_content_types: Vec::new(),
+            peer_sync_tags: Vec::new(),
+            ipc_enabled: false,
             paste_on_select: false,
             show_notifications: true,
+            paste_queue_order: default_paste_queue_order(),
+            type_out_delay_ms: default_type_out_delay_ms(),
+            type_out_max_length: default_type_out_max_length(),
+            enable_text_expansion: false,
+            text_expansion_delimiters: default_text_expansion_delimiters(),
+            text_expansion_excluded_apps: Vec::new(),
+            auto_tag_rules: Vec::new(),
+            hooks: Vec::new(),
+            hooks_max_concurrent: default_hooks_max_concurrent(),
+            enabled_plugins: Vec::new(),
             always_on_top: true,
             display_mode: "compact".to_string(),
             timeline_mode: default_timeline_mode(),
@@ -223,9 +403,7 @@ impl Settings {
         let settings_path = app_dir.join("settings.json");
         if settings_path.exists() {
             let content = std::fs::read_to_string(&settings_path).map_err(|e| e.to_string())?;
-            let mut settings: Settings =
-                serde_json::from_str(&content).map_err(|e| e.to_string())?;
-            settings.enable_input_panel = false;
+            let settings: Settings = serde_json::from_str(&content).map_err(|e| e.to_string())?;
             Ok(settings)
         } else {
             let settings = Settings::default();
@@ -247,11 +425,52 @@ impl Settings {
         Ok(())
     }
 
+    pub fn backup_config(&self) -> BackupConfig {
+        BackupConfig {
+            enabled: self.backup_enabled,
+            dir: self.backup_dir.clone(),
+            interval_hours: self.backup_interval_hours,
+            keep_daily: self.backup_keep_daily,
+            keep_weekly: self.backup_keep_weekly,
+        }
+    }
+
+    pub fn sync_config(&self) -> SyncConfig {
+        SyncConfig {
+            enabled: self.sync_enabled,
+            dir: self.sync_dir.clone(),
+            interval_minutes: self.sync_interval_minutes,
+        }
+    }
+
+    pub fn peer_sync_config(&self) -> PeerSyncConfig {
+        PeerSyncConfig {
+            enabled: self.peer_sync_enabled,
+            listen_address: self
+                .peer_sync_listen_address
+                .as_deref()
+                .and_then(|address| address.trim().parse().ok())
+                .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            port: self.peer_sync_port,
+            device_name: self
+                .peer_sync_device_name
+                .clone()
+                .filter(|name| !name.trim().is_empty())
+                .unwrap_or_else(default_device_name),
+            interval_minutes: self.peer_sync_interval_minutes,
+            filter: ShareFilter {
+                content_types: self.peer_sync_content_types.clone(),
+                tags: self.peer_sync_tags.clone(),
+            },
+        }
+    }
+
     /// Validate shortcuts for conflicts
     pub fn validate_shortcuts(&self) -> Vec<String> {
         let mut conflicts = Vec::new();
         let shortcut_list = vec![
             ("toggle_window", &self.shortcuts.toggle_window),
+            ("paste_from_queue", &self.shortcuts.paste_from_queue),
             ("focus_search", &self.shortcuts.focus_search),
             ("navigate_up", &self.shortcuts.navigate_up),
             ("navigate_down", &self.shortcuts.navigate_down),
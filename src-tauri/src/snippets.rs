@@ -0,0 +1,444 @@
+//! Snippet library: reusable text organised in folders, with placeholders
+//! that are expanded when the snippet is pasted.
+//!
+//! Supported placeholders:
+//! - `{date}`, `{date:%d.%m.%Y}`: current local date, optionally with a chrono format
+//! - `{time}`, `{time:%H:%M}`: current local time, optionally with a chrono format
+//! - `{clipboard}`: current clipboard text
+//! - `{uuid}`: a new random UUID
+//! - `{cursor}`: where the caret is placed after pasting
+//! - `{input:Name}`: a value the user is asked for before pasting
+//!
+//! `{{` and `}}` produce literal braces. Unknown placeholders are kept as typed.
+
+use crate::clipboard::clip_plain_text;
+use crate::db::{ClipItem, Database, Snippet, SnippetFolder};
+use chrono::{Local, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+/// Longest default name given to a snippet promoted from a clip
+const PROMOTED_NAME_MAX_CHARS: usize = 40;
+
+/// Snippet text with all placeholders replaced
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpandedSnippet {
+    pub text: String,
+    /// Characters between `{cursor}` and the end of the text, i.e. how far the
+    /// caret moves back after pasting
+    pub cursor_from_end: Option<usize>,
+}
+
+pub struct SnippetManager {
+    db: Arc<Mutex<Database>>,
+}
+
+impl SnippetManager {
+    pub fn new(db: Arc<Mutex<Database>>) -> Self {
+        SnippetManager { db }
+    }
+
+    pub fn get_snippets(
+        &self,
+        folder_id: Option<&str>,
+        query: Option<&str>,
+    ) -> Result<Vec<Snippet>, String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.get_snippets(folder_id, query).map_err(|e| e.to_string())
+    }
+
+    pub fn get_snippet(&self, id: &str) -> Result<Snippet, String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.get_snippet(id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Snippet not found".to_string())
+    }
+
+    /// Snippets that have an abbreviation for text expansion
+    pub fn get_abbreviations(&self) -> Result<Vec<Snippet>, String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.get_snippets_with_abbreviation()
+            .map_err(|e| e.to_string())
+    }
+
+    /// Create a snippet, or update it when `id` is given
+    pub fn save_snippet(
+        &self,
+        id: Option<String>,
+        name: String,
+        content: String,
+        folder_id: Option<String>,
+        abbreviation: Option<String>,
+        expansion_apps: Vec<String>,
+    ) -> Result<Snippet, String> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err("Snippet name cannot be empty".to_string());
+        }
+
+        let abbreviation = abbreviation
+            .map(|a| a.trim().to_string())
+            .filter(|a| !a.is_empty());
+        if let Some(abbreviation) = &abbreviation {
+            if abbreviation.chars().any(char::is_whitespace) {
+                return Err("Abbreviation cannot contain spaces".to_string());
+            }
+        }
+
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+
+        if let Some(abbreviation) = &abbreviation {
+            let taken = db
+                .get_snippets_with_abbreviation()
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .any(|s| {
+                    s.abbreviation.as_ref() == Some(abbreviation) && Some(&s.id) != id.as_ref()
+                });
+            if taken {
+                return Err(format!("Abbreviation '{}' is already in use", abbreviation));
+            }
+        }
+
+        let now = Utc::now();
+        let existing = match &id {
+            Some(id) => Some(
+                db.get_snippet(id)
+                    .map_err(|e| e.to_string())?
+                    .ok_or_else(|| "Snippet not found".to_string())?,
+            ),
+            None => None,
+        };
+
+        let snippet = Snippet {
+            id: id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            folder_id,
+            name,
+            content,
+            abbreviation,
+            expansion_apps: expansion_apps
+                .into_iter()
+                .map(|app| app.trim().to_string())
+                .filter(|app| !app.is_empty())
+                .collect(),
+            created_at: existing.as_ref().map(|s| s.created_at).unwrap_or(now),
+            updated_at: now,
+            use_count: existing.as_ref().map(|s| s.use_count).unwrap_or(0),
+        };
+
+        db.save_snippet(&snippet).map_err(|e| e.to_string())?;
+        Ok(snippet)
+    }
+
+    /// Create a snippet from the text of a history item
+    pub fn promote_clip(
+        &self,
+        clip: &ClipItem,
+        name: Option<String>,
+        folder_id: Option<String>,
+        abbreviation: Option<String>,
+    ) -> Result<Snippet, String> {
+        let content = clip_plain_text(clip)?;
+        let name = name
+            .filter(|n| !n.trim().is_empty())
+            .unwrap_or_else(|| default_snippet_name(&content));
+
+        self.save_snippet(None, name, content, folder_id, abbreviation, Vec::new())
+    }
+
+    pub fn delete_snippet(&self, id: &str) -> Result<(), String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.delete_snippet(id).map_err(|e| e.to_string())
+    }
+
+    pub fn get_folders(&self) -> Result<Vec<SnippetFolder>, String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.get_snippet_folders().map_err(|e| e.to_string())
+    }
+
+    pub fn create_folder(
+        &self,
+        name: String,
+        parent_id: Option<String>,
+    ) -> Result<SnippetFolder, String> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err("Folder name cannot be empty".to_string());
+        }
+
+        let folder = SnippetFolder {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            parent_id,
+            created_at: Utc::now(),
+        };
+
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.save_snippet_folder(&folder).map_err(|e| e.to_string())?;
+        Ok(folder)
+    }
+
+    pub fn rename_folder(&self, id: &str, name: String) -> Result<(), String> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err("Folder name cannot be empty".to_string());
+        }
+
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        let mut folder = db
+            .get_snippet_folders()
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .find(|f| f.id == id)
+            .ok_or_else(|| "Folder not found".to_string())?;
+        folder.name = name;
+        db.save_snippet_folder(&folder).map_err(|e| e.to_string())
+    }
+
+    pub fn delete_folder(&self, id: &str) -> Result<(), String> {
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.delete_snippet_folder(id).map_err(|e| e.to_string())
+    }
+
+    /// Names of the `{input:Name}` values a snippet asks for, in order
+    pub fn get_inputs(&self, id: &str) -> Result<Vec<String>, String> {
+        Ok(template_inputs(&self.get_snippet(id)?.content))
+    }
+
+    /// Expand a snippet for pasting and count it as used
+    pub fn expand(
+        &self,
+        id: &str,
+        inputs: &HashMap<String, String>,
+    ) -> Result<ExpandedSnippet, String> {
+        let snippet = self.get_snippet(id)?;
+        let expanded = expand_template(&snippet.content, inputs, current_clipboard_text)?;
+
+        let db = self.db.lock().map_err(|e| e.to_string())?;
+        db.record_snippet_use(id).map_err(|e| e.to_string())?;
+
+        Ok(expanded)
+    }
+}
+
+/// A placeholder found in a template
+enum Placeholder<'a> {
+    Date(Option<&'a str>),
+    Time(Option<&'a str>),
+    Clipboard,
+    Uuid,
+    Cursor,
+    Input(&'a str),
+}
+
+impl<'a> Placeholder<'a> {
+    /// Parse the text between braces, `None` if it is not a placeholder
+    fn parse(token: &'a str) -> Option<Self> {
+        let (name, arg) = match token.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (token, None),
+        };
+
+        match (name, arg) {
+            ("date", arg) => Some(Placeholder::Date(arg)),
+            ("time", arg) => Some(Placeholder::Time(arg)),
+            ("clipboard", None) => Some(Placeholder::Clipboard),
+            ("uuid", None) => Some(Placeholder::Uuid),
+            ("cursor", None) => Some(Placeholder::Cursor),
+            ("input", Some(arg)) if !arg.trim().is_empty() => Some(Placeholder::Input(arg.trim())),
+            _ => None,
+        }
+    }
+}
+
+/// Segment of a template: literal text or a placeholder
+enum Segment<'a> {
+    Text(&'a str),
+    Placeholder(Placeholder<'a>),
+}
+
+/// Split a template into literal text and placeholders
+fn parse_template(template: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = template;
+
+    while let Some(pos) = rest.find(['{', '}']) {
+        let (text, tail) = rest.split_at(pos);
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+
+        // Doubled braces are literal
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            segments.push(Segment::Text(&tail[..1]));
+            rest = &tail[2..];
+            continue;
+        }
+
+        if let Some(inner) = tail.strip_prefix('{') {
+            let placeholder = inner
+                .find(['{', '}'])
+                .filter(|&end| inner[end..].starts_with('}'))
+                .and_then(|end| Placeholder::parse(&inner[..end]).map(|p| (p, end + 2)));
+
+            if let Some((placeholder, len)) = placeholder {
+                segments.push(Segment::Placeholder(placeholder));
+                rest = &tail[len..];
+                continue;
+            }
+        }
+
+        segments.push(Segment::Text(&tail[..1]));
+        rest = &tail[1..];
+    }
+
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest));
+    }
+
+    segments
+}
+
+/// Names of the `{input:Name}` placeholders in a template, without duplicates
+pub fn template_inputs(template: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for segment in parse_template(template) {
+        if let Segment::Placeholder(Placeholder::Input(name)) = segment {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Replace the placeholders of a template.
+///
+/// `clipboard_text` is only called when the template uses `{clipboard}`.
+pub fn expand_template(
+    template: &str,
+    inputs: &HashMap<String, String>,
+    clipboard_text: impl Fn() -> String,
+) -> Result<ExpandedSnippet, String> {
+    let now = Local::now();
+    let mut text = String::new();
+    let mut cursor = None;
+
+    for segment in parse_template(template) {
+        match segment {
+            Segment::Text(literal) => text.push_str(literal),
+            Segment::Placeholder(placeholder) => match placeholder {
+                Placeholder::Date(format) | Placeholder::Time(format) => {
+                    let default = if matches!(placeholder, Placeholder::Date(_)) {
+                        "%Y-%m-%d"
+                    } else {
+                        "%H:%M:%S"
+                    };
+                    let format = format.unwrap_or(default);
+                    write!(text, "{}", now.format(format))
+                        .map_err(|_| format!("Invalid date format '{}'", format))?;
+                }
+                Placeholder::Clipboard => text.push_str(&clipboard_text()),
+                Placeholder::Uuid => text.push_str(&uuid::Uuid::new_v4().to_string()),
+                Placeholder::Cursor => {
+                    // Only the first cursor marker counts
+                    if cursor.is_none() {
+                        cursor = Some(text.chars().count());
+                    }
+                }
+                Placeholder::Input(name) => {
+                    let value = inputs
+                        .get(name)
+                        .ok_or_else(|| format!("Missing value for '{}'", name))?;
+                    text.push_str(value);
+                }
+            },
+        }
+    }
+
+    let cursor_from_end = cursor.map(|position| text.chars().count() - position);
+
+    Ok(ExpandedSnippet {
+        text,
+        cursor_from_end,
+    })
+}
+
+fn current_clipboard_text() -> String {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .unwrap_or_default()
+}
+
+/// First line of the content, shortened
+fn default_snippet_name(content: &str) -> String {
+    let first_line = content
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("Snippet");
+
+    if first_line.chars().count() > PROMOTED_NAME_MAX_CHARS {
+        let short: String = first_line.chars().take(PROMOTED_NAME_MAX_CHARS).collect();
+        format!("{}...", short.trim_end())
+    } else {
+        first_line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(template: &str, inputs: &[(&str, &str)]) -> Result<ExpandedSnippet, String> {
+        let inputs = inputs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        expand_template(template, &inputs, || "copied".to_string())
+    }
+
+    #[test]
+    fn doubled_braces_and_unknown_placeholders_stay_literal() {
+        let expanded = expand("{{x}} {unknown} {clipboard:x} {input:} {", &[]).unwrap();
+        assert_eq!(expanded.text, "{x} {unknown} {clipboard:x} {input:} {");
+        assert_eq!(expanded.cursor_from_end, None);
+
+        assert_eq!(expand("}} a}", &[]).unwrap().text, "} a}");
+        assert_eq!(expand("{{clipboard}}", &[]).unwrap().text, "{clipboard}");
+        assert_eq!(expand("{{{clipboard}}}", &[]).unwrap().text, "{copied}");
+    }
+
+    #[test]
+    fn cursor_is_counted_in_characters_from_the_end() {
+        let expanded = expand("é{cursor}ü{cursor}ß", &[]).unwrap();
+        assert_eq!(expanded.text, "éüß");
+        assert_eq!(expanded.cursor_from_end, Some(2));
+    }
+
+    #[test]
+    fn inputs_are_filled_and_listed_once() {
+        let template = "Hi {input:Name}, {input: Name } and {input:City}";
+        assert_eq!(template_inputs(template), vec!["Name", "City"]);
+
+        let expanded = expand(template, &[("Name", "Ada"), ("City", "Paris")]).unwrap();
+        assert_eq!(expanded.text, "Hi Ada, Ada and Paris");
+
+        assert_eq!(
+            expand(template, &[("Name", "Ada")]).unwrap_err(),
+            "Missing value for 'City'"
+        );
+        assert!(template_inputs("{input:}").is_empty());
+    }
+
+    #[test]
+    fn invalid_date_formats_are_reported() {
+        assert_eq!(
+            expand("{date:%Q}", &[]).unwrap_err(),
+            "Invalid date format '%Q'"
+        );
+        assert_eq!(expand("{time:%H}", &[]).unwrap().text.len(), 2);
+    }
+}
@@ -0,0 +1,710 @@
+//! Sync between devices. Changes made here are found by comparing the clips
+//! marked as changed with their state at the last sync and recorded in a
+//! journal in the database,
+//! together with the changes received from other devices. The journal is
+//! exchanged through a shared folder, below, or with paired devices on the
+//! local network (`peer_sync`).
+//!
+//! In a shared folder, such as one synced by Syncthing or a network share,
+//! every device appends its changes to its own log file and replays the logs
+//! of the other devices, so no file is ever written by two devices.
+//!
+//! Clips are identified by their id, the hash of their content. Conflicting
+//! changes to the same clip are settled by the time they were made: the later
+//! one wins, separately for whether the clip exists, its tags and its pin.
+
+use crate::archive::{archive_clip, restore_image, ArchivedClip};
+use crate::clipboard::{remove_clip_files, ClipboardManager};
+use crate::db::{
+    get_app_dir, is_valid_clip_id, ContentType, Database, SyncChangeRecord, SyncState,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+use tauri::Emitter;
+
+/// Folder inside the sync folder that holds the change logs
+const SYNC_SUBDIR: &str = "fat-clip-sync";
+const LOG_EXTENSION: &str = "jsonl";
+
+/// Journal entries read from the database at a time
+const JOURNAL_BATCH: i64 = 500;
+
+/// How often the scheduler checks whether a sync is due
+const SCHEDULER_INTERVAL: Duration = Duration::from_secs(15);
+
+/// A change as exchanged with other devices: one line of a change log
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SyncEntry {
+    /// When the change was made
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub change: SyncChange,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub(crate) enum SyncChange {
+    /// A clip was added, with its image embedded for image clips
+    Insert {
+        clip: Box<ArchivedClip>,
+    },
+    Delete {
+        id: String,
+    },
+    Tags {
+        id: String,
+        tags: Vec<String>,
+    },
+    Pin {
+        id: String,
+        pinned: bool,
+    },
+}
+
+impl SyncChange {
+    pub fn clip_id(&self) -> &str {
+        match self {
+            SyncChange::Insert { clip } => &clip.clip.id,
+            SyncChange::Delete { id }
+            | SyncChange::Tags { id, .. }
+            | SyncChange::Pin { id, .. } => id,
+        }
+    }
+
+    /// Operation and value as stored in the journal
+    fn journal_fields(&self) -> (&'static str, Option<String>) {
+        match self {
+            SyncChange::Insert { .. } => ("insert", None),
+            SyncChange::Delete { .. } => ("delete", None),
+            SyncChange::Tags { tags, .. } => ("tags", serde_json::to_string(tags).ok()),
+            SyncChange::Pin { pinned, .. } => ("pin", Some(pinned.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncSummary {
+    /// Changes sent to other devices
+    pub sent: usize,
+    /// Changes of other devices applied to the history
+    pub received: usize,
+}
+
+/// Syncs a history with a folder as the device `device_id`
+pub struct SyncEngine {
+    dir: PathBuf,
+    device_id: String,
+}
+
+impl SyncEngine {
+    pub fn new(folder: &Path, device_id: &str) -> Self {
+        SyncEngine {
+            dir: folder.join(SYNC_SUBDIR),
+            device_id: device_id.to_string(),
+        }
+    }
+
+    /// Log the local changes, then apply those of the other devices. Local
+    /// changes are logged first so they are timestamped before they are
+    /// compared with remote ones. The database is only locked while it is
+    /// used, not while the sync folder is read or written.
+    pub fn sync(&self, db: &Mutex<Database>) -> Result<SyncSummary, String> {
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create sync folder: {}", e))?;
+
+        {
+            let db = lock(db)?;
+            record_local_changes(&db)?;
+        }
+        let sent = self.push(db)?;
+        let received = self.pull(db)?;
+        Ok(SyncSummary { sent, received })
+    }
+
+    fn log_path(&self, device_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", device_id, LOG_EXTENSION))
+    }
+
+    /// Read what is new in the logs of the other devices
+    fn pull(&self, db: &Mutex<Database>) -> Result<usize, String> {
+        let mut received = 0;
+
+        for entry in std::fs::read_dir(&self.dir).map_err(|e| e.to_string())? {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path.extension().is_none_or(|ext| ext != LOG_EXTENSION) {
+                continue;
+            }
+            let Some(device_id) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            if device_id == self.device_id {
+                continue;
+            }
+
+            let offset = lock(db)?
+                .get_sync_cursor(device_id)
+                .map_err(|e| e.to_string())?;
+            let (entries, offset) = read_log(&path, offset)?;
+
+            let db = lock(db)?;
+            for entry in entries {
+                match apply_change(&db, entry, device_id) {
+                    Ok(true) => received += 1,
+                    Ok(false) => {}
+                    Err(e) => eprintln!("Failed to apply change from {}: {}", device_id, e),
+                }
+            }
+            db.set_sync_cursor(device_id, offset)
+                .map_err(|e| e.to_string())?;
+        }
+
+        Ok(received)
+    }
+
+    /// Append the journal entries made here since the last push to our log.
+    /// Changes received from other devices are not logged again, as every
+    /// device reads the logs of all others. The position in our own journal is
+    /// kept as the cursor of this device.
+    fn push(&self, db: &Mutex<Database>) -> Result<usize, String> {
+        let (lines, sent, logged) = {
+            let db = lock(db)?;
+            self.unlogged_lines(&db)?
+        };
+
+        if !lines.is_empty() {
+            let mut log = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.log_path(&self.device_id))
+                .map_err(|e| format!("Failed to open change log: {}", e))?;
+            log.write_all(&lines)
+                .and_then(|_| log.sync_all())
+                .map_err(|e| format!("Failed to write change log: {}", e))?;
+        }
+
+        lock(db)?
+            .set_sync_cursor(&self.device_id, logged as u64)
+            .map_err(|e| e.to_string())?;
+        Ok(sent)
+    }
+
+    /// Log lines of the local journal entries after our cursor, their number
+    /// and the journal position they reach
+    fn unlogged_lines(&self, db: &Database) -> Result<(Vec<u8>, usize, i64), String> {
+        let mut logged = db
+            .get_sync_cursor(&self.device_id)
+            .map_err(|e| e.to_string())? as i64;
+
+        let mut lines = Vec::new();
+        let mut sent = 0;
+        loop {
+            let records = db
+                .get_sync_changes(logged, JOURNAL_BATCH)
+                .map_err(|e| e.to_string())?;
+            let Some(last) = records.last() else {
+                break;
+            };
+            logged = last.seq;
+
+            for record in records.iter().filter(|r| r.origin.is_none()) {
+                if let Some(entry) = journal_entry(db, record)? {
+                    lines.extend(serde_json::to_vec(&entry).map_err(|e| e.to_string())?);
+                    lines.push(b'\n');
+                    sent += 1;
+                }
+            }
+        }
+
+        Ok((lines, sent, logged))
+    }
+}
+
+fn lock(db: &Mutex<Database>) -> Result<MutexGuard<'_, Database>, String> {
+    db.lock().map_err(|e| e.to_string())
+}
+
+/// Compare the clips marked as changed with their last synced state and
+/// journal the differences. Returns the number of changes.
+pub(crate) fn record_local_changes(db: &Database) -> Result<usize, String> {
+    let now = Utc::now();
+    let mut recorded = 0;
+
+    for (mark, id) in db.get_sync_dirty().map_err(|e| e.to_string())? {
+        let clip = db.get_clip_by_id(&id).map_err(|e| e.to_string())?;
+        let state = db.get_sync_state(&id).map_err(|e| e.to_string())?;
+
+        // Journal operations and values, as in `SyncChange::journal_fields`
+        let mut changes: Vec<(&str, Option<String>)> = Vec::new();
+        let updated = match (clip, state) {
+            (Some(clip), Some(mut state)) if state.present => {
+                if state.tags != clip.tags {
+                    changes.push(("tags", serde_json::to_string(&clip.tags).ok()));
+                    state.tags = clip.tags;
+                    state.tags_at = now;
+                }
+                if state.pinned != clip.pinned {
+                    changes.push(("pin", Some(clip.pinned.to_string())));
+                    state.pinned = clip.pinned;
+                    state.pinned_at = now;
+                }
+                (!changes.is_empty()).then_some(state)
+            }
+            // New, or added again after it was deleted
+            (Some(clip), _) => {
+                changes.push(("insert", None));
+                Some(SyncState {
+                    clip_id: id.clone(),
+                    present: true,
+                    present_at: now,
+                    tags: clip.tags,
+                    tags_at: now,
+                    pinned: clip.pinned,
+                    pinned_at: now,
+                })
+            }
+            // Synced but gone from the history
+            (None, Some(mut state)) if state.present => {
+                changes.push(("delete", None));
+                state.present = false;
+                state.present_at = now;
+                Some(state)
+            }
+            (None, _) => None,
+        };
+
+        // The journal is written before the state, so a change is at worst
+        // recorded twice but never lost
+        for (op, value) in &changes {
+            db.add_sync_change(now, op, &id, value.as_deref(), None)
+                .map_err(|e| e.to_string())?;
+        }
+        if let Some(state) = &updated {
+            db.save_sync_state(state).map_err(|e| e.to_string())?;
+        }
+        db.clear_sync_dirty(mark).map_err(|e| e.to_string())?;
+        recorded += changes.len();
+    }
+
+    Ok(recorded)
+}
+
+/// The change a journal entry stands for. Insertions of clips that have been
+/// deleted since are left out; their deletion follows later in the journal.
+pub(crate) fn journal_entry(
+    db: &Database,
+    record: &SyncChangeRecord,
+) -> Result<Option<SyncEntry>, String> {
+    let id = record.clip_id.clone();
+    let change = match record.op.as_str() {
+        "insert" => match db.get_clip_by_id(&id).map_err(|e| e.to_string())? {
+            Some(clip) => SyncChange::Insert {
+                clip: Box::new(archive_clip(clip)),
+            },
+            None => return Ok(None),
+        },
+        "delete" => SyncChange::Delete { id },
+        "tags" => SyncChange::Tags {
+            id,
+            tags: record
+                .value
+                .as_deref()
+                .and_then(|value| serde_json::from_str(value).ok())
+                .unwrap_or_default(),
+        },
+        "pin" => SyncChange::Pin {
+            id,
+            pinned: record.value.as_deref() == Some("true"),
+        },
+        _ => return Ok(None),
+    };
+
+    Ok(Some(SyncEntry {
+        at: record.at,
+        change,
+    }))
+}
+
+/// Entries of a log from byte `offset` on, and the offset after the last
+/// complete line. A line still being written by the other device is left for
+/// the next sync.
+fn read_log(path: &Path, offset: u64) -> Result<(Vec<SyncEntry>, u64), String> {
+    let mut file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+
+    // A log that shrank was replaced; replaying it is harmless
+    let offset = if offset > len { 0 } else { offset };
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|e| e.to_string())?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).map_err(|e| e.to_string())?;
+
+    let Some(end) = data.iter().rposition(|&b| b == b'\n') else {
+        return Ok((Vec::new(), offset));
+    };
+
+    let entries = data[..end]
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .filter_map(|line| match serde_json::from_slice(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                eprintln!("Skipping invalid change in {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect();
+
+    Ok((entries, offset + end as u64 + 1))
+}
+
+/// Apply a change received from the device `origin` unless the same or a later
+/// change is already known here. Applied changes are added to the journal so
+/// they can be passed on. Returns whether the change was applied.
+pub(crate) fn apply_change(db: &Database, entry: SyncEntry, origin: &str) -> Result<bool, String> {
+    let at = entry.at;
+    let id = entry.change.clip_id().to_string();
+    if !is_valid_clip_id(&id) {
+        return Err(format!("Invalid clip id {:?}", id));
+    }
+    let (op, value) = entry.change.journal_fields();
+
+    let existing = db.get_clip_by_id(&id).map_err(|e| e.to_string())?;
+    let mut state = db
+        .get_sync_state(&id)
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| unknown_state(&id));
+    let mut journal = true;
+
+    match entry.change {
+        SyncChange::Insert { clip } => {
+            if state.present_at >= at {
+                return Ok(false);
+            }
+
+            match existing {
+                Some(existing) => {
+                    // Keep the usage of whichever device used it last
+                    if clip.clip.last_used_at > existing.last_used_at {
+                        db.set_clip_timestamps(&id, existing.created_at, clip.clip.last_used_at)
+                            .map_err(|e| e.to_string())?;
+                    }
+                }
+                None => {
+                    let clip = if clip.clip.content_type == ContentType::Image {
+                        restore_image(*clip)?
+                    } else {
+                        clip.clip
+                    };
+                    db.insert_clip(&clip).map_err(|e| e.to_string())?;
+
+                    // Tag and pin changes may have arrived before the clip
+                    if state.tags_at > at {
+                        db.update_clip_tags(&id, state.tags.clone())
+                            .map_err(|e| e.to_string())?;
+                    } else {
+                        state.tags = clip.tags.clone();
+                        state.tags_at = at;
+                    }
+                    if state.pinned_at > at {
+                        db.toggle_pin(&id, state.pinned)
+                            .map_err(|e| e.to_string())?;
+                    } else {
+                        state.pinned = clip.pinned;
+                        state.pinned_at = at;
+                    }
+                }
+            }
+
+            state.present = true;
+            state.present_at = at;
+        }
+        SyncChange::Delete { .. } => {
+            if state.present_at >= at {
+                return Ok(false);
+            }
+
+            match existing {
+                // Used here after it was deleted there: the clip is marked as
+                // unsynced so it is added back on the other devices
+                Some(existing) if existing.last_used_at > at => journal = false,
+                Some(existing) => {
+                    remove_clip_files(&existing);
+                    db.delete_clip(&id).map_err(|e| e.to_string())?;
+                }
+                None => {}
+            }
+
+            state.present = false;
+            state.present_at = at;
+        }
+        SyncChange::Tags { tags, .. } => {
+            if state.tags_at >= at {
+                return Ok(false);
+            }
+            if existing.is_some() {
+                db.update_clip_tags(&id, tags.clone())
+                    .map_err(|e| e.to_string())?;
+            }
+            state.tags = tags;
+            state.tags_at = at;
+        }
+        SyncChange::Pin { pinned, .. } => {
+            if state.pinned_at >= at {
+                return Ok(false);
+            }
+            if existing.is_some() {
+                db.toggle_pin(&id, pinned).map_err(|e| e.to_string())?;
+            }
+            state.pinned = pinned;
+            state.pinned_at = at;
+        }
+    }
+
+    db.save_sync_state(&state).map_err(|e| e.to_string())?;
+    if journal {
+        db.add_sync_change(at, op, &id, value.as_deref(), Some(origin))
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(true)
+}
+
+/// State of a clip that has not been synced yet
+fn unknown_state(clip_id: &str) -> SyncState {
+    let never = DateTime::<Utc>::UNIX_EPOCH;
+    SyncState {
+        clip_id: clip_id.to_string(),
+        present: false,
+        present_at: never,
+        tags: Vec::new(),
+        tags_at: never,
+        pinned: false,
+        pinned_at: never,
+    }
+}
+
+/// Id of this device for sync, created on first use
+pub fn device_id() -> Result<String, String> {
+    let path = get_app_dir().join("device_id");
+    if let Ok(id) = std::fs::read_to_string(&path) {
+        let id = id.trim();
+        if !id.is_empty() {
+            return Ok(id.to_string());
+        }
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    std::fs::write(&path, &id).map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncConfig {
+    pub enabled: bool,
+    pub dir: Option<String>,
+    pub interval_minutes: u64,
+}
+
+/// Runs the sync engine on a schedule for the app's history
+pub struct SyncManager {
+    clipboard_manager: Arc<ClipboardManager>,
+    config: Mutex<SyncConfig>,
+    last_sync: Mutex<Option<DateTime<Utc>>>,
+    // Only one sync runs at a time
+    busy: Mutex<()>,
+    scheduler_started: AtomicBool,
+}
+
+impl SyncManager {
+    pub fn new(clipboard_manager: Arc<ClipboardManager>, config: SyncConfig) -> Self {
+        SyncManager {
+            clipboard_manager,
+            config: Mutex::new(config),
+            last_sync: Mutex::new(None),
+            busy: Mutex::new(()),
+            scheduler_started: AtomicBool::new(false),
+        }
+    }
+
+    pub fn set_config(&self, config: SyncConfig) {
+        if let Ok(mut current) = self.config.lock() {
+            *current = config;
+        }
+    }
+
+    /// Start the thread that syncs on schedule. The frontend is told when
+    /// changes of other devices were applied.
+    pub fn start_scheduler(self: &Arc<Self>, app: tauri::AppHandle) {
+        if self.scheduler_started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let manager = self.clone();
+        thread::spawn(move || loop {
+            if manager.is_sync_due() {
+                match manager.sync_now() {
+                    Ok(summary) if summary.received > 0 => {
+                        let _ = app.emit("clipboard-updated", ());
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Sync failed: {}", e),
+                }
+            }
+            thread::sleep(SCHEDULER_INTERVAL);
+        });
+    }
+
+    fn is_sync_due(&self) -> bool {
+        let config = match self.config.lock() {
+            Ok(config) => config.clone(),
+            Err(_) => return false,
+        };
+        if !config.enabled {
+            return false;
+        }
+
+        let interval = chrono::Duration::minutes(config.interval_minutes.max(1) as i64);
+        self.last_sync
+            .lock()
+            .map(|last| last.is_none_or(|last| Utc::now() - last >= interval))
+            .unwrap_or(false)
+    }
+
+    /// Sync with the configured folder now
+    pub fn sync_now(&self) -> Result<SyncSummary, String> {
+        let _busy = self.busy.lock().map_err(|e| e.to_string())?;
+
+        let folder = self
+            .config
+            .lock()
+            .map_err(|e| e.to_string())?
+            .dir
+            .clone()
+            .filter(|dir| !dir.trim().is_empty())
+            .ok_or_else(|| "No sync folder is set".to_string())?;
+        let folder = PathBuf::from(folder);
+        if !folder.is_dir() {
+            return Err(format!(
+                "The sync folder {} does not exist",
+                folder.display()
+            ));
+        }
+
+        let engine = SyncEngine::new(&folder, &device_id()?);
+        let result = engine.sync(&self.clipboard_manager.db);
+
+        if let Ok(mut last) = self.last_sync.lock() {
+            *last = Some(Utc::now());
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{generate_content_hash, ClipItem};
+
+    struct Device {
+        _dir: tempfile::TempDir,
+        db: Mutex<Database>,
+        engine: SyncEngine,
+    }
+
+    impl Device {
+        fn new(folder: &Path, device_id: &str) -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let db = Database::new(dir.path().to_path_buf()).unwrap();
+            Device {
+                _dir: dir,
+                db: Mutex::new(db),
+                engine: SyncEngine::new(folder, device_id),
+            }
+        }
+
+        fn sync(&self) -> SyncSummary {
+            self.engine.sync(&self.db).unwrap()
+        }
+
+        fn clip(&self, id: &str) -> Option<ClipItem> {
+            self.db.lock().unwrap().get_clip_by_id(id).unwrap()
+        }
+    }
+
+    fn text_clip(text: &str) -> ClipItem {
+        ClipItem {
+            id: generate_content_hash(text),
+            content_type: ContentType::Plain,
+            content: text.to_string(),
+            preview_text: text.to_string(),
+            tags: vec![],
+            source_app: String::new(),
+            created_at: Utc::now(),
+            last_used_at: Utc::now(),
+            pinned: false,
+            metadata: None,
+            use_count: 0,
+        }
+    }
+
+    #[test]
+    fn devices_exchange_changes_through_a_folder() {
+        let folder = tempfile::tempdir().unwrap();
+        let a = Device::new(folder.path(), "a");
+        let b = Device::new(folder.path(), "b");
+
+        let clip = text_clip("shared");
+        a.db.lock().unwrap().insert_clip(&clip).unwrap();
+        assert_eq!(a.sync().sent, 1);
+        assert_eq!(b.sync().received, 1);
+        assert_eq!(b.clip(&clip.id).unwrap().content, "shared");
+
+        // Applied remote changes are not sent back
+        assert_eq!(b.sync().sent, 0);
+
+        {
+            let db = b.db.lock().unwrap();
+            db.update_clip_tags(&clip.id, vec!["work".to_string()])
+                .unwrap();
+            db.toggle_pin(&clip.id, true).unwrap();
+        }
+        assert_eq!(b.sync().sent, 2);
+        assert_eq!(a.sync().received, 2);
+        let synced = a.clip(&clip.id).unwrap();
+        assert_eq!(synced.tags, vec!["work"]);
+        assert!(synced.pinned);
+
+        a.db.lock().unwrap().delete_clip(&clip.id).unwrap();
+        a.sync();
+        b.sync();
+        assert!(b.clip(&clip.id).is_none());
+
+        // Nothing is left to record once everything is synced
+        assert_eq!(record_local_changes(&a.db.lock().unwrap()).unwrap(), 0);
+        assert_eq!(record_local_changes(&b.db.lock().unwrap()).unwrap(), 0);
+    }
+
+    #[test]
+    fn only_marked_clips_are_compared() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().to_path_buf()).unwrap();
+        db.insert_clip(&text_clip("one")).unwrap();
+        db.insert_clip(&text_clip("two")).unwrap();
+        assert_eq!(record_local_changes(&db).unwrap(), 2);
+        assert!(db.get_sync_dirty().unwrap().is_empty());
+
+        // Using a clip again does not change what is synced
+        db.record_clip_use(&generate_content_hash("one")).unwrap();
+        assert!(db.get_sync_dirty().unwrap().is_empty());
+
+        db.toggle_pin(&generate_content_hash("two"), true).unwrap();
+        assert_eq!(db.get_sync_dirty().unwrap().len(), 1);
+        assert_eq!(record_local_changes(&db).unwrap(), 1);
+    }
+}
@@ -0,0 +1,453 @@
+//! Text expansion: typing a snippet abbreviation such as ";sig" followed by a
+//! space or delimiter replaces the abbreviation with the expanded snippet.
+
+use crate::active_app;
+use crate::clipboard::ClipboardManager;
+use crate::clipboard_writer;
+use crate::key_listener::KeyListener;
+use crate::snippets::SnippetManager;
+use rdev::{Event, EventType, Key};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Longest run of typed characters kept while looking for an abbreviation
+const MAX_TYPED_CHARS: usize = 64;
+
+/// How long the pasted expansion stays on the clipboard before the previous
+/// contents are put back, so the target application has read it
+const CLIPBOARD_RESTORE_DELAY: Duration = Duration::from_millis(300);
+
+struct Abbreviation {
+    text: String,
+    snippet_id: String,
+    // Applications the abbreviation is limited to, empty for all
+    apps: Vec<String>,
+}
+
+#[derive(Default)]
+struct ExpansionState {
+    enabled: bool,
+    suspended: bool,
+    delimiters: String,
+    excluded_apps: Vec<String>,
+    abbreviations: Vec<Abbreviation>,
+    // Characters typed since the last delimiter
+    typed: String,
+    ctrl: bool,
+    meta: bool,
+}
+
+/// An abbreviation that was just completed by a delimiter
+struct PendingExpansion {
+    snippet_id: String,
+    apps: Vec<String>,
+    excluded_apps: Vec<String>,
+    abbreviation_len: usize,
+    delimiter: char,
+}
+
+pub struct TextExpansionManager {
+    state: Arc<Mutex<ExpansionState>>,
+    snippets: Arc<SnippetManager>,
+    clipboard_manager: Arc<ClipboardManager>,
+    // Set while an expansion sends its own key events
+    expanding: Arc<AtomicBool>,
+    key_listener: Arc<KeyListener>,
+    listener_started: AtomicBool,
+}
+
+impl TextExpansionManager {
+    pub fn new(
+        snippets: Arc<SnippetManager>,
+        clipboard_manager: Arc<ClipboardManager>,
+        key_listener: Arc<KeyListener>,
+    ) -> Self {
+        TextExpansionManager {
+            state: Arc::new(Mutex::new(ExpansionState::default())),
+            snippets,
+            clipboard_manager,
+            expanding: Arc::new(AtomicBool::new(false)),
+            key_listener,
+            listener_started: AtomicBool::new(false),
+        }
+    }
+
+    /// Enable or disable expansion. The keystroke listener is started on first
+    /// use and keeps running; while disabled it ignores all events.
+    pub fn set_enabled(&self, enabled: bool) {
+        if let Ok(mut state) = self.state.lock() {
+            state.enabled = enabled;
+            state.typed.clear();
+        }
+
+        if !enabled {
+            return;
+        }
+
+        if let Err(e) = self.reload_abbreviations() {
+            eprintln!("Failed to load abbreviations: {}", e);
+        }
+
+        if !self.listener_started.swap(true, Ordering::SeqCst) {
+            self.start_listener();
+        }
+    }
+
+    pub fn set_delimiters(&self, delimiters: String) {
+        if let Ok(mut state) = self.state.lock() {
+            state.delimiters = delimiters;
+        }
+    }
+
+    pub fn set_excluded_apps(&self, apps: Vec<String>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.excluded_apps = apps;
+        }
+    }
+
+    /// Pause expansion without changing the setting, e.g. while entering secrets
+    pub fn set_suspended(&self, suspended: bool) {
+        if let Ok(mut state) = self.state.lock() {
+            state.suspended = suspended;
+            state.typed.clear();
+        }
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.state.lock().map(|s| s.suspended).unwrap_or(false)
+    }
+
+    /// Re-read the abbreviations after snippets changed
+    pub fn reload_abbreviations(&self) -> Result<(), String> {
+        let abbreviations = self
+            .snippets
+            .get_abbreviations()?
+            .into_iter()
+            .filter_map(|snippet| {
+                snippet.abbreviation.map(|text| Abbreviation {
+                    text,
+                    snippet_id: snippet.id,
+                    apps: snippet.expansion_apps,
+                })
+            })
+            .collect();
+
+        let mut state = self.state.lock().map_err(|e| e.to_string())?;
+        state.abbreviations = abbreviations;
+        Ok(())
+    }
+
+    fn start_listener(&self) {
+        let state = self.state.clone();
+        let snippets = self.snippets.clone();
+        let clipboard_manager = self.clipboard_manager.clone();
+        let expanding = self.expanding.clone();
+
+        self.key_listener.add_handler(move |event| {
+            // Our own backspaces and paste keys
+            if expanding.load(Ordering::SeqCst) {
+                return;
+            }
+
+            let pending = match state.lock() {
+                Ok(mut state) => handle_event(&mut state, event),
+                Err(_) => None,
+            };
+
+            if let Some(pending) = pending {
+                // Leave the event hook quickly, expand on another thread
+                expanding.store(true, Ordering::SeqCst);
+
+                let snippets = snippets.clone();
+                let clipboard_manager = clipboard_manager.clone();
+                let expanding = expanding.clone();
+                thread::spawn(move || {
+                    if let Err(e) = expand(&snippets, &clipboard_manager, &pending) {
+                        eprintln!("Failed to expand abbreviation: {}", e);
+                    }
+
+                    // Let the simulated key events reach the listener first
+                    thread::sleep(Duration::from_millis(100));
+                    expanding.store(false, Ordering::SeqCst);
+                });
+            }
+        });
+    }
+}
+
+/// Update the typed text for a global input event and report a completed
+/// abbreviation
+fn handle_event(state: &mut ExpansionState, event: &Event) -> Option<PendingExpansion> {
+    if !state.enabled || state.suspended {
+        return None;
+    }
+
+    let key = match event.event_type {
+        EventType::KeyPress(key) => key,
+        EventType::KeyRelease(key) => {
+            set_modifier(state, key, false);
+            return None;
+        }
+        // Clicking moves the caret, the typed text no longer precedes it
+        EventType::ButtonPress(_) => {
+            state.typed.clear();
+            return None;
+        }
+        _ => return None,
+    };
+
+    if set_modifier(state, key, true) {
+        return None;
+    }
+
+    if key == Key::Backspace {
+        state.typed.pop();
+        return None;
+    }
+
+    let text = match typed_text(state, event) {
+        Some(text) => text,
+        None => {
+            // Shortcuts and navigation keys break the typed sequence
+            state.typed.clear();
+            return None;
+        }
+    };
+
+    let mut pending = None;
+    for c in text.chars() {
+        if c == ' ' || state.delimiters.contains(c) {
+            pending = state
+                .abbreviations
+                .iter()
+                .find(|a| a.text == state.typed)
+                .map(|a| PendingExpansion {
+                    snippet_id: a.snippet_id.clone(),
+                    apps: a.apps.clone(),
+                    excluded_apps: state.excluded_apps.clone(),
+                    abbreviation_len: a.text.chars().count(),
+                    delimiter: c,
+                });
+            state.typed.clear();
+        } else {
+            state.typed.push(c);
+        }
+    }
+
+    let count = state.typed.chars().count();
+    if count > MAX_TYPED_CHARS {
+        state.typed = state.typed.chars().skip(count - MAX_TYPED_CHARS).collect();
+    }
+
+    pending
+}
+
+/// Track modifier keys. Returns true if `key` is a modifier.
+fn set_modifier(state: &mut ExpansionState, key: Key, down: bool) -> bool {
+    match key {
+        Key::ControlLeft | Key::ControlRight => state.ctrl = down,
+        Key::MetaLeft | Key::MetaRight => state.meta = down,
+        Key::Alt | Key::AltGr | Key::ShiftLeft | Key::ShiftRight | Key::CapsLock => {}
+        _ => return false,
+    }
+    true
+}
+
+/// Printable text produced by a key press, ignoring shortcuts
+fn typed_text(state: &ExpansionState, event: &Event) -> Option<String> {
+    if state.ctrl || state.meta {
+        return None;
+    }
+
+    event
+        .name
+        .as_ref()
+        .filter(|name| !name.is_empty() && !name.chars().any(char::is_control))
+        .cloned()
+}
+
+/// Replace the abbreviation and its delimiter with the expanded snippet,
+/// unless the focused application or field rules it out
+fn expand(
+    snippets: &SnippetManager,
+    clipboard_manager: &ClipboardManager,
+    pending: &PendingExpansion,
+) -> Result<(), String> {
+    if active_app::is_password_field_focused() {
+        return Ok(());
+    }
+
+    let app = active_app::foreground_app();
+    if let Some(app) = &app {
+        if active_app::app_matches(app, &pending.excluded_apps) {
+            return Ok(());
+        }
+    }
+    if !pending.apps.is_empty() {
+        let in_scope = app
+            .as_deref()
+            .is_some_and(|app| active_app::app_matches(app, &pending.apps));
+        if !in_scope {
+            return Ok(());
+        }
+    }
+
+    // Snippets that ask for input values cannot be expanded inline
+    let expanded = snippets.expand(&pending.snippet_id, &HashMap::new())?;
+
+    // Keep the delimiter the user typed after the expansion
+    let text = format!("{}{}", expanded.text, pending.delimiter);
+
+    // The expansion goes through the clipboard, the user's contents are put
+    // back afterwards
+    let saved = clipboard_writer::save_clipboard();
+    let result = paste_expansion(clipboard_manager, pending, &text, expanded.cursor_from_end);
+
+    thread::sleep(CLIPBOARD_RESTORE_DELAY);
+    clipboard_manager.restore_clipboard(saved)?;
+
+    result
+}
+
+/// Replace the abbreviation with `text` and move the caret to the cursor
+/// placeholder, if any
+fn paste_expansion(
+    clipboard_manager: &ClipboardManager,
+    pending: &PendingExpansion,
+    text: &str,
+    cursor_from_end: Option<usize>,
+) -> Result<(), String> {
+    clipboard_manager.erase_and_paste(pending.abbreviation_len + 1, text)?;
+
+    if let Some(count) = cursor_from_end {
+        thread::sleep(Duration::from_millis(50));
+        clipboard_writer::tap_key(Key::LeftArrow, count + 1)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rdev::Button;
+    use std::time::SystemTime;
+
+    fn state() -> ExpansionState {
+        ExpansionState {
+            enabled: true,
+            delimiters: ".,".to_string(),
+            excluded_apps: vec!["keepass".to_string()],
+            abbreviations: vec![Abbreviation {
+                text: ";sig".to_string(),
+                snippet_id: "s1".to_string(),
+                apps: vec![],
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn event(event_type: EventType, name: Option<&str>) -> Event {
+        Event {
+            time: SystemTime::now(),
+            name: name.map(str::to_string),
+            event_type,
+        }
+    }
+
+    fn press(key: Key, name: Option<&str>) -> Event {
+        event(EventType::KeyPress(key), name)
+    }
+
+    /// Type `text` one character per key press, returning the last result
+    fn type_text(state: &mut ExpansionState, text: &str) -> Option<PendingExpansion> {
+        let mut pending = None;
+        for c in text.chars() {
+            pending = handle_event(state, &press(Key::Unknown(0), Some(&c.to_string())));
+        }
+        pending
+    }
+
+    #[test]
+    fn delimiters_complete_abbreviations() {
+        let mut state = state();
+        let pending = type_text(&mut state, "x ;sig.").unwrap();
+        assert_eq!(pending.snippet_id, "s1");
+        assert_eq!(pending.abbreviation_len, 4);
+        assert_eq!(pending.delimiter, '.');
+        assert_eq!(pending.excluded_apps, vec!["keepass"]);
+        assert!(state.typed.is_empty());
+
+        assert!(type_text(&mut state, "a;sig ").is_none());
+        assert!(type_text(&mut state, ";sig ").is_some());
+    }
+
+    #[test]
+    fn backspace_corrects_the_typed_text() {
+        let mut state = state();
+        type_text(&mut state, ";sgi");
+        handle_event(&mut state, &press(Key::Backspace, None));
+        handle_event(&mut state, &press(Key::Backspace, None));
+        assert!(type_text(&mut state, "ig,").is_some());
+    }
+
+    #[test]
+    fn shortcuts_and_clicks_reset_the_typed_text() {
+        let mut state = state();
+        type_text(&mut state, ";si");
+        handle_event(&mut state, &press(Key::ControlLeft, None));
+        assert!(handle_event(&mut state, &press(Key::KeyG, Some("g"))).is_none());
+        handle_event(
+            &mut state,
+            &event(EventType::KeyRelease(Key::ControlLeft), None),
+        );
+        assert!(state.typed.is_empty());
+
+        type_text(&mut state, ";si");
+        handle_event(&mut state, &press(Key::MetaLeft, None));
+        handle_event(&mut state, &press(Key::KeyG, Some("g")));
+        handle_event(
+            &mut state,
+            &event(EventType::KeyRelease(Key::MetaLeft), None),
+        );
+        assert!(type_text(&mut state, "g ").is_none());
+
+        type_text(&mut state, ";si");
+        handle_event(
+            &mut state,
+            &event(EventType::ButtonPress(Button::Left), None),
+        );
+        assert!(type_text(&mut state, "g ").is_none());
+
+        // Shift is a modifier that keeps the sequence
+        type_text(&mut state, ";s");
+        handle_event(&mut state, &press(Key::ShiftLeft, None));
+        assert!(type_text(&mut state, "ig ").is_some());
+
+        type_text(&mut state, ";si");
+        handle_event(&mut state, &press(Key::LeftArrow, None));
+        assert!(type_text(&mut state, "g ").is_none());
+    }
+
+    #[test]
+    fn nothing_is_tracked_while_disabled_or_suspended() {
+        let mut state = state();
+        state.suspended = true;
+        assert!(type_text(&mut state, ";sig ").is_none());
+        assert!(state.typed.is_empty());
+
+        state.suspended = false;
+        state.enabled = false;
+        assert!(type_text(&mut state, ";sig ").is_none());
+    }
+
+    #[test]
+    fn typed_text_is_bounded() {
+        let mut state = state();
+        type_text(&mut state, &"a".repeat(MAX_TYPED_CHARS * 2));
+        assert_eq!(state.typed.chars().count(), MAX_TYPED_CHARS);
+    }
+}
@@ -0,0 +1,392 @@
+//! Text transformations that can be chained and applied to clips, e.g.
+//! `["strip_html", "collapse_whitespace", "lower_case"]`.
+//!
+//! A new transformation only needs an entry in `TRANSFORMS`.
+
+use crate::clipboard::{clip_plain_text, html_to_plain_text};
+use crate::db::{ClipItem, ContentType, RichTextContent};
+use base64::Engine;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+type TransformFn = fn(&str) -> Result<String, String>;
+
+struct Transform {
+    id: &'static str,
+    name: &'static str,
+    apply: TransformFn,
+}
+
+const TRANSFORMS: &[Transform] = &[
+    Transform {
+        id: "trim",
+        name: "Trim",
+        apply: trim,
+    },
+    Transform {
+        id: "collapse_whitespace",
+        name: "Collapse whitespace",
+        apply: collapse_whitespace,
+    },
+    Transform {
+        id: "upper_case",
+        name: "UPPER CASE",
+        apply: upper_case,
+    },
+    Transform {
+        id: "lower_case",
+        name: "lower case",
+        apply: lower_case,
+    },
+    Transform {
+        id: "title_case",
+        name: "Title Case",
+        apply: title_case,
+    },
+    Transform {
+        id: "snake_case",
+        name: "snake_case",
+        apply: snake_case,
+    },
+    Transform {
+        id: "camel_case",
+        name: "camelCase",
+        apply: camel_case,
+    },
+    Transform {
+        id: "json_pretty",
+        name: "Format JSON",
+        apply: json_pretty,
+    },
+    Transform {
+        id: "json_minify",
+        name: "Minify JSON",
+        apply: json_minify,
+    },
+    Transform {
+        id: "url_encode",
+        name: "URL encode",
+        apply: url_encode,
+    },
+    Transform {
+        id: "url_decode",
+        name: "URL decode",
+        apply: url_decode,
+    },
+    Transform {
+        id: "base64_encode",
+        name: "Base64 encode",
+        apply: base64_encode,
+    },
+    Transform {
+        id: "base64_decode",
+        name: "Base64 decode",
+        apply: base64_decode,
+    },
+    Transform {
+        id: "sort_lines",
+        name: "Sort lines",
+        apply: sort_lines,
+    },
+    Transform {
+        id: "dedupe_lines",
+        name: "Remove duplicate lines",
+        apply: dedupe_lines,
+    },
+    Transform {
+        id: "strip_html",
+        name: "Strip HTML",
+        apply: strip_html,
+    },
+];
+
+/// A transformation as shown in the UI
+#[derive(Debug, Clone, Serialize)]
+pub struct TransformInfo {
+    pub id: String,
+    pub name: String,
+}
+
+pub fn available_transforms() -> Vec<TransformInfo> {
+    TRANSFORMS
+        .iter()
+        .map(|t| TransformInfo {
+            id: t.id.to_string(),
+            name: t.name.to_string(),
+        })
+        .collect()
+}
+
+/// Apply the transformations in order, each one to the output of the previous
+pub fn apply_transforms(text: &str, operations: &[String]) -> Result<String, String> {
+    if operations.is_empty() {
+        return Err("No transformation selected".to_string());
+    }
+
+    let mut text = text.to_string();
+    for operation in operations {
+        let transform = TRANSFORMS
+            .iter()
+            .find(|t| t.id == operation)
+            .ok_or_else(|| format!("Unknown transformation '{}'", operation))?;
+        text = (transform.apply)(&text).map_err(|e| format!("{}: {}", transform.name, e))?;
+    }
+    Ok(text)
+}
+
+fn trim(text: &str) -> Result<String, String> {
+    Ok(text.trim().to_string())
+}
+
+/// Collapse runs of spaces and tabs within each line and drop blank lines
+fn collapse_whitespace(text: &str) -> Result<String, String> {
+    Ok(text
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn upper_case(text: &str) -> Result<String, String> {
+    Ok(text.to_uppercase())
+}
+
+fn lower_case(text: &str) -> Result<String, String> {
+    Ok(text.to_lowercase())
+}
+
+fn title_case(text: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut word_start = true;
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            if word_start {
+                result.extend(c.to_uppercase());
+            } else {
+                result.extend(c.to_lowercase());
+            }
+            word_start = false;
+        } else {
+            result.push(c);
+            word_start = c.is_whitespace() || c == '-' || c == '_';
+        }
+    }
+    Ok(result)
+}
+
+fn snake_case(text: &str) -> Result<String, String> {
+    Ok(split_words(text)
+        .iter()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("_"))
+}
+
+fn camel_case(text: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    for (i, word) in split_words(text).iter().enumerate() {
+        let word = word.to_lowercase();
+        if i == 0 {
+            result.push_str(&word);
+        } else {
+            let mut chars = word.chars();
+            if let Some(first) = chars.next() {
+                result.extend(first.to_uppercase());
+                result.push_str(chars.as_str());
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Split identifiers and prose into words: "fooBar baz-qux" gives
+/// ["foo", "Bar", "baz", "qux"]
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut prev: Option<char> = None;
+
+    for c in text.chars() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            prev = None;
+            continue;
+        }
+
+        // A lower-to-upper change starts a new word in camelCase input
+        let boundary =
+            prev.is_some_and(|p| (p.is_lowercase() || p.is_ascii_digit()) && c.is_uppercase());
+        if boundary && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+
+        current.push(c);
+        prev = Some(c);
+    }
+
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// A JSON value that keeps the order of object keys, so formatting does not
+/// sort them
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum OrderedJson {
+    Null,
+    Bool(bool),
+    Number(serde_json::Number),
+    String(String),
+    Array(Vec<OrderedJson>),
+    Object(IndexMap<String, OrderedJson>),
+}
+
+fn parse_json(text: &str) -> Result<OrderedJson, String> {
+    serde_json::from_str(text.trim()).map_err(|e| format!("Invalid JSON: {}", e))
+}
+
+fn json_pretty(text: &str) -> Result<String, String> {
+    serde_json::to_string_pretty(&parse_json(text)?).map_err(|e| e.to_string())
+}
+
+fn json_minify(text: &str) -> Result<String, String> {
+    serde_json::to_string(&parse_json(text)?).map_err(|e| e.to_string())
+}
+
+/// Percent-encode everything except unreserved characters (RFC 3986)
+fn url_encode(text: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            result.push(byte as char);
+        } else {
+            result.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    Ok(result)
+}
+
+/// Decode percent-escapes; "+" is decoded as a space like in query strings
+fn url_decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = bytes
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| format!("Invalid escape at position {}", i))?;
+                decoded.push(hex);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8(decoded).map_err(|_| "Decoded text is not valid UTF-8".to_string())
+}
+
+fn base64_encode(text: &str) -> Result<String, String> {
+    Ok(base64::engine::general_purpose::STANDARD.encode(text))
+}
+
+/// Decode standard or URL-safe base64, with or without padding
+fn base64_decode(text: &str) -> Result<String, String> {
+    use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD};
+
+    let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let decoded = [STANDARD, URL_SAFE, STANDARD_NO_PAD, URL_SAFE_NO_PAD]
+        .iter()
+        .find_map(|engine| engine.decode(&compact).ok())
+        .ok_or_else(|| "Invalid base64".to_string())?;
+
+    String::from_utf8(decoded).map_err(|_| "Decoded data is not text".to_string())
+}
+
+fn sort_lines(text: &str) -> Result<String, String> {
+    let mut lines: Vec<&str> = text.lines().collect();
+    lines.sort_by_key(|line| line.to_lowercase());
+    Ok(lines.join("\n"))
+}
+
+/// Remove repeated lines, keeping the first occurrence of each
+fn dedupe_lines(text: &str) -> Result<String, String> {
+    let mut seen = HashSet::new();
+    Ok(text
+        .lines()
+        .filter(|line| seen.insert(*line))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn strip_html(text: &str) -> Result<String, String> {
+    Ok(html_to_plain_text(text))
+}
+
+/// What to do with the transformed text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransformOutput {
+    /// Only return the text, e.g. for a preview
+    #[default]
+    Preview,
+    /// Save the text as a new clip
+    Clip,
+    /// Write the text to the system clipboard
+    Clipboard,
+}
+
+/// The text a clip is transformed from. Rich clips start from their HTML when
+/// the chain strips markup and from their plain text otherwise.
+pub fn transform_input(clip: &ClipItem, operations: &[String]) -> Result<String, String> {
+    if clip.content_type == ContentType::Rich
+        && operations.first().is_some_and(|op| op == "strip_html")
+    {
+        let rich: RichTextContent = serde_json::from_str(&clip.content)
+            .map_err(|e| format!("Invalid rich text content: {}", e))?;
+        if let Some(html) = rich.html.filter(|html| !html.is_empty()) {
+            return Ok(html);
+        }
+    }
+
+    clip_plain_text(clip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_formatting_keeps_key_order() {
+        let text = r#"{"zeta": 1, "alpha": {"b": [true, null], "a": -2.5}}"#;
+        assert_eq!(
+            json_minify(text).unwrap(),
+            r#"{"zeta":1,"alpha":{"b":[true,null],"a":-2.5}}"#
+        );
+        assert!(json_pretty(text).unwrap().starts_with("{\n  \"zeta\": 1,"));
+    }
+
+    #[test]
+    fn invalid_json_is_reported() {
+        assert!(json_minify("{\"a\": }")
+            .unwrap_err()
+            .starts_with("Invalid JSON"));
+    }
+}
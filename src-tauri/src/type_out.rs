@@ -0,0 +1,278 @@
+//! Type-out mode: enter clip text through simulated key events instead of the
+//! clipboard, for web forms and remote consoles that block pasting.
+
+use rdev::{simulate, EventType, Key};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use tauri_plugin_global_shortcut::Shortcut;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeOutResult {
+    pub typed_chars: usize,
+    pub cancelled: bool,
+}
+
+pub struct TypeOutManager {
+    running: AtomicBool,
+    cancel_requested: AtomicBool,
+    // Global shortcut registered while typing is in progress
+    cancel_shortcut: Mutex<Option<Shortcut>>,
+}
+
+impl TypeOutManager {
+    pub fn new() -> Self {
+        TypeOutManager {
+            running: AtomicBool::new(false),
+            cancel_requested: AtomicBool::new(false),
+            cancel_shortcut: Mutex::new(None),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Stop a type-out in progress after the current character
+    pub fn cancel(&self) {
+        if self.is_running() {
+            self.cancel_requested.store(true, Ordering::SeqCst);
+        }
+    }
+
+    pub fn set_cancel_shortcut(&self, shortcut: Option<Shortcut>) {
+        if let Ok(mut current) = self.cancel_shortcut.lock() {
+            *current = shortcut;
+        }
+    }
+
+    /// Whether `shortcut` is the cancel shortcut of the running type-out
+    pub fn is_cancel_shortcut(&self, shortcut: &Shortcut) -> bool {
+        self.cancel_shortcut
+            .lock()
+            .map(|current| current.as_ref() == Some(shortcut))
+            .unwrap_or(false)
+    }
+
+    /// Claim the type-out before typing, failing if one is already running.
+    /// Released by `finish`.
+    pub fn start(&self) -> Result<(), String> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Err("Type-out is already running".to_string());
+        }
+        self.cancel_requested.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub fn finish(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+
+    /// Type `text` after `start`, waiting `delay` between keys.
+    ///
+    /// Newlines are sent as Enter and tabs as Tab, other control characters are
+    /// skipped. Everything else is typed as Unicode, independent of the keyboard
+    /// layout.
+    pub fn type_text(&self, text: &str, delay: Duration) -> Result<TypeOutResult, String> {
+        let mut typed_chars = 0;
+
+        for segment in segments(text) {
+            if self.cancel_requested.load(Ordering::SeqCst) {
+                return Ok(TypeOutResult {
+                    typed_chars,
+                    cancelled: true,
+                });
+            }
+
+            match segment {
+                Segment::Key(key) => {
+                    tap_key(key)?;
+                    typed_chars += 1;
+                }
+                Segment::Text(text, chars) => {
+                    type_unicode_text(&text, delay)?;
+                    typed_chars += chars;
+                }
+            }
+            thread::sleep(delay);
+        }
+
+        Ok(TypeOutResult {
+            typed_chars,
+            cancelled: false,
+        })
+    }
+}
+
+/// Text is typed in chunks of this many characters; a cancel takes effect
+/// between chunks
+const CHUNK_SIZE: usize = 50;
+
+enum Segment {
+    Key(Key),
+    /// Text without control characters and its length in characters
+    Text(String, usize),
+}
+
+fn segments(text: &str) -> Vec<Segment> {
+    // Treat CRLF as a single line break
+    let text = text.replace("\r\n", "\n");
+
+    let mut segments = Vec::new();
+    let mut chunk = String::new();
+    let mut chunk_chars = 0;
+
+    for c in text.chars() {
+        let key = match c {
+            '\n' | '\r' => Some(Key::Return),
+            '\t' => Some(Key::Tab),
+            c if c.is_control() => continue,
+            _ => None,
+        };
+
+        if (key.is_some() || chunk_chars == CHUNK_SIZE) && chunk_chars > 0 {
+            segments.push(Segment::Text(std::mem::take(&mut chunk), chunk_chars));
+            chunk_chars = 0;
+        }
+
+        match key {
+            Some(key) => segments.push(Segment::Key(key)),
+            None => {
+                chunk.push(c);
+                chunk_chars += 1;
+            }
+        }
+    }
+
+    if chunk_chars > 0 {
+        segments.push(Segment::Text(chunk, chunk_chars));
+    }
+    segments
+}
+
+fn tap_key(key: Key) -> Result<(), String> {
+    simulate(&EventType::KeyPress(key)).map_err(|e| format!("Failed to simulate key: {:?}", e))?;
+    simulate(&EventType::KeyRelease(key)).map_err(|e| format!("Failed to simulate key: {:?}", e))
+}
+
+/// Type text without control characters one character at a time
+#[cfg(not(target_os = "linux"))]
+fn type_unicode_text(text: &str, delay: Duration) -> Result<(), String> {
+    for (i, c) in text.chars().enumerate() {
+        if i > 0 {
+            thread::sleep(delay);
+        }
+        type_unicode_char(c)?;
+    }
+    Ok(())
+}
+
+// ==================== Windows Implementations ====================
+
+/// Type a single character with `KEYEVENTF_UNICODE` (Windows)
+#[cfg(windows)]
+fn type_unicode_char(c: char) -> Result<(), String> {
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+        SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYEVENTF_KEYUP, KEYEVENTF_UNICODE,
+    };
+
+    let mut units = [0u16; 2];
+    let inputs: Vec<INPUT> = c
+        .encode_utf16(&mut units)
+        .iter()
+        .flat_map(|&unit| {
+            [KEYEVENTF_UNICODE, KEYEVENTF_UNICODE | KEYEVENTF_KEYUP].map(|flags| INPUT {
+                r#type: INPUT_KEYBOARD,
+                Anonymous: INPUT_0 {
+                    ki: KEYBDINPUT {
+                        wVk: 0,
+                        wScan: unit,
+                        dwFlags: flags,
+                        time: 0,
+                        dwExtraInfo: 0,
+                    },
+                },
+            })
+        })
+        .collect();
+
+    let sent = unsafe {
+        SendInput(
+            inputs.len() as u32,
+            inputs.as_ptr(),
+            std::mem::size_of::<INPUT>() as i32,
+        )
+    };
+
+    if sent as usize == inputs.len() {
+        Ok(())
+    } else {
+        Err("Failed to send keyboard input".to_string())
+    }
+}
+
+// ==================== macOS Implementations ====================
+
+/// Type a single character by attaching it to a synthetic key event (macOS)
+#[cfg(target_os = "macos")]
+fn type_unicode_char(c: char) -> Result<(), String> {
+    use core_graphics::event::{CGEvent, CGEventTapLocation};
+    use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+
+    let mut units = [0u16; 2];
+    let units = c.encode_utf16(&mut units);
+
+    for keydown in [true, false] {
+        let source = CGEventSource::new(CGEventSourceStateID::HIDSystemState)
+            .map_err(|_| "Failed to create event source".to_string())?;
+        let event = CGEvent::new_keyboard_event(source, 0, keydown)
+            .map_err(|_| "Failed to create keyboard event".to_string())?;
+        event.set_string_from_utf16_unchecked(units);
+        event.post(CGEventTapLocation::HID);
+    }
+
+    Ok(())
+}
+
+// ==================== Linux Implementations ====================
+
+/// Type text without control characters with one xdotool (X11) or wtype
+/// (Wayland) run.
+///
+/// The tool of the running session is tried first. The other one is only
+/// tried if the first is not installed: a run that failed may already have
+/// typed part of the text.
+#[cfg(target_os = "linux")]
+fn type_unicode_text(text: &str, delay: Duration) -> Result<(), String> {
+    use std::io::ErrorKind;
+    use std::process::Command;
+
+    let delay_ms = delay.as_millis().to_string();
+    let xdotool = ("xdotool", vec!["type", "--delay", &delay_ms, "--", text]);
+    let wtype = ("wtype", vec!["-d", &delay_ms, "--", text]);
+
+    let tools = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        [wtype, xdotool]
+    } else {
+        [xdotool, wtype]
+    };
+
+    for (program, args) in tools {
+        match Command::new(program).args(&args).output() {
+            Ok(output) if output.status.success() => return Ok(()),
+            Ok(output) => {
+                return Err(format!(
+                    "Failed to type text with {}: {}",
+                    program,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("Failed to run {}: {}", program, e)),
+        }
+    }
+
+    Err("Failed to type text: install xdotool (X11) or wtype (Wayland)".to_string())
+}
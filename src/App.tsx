@@ -3,6 +3,7 @@ import { Home } from "./pages/Home";
 import { Settings } from "./pages/Settings";
 import { Tags } from "./pages/Tags";
 import { Shortcuts } from "./pages/Shortcuts";
+import { Hooks } from "./pages/Hooks";
 
 function App() {
   return (
@@ -11,6 +12,7 @@ function App() {
       <Route path="/settings" element={<Settings />} />
       <Route path="/tags" element={<Tags />} />
       <Route path="/shortcuts" element={<Shortcuts />} />
+      <Route path="/hooks" element={<Hooks />} />
     </Routes>
   );
 }
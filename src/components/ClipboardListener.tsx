@@ -22,6 +22,7 @@ export interface ClipItem {
   created_at: string;
   last_used_at: string;
   pinned: boolean;
+  use_count?: number;
   metadata?: {
     width?: number;
     height?: number;
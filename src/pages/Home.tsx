@@ -334,19 +334,17 @@ export function Home() {
         // For images, we need to handle differently
         const imageData = await invoke<Uint8Array>("get_clip_image_data", { clipId: clip.id });
         if (imageData) {
-          await invoke("write_image_to_clipboard", { imageData: Array.from(imageData) });
+          await invoke("write_image_to_clipboard", { imageData: Array.from(imageData), clipId: clip.id });
         }
       } else if (clip.content_type === "Rich") {
-        // For rich text, extract plain text for now
-        const { plain } = parseRichTextContent(clip.content);
-        await invoke("write_to_clipboard", { content: plain });
+        // Rich text keeps its HTML/RTF formats with a plain text fallback
+        await invoke("write_rich_clip_to_clipboard", { clipId: clip.id });
       } else if (clip.content_type === "File") {
-        // For files, copy the file paths
-        const paths = parseFileContent(clip.content);
-        await invoke("write_to_clipboard", { content: paths.join("\n") });
+        // Files go back as real files; fails if any of them was removed
+        await invoke("write_file_clip_to_clipboard", { clipId: clip.id });
       } else {
         // Plain text
-        await invoke("write_to_clipboard", { content: clip.content });
+        await invoke("write_to_clipboard", { content: clip.content, clipId: clip.id });
       }
       
       setCopiedId(clip.id);
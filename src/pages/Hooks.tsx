@@ -0,0 +1,193 @@
+import { useState, useEffect } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { useNavigate } from "react-router-dom";
+import { useTranslation } from "react-i18next";
+import {
+  Box,
+  Typography,
+  Paper,
+  Button,
+  IconButton,
+  Tooltip,
+  Chip,
+  Collapse,
+} from "@mui/material";
+import {
+  ArrowBack as BackIcon,
+  DeleteSweep as ClearIcon,
+  Terminal as TerminalIcon,
+  KeyboardArrowDown as ExpandIcon,
+  KeyboardArrowUp as CollapseIcon,
+} from "@mui/icons-material";
+
+interface HookRun {
+  id: number;
+  hook_id: string;
+  hook_name: string;
+  trigger: "new_clip" | "pin" | "tag_added";
+  clip_id: string;
+  started_at: string;
+  duration_ms: number;
+  exit_code: number | null;
+  timed_out: boolean;
+  stdout: string;
+  stderr: string;
+  error: string | null;
+}
+
+const triggerKeys: Record<HookRun["trigger"], string> = {
+  new_clip: "settings.hooks.triggerNewClip",
+  pin: "settings.hooks.triggerPin",
+  tag_added: "settings.hooks.triggerTagAdded",
+};
+
+function RunStatus({ run }: { run: HookRun }) {
+  const { t } = useTranslation();
+
+  if (run.error) {
+    return <Chip size="small" color="error" label={t("settings.hooks.failed")} />;
+  }
+  if (run.timed_out) {
+    return <Chip size="small" color="warning" label={t("settings.hooks.timedOut")} />;
+  }
+  return (
+    <Chip
+      size="small"
+      color={run.exit_code === 0 ? "success" : "error"}
+      label={t("settings.hooks.exitCode", { code: run.exit_code ?? "?" })}
+    />
+  );
+}
+
+function Output({ label, text }: { label: string; text: string }) {
+  return (
+    <Box sx={{ mt: 1 }}>
+      <Typography variant="caption" color="text.secondary">
+        {label}
+      </Typography>
+      <Box
+        component="pre"
+        sx={{
+          m: 0,
+          p: 1,
+          borderRadius: 1,
+          bgcolor: "action.hover",
+          fontSize: 12,
+          maxHeight: 200,
+          overflow: "auto",
+          whiteSpace: "pre-wrap",
+          wordBreak: "break-all",
+        }}
+      >
+        {text}
+      </Box>
+    </Box>
+  );
+}
+
+export function Hooks() {
+  const { t } = useTranslation();
+  const navigate = useNavigate();
+  const [runs, setRuns] = useState<HookRun[]>([]);
+  const [expandedId, setExpandedId] = useState<number | null>(null);
+
+  useEffect(() => {
+    loadRuns();
+
+    const unlisten = listen("hook-runs-updated", () => {
+      loadRuns();
+    });
+
+    return () => {
+      unlisten.then((f) => f());
+    };
+  }, []);
+
+  const loadRuns = async () => {
+    try {
+      const result = await invoke<HookRun[]>("get_hook_runs");
+      setRuns(result);
+    } catch (error) {
+      console.error("Failed to load hook runs:", error);
+    }
+  };
+
+  const handleClear = async () => {
+    try {
+      await invoke("clear_hook_runs");
+      setRuns([]);
+    } catch (error) {
+      console.error("Failed to clear hook runs:", error);
+    }
+  };
+
+  return (
+    <Box sx={{ height: "100vh", display: "flex", flexDirection: "column", bgcolor: "background.default" }}>
+      {/* Header */}
+      <Paper elevation={0} sx={{ p: 1.5, borderRadius: 0, borderBottom: 1, borderColor: "divider" }}>
+        <Box sx={{ display: "flex", alignItems: "center", gap: 1.5 }}>
+          <Tooltip title={t("common.back")}>
+            <IconButton onClick={() => navigate("/settings")} size="small">
+              <BackIcon fontSize="small" />
+            </IconButton>
+          </Tooltip>
+          <TerminalIcon sx={{ color: "primary.main", fontSize: 20 }} />
+          <Typography variant="subtitle1" sx={{ flex: 1, fontWeight: 600 }}>
+            {t("settings.hooks.pageTitle")}
+          </Typography>
+          <Button
+            variant="outlined"
+            startIcon={<ClearIcon />}
+            onClick={handleClear}
+            disabled={runs.length === 0}
+            size="small"
+          >
+            {t("settings.hooks.clear")}
+          </Button>
+        </Box>
+      </Paper>
+
+      {/* Content */}
+      <Box sx={{ flex: 1, overflow: "auto", p: 2 }}>
+        <Box sx={{ maxWidth: 800, mx: "auto" }}>
+          {runs.length === 0 && (
+            <Typography variant="body2" color="text.secondary" sx={{ textAlign: "center", mt: 4 }}>
+              {t("settings.hooks.noRuns")}
+            </Typography>
+          )}
+
+          {runs.map((run) => {
+            const expanded = expandedId === run.id;
+            return (
+              <Paper key={run.id} variant="outlined" sx={{ p: 1.5, mb: 1 }}>
+                <Box
+                  onClick={() => setExpandedId(expanded ? null : run.id)}
+                  sx={{ display: "flex", alignItems: "center", gap: 1, cursor: "pointer" }}
+                >
+                  <Box sx={{ flex: 1, minWidth: 0 }}>
+                    <Typography variant="body2" sx={{ fontWeight: 500 }} noWrap>
+                      {run.hook_name}
+                    </Typography>
+                    <Typography variant="caption" color="text.secondary">
+                      {t(triggerKeys[run.trigger])} · {new Date(run.started_at).toLocaleString()} ·{" "}
+                      {t("settings.hooks.duration", { ms: run.duration_ms })}
+                    </Typography>
+                  </Box>
+                  <RunStatus run={run} />
+                  {expanded ? <CollapseIcon fontSize="small" /> : <ExpandIcon fontSize="small" />}
+                </Box>
+
+                <Collapse in={expanded}>
+                  {run.error && <Output label={t("settings.hooks.error")} text={run.error} />}
+                  <Output label={t("settings.hooks.stdout")} text={run.stdout || t("settings.hooks.noOutput")} />
+                  <Output label={t("settings.hooks.stderr")} text={run.stderr || t("settings.hooks.noOutput")} />
+                </Collapse>
+              </Paper>
+            );
+          })}
+        </Box>
+      </Box>
+    </Box>
+  );
+}
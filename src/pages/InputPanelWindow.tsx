@@ -191,9 +191,9 @@ export function InputPanelWindow() {
 
       // Call paste_and_cleanup to delete /v and search content, then paste
       await invoke("paste_and_cleanup", {
-        content: clip.content,
-        trigger_len: triggerLen,
-        search_len: searchLen,
+        clipId: clip.id,
+        triggerLen,
+        searchLen,
       });
 
       const isMainVisible = await invoke<boolean>("is_main_window_visible");
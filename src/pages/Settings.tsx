@@ -34,6 +34,7 @@ import {
   Info as InfoIcon,
   Window as WindowIcon,
   ChevronRight as ChevronRightIcon,
+  Terminal as TerminalIcon,
 } from "@mui/icons-material";
 import { languages } from "../i18n";
 import { usePlatform } from "../hooks/usePlatform";
@@ -184,12 +185,8 @@ export function Settings() {
   const loadSettings = async () => {
     try {
       const result = await invoke<SettingsData>("get_settings");
-      const normalized = {
-        ...result,
-        enable_input_panel: false,
-      };
-      setSettings(normalized);
-      setOriginalSettings(normalized);
+      setSettings(result);
+      setOriginalSettings(result);
       
       // Apply language
       const lang = result.language === "system" ? navigator.language.split("-")[0] : result.language;
@@ -220,7 +217,6 @@ export function Settings() {
       const settingsToSave = {
         ...originalSettings,
         ...settings,
-        enable_input_panel: false,
       };
       await invoke("save_settings", { settings: settingsToSave });
       setSettings(settingsToSave);
@@ -411,12 +407,47 @@ export function Settings() {
             </Box>
             <Divider sx={{ mb: 2 }} />
 
-            <Typography variant="body2" color="text.secondary">
-              {t("settings.experimental.enableInputPanel")}
-            </Typography>
-            <Typography variant="caption" color="text.secondary" display="block" sx={{ mt: 0.5 }}>
-              {t("settings.experimental.disabledNotice")}
-            </Typography>
+            <FormControlLabel
+              control={
+                <Switch
+                  size="small"
+                  checked={settings.enable_input_panel}
+                  onChange={(e) => handleChange("enable_input_panel", e.target.checked)}
+                />
+              }
+              label={
+                <Box>
+                  <Typography variant="body2">{t("settings.experimental.enableInputPanel")}</Typography>
+                  <Typography variant="caption" color="text.secondary" display="block">
+                    {t("settings.experimental.inputPanelDescription", { trigger: settings.input_panel_trigger })}
+                  </Typography>
+                </Box>
+              }
+            />
+
+            {settings.enable_input_panel && (
+              <Box sx={{ display: "flex", gap: 2, mt: 2 }}>
+                <TextField
+                  size="small"
+                  label={t("settings.experimental.triggerSequence")}
+                  helperText={t("settings.experimental.triggerHint")}
+                  value={settings.input_panel_trigger}
+                  onChange={(e) => handleChange("input_panel_trigger", e.target.value)}
+                  sx={{ flex: 1 }}
+                />
+                <FormControl size="small" sx={{ flex: 1 }}>
+                  <InputLabel>{t("settings.experimental.selectionModifier")}</InputLabel>
+                  <Select
+                    value={settings.input_panel_selection_modifier}
+                    onChange={(e) => handleChange("input_panel_selection_modifier", e.target.value)}
+                    label={t("settings.experimental.selectionModifier")}
+                  >
+                    <MenuItem value="ctrl">Ctrl + 1-9</MenuItem>
+                    <MenuItem value="alt">Alt + 1-9</MenuItem>
+                  </Select>
+                </FormControl>
+              </Box>
+            )}
             
             {/* Linux Display Server Selection */}
             {isLinux && (
@@ -492,6 +523,38 @@ export function Settings() {
             </Paper>
           </Paper>
 
+          {/* Hooks Section */}
+          <Paper sx={{ p: 2, mb: 2 }}>
+            <Box sx={{ display: "flex", alignItems: "center", gap: 1, mb: 1.5 }}>
+              <TerminalIcon color="primary" fontSize="small" />
+              <Typography variant="subtitle2">{t("settings.hooks.title")}</Typography>
+            </Box>
+            <Divider sx={{ mb: 2 }} />
+
+            <Paper
+              variant="outlined"
+              onClick={() => navigate("/hooks")}
+              sx={{
+                p: 1.5,
+                display: "flex",
+                alignItems: "center",
+                justifyContent: "space-between",
+                cursor: "pointer",
+                "&:hover": { bgcolor: "action.hover" },
+              }}
+            >
+              <Box>
+                <Typography variant="body2" sx={{ fontWeight: 500 }}>
+                  {t("settings.hooks.viewRuns")}
+                </Typography>
+                <Typography variant="caption" color="text.secondary">
+                  {t("settings.hooks.viewRunsDescription")}
+                </Typography>
+              </Box>
+              <ChevronRightIcon color="action" />
+            </Paper>
+          </Paper>
+
           {/* Appearance Section */}
           <Paper sx={{ p: 2, mb: 2 }}>
             <Box sx={{ display: "flex", alignItems: "center", gap: 1, mb: 1.5 }}>